@@ -4,6 +4,8 @@ pub(crate) mod item;
 pub(crate) mod mode;
 pub(crate) mod time_spent;
 
+use std::sync::Arc;
+
 use ahash::HashMap;
 use chrono::{DateTime, Utc};
 use ouroboros::self_referencing;
@@ -23,7 +25,7 @@ use self::{
 
 #[self_referencing]
 pub(crate) struct BaseData {
-    surreal_tables: SurrealTables,
+    surreal_tables: Arc<SurrealTables>,
     now: DateTime<Utc>,
 
     #[borrows(surreal_tables, now)]
@@ -34,7 +36,7 @@ pub(crate) struct BaseData {
     #[covariant]
     active_items: Vec<&'this Item<'this>>,
 
-    #[borrows(surreal_tables)]
+    #[borrows(surreal_tables, now)]
     #[covariant]
     events: HashMap<&'this RecordId, Event<'this>>,
 
@@ -48,15 +50,16 @@ pub(crate) struct BaseData {
 }
 
 impl BaseData {
+    /// Takes a snapshot that is shared with the data storage layer or tables that are made up, for example in a test
     pub(crate) fn new_from_surreal_tables(
-        surreal_tables: SurrealTables,
+        surreal_tables: impl Into<Arc<SurrealTables>>,
         now: DateTime<Utc>,
     ) -> Self {
         BaseDataBuilder {
-            surreal_tables,
+            surreal_tables: surreal_tables.into(),
             items_builder: |surreal_tables, now| surreal_tables.make_items(now),
             active_items_builder: |items| items.filter_active_items(),
-            events_builder: |surreal_tables| surreal_tables.make_events(),
+            events_builder: |surreal_tables, now| surreal_tables.make_events(now),
             now,
             time_spent_log_builder: |surreal_tables| surreal_tables.make_time_spent_log().collect(),
             modes_builder: |surreal_tables| surreal_tables.make_modes().collect(),
@@ -96,6 +99,19 @@ impl BaseData {
     pub(crate) fn get_modes(&self) -> &[Mode] {
         self.borrow_modes()
     }
+
+    /// Every tag given to an item that is not finished, sorted and each listed once
+    pub(crate) fn get_tags_in_use(&self) -> Vec<&str> {
+        let mut tags = self
+            .get_active_items()
+            .iter()
+            .flat_map(|x| x.get_tags().iter().map(|x| x.as_str()))
+            .collect::<Vec<_>>();
+        tags.sort();
+        tags.dedup();
+        tags.sort_by_key(|x| x.to_lowercase());
+        tags
+    }
 }
 
 pub(crate) struct Visited<'s, 'v> {
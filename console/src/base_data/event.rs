@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
 use surrealdb::opt::RecordId;
 
@@ -7,6 +9,7 @@ use crate::data_storage::surrealdb_layer::surreal_event::SurrealEvent;
 pub(crate) struct Event<'s> {
     id: &'s RecordId,
     last_updated: DateTime<Utc>,
+    triggered: bool,
     surreal_event: &'s SurrealEvent,
 }
 
@@ -17,18 +20,31 @@ impl PartialEq for Event<'_> {
 }
 
 impl<'s> Event<'s> {
-    pub(crate) fn new(surreal_event: &'s SurrealEvent) -> Self {
+    pub(crate) fn new(surreal_event: &'s SurrealEvent, now: &DateTime<Utc>) -> Self {
         let id = surreal_event.id.as_ref().expect("In DB");
         let last_updated = surreal_event.last_updated.clone().into();
+        //A recurring event is untriggered by time passing rather than by a change being saved
+        let triggered = surreal_event.triggered
+            && surreal_event.untrigger_after.is_none_or(|untrigger_after| {
+                let untrigger_after: Duration = untrigger_after.into();
+                chrono::Duration::from_std(untrigger_after)
+                    .is_ok_and(|untrigger_after| last_updated + untrigger_after > *now)
+            });
         Event {
             id,
             surreal_event,
             last_updated,
+            triggered,
         }
     }
 
     pub(crate) fn is_active(&self) -> bool {
-        !self.surreal_event.triggered
+        !self.triggered
+    }
+
+    /// Some if this is a recurring event, see `SurrealEvent::untrigger_after`
+    pub(crate) fn get_untrigger_after(&self) -> Option<Duration> {
+        self.surreal_event.untrigger_after.map(|x| x.into())
     }
 
     pub(crate) fn get_surreal_record_id(&self) -> &RecordId {
@@ -10,8 +10,9 @@ use surrealdb::{
 use crate::{
     calculated_data::parent_lookup::ParentLookup,
     data_storage::surrealdb_layer::surreal_item::{
-        Responsibility, SurrealDependency, SurrealFrequency, SurrealItem, SurrealItemType,
-        SurrealMotivationKind, SurrealOrderedSubItem, SurrealReviewGuidance, SurrealUrgencyPlan,
+        Responsibility, SurrealCompletion, SurrealDeadline, SurrealDependency, SurrealFrequency,
+        SurrealItem, SurrealItemType, SurrealLink, SurrealMotivationKind, SurrealOrderedSubItem,
+        SurrealRecurrence, SurrealReviewGuidance, SurrealUrgencyPlan,
     },
 };
 
@@ -202,6 +203,59 @@ impl<'b> Item<'b> {
         &self.surreal_item.urgency_plan
     }
 
+    /// The user defined modes this item is done in, not counting the modes of its larger items
+    pub(crate) fn get_modes(&self) -> &[RecordId] {
+        &self.surreal_item.modes
+    }
+
+    /// Only the tags given to this item, `ItemNode::get_tags` includes the tags of its larger items
+    pub(crate) fn get_tags(&self) -> &[String] {
+        &self.surreal_item.tags
+    }
+
+    pub(crate) fn get_recurrence(&self) -> Option<&SurrealRecurrence> {
+        self.surreal_item.recurrence.as_ref()
+    }
+
+    /// Each time a recurring item was finished, oldest first
+    pub(crate) fn get_completions(&self) -> &[SurrealCompletion] {
+        &self.surreal_item.completions
+    }
+
+    pub(crate) fn get_deadline(&self) -> Option<&SurrealDeadline> {
+        self.surreal_item.deadline.as_ref()
+    }
+
+    /// For the item itself, not counting its smaller items
+    pub(crate) fn get_estimate(&self) -> Option<Duration> {
+        self.surreal_item.estimate.map(|x| x.into())
+    }
+
+    pub(crate) fn get_notes(&self) -> &str {
+        &self.surreal_item.notes
+    }
+
+    /// Includes the link from `notes_location` that items could have before they could have more than one
+    pub(crate) fn get_links(&self) -> Vec<SurrealLink> {
+        self.surreal_item
+            .notes_location
+            .as_link()
+            .into_iter()
+            .chain(self.surreal_item.links.iter().cloned())
+            .collect()
+    }
+
+    /// Case insensitive, for searching by more than the summary
+    pub(crate) fn notes_or_links_contain(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        !text.is_empty()
+            && (self.get_notes().to_lowercase().contains(&text)
+                || self
+                    .get_links()
+                    .iter()
+                    .any(|x| x.target.to_lowercase().contains(&text)))
+    }
+
     pub(crate) fn get_surreal_dependencies(&self) -> &Vec<SurrealDependency> {
         &self.surreal_item.dependencies
     }
@@ -19,6 +19,10 @@ impl<'s> Mode<'s> {
         &self.surreal_mode.parent
     }
 
+    pub(crate) fn get_display_order(&self) -> u32 {
+        self.surreal_mode.display_order
+    }
+
     pub(crate) fn get_surreal_id(&self) -> &'s Thing {
         self.surreal_mode
             .id
@@ -1,3 +1,4 @@
+pub(crate) mod node_shapes;
 pub(crate) mod parent_lookup;
 
 use crate::{
@@ -13,6 +14,7 @@ use chrono::{DateTime, Utc};
 use ouroboros::self_referencing;
 use surrealdb::opt::RecordId;
 
+use node_shapes::NodeShapes;
 use parent_lookup::ParentLookup;
 
 #[self_referencing]
@@ -45,19 +47,31 @@ pub(crate) struct CalculatedData {
 
 impl CalculatedData {
     pub(crate) fn new_from_base_data(base_data: BaseData) -> Self {
+        CalculatedData::new_from_base_data_and_shapes(base_data, &mut NodeShapes::default(), None)
+    }
+
+    /// Reuses the shapes from the snapshot before this one for the items that `changed` did not touch, see
+    /// `NodeShapes::update`
+    pub(crate) fn new_from_base_data_and_shapes(
+        base_data: BaseData,
+        node_shapes: &mut NodeShapes,
+        changed: Option<&[RecordId]>,
+    ) -> Self {
         CalculatedDataBuilder {
             base_data,
             parent_lookup_builder: |base_data| {
                 ParentLookup::new(base_data.get_items())
             },
             items_nodes_builder: |base_data, parent_lookup| {
-                base_data
-                    .get_items()
+                let all_items = base_data.get_items();
+                node_shapes.update(all_items, parent_lookup, changed);
+                all_items
                     .iter()
                     .map(|(k, x)| {
+                        let shape = node_shapes.get(k).expect("update makes a shape for every item");
                         (
                             *k,
-                            ItemNode::new(x, base_data.get_items(), parent_lookup, base_data.get_events(), base_data.get_time_spent_log()),
+                            ItemNode::new_from_shape(x, shape, all_items, base_data.get_events(), base_data.get_time_spent_log()),
                         )
                     })
                     .collect::<HashMap<_, _>>()
@@ -99,7 +113,7 @@ impl CalculatedData {
                 match surreal_current_mode {
                     None => CurrentMode::default(),
                     Some(surreal_current_mode) =>
-                        CurrentMode::new(surreal_current_mode)
+                        CurrentMode::new(surreal_current_mode, base_data.get_modes())
                 }
             },
             mode_nodes_builder: |base_data| {
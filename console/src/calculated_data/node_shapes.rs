@@ -0,0 +1,307 @@
+use std::{iter, sync::Arc};
+
+use ahash::{HashMap, HashSet};
+use surrealdb::opt::RecordId;
+
+use crate::{
+    base_data::{Visited, item::Item},
+    node::item_node::{
+        GrowingItemNode, ShrinkingItemNode, create_growing_nodes, create_shrinking_nodes,
+    },
+};
+
+use super::parent_lookup::ParentLookup;
+
+/// The larger and smaller items of an item as record ids so they can be kept from one snapshot of the tables to the
+/// next. Finding them means walking the whole tree which is most of the work of making the item nodes.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ItemShape {
+    pub(crate) larger: Vec<GrowingShape>,
+    pub(crate) smaller: Vec<ShrinkingShape>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct GrowingShape {
+    pub(crate) id: RecordId,
+    pub(crate) larger: Vec<GrowingShape>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ShrinkingShape {
+    pub(crate) id: RecordId,
+    pub(crate) smaller: Vec<ShrinkingShape>,
+}
+
+impl ItemShape {
+    pub(crate) fn new<'s>(
+        item: &'s Item<'s>,
+        all_items: &'s HashMap<&'s RecordId, Item<'s>>,
+        parent_lookup: &'s ParentLookup<'s>,
+    ) -> Self {
+        let visited = Visited::new(item.get_surreal_record_id(), None);
+        let parents = item.find_parents(parent_lookup, &visited);
+        let parents = create_growing_nodes(parents, parent_lookup, &visited);
+        let visited: Vec<&RecordId> = iter::once(item.get_surreal_record_id())
+            .chain(parents.iter().flat_map(|x| {
+                x.get_self_and_parents(Vec::default())
+                    .into_iter()
+                    .map(|x| x.get_surreal_record_id())
+            }))
+            .collect();
+        let children = item.find_children(all_items, &visited);
+        let children = create_shrinking_nodes(&children, all_items, visited);
+        ItemShape {
+            larger: parents.iter().map(GrowingShape::new).collect(),
+            smaller: children.iter().map(ShrinkingShape::new).collect(),
+        }
+    }
+
+    fn add_ids_to(&self, ids: &mut HashSet<RecordId>) {
+        for larger in self.larger.iter() {
+            larger.add_ids_to(ids);
+        }
+        for smaller in self.smaller.iter() {
+            smaller.add_ids_to(ids);
+        }
+    }
+}
+
+impl GrowingShape {
+    fn new(node: &GrowingItemNode<'_>) -> Self {
+        GrowingShape {
+            id: node.get_surreal_record_id().clone(),
+            larger: node.larger.iter().map(GrowingShape::new).collect(),
+        }
+    }
+
+    pub(crate) fn to_node<'s>(
+        &self,
+        all_items: &'s HashMap<&'s RecordId, Item<'s>>,
+    ) -> GrowingItemNode<'s> {
+        GrowingItemNode {
+            item: all_items
+                .get(&self.id)
+                .expect("Shapes are updated whenever the items change"),
+            larger: self.larger.iter().map(|x| x.to_node(all_items)).collect(),
+        }
+    }
+
+    fn add_ids_to(&self, ids: &mut HashSet<RecordId>) {
+        ids.insert(self.id.clone());
+        for larger in self.larger.iter() {
+            larger.add_ids_to(ids);
+        }
+    }
+}
+
+impl ShrinkingShape {
+    fn new(node: &ShrinkingItemNode<'_>) -> Self {
+        ShrinkingShape {
+            id: node.get_surreal_record_id().clone(),
+            smaller: node.smaller.iter().map(ShrinkingShape::new).collect(),
+        }
+    }
+
+    pub(crate) fn to_node<'s>(
+        &self,
+        all_items: &'s HashMap<&'s RecordId, Item<'s>>,
+    ) -> ShrinkingItemNode<'s> {
+        ShrinkingItemNode {
+            item: all_items
+                .get(&self.id)
+                .expect("Shapes are updated whenever the items change"),
+            smaller: self.smaller.iter().map(|x| x.to_node(all_items)).collect(),
+        }
+    }
+
+    fn add_ids_to(&self, ids: &mut HashSet<RecordId>) {
+        ids.insert(self.id.clone());
+        for smaller in self.smaller.iter() {
+            smaller.add_ids_to(ids);
+        }
+    }
+}
+
+/// The shape of every item, kept between snapshots of the tables so only the items a change touched are worked out
+/// again
+#[derive(Default)]
+pub(crate) struct NodeShapes {
+    shapes: HashMap<RecordId, Arc<ItemShape>>,
+}
+
+impl NodeShapes {
+    /// `changed` is the records saved or deleted since the last update, None when that is not known so every shape is
+    /// worked out again.
+    ///
+    /// The shape of a changed item lists every item whose shape could include it, its larger items above it and, as
+    /// each shape also holds the larger items of the item, its smaller items below it. So those are worked out again
+    /// using the shape from before the change, for what it used to touch, and after, for what it touches now.
+    pub(crate) fn update<'s>(
+        &mut self,
+        all_items: &'s HashMap<&'s RecordId, Item<'s>>,
+        parent_lookup: &'s ParentLookup<'s>,
+        changed: Option<&[RecordId]>,
+    ) {
+        match changed {
+            None => self.shapes.clear(),
+            Some(changed) => {
+                let mut affected = HashSet::default();
+                for id in changed.iter() {
+                    if let Some(before) = self.shapes.remove(id) {
+                        before.add_ids_to(&mut affected);
+                    }
+                    if let Some(item) = all_items.get(id) {
+                        ItemShape::new(item, all_items, parent_lookup).add_ids_to(&mut affected);
+                    }
+                }
+                for id in affected.iter() {
+                    self.shapes.remove(id);
+                }
+            }
+        }
+        self.shapes.retain(|id, _| all_items.contains_key(id));
+        for (id, item) in all_items.iter() {
+            if !self.shapes.contains_key(*id) {
+                let shape = ItemShape::new(item, all_items, parent_lookup);
+                self.shapes.insert((*id).clone(), Arc::new(shape));
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, id: &RecordId) -> Option<&Arc<ItemShape>> {
+        self.shapes.get(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::Utc;
+    use surrealdb::opt::RecordId;
+
+    use crate::{
+        calculated_data::parent_lookup::ParentLookup,
+        data_storage::surrealdb_layer::{
+            surreal_item::{
+                SurrealItem, SurrealItemBuilder, SurrealItemType, SurrealOrderedSubItem,
+            },
+            surreal_tables::{SurrealTables, SurrealTablesBuilder},
+        },
+    };
+
+    use super::NodeShapes;
+
+    fn item(id: &str, smaller: &[&str]) -> SurrealItem {
+        SurrealItemBuilder::default()
+            .id(Some(("surreal_item", id).into()))
+            .summary(id)
+            .item_type(SurrealItemType::Action)
+            .smaller_items_in_priority_order(
+                smaller
+                    .iter()
+                    .map(|x| SurrealOrderedSubItem::SubItem {
+                        surreal_item_id: ("surreal_item", *x).into(),
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .build()
+            .unwrap()
+    }
+
+    fn update(
+        node_shapes: &mut NodeShapes,
+        surreal_tables: &SurrealTables,
+        changed: Option<&[RecordId]>,
+    ) {
+        let now = Utc::now();
+        let items = surreal_tables.make_items(&now);
+        let parent_lookup = ParentLookup::new(&items);
+        node_shapes.update(&items, &parent_lookup, changed);
+    }
+
+    #[test]
+    fn only_the_items_a_change_touches_are_worked_out_again() {
+        let id = |x: &str| -> RecordId { ("surreal_item", x).into() };
+        let before = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                item("goal", &["step"]),
+                item("step", &["detail"]),
+                item("detail", &[]),
+                item("other goal", &["other step"]),
+                item("other step", &[]),
+            ])
+            .build()
+            .expect("no required fields");
+        let mut node_shapes = NodeShapes::default();
+        update(&mut node_shapes, &before, None);
+        let shapes_before = ["goal", "step", "detail", "other goal", "other step"]
+            .map(|x| node_shapes.get(&id(x)).unwrap().clone());
+
+        //A new smaller item is added under step
+        let after = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                item("goal", &["step"]),
+                item("step", &["detail", "new detail"]),
+                item("detail", &[]),
+                item("new detail", &[]),
+                item("other goal", &["other step"]),
+                item("other step", &[]),
+            ])
+            .build()
+            .expect("no required fields");
+        update(
+            &mut node_shapes,
+            &after,
+            Some(&[id("step"), id("new detail")]),
+        );
+        let shapes_after = ["goal", "step", "detail", "other goal", "other step"]
+            .map(|x| node_shapes.get(&id(x)).unwrap().clone());
+
+        //The larger and smaller items of step are worked out again
+        assert!(!Arc::ptr_eq(&shapes_before[0], &shapes_after[0]));
+        assert!(!Arc::ptr_eq(&shapes_before[1], &shapes_after[1]));
+        assert!(!Arc::ptr_eq(&shapes_before[2], &shapes_after[2]));
+        assert_eq!(shapes_before[2], shapes_after[2]);
+        assert_eq!(shapes_after[1].smaller.len(), 2);
+        assert_eq!(
+            node_shapes.get(&id("new detail")).unwrap().larger[0].id,
+            id("step")
+        );
+
+        //Items that are not connected to step are reused
+        assert!(Arc::ptr_eq(&shapes_before[3], &shapes_after[3]));
+        assert!(Arc::ptr_eq(&shapes_before[4], &shapes_after[4]));
+
+        //Every shape is the same as if it were all worked out from scratch
+        let mut from_scratch = NodeShapes::default();
+        update(&mut from_scratch, &after, None);
+        for (id, shape) in from_scratch.shapes.iter() {
+            assert_eq!(node_shapes.get(id), Some(shape));
+        }
+        assert_eq!(node_shapes.shapes.len(), from_scratch.shapes.len());
+
+        //Deleting an item drops its shape and works out again the shapes that included it
+        let deleted = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                item("goal", &["step"]),
+                item("step", &["detail"]),
+                item("detail", &[]),
+                item("other goal", &["other step"]),
+                item("other step", &[]),
+            ])
+            .build()
+            .expect("no required fields");
+        update(
+            &mut node_shapes,
+            &deleted,
+            Some(&[id("step"), id("new detail")]),
+        );
+        assert!(node_shapes.get(&id("new detail")).is_none());
+        assert_eq!(node_shapes.get(&id("goal")), Some(&shapes_before[0]));
+        assert!(Arc::ptr_eq(
+            &shapes_before[3],
+            node_shapes.get(&id("other goal")).unwrap()
+        ));
+    }
+}
@@ -0,0 +1,639 @@
+use std::{
+    env,
+    error::Error,
+    ffi::{OsStr, OsString},
+    path::PathBuf,
+    time::Duration,
+};
+
+/// The name of the environment variable that can be used to give the location of the database file
+pub(crate) const DATABASE_ENVIRONMENT_VARIABLE: &str = "ON_PURPOSE_DB";
+
+const DEFAULT_PROFILE: &str = "default";
+
+const DEFAULT_BACKUP_INTERVAL_MINUTES: u64 = 60;
+const DEFAULT_KEEP_DAILY: usize = 7;
+const DEFAULT_KEEP_WEEKLY: usize = 4;
+
+/// How long ago an item must have been finished before it is archived, when no other age is given
+pub(crate) const DEFAULT_ARCHIVE_OLDER_THAN_DAYS: u32 = 90;
+
+pub(crate) struct CommandLine {
+    pub(crate) command: Command,
+    pub(crate) database: DatabaseLocation,
+    pub(crate) backup_schedule: BackupSchedule,
+    /// A folder shared between devices to sync through, None means this device does not sync
+    pub(crate) sync_folder: Option<PathBuf>,
+    /// Create an encrypted database, an existing encrypted database is used even if this is not given
+    pub(crate) encrypted: bool,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub(crate) enum Command {
+    /// Present the "Do Now" list, this is the normal way to run On Purpose
+    Run,
+    PrintHelp,
+    /// Write every table to a versioned JSON document, to standard output if no file is given
+    Export {
+        to: Option<PathBuf>,
+    },
+    /// Load a JSON document that was created by export, replace must be given if the database already has data
+    Import {
+        from: PathBuf,
+        replace: bool,
+    },
+    /// Look for problems in how the records refer to each other and offer to repair them
+    Check,
+    /// Move the items finished more than `older_than_days` ago, and the time spent on them, into the archive
+    Archive {
+        older_than_days: u32,
+    },
+    /// Replace the database with a backup, after asking to be sure
+    Restore {
+        from: PathBuf,
+    },
+    /// Encrypt the database with a new passphrase
+    ChangePassphrase,
+}
+
+/// A backup is made when On Purpose starts and then every `interval`, None means only when it starts. The newest
+/// backup from each of the last `keep_daily` days and each of the last `keep_weekly` weeks is kept.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub(crate) struct BackupSchedule {
+    pub(crate) interval: Option<Duration>,
+    pub(crate) keep_daily: usize,
+    pub(crate) keep_weekly: usize,
+}
+
+impl Default for BackupSchedule {
+    fn default() -> Self {
+        BackupSchedule {
+            interval: Some(Duration::from_secs(DEFAULT_BACKUP_INTERVAL_MINUTES * 60)),
+            keep_daily: DEFAULT_KEEP_DAILY,
+            keep_weekly: DEFAULT_KEEP_WEEKLY,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub(crate) enum DatabaseLocation {
+    InMemory,
+    File(PathBuf),
+}
+
+impl DatabaseLocation {
+    /// The endpoint string that SurrealDB expects, for example "mem://" or "file://c:/.on_purpose.db"
+    pub(crate) fn to_endpoint(&self) -> String {
+        match self {
+            DatabaseLocation::InMemory => "mem://".to_string(),
+            DatabaseLocation::File(path) => format!("file://{}", path.display()),
+        }
+    }
+
+    /// Backups are kept next to the database, for example "default.db" is backed up to "default.backups"
+    pub(crate) fn backup_directory(&self) -> Option<PathBuf> {
+        match self {
+            DatabaseLocation::InMemory => None,
+            DatabaseLocation::File(path) => {
+                let file_stem = path.file_stem().unwrap_or(path.as_os_str());
+                let mut backup_directory_name = file_stem.to_os_string();
+                backup_directory_name.push(".backups");
+                Some(path.with_file_name(backup_directory_name))
+            }
+        }
+    }
+
+    /// The encrypted database is kept next to where SurrealDB would keep the unencrypted one, for example "default.db"
+    /// is encrypted to "default.encrypted"
+    pub(crate) fn encrypted_file(&self) -> Option<PathBuf> {
+        match self {
+            DatabaseLocation::InMemory => None,
+            DatabaseLocation::File(path) => Some(path.with_extension("encrypted")),
+        }
+    }
+}
+
+impl CommandLine {
+    pub(crate) fn from_env() -> Result<Self, Box<dyn Error>> {
+        Self::parse(
+            env::args_os().skip(1).collect(),
+            env::var_os(DATABASE_ENVIRONMENT_VARIABLE),
+            dirs_next::data_dir(),
+        )
+    }
+
+    /// `arguments` should not include the name of the executable. `environment_database` is the value of the
+    /// ON_PURPOSE_DB environment variable and `data_dir` is the platform data directory, for example
+    /// $XDG_DATA_HOME or ~/.local/share on Linux and %APPDATA% on Windows. These are passed in so this can be tested.
+    pub(crate) fn parse(
+        arguments: Vec<OsString>,
+        environment_database: Option<OsString>,
+        data_dir: Option<PathBuf>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut arguments = pico_args::Arguments::from_vec(arguments);
+
+        if arguments.contains(["-h", "--help"]) {
+            return Ok(CommandLine {
+                command: Command::PrintHelp,
+                database: DatabaseLocation::InMemory,
+                backup_schedule: BackupSchedule::default(),
+                sync_folder: None,
+                encrypted: false,
+            });
+        }
+
+        //inmemorydb was the original way to ask for an in memory database so it is still supported
+        let subcommand = arguments.subcommand()?;
+        let in_memory =
+            subcommand.as_deref() == Some("inmemorydb") || arguments.contains("--in-memory");
+        let database_path: Option<PathBuf> = arguments.opt_value_from_os_str("--db", parse_path)?;
+        let profile: Option<String> = arguments.opt_value_from_str("--profile")?;
+        let backup_interval_minutes: Option<u64> =
+            arguments.opt_value_from_str("--backup-interval")?;
+        let keep_daily: Option<usize> = arguments.opt_value_from_str("--keep-daily")?;
+        let keep_weekly: Option<usize> = arguments.opt_value_from_str("--keep-weekly")?;
+        let sync_folder: Option<PathBuf> =
+            arguments.opt_value_from_os_str("--sync-folder", parse_path)?;
+        let encrypted = arguments.contains("--encrypted");
+        let default_backup_schedule = BackupSchedule::default();
+        let backup_schedule = BackupSchedule {
+            interval: match backup_interval_minutes {
+                //Zero turns off the backups while running, one is still made when starting
+                Some(0) => None,
+                Some(minutes) => Some(Duration::from_secs(minutes * 60)),
+                None => default_backup_schedule.interval,
+            },
+            keep_daily: keep_daily.unwrap_or(default_backup_schedule.keep_daily),
+            keep_weekly: keep_weekly.unwrap_or(default_backup_schedule.keep_weekly),
+        };
+
+        //Free arguments must be parsed last
+        let command = match subcommand.as_deref() {
+            None | Some("inmemorydb") => Command::Run,
+            Some("export") => Command::Export {
+                to: arguments.opt_free_from_os_str(parse_path)?,
+            },
+            Some("import") => {
+                let replace = arguments.contains("--replace");
+                let from = arguments
+                    .opt_free_from_os_str(parse_path)?
+                    .ok_or("import needs the path of the file to import")?;
+                Command::Import { from, replace }
+            }
+            Some("check") => Command::Check,
+            Some("archive") => Command::Archive {
+                older_than_days: arguments
+                    .opt_value_from_str("--older-than")?
+                    .unwrap_or(DEFAULT_ARCHIVE_OLDER_THAN_DAYS),
+            },
+            Some("restore") => Command::Restore {
+                from: arguments
+                    .opt_value_from_os_str("--from", parse_path)?
+                    .ok_or(
+                        "restore needs --from <backup> with the path of the backup to restore",
+                    )?,
+            },
+            Some("change-passphrase") => Command::ChangePassphrase,
+            Some(unknown) => {
+                return Err(format!("Unknown command \"{}\"\n\n{}", unknown, help_string()).into());
+            }
+        };
+
+        let remaining = arguments.finish();
+        if !remaining.is_empty() {
+            return Err(format!(
+                "Unexpected argument(s): {:?}\n\n{}",
+                remaining,
+                help_string()
+            )
+            .into());
+        }
+
+        let database = if in_memory {
+            if database_path.is_some() || profile.is_some() {
+                return Err(
+                    "An in memory database cannot be combined with --db or --profile".into(),
+                );
+            }
+            if encrypted {
+                return Err(
+                    "An in memory database is never saved so it cannot be encrypted".into(),
+                );
+            }
+            DatabaseLocation::InMemory
+        } else if let Some(database_path) = database_path {
+            if profile.is_some() {
+                return Err("--db and --profile cannot be used together".into());
+            }
+            DatabaseLocation::File(database_path)
+        } else if let Some(profile) = profile {
+            DatabaseLocation::File(profile_path(&profile, data_dir)?)
+        } else if let Some(environment_database) = environment_database {
+            DatabaseLocation::File(environment_database.into())
+        } else {
+            DatabaseLocation::File(default_path(data_dir)?)
+        };
+
+        Ok(CommandLine {
+            command,
+            database,
+            backup_schedule,
+            sync_folder,
+            encrypted,
+        })
+    }
+}
+
+fn parse_path(path: &OsStr) -> Result<PathBuf, &'static str> {
+    if path.is_empty() {
+        Err("the path cannot be empty")
+    } else {
+        Ok(path.into())
+    }
+}
+
+fn profile_path(profile: &str, data_dir: Option<PathBuf>) -> Result<PathBuf, Box<dyn Error>> {
+    //The profile name becomes part of a file name so only allow characters that are safe on every platform
+    if profile.is_empty()
+        || !profile
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "Invalid profile name \"{}\", only letters, numbers, '-', and '_' are allowed",
+            profile
+        )
+        .into());
+    }
+
+    let data_dir = data_dir.ok_or(
+        "Unable to find the data directory for this platform, use --db to give the database location",
+    )?;
+    Ok(data_dir
+        .join("on_purpose")
+        .join(format!("{}.db", profile.to_lowercase())))
+}
+
+fn default_path(data_dir: Option<PathBuf>) -> Result<PathBuf, Box<dyn Error>> {
+    //Before there was a way to give the database location it was always stored here on Windows, keep using it if it exists
+    #[cfg(windows)]
+    {
+        let legacy_path = PathBuf::from("c:/.on_purpose.db");
+        if legacy_path.exists() {
+            return Ok(legacy_path);
+        }
+    }
+
+    profile_path(DEFAULT_PROFILE, data_dir)
+}
+
+pub(crate) fn help_string() -> String {
+    format!(
+        concat!(
+            "Usage: on_purpose [OPTIONS]\n",
+            "       on_purpose export [<file>] [OPTIONS]\n",
+            "       on_purpose import <file> [--replace] [OPTIONS]\n",
+            "       on_purpose restore --from <backup> [OPTIONS]\n",
+            "       on_purpose check [OPTIONS]\n",
+            "       on_purpose archive [--older-than <days>] [OPTIONS]\n",
+            "       on_purpose change-passphrase [OPTIONS]\n",
+            "\n",
+            "Commands:\n",
+            "  export [<file>]      Write all of the data to a JSON file, or to the console if no file is given\n",
+            "  import <file>        Load a JSON file that was made by export, use --replace to overwrite existing data\n",
+            "  restore --from <backup>\n",
+            "                       Replace all of the data with a backup, a copy of the data being replaced is kept\n",
+            "  check                Look for problems like references to items that no longer exist and offer to repair them\n",
+            "  archive [--older-than <days>]\n",
+            "                       Move items finished more than <days> ago out of the way, they can still be searched\n",
+            "                       and brought back (default {} days)\n",
+            "  change-passphrase    Pick a new passphrase for an encrypted database\n",
+            "\n",
+            "Options:\n",
+            "  --db <path>          Location of the database to use\n",
+            "  --profile <name>     Use a separate named database, for example \"work\" or \"home\"\n",
+            "  --in-memory          Use an in memory database that is not saved when the program exits\n",
+            "  --backup-interval <minutes>\n",
+            "                       How often to back up while running, 0 means only when starting (default {})\n",
+            "  --keep-daily <count> How many days to keep a backup from (default {})\n",
+            "  --keep-weekly <count>\n",
+            "                       How many weeks to keep a backup from (default {})\n",
+            "  --sync-folder <path> Sync with other devices through a shared folder, for example one synced by\n",
+            "                       OneDrive or Syncthing, give each device the same folder. It cannot be used\n",
+            "                       with an encrypted database because the changes are not encrypted\n",
+            "  --encrypted          Create an encrypted database, a passphrase is asked for when starting and the\n",
+            "                       exports and backups are encrypted too\n",
+            "  -h, --help           Print this help\n",
+            "\n",
+            "If neither --db or --profile is given then the {} environment variable is used, if it is\n",
+            "set, otherwise the default profile in the data directory for this platform is used.\n",
+            "\n",
+            "Backups are kept in a directory next to the database, for example default.backups for default.db.\n",
+            "An encrypted database is kept next to it too, for example default.encrypted, and is used whenever it\n",
+            "exists. To encrypt an existing database export it, start with --encrypted, and import the export."
+        ),
+        DEFAULT_ARCHIVE_OLDER_THAN_DAYS,
+        DEFAULT_BACKUP_INTERVAL_MINUTES,
+        DEFAULT_KEEP_DAILY,
+        DEFAULT_KEEP_WEEKLY,
+        DATABASE_ENVIRONMENT_VARIABLE
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, path::PathBuf};
+
+    use std::time::Duration;
+
+    use super::{
+        BackupSchedule, Command, CommandLine, DEFAULT_ARCHIVE_OLDER_THAN_DAYS, DatabaseLocation,
+    };
+
+    fn arguments(arguments: &[&str]) -> Vec<OsString> {
+        arguments.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn no_arguments_uses_the_default_profile_in_the_data_directory() {
+        let command_line =
+            CommandLine::parse(arguments(&[]), None, Some(PathBuf::from("/data"))).unwrap();
+
+        assert_eq!(
+            command_line.database,
+            DatabaseLocation::File(PathBuf::from("/data/on_purpose/default.db"))
+        );
+    }
+
+    #[test]
+    fn db_argument_is_used_over_the_environment_variable() {
+        let command_line = CommandLine::parse(
+            arguments(&["--db", "/somewhere/else.db"]),
+            Some(OsString::from("/from/environment.db")),
+            Some(PathBuf::from("/data")),
+        )
+        .unwrap();
+
+        assert_eq!(
+            command_line.database,
+            DatabaseLocation::File(PathBuf::from("/somewhere/else.db"))
+        );
+    }
+
+    #[test]
+    fn environment_variable_is_used_when_no_arguments_are_given() {
+        let command_line = CommandLine::parse(
+            arguments(&[]),
+            Some(OsString::from("/from/environment.db")),
+            Some(PathBuf::from("/data")),
+        )
+        .unwrap();
+
+        assert_eq!(
+            command_line.database,
+            DatabaseLocation::File(PathBuf::from("/from/environment.db"))
+        );
+    }
+
+    #[test]
+    fn each_profile_gets_its_own_database_file() {
+        let work = CommandLine::parse(
+            arguments(&["--profile", "work"]),
+            Some(OsString::from("/from/environment.db")),
+            Some(PathBuf::from("/data")),
+        )
+        .unwrap();
+        let home = CommandLine::parse(
+            arguments(&["--profile", "Home"]),
+            None,
+            Some(PathBuf::from("/data")),
+        )
+        .unwrap();
+
+        assert_eq!(
+            work.database,
+            DatabaseLocation::File(PathBuf::from("/data/on_purpose/work.db"))
+        );
+        assert_eq!(
+            home.database,
+            DatabaseLocation::File(PathBuf::from("/data/on_purpose/home.db"))
+        );
+    }
+
+    #[test]
+    fn a_profile_name_that_is_not_a_safe_file_name_is_an_error() {
+        let result = CommandLine::parse(
+            arguments(&["--profile", "../work"]),
+            None,
+            Some(PathBuf::from("/data")),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn inmemorydb_is_still_supported() {
+        let legacy = CommandLine::parse(
+            arguments(&["inmemorydb"]),
+            None,
+            Some(PathBuf::from("/data")),
+        )
+        .unwrap();
+        let flag = CommandLine::parse(
+            arguments(&["--in-memory"]),
+            None,
+            Some(PathBuf::from("/data")),
+        )
+        .unwrap();
+
+        assert_eq!(legacy.database, DatabaseLocation::InMemory);
+        assert_eq!(flag.database, DatabaseLocation::InMemory);
+        assert_eq!(legacy.database.to_endpoint(), "mem://");
+        assert_eq!(legacy.database.backup_directory(), None);
+    }
+
+    #[test]
+    fn backups_are_kept_next_to_the_database() {
+        let database = DatabaseLocation::File(PathBuf::from("/data/on_purpose/work.db"));
+
+        assert_eq!(
+            database.backup_directory(),
+            Some(PathBuf::from("/data/on_purpose/work.backups"))
+        );
+    }
+
+    #[test]
+    fn help_is_printed_when_asked_for() {
+        let command_line =
+            CommandLine::parse(arguments(&["--help"]), None, Some(PathBuf::from("/data"))).unwrap();
+
+        assert_eq!(command_line.command, Command::PrintHelp);
+    }
+
+    #[test]
+    fn export_and_import_take_a_file() {
+        let export = CommandLine::parse(
+            arguments(&["export", "--profile", "work", "out.json"]),
+            None,
+            Some(PathBuf::from("/data")),
+        )
+        .unwrap();
+        let import = CommandLine::parse(
+            arguments(&["import", "in.json", "--replace"]),
+            None,
+            Some(PathBuf::from("/data")),
+        )
+        .unwrap();
+
+        assert_eq!(
+            export.command,
+            Command::Export {
+                to: Some(PathBuf::from("out.json"))
+            }
+        );
+        assert_eq!(
+            export.database,
+            DatabaseLocation::File(PathBuf::from("/data/on_purpose/work.db"))
+        );
+        assert_eq!(
+            import.command,
+            Command::Import {
+                from: PathBuf::from("in.json"),
+                replace: true
+            }
+        );
+    }
+
+    #[test]
+    fn restore_takes_the_backup_to_restore_and_the_backup_schedule_can_be_changed() {
+        let restore = CommandLine::parse(
+            arguments(&["restore", "--from", "backup.json"]),
+            None,
+            Some(PathBuf::from("/data")),
+        )
+        .unwrap();
+        let schedule = CommandLine::parse(
+            arguments(&["--backup-interval", "0", "--keep-daily", "3"]),
+            None,
+            Some(PathBuf::from("/data")),
+        )
+        .unwrap();
+
+        assert_eq!(
+            restore.command,
+            Command::Restore {
+                from: PathBuf::from("backup.json")
+            }
+        );
+        assert_eq!(restore.backup_schedule, BackupSchedule::default());
+        assert_eq!(
+            restore.backup_schedule.interval,
+            Some(Duration::from_secs(60 * 60))
+        );
+        assert_eq!(
+            schedule.backup_schedule,
+            BackupSchedule {
+                interval: None,
+                keep_daily: 3,
+                keep_weekly: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn sync_folder_is_only_set_when_given() {
+        let syncing = CommandLine::parse(
+            arguments(&["--sync-folder", "/shared/on_purpose"]),
+            None,
+            Some(PathBuf::from("/data")),
+        )
+        .unwrap();
+        let not_syncing =
+            CommandLine::parse(arguments(&[]), None, Some(PathBuf::from("/data"))).unwrap();
+
+        assert_eq!(
+            syncing.sync_folder,
+            Some(PathBuf::from("/shared/on_purpose"))
+        );
+        assert_eq!(not_syncing.sync_folder, None);
+    }
+
+    #[test]
+    fn the_encrypted_database_is_kept_next_to_the_database() {
+        let encrypted = CommandLine::parse(
+            arguments(&["change-passphrase", "--encrypted", "--db", "/data/work.db"]),
+            None,
+            None,
+        )
+        .unwrap();
+        let in_memory = CommandLine::parse(arguments(&["--in-memory", "--encrypted"]), None, None);
+
+        assert_eq!(encrypted.command, Command::ChangePassphrase);
+        assert!(encrypted.encrypted);
+        assert_eq!(
+            encrypted.database.encrypted_file(),
+            Some(PathBuf::from("/data/work.encrypted"))
+        );
+        assert!(in_memory.is_err());
+    }
+
+    #[test]
+    fn check_is_a_command() {
+        let command_line =
+            CommandLine::parse(arguments(&["check"]), None, Some(PathBuf::from("/data"))).unwrap();
+
+        assert_eq!(command_line.command, Command::Check);
+    }
+
+    #[test]
+    fn archive_uses_the_default_age_unless_one_is_given() {
+        let default_age =
+            CommandLine::parse(arguments(&["archive"]), None, Some(PathBuf::from("/data")))
+                .unwrap();
+        let given_age = CommandLine::parse(
+            arguments(&["archive", "--older-than", "30"]),
+            None,
+            Some(PathBuf::from("/data")),
+        )
+        .unwrap();
+
+        assert_eq!(
+            default_age.command,
+            Command::Archive {
+                older_than_days: DEFAULT_ARCHIVE_OLDER_THAN_DAYS
+            }
+        );
+        assert_eq!(
+            given_age.command,
+            Command::Archive {
+                older_than_days: 30
+            }
+        );
+    }
+
+    #[test]
+    fn restore_without_a_backup_is_an_error() {
+        let result =
+            CommandLine::parse(arguments(&["restore"]), None, Some(PathBuf::from("/data")));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn import_without_a_file_is_an_error() {
+        let result = CommandLine::parse(arguments(&["import"]), None, Some(PathBuf::from("/data")));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn db_and_profile_together_is_an_error() {
+        let result = CommandLine::parse(
+            arguments(&["--db", "/somewhere.db", "--profile", "work"]),
+            None,
+            Some(PathBuf::from("/data")),
+        );
+
+        assert!(result.is_err());
+    }
+}
@@ -1 +1,6 @@
+pub(crate) mod backups;
+pub(crate) mod delete_and_merge;
+pub(crate) mod encryption;
+pub(crate) mod integrity;
+pub(crate) mod json_export;
 pub(crate) mod surrealdb_layer;
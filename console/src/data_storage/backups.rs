@@ -0,0 +1,458 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ahash::HashSet;
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, Utc};
+use inquire::{Confirm, InquireError};
+use tokio::sync::mpsc::Sender;
+
+use crate::command_line::BackupSchedule;
+
+use super::{
+    encryption::{self, EncryptionKey, encrypt_if_key},
+    json_export::ExportDocument,
+    surrealdb_layer::{
+        data_layer_commands::DataLayerCommands,
+        migrations::{BEFORE_MIGRATION_PREFIX, read_before_migration_backup},
+        surreal_archive::SurrealArchive,
+        surreal_tables::SurrealTables,
+    },
+};
+
+/// Only backups with this prefix are removed when rotating, other files in the directory, like the backups that are
+/// made before the database is upgraded, are left alone
+const BACKUP_PREFIX: &str = "backup_";
+const BEFORE_RESTORE_PREFIX: &str = "before_restore_";
+const BACKUP_EXTENSION: &str = "json";
+const ENCRYPTED_BACKUP_EXTENSION: &str = "encrypted";
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d_%H%M%S";
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub(crate) struct BackupSettings {
+    pub(crate) directory: PathBuf,
+    pub(crate) schedule: BackupSchedule,
+}
+
+/// Writes the tables and the archive to a new backup and then removes the backups that are no longer kept by the schedule.
+/// If there is an encryption key then the backup is encrypted with it.
+pub(crate) fn make_backup(
+    surreal_tables: &SurrealTables,
+    surreal_archive: &SurrealArchive,
+    settings: &BackupSettings,
+    encryption: Option<&EncryptionKey>,
+    now: NaiveDateTime,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let path = write_backup(
+        surreal_tables,
+        surreal_archive,
+        &settings.directory,
+        BACKUP_PREFIX,
+        encryption,
+        now,
+    )?;
+
+    let mut backups = Vec::default();
+    for entry in fs::read_dir(&settings.directory)? {
+        let path = entry?.path();
+        if let Some(taken) = backup_taken(&path) {
+            backups.push((path, taken));
+        }
+    }
+    for remove in backups_to_remove(
+        &backups,
+        settings.schedule.keep_daily,
+        settings.schedule.keep_weekly,
+    ) {
+        fs::remove_file(remove)?;
+    }
+    Ok(path)
+}
+
+fn write_backup(
+    surreal_tables: &SurrealTables,
+    surreal_archive: &SurrealArchive,
+    directory: &Path,
+    prefix: &str,
+    encryption: Option<&EncryptionKey>,
+    now: NaiveDateTime,
+) -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(directory)?;
+    let path = directory.join(format!(
+        "{}{}.{}",
+        prefix,
+        now.format(TIMESTAMP_FORMAT),
+        if encryption.is_some() {
+            ENCRYPTED_BACKUP_EXTENSION
+        } else {
+            BACKUP_EXTENSION
+        }
+    ));
+    let document = ExportDocument::new(
+        surreal_tables.clone(),
+        surreal_archive.clone(),
+        Utc::now().into(),
+    );
+    //Written to a temporary file first so a backup is never left half written if the program is closed part way through
+    let temporary_path = path.with_extension("partial");
+    let contents = encrypt_if_key(document.to_json()?.into_bytes(), encryption)?;
+    fs::write(&temporary_path, contents)?;
+    fs::rename(&temporary_path, &path)?;
+    Ok(path)
+}
+
+/// The backups in `directory` that are not encrypted, for example the ones made before the database was encrypted. The
+/// rotated backups are removed as newer encrypted ones are made but the others, like the copies made before a restore
+/// or an upgrade, are kept until they are deleted by hand.
+pub(crate) fn plain_text_backups(directory: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        //No backups have been made yet
+        return Vec::default();
+    };
+    let mut plain_text = entries
+        .filter_map(|x| x.ok().map(|x| x.path()))
+        .filter(|x| {
+            x.is_file()
+                && x.extension()
+                    .is_none_or(|x| x != ENCRYPTED_BACKUP_EXTENSION)
+        })
+        .collect::<Vec<_>>();
+    plain_text.sort();
+    plain_text
+}
+
+/// When the backup was taken, from the file name, or None if the file is not a backup that is rotated. Encrypted and
+/// unencrypted backups are rotated together.
+fn backup_taken(path: &Path) -> Option<NaiveDateTime> {
+    let extension = path.extension()?;
+    if extension != BACKUP_EXTENSION && extension != ENCRYPTED_BACKUP_EXTENSION {
+        return None;
+    }
+    let timestamp = path.file_stem()?.to_str()?.strip_prefix(BACKUP_PREFIX)?;
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()
+}
+
+/// The newest backup from each of the most recent `keep_daily` days and from each of the most recent `keep_weekly`
+/// weeks is kept, everything else is returned to be removed
+fn backups_to_remove(
+    backups: &[(PathBuf, NaiveDateTime)],
+    keep_daily: usize,
+    keep_weekly: usize,
+) -> Vec<PathBuf> {
+    let mut newest_first = backups.iter().collect::<Vec<_>>();
+    newest_first.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let mut days = HashSet::default();
+    let mut weeks = HashSet::default();
+    let mut keep = HashSet::default();
+    for (path, taken) in newest_first.iter() {
+        let date = taken.date();
+        if days.len() < keep_daily && days.insert(date) {
+            keep.insert(path);
+        }
+        let week = date.iso_week();
+        if weeks.len() < keep_weekly && weeks.insert((week.year(), week.week())) {
+            keep.insert(path);
+        }
+    }
+
+    newest_first
+        .into_iter()
+        .filter(|(path, _)| !keep.contains(path))
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+/// Reads a backup made by `make_backup`, before a restore, or before upgrading the database, which is SurrealDB's own
+/// export from the schema version before the upgrade rather than JSON
+pub(crate) async fn read_backup(
+    from: &Path,
+    encryption: Option<&EncryptionKey>,
+) -> Result<ExportDocument, Box<dyn Error>> {
+    let contents = encryption::read_to_string(from, encryption)?;
+    let before_migration = from
+        .file_name()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| x.starts_with(BEFORE_MIGRATION_PREFIX));
+    if !before_migration {
+        return ExportDocument::from_json(&contents);
+    }
+    let (tables, archive) = read_before_migration_backup(contents).await?;
+    let taken = DateTime::<Utc>::from(fs::metadata(from)?.modified()?);
+    Ok(ExportDocument::new(tables, archive, taken.into()))
+}
+
+/// Replaces every table with what is in the backup after asking first. The data that is being replaced is saved to
+/// the backup directory so a restore can itself be undone by restoring that. A database in memory has no backup
+/// directory so it is saved next to the backup instead. An encrypted backup can be restored, if it was made with an
+/// earlier passphrase then that passphrase is asked for.
+pub(crate) async fn restore_from(
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+    from: &Path,
+    backup_directory: Option<&Path>,
+    encryption: Option<&EncryptionKey>,
+) -> Result<(), Box<dyn Error>> {
+    let document = read_backup(from, encryption).await?;
+
+    let confirmed = Confirm::new(&format!(
+        "Replace all of the data in the database with the backup taken {}, which has {} items?",
+        document.exported,
+        document.tables.surreal_items.len()
+    ))
+    .with_default(false)
+    .prompt();
+    match confirmed {
+        Ok(true) => {}
+        Ok(false) | Err(InquireError::OperationCanceled) => {
+            println!("Nothing was restored");
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    let backup_directory =
+        backup_directory.unwrap_or_else(|| from.parent().unwrap_or(Path::new("")));
+    let current = SurrealTables::new(send_to_data_storage_layer).await?;
+    let current_archive = SurrealArchive::new(send_to_data_storage_layer).await?;
+    if !current.is_empty() || !current_archive.is_empty() {
+        //Nothing is restored unless the data being replaced was saved first
+        let saved_to = write_backup(
+            &current,
+            &current_archive,
+            backup_directory,
+            BEFORE_RESTORE_PREFIX,
+            encryption,
+            Local::now().naive_local(),
+        )?;
+        println!(
+            "The data being replaced was saved to {}",
+            saved_to.display()
+        );
+    }
+
+    let items_count = document.tables.surreal_items.len();
+    DataLayerCommands::import_raw_data(
+        send_to_data_storage_layer,
+        document.tables,
+        document.archive,
+        true,
+    )
+    .await?;
+    println!("Restored {} items from {}", items_count, from.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, process};
+
+    use chrono::{NaiveDate, NaiveDateTime};
+    use surrealdb::engine::any::connect;
+    use tokio::sync::mpsc;
+
+    use crate::{
+        command_line::BackupSchedule,
+        data_storage::{
+            encryption::{EncryptionKey, is_encrypted},
+            surrealdb_layer::{
+                data_layer_commands::{
+                    DATABASE, DataLayerCommands, DataStorageSettings, NAMESPACE,
+                    data_storage_start_and_run, load_from_surrealdb,
+                },
+                migrations::{BEFORE_MIGRATION_PREFIX, migrate_if_needed},
+                surreal_archive::SurrealArchive,
+                surreal_item::{
+                    SurrealItem, SurrealItemBuilder, SurrealItemOldVersion,
+                    SurrealItemOldVersionBuilder, SurrealItemType, SurrealMotivationKind,
+                },
+                surreal_tables::{SurrealTables, SurrealTablesBuilder},
+            },
+        },
+    };
+
+    use super::{
+        BackupSettings, backup_taken, backups_to_remove, make_backup, plain_text_backups,
+        read_backup,
+    };
+
+    fn backup(year: i32, month: u32, day: u32, hour: u32) -> (PathBuf, NaiveDateTime) {
+        let taken = NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap();
+        (
+            PathBuf::from(format!("backup_{}.json", taken.format("%Y-%m-%d_%H%M%S"))),
+            taken,
+        )
+    }
+
+    #[test]
+    fn the_newest_backup_of_each_recent_day_and_week_is_kept() {
+        //2025-03-03 is a Monday
+        let backups = vec![
+            backup(2025, 2, 17, 9),
+            backup(2025, 2, 24, 9),
+            backup(2025, 2, 26, 9),
+            backup(2025, 3, 3, 9),
+            backup(2025, 3, 4, 9),
+            backup(2025, 3, 5, 9),
+            backup(2025, 3, 5, 10),
+        ];
+
+        let mut removed = backups_to_remove(&backups, 2, 2);
+        removed.sort();
+
+        //Kept: the newest from the 5th and the 4th (daily) and the newest from this week and last week (weekly)
+        assert_eq!(
+            removed,
+            vec![
+                backups[0].0.clone(),
+                backups[1].0.clone(),
+                backups[3].0.clone(),
+                backups[5].0.clone(),
+            ]
+        );
+    }
+
+    #[test]
+    fn making_a_backup_removes_the_ones_that_are_no_longer_kept() {
+        let directory = std::env::temp_dir().join(format!("on_purpose_backups_{}", process::id()));
+        let settings = BackupSettings {
+            directory: directory.clone(),
+            schedule: BackupSchedule {
+                interval: None,
+                keep_daily: 1,
+                keep_weekly: 1,
+            },
+        };
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                SurrealItemBuilder::default()
+                    .id(Some(("item", "1").into()))
+                    .summary("Backed up")
+                    .build()
+                    .unwrap(),
+            ])
+            .build()
+            .unwrap();
+
+        let first = make_backup(
+            &surreal_tables,
+            &SurrealArchive::default(),
+            &settings,
+            None,
+            backup(2025, 3, 5, 9).1,
+        )
+        .unwrap();
+        let second = make_backup(
+            &surreal_tables,
+            &SurrealArchive::default(),
+            &settings,
+            None,
+            backup(2025, 3, 5, 10).1,
+        )
+        .unwrap();
+
+        assert!(!first.exists());
+        assert!(second.exists());
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+
+        //Once the database is encrypted the older unencrypted backups are still rotated out
+        let key = EncryptionKey::new("correct horse battery staple").unwrap();
+        let third = make_backup(
+            &surreal_tables,
+            &SurrealArchive::default(),
+            &settings,
+            Some(&key),
+            backup(2025, 3, 5, 11).1,
+        )
+        .unwrap();
+
+        assert!(!second.exists());
+        let contents = fs::read(&third).unwrap();
+        assert!(is_encrypted(&contents));
+        let json = String::from_utf8(key.decrypt(&contents).unwrap()).unwrap();
+        assert!(json.contains("Backed up"));
+
+        //A copy made before a restore is not rotated so it stays in plain text until it is deleted
+        let before_restore = directory.join("before_restore_2025-03-05_080000.json");
+        fs::write(&before_restore, "{}").unwrap();
+        assert_eq!(plain_text_backups(&directory), vec![before_restore]);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn only_rotated_backups_are_recognized() {
+        assert!(backup_taken(&backup(2025, 3, 5, 10).0).is_some());
+        assert!(backup_taken(&PathBuf::from("backup_2025-03-05_100000.encrypted")).is_some());
+        assert!(
+            backup_taken(&PathBuf::from(
+                "before_schema_version_2_2025-03-05_100000.surql"
+            ))
+            .is_none()
+        );
+        assert!(backup_taken(&PathBuf::from("before_restore_2025-03-05_100000.json")).is_none());
+        assert!(backup_taken(&PathBuf::from("backup_not_a_date.json")).is_none());
+    }
+
+    #[tokio::test]
+    async fn the_backup_made_before_upgrading_can_be_restored() {
+        let directory =
+            std::env::temp_dir().join(format!("on_purpose_before_upgrading_{}", process::id()));
+        let db = connect("mem://").await.unwrap();
+        db.use_ns(NAMESPACE).use_db(DATABASE).await.unwrap();
+        let old_item = SurrealItemOldVersionBuilder::default()
+            .id(Some((SurrealItem::TABLE_NAME, "1").into()))
+            .summary("Old motivation")
+            .item_type(SurrealItemType::Motivation(SurrealMotivationKind::CoreWork))
+            .review_guidance(None)
+            .build()
+            .unwrap();
+        let _: Option<SurrealItemOldVersion> = db
+            .create((SurrealItem::TABLE_NAME, "1"))
+            .content(old_item)
+            .await
+            .unwrap();
+
+        migrate_if_needed(&db, Some(&directory), None)
+            .await
+            .unwrap();
+        let migrated = load_from_surrealdb(&db).await.unwrap();
+
+        let backups = fs::read_dir(&directory)
+            .unwrap()
+            .map(|x| x.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(backups.len(), 1);
+        assert!(
+            backups[0]
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .starts_with(BEFORE_MIGRATION_PREFIX)
+        );
+        //The backup holds the item from before the upgrade and it is upgraded the same way as it is read back
+        let document = read_backup(&backups[0], None).await.unwrap();
+        assert_eq!(document.tables, migrated);
+
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+        DataLayerCommands::import_raw_data(&sender, document.tables, document.archive, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            SurrealTables::new(&sender).await.unwrap().as_ref(),
+            &migrated
+        );
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,651 @@
+use std::fmt::{self, Display, Formatter};
+
+use surrealdb::sql::Thing;
+
+use super::{
+    integrity::{Reference, change_reference, describe, references},
+    surrealdb_layer::{
+        surreal_item::{SurrealDependency, SurrealOrderedSubItem},
+        surreal_tables::SurrealTables,
+        surreal_undo_journal::SurrealRecord,
+    },
+};
+
+/// One record that deleting or merging an item will change, the record is saved as `state` or deleted if `state` is
+/// None. The plan is shown to the user before anything is changed.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub(crate) struct PlannedChange {
+    pub(crate) description: String,
+    pub(crate) id: Thing,
+    pub(crate) state: Option<SurrealRecord>,
+}
+
+impl Display for PlannedChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.description)
+    }
+}
+
+/// Everything that changes when the item or event `delete` is deleted. Every reference to it is removed and a record
+/// that makes no sense without it, like an in the moment priority that chose it, is deleted along with it. The smaller
+/// items of `delete` are not deleted, they just lose a parent, and items waiting on a deleted event stop waiting. Gives
+/// back None if there is no such item or event.
+pub(crate) fn plan_delete(
+    surreal_tables: &SurrealTables,
+    delete: &Thing,
+) -> Option<Vec<PlannedChange>> {
+    let record = find_item_or_event(surreal_tables, delete)?;
+    let mut planned = vec![PlannedChange {
+        description: format!("Delete {}", describe(&record)),
+        id: delete.clone(),
+        state: None,
+    }];
+    for record in surreal_tables.all_records() {
+        let id = record.id().expect("In DB");
+        if id == delete {
+            continue;
+        }
+        let kinds = reference_kinds(&record, delete);
+        if kinds.is_empty() {
+            continue;
+        }
+        let mut state = kinds.iter().try_fold(record.clone(), |record, kind| {
+            change_reference(&record, *kind, delete, None)
+        });
+        if let Some(SurrealRecord::TimeSpent(time_spent)) = &state
+            && time_spent.working_on.is_empty()
+        {
+            state = None;
+        }
+        let description = match state {
+            Some(_) => format!(
+                "Remove it from {} ({})",
+                describe(&record),
+                join_kinds(&kinds)
+            ),
+            None => format!("Delete {}", describe(&record)),
+        };
+        planned.push(PlannedChange {
+            description,
+            id: id.clone(),
+            state,
+        });
+    }
+    Some(planned)
+}
+
+/// Everything that changes when `merge` is folded into `keep`, which are either both items or both events. An item
+/// `keep` takes on the smaller items and dependencies of `merge`, every reference to `merge` is pointed at `keep` and
+/// then `merge` is deleted. Everything else, like the summary and whether it is finished or triggered, comes from
+/// `keep`. When one of them is below the other the items between them would end up both above and below `keep`, so
+/// they stay below it and stop being larger than it. Gives back None if either does not exist, they are the same record
+/// or one is an item and the other an event.
+pub(crate) fn plan_merge(
+    surreal_tables: &SurrealTables,
+    keep: &Thing,
+    merge: &Thing,
+) -> Option<Vec<PlannedChange>> {
+    if keep == merge {
+        return None;
+    }
+    let keep_record = find_item_or_event(surreal_tables, keep)?;
+    let merge_record = find_item_or_event(surreal_tables, merge)?;
+    let merge_item = match (&keep_record, &merge_record) {
+        (SurrealRecord::Item(..), SurrealRecord::Item(merge_item)) => Some(merge_item),
+        (SurrealRecord::Event(..), SurrealRecord::Event(..)) => None,
+        _ => return None,
+    };
+    let keep_description = describe(&keep_record);
+    let merge_description = describe(&merge_record);
+
+    let mut planned = Vec::default();
+    for record in surreal_tables.all_records() {
+        let id = record.id().expect("In DB");
+        if id == merge {
+            continue;
+        }
+        let mut state = record.clone();
+        if id == keep
+            && let SurrealRecord::Item(item) = &mut state
+            && let Some(merge_item) = merge_item
+        {
+            item.smaller_items_in_priority_order
+                .extend(merge_item.smaller_items_in_priority_order.iter().cloned());
+            item.dependencies
+                .extend(merge_item.dependencies.iter().cloned());
+            item.modes.extend(merge_item.modes.iter().cloned());
+            if !merge_item.notes.is_empty() {
+                if !item.notes.is_empty() {
+                    item.notes.push_str("\n\n");
+                }
+                item.notes.push_str(&merge_item.notes);
+            }
+            item.links.extend(merge_item.notes_location.as_link());
+            item.links.extend(merge_item.links.iter().cloned());
+            item.tags.extend(merge_item.tags.iter().cloned());
+            if item.deadline.is_none() {
+                item.deadline = merge_item.deadline.clone();
+            }
+            if item.estimate.is_none() {
+                item.estimate = merge_item.estimate;
+            }
+        }
+        let kinds = reference_kinds(&state, merge);
+        if kinds.is_empty() && id != keep {
+            continue;
+        }
+        for kind in kinds.iter() {
+            //keep can't refer to itself so its references to merge are dropped rather than pointed at keep
+            let replacement = if id == keep { None } else { Some(keep) };
+            state = change_reference(&state, *kind, merge, replacement)
+                .expect("Only removing a priority's choice deletes the record");
+        }
+        let state = without_duplicates(state);
+        if state == record {
+            continue;
+        }
+        let description = if id == keep {
+            format!(
+                "Give {} the smaller items and waiting on of {}",
+                keep_description, merge_description
+            )
+        } else {
+            format!(
+                "Point {} at {} instead ({})",
+                describe(&record),
+                keep_description,
+                join_kinds(&kinds)
+            )
+        };
+        planned.push(PlannedChange {
+            description,
+            id: id.clone(),
+            state: Some(state),
+        });
+    }
+    if merge_item.is_some() {
+        remove_loops(surreal_tables, keep, merge, &keep_description, &mut planned);
+    }
+    planned.push(PlannedChange {
+        description: format!("Delete {}", merge_description),
+        id: merge.clone(),
+        state: None,
+    });
+    Some(planned)
+}
+
+/// Every item below `keep` once it is merged that would also be larger than `keep` stops being larger than it
+fn remove_loops(
+    surreal_tables: &SurrealTables,
+    keep: &Thing,
+    merge: &Thing,
+    keep_description: &str,
+    planned: &mut Vec<PlannedChange>,
+) {
+    let merged = surreal_tables
+        .surreal_items
+        .iter()
+        .filter(|x| x.id.as_ref() != Some(merge))
+        .map(|x| {
+            match planned
+                .iter()
+                .find(|change| x.id.as_ref() == Some(&change.id))
+            {
+                Some(PlannedChange {
+                    state: Some(SurrealRecord::Item(item)),
+                    ..
+                }) => item.clone(),
+                _ => x.clone(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut below = vec![keep];
+    let mut next = 0;
+    while let Some(id) = below.get(next).copied() {
+        next += 1;
+        let smaller = merged
+            .iter()
+            .filter(|x| x.id.as_ref() == Some(id))
+            .flat_map(|x| x.smaller_items_in_priority_order.iter());
+        for SurrealOrderedSubItem::SubItem { surreal_item_id } in smaller {
+            if !below.contains(&surreal_item_id) {
+                below.push(surreal_item_id);
+            }
+        }
+    }
+    let keep_as_smaller = SurrealOrderedSubItem::SubItem {
+        surreal_item_id: keep.clone(),
+    };
+    for item in merged.iter() {
+        let id = item.id.as_ref().expect("In DB");
+        if id == keep
+            || !below.contains(&id)
+            || !item
+                .smaller_items_in_priority_order
+                .contains(&keep_as_smaller)
+        {
+            continue;
+        }
+        let mut state = item.clone();
+        state
+            .smaller_items_in_priority_order
+            .retain(|x| *x != keep_as_smaller);
+        match planned.iter_mut().find(|x| &x.id == id) {
+            Some(change) => {
+                change.description = format!(
+                    "{}, except as a smaller item as it is below {} once merged",
+                    change.description, keep_description
+                );
+                change.state = Some(SurrealRecord::Item(state));
+            }
+            None => planned.push(PlannedChange {
+                description: format!(
+                    "Remove {} from the smaller items of {} as it is below it once merged",
+                    keep_description,
+                    describe(&SurrealRecord::Item(item.clone()))
+                ),
+                id: id.clone(),
+                state: Some(SurrealRecord::Item(state)),
+            }),
+        }
+    }
+}
+
+fn find_item_or_event(surreal_tables: &SurrealTables, id: &Thing) -> Option<SurrealRecord> {
+    let item = surreal_tables
+        .surreal_items
+        .iter()
+        .find(|x| x.id.as_ref() == Some(id))
+        .cloned()
+        .map(SurrealRecord::Item);
+    item.or_else(|| {
+        surreal_tables
+            .surreal_events
+            .iter()
+            .find(|x| x.id.as_ref() == Some(id))
+            .cloned()
+            .map(SurrealRecord::Event)
+    })
+}
+
+/// Each kind of reference that `record` has to `target`, once each
+fn reference_kinds(record: &SurrealRecord, target: &Thing) -> Vec<Reference> {
+    let mut kinds = Vec::default();
+    for (reference, id) in references(record) {
+        if id == target && !kinds.contains(&reference) {
+            kinds.push(reference);
+        }
+    }
+    kinds
+}
+
+fn join_kinds(kinds: &[Reference]) -> String {
+    kinds
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Pointing two references at the same item can leave the same reference twice, or an item referring to itself, the
+/// first and so most important one is kept
+pub(crate) fn without_duplicates(mut record: SurrealRecord) -> SurrealRecord {
+    match &mut record {
+        SurrealRecord::Item(item) => {
+            let id = item.id.clone();
+            item.smaller_items_in_priority_order.retain(|x| match x {
+                SurrealOrderedSubItem::SubItem { surreal_item_id } => {
+                    Some(surreal_item_id) != id.as_ref()
+                }
+            });
+            item.dependencies.retain(|x| match x {
+                SurrealDependency::AfterItem(dependency_id)
+                | SurrealDependency::DuringItem(dependency_id) => {
+                    Some(dependency_id) != id.as_ref()
+                }
+                SurrealDependency::AfterEvent(..) | SurrealDependency::AfterDateTime(..) => true,
+            });
+            dedupe(&mut item.smaller_items_in_priority_order);
+            dedupe(&mut item.dependencies);
+            dedupe(&mut item.modes);
+            dedupe(&mut item.links);
+            dedupe(&mut item.tags);
+        }
+        SurrealRecord::InTheMomentPriority(priority) => {
+            let choice = priority.choice.get_record_id().clone();
+            priority.not_chosen.retain(|x| *x.get_record_id() != choice);
+            dedupe(&mut priority.not_chosen);
+        }
+        SurrealRecord::TimeSpent(time_spent) => dedupe(&mut time_spent.working_on),
+        SurrealRecord::CurrentMode(current_mode) => dedupe(&mut current_mode.modes),
+        SurrealRecord::Mode(..) | SurrealRecord::Event(..) => {}
+    }
+    record
+}
+
+fn dedupe<T: PartialEq>(list: &mut Vec<T>) {
+    let mut kept: Vec<T> = Vec::with_capacity(list.len());
+    for x in list.drain(..) {
+        if !kept.contains(&x) {
+            kept.push(x);
+        }
+    }
+    *list = kept;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use surrealdb::sql::{Datetime, Thing};
+
+    use crate::data_storage::surrealdb_layer::{
+        surreal_event::SurrealEvent,
+        surreal_in_the_moment_priority::{
+            SurrealAction, SurrealInTheMomentPriority, SurrealPriorityKind,
+        },
+        surreal_item::{SurrealDependency, SurrealItem, SurrealItemBuilder, SurrealOrderedSubItem},
+        surreal_tables::{SurrealTables, SurrealTablesBuilder},
+        surreal_time_spent::SurrealTimeSpent,
+    };
+
+    use super::{PlannedChange, plan_delete, plan_merge};
+
+    fn id(id: &str) -> Thing {
+        (SurrealItem::TABLE_NAME, id).into()
+    }
+
+    fn smaller(key: &str) -> SurrealOrderedSubItem {
+        SurrealOrderedSubItem::SubItem {
+            surreal_item_id: id(key),
+        }
+    }
+
+    fn item(key: &str) -> SurrealItemBuilder {
+        let mut builder = SurrealItemBuilder::default();
+        builder.id(Some(id(key))).summary(key);
+        builder
+    }
+
+    fn time_spent(key: &str, working_on: &[&str]) -> SurrealTimeSpent {
+        SurrealTimeSpent {
+            id: Some(("time_spent_log", key).into()),
+            version: 1,
+            working_on: working_on
+                .iter()
+                .map(|x| SurrealAction::MakeProgress(id(x)))
+                .collect(),
+            why_in_scope: vec![],
+            urgency: None,
+            when_started: Utc::now().into(),
+            when_stopped: Utc::now().into(),
+            dedication: None,
+        }
+    }
+
+    fn event(key: &str) -> SurrealEvent {
+        SurrealEvent {
+            id: Some(event_id(key)),
+            version: 0,
+            last_updated: Datetime::default(),
+            triggered: false,
+            summary: key.into(),
+            untrigger_after: None,
+        }
+    }
+
+    fn event_id(key: &str) -> Thing {
+        (SurrealEvent::TABLE_NAME, key).into()
+    }
+
+    fn apply(mut surreal_tables: SurrealTables, planned: Vec<PlannedChange>) -> SurrealTables {
+        for change in planned {
+            surreal_tables.apply(&change.id, change.state);
+        }
+        surreal_tables
+    }
+
+    fn find<'a>(surreal_tables: &'a SurrealTables, key: &str) -> Option<&'a SurrealItem> {
+        surreal_tables
+            .surreal_items
+            .iter()
+            .find(|x| x.id == Some(id(key)))
+    }
+
+    #[test]
+    fn deleting_an_item_removes_every_reference_to_it() {
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                item("parent")
+                    .smaller_items_in_priority_order(vec![smaller("delete"), smaller("other")])
+                    .build()
+                    .unwrap(),
+                item("delete")
+                    .smaller_items_in_priority_order(vec![smaller("child")])
+                    .build()
+                    .unwrap(),
+                item("other")
+                    .dependencies(vec![SurrealDependency::AfterItem(id("delete"))])
+                    .build()
+                    .unwrap(),
+                item("child").build().unwrap(),
+            ])
+            .surreal_time_spent_log(vec![
+                time_spent("only", &["delete"]),
+                time_spent("shared", &["delete", "other"]),
+            ])
+            .surreal_in_the_moment_priorities(vec![SurrealInTheMomentPriority {
+                id: Some(("in_the_moment_priorities", "1").into()),
+                choice: SurrealAction::MakeProgress(id("delete")),
+                kind: SurrealPriorityKind::HighestPriority,
+                not_chosen: vec![SurrealAction::MakeProgress(id("other"))],
+                in_effect_until: vec![],
+                created: Utc::now().into(),
+            }])
+            .build()
+            .unwrap();
+
+        let planned = plan_delete(&surreal_tables, &id("delete")).unwrap();
+        assert_eq!(planned.len(), 6);
+
+        let deleted = apply(surreal_tables, planned);
+        assert!(find(&deleted, "delete").is_none());
+        assert_eq!(
+            find(&deleted, "parent")
+                .unwrap()
+                .smaller_items_in_priority_order,
+            vec![smaller("other")]
+        );
+        assert!(find(&deleted, "other").unwrap().dependencies.is_empty());
+        assert!(find(&deleted, "child").is_some());
+        assert_eq!(deleted.surreal_time_spent_log.len(), 1);
+        assert_eq!(
+            deleted.surreal_time_spent_log[0].working_on,
+            vec![SurrealAction::MakeProgress(id("other"))]
+        );
+        assert!(deleted.surreal_in_the_moment_priorities.is_empty());
+
+        assert_eq!(plan_delete(&deleted, &id("delete")), None);
+    }
+
+    #[test]
+    fn merging_combines_children_parents_dependencies_and_time_spent() {
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                item("parent of both")
+                    .smaller_items_in_priority_order(vec![smaller("merge"), smaller("keep")])
+                    .build()
+                    .unwrap(),
+                item("parent of merge")
+                    .smaller_items_in_priority_order(vec![smaller("merge")])
+                    .build()
+                    .unwrap(),
+                item("keep")
+                    .smaller_items_in_priority_order(vec![smaller("shared child")])
+                    .build()
+                    .unwrap(),
+                item("merge")
+                    .smaller_items_in_priority_order(vec![
+                        smaller("shared child"),
+                        smaller("merge child"),
+                        smaller("keep"),
+                    ])
+                    .dependencies(vec![SurrealDependency::AfterItem(id("waited on"))])
+                    .build()
+                    .unwrap(),
+                item("shared child").build().unwrap(),
+                item("merge child").build().unwrap(),
+                item("waited on").build().unwrap(),
+            ])
+            .surreal_time_spent_log(vec![
+                time_spent("merge", &["merge"]),
+                time_spent("both", &["keep", "merge"]),
+            ])
+            .build()
+            .unwrap();
+
+        assert_eq!(plan_merge(&surreal_tables, &id("keep"), &id("keep")), None);
+        assert_eq!(
+            plan_merge(&surreal_tables, &id("keep"), &id("missing")),
+            None
+        );
+
+        let planned = plan_merge(&surreal_tables, &id("keep"), &id("merge")).unwrap();
+        let merged = apply(surreal_tables, planned);
+
+        assert!(find(&merged, "merge").is_none());
+        let keep = find(&merged, "keep").unwrap();
+        assert_eq!(
+            keep.smaller_items_in_priority_order,
+            vec![smaller("shared child"), smaller("merge child")]
+        );
+        assert_eq!(
+            keep.dependencies,
+            vec![SurrealDependency::AfterItem(id("waited on"))]
+        );
+        assert_eq!(
+            find(&merged, "parent of both")
+                .unwrap()
+                .smaller_items_in_priority_order,
+            vec![smaller("keep")]
+        );
+        assert_eq!(
+            find(&merged, "parent of merge")
+                .unwrap()
+                .smaller_items_in_priority_order,
+            vec![smaller("keep")]
+        );
+        assert!(
+            merged
+                .surreal_time_spent_log
+                .iter()
+                .all(|x| x.working_on == vec![SurrealAction::MakeProgress(id("keep"))])
+        );
+    }
+
+    #[test]
+    fn merging_an_item_with_one_above_or_below_it_does_not_make_a_loop() {
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                item("top")
+                    .smaller_items_in_priority_order(vec![smaller("between")])
+                    .build()
+                    .unwrap(),
+                item("between")
+                    .smaller_items_in_priority_order(vec![smaller("bottom")])
+                    .build()
+                    .unwrap(),
+                item("bottom")
+                    .smaller_items_in_priority_order(vec![smaller("below bottom")])
+                    .build()
+                    .unwrap(),
+                item("below bottom").build().unwrap(),
+            ])
+            .build()
+            .unwrap();
+
+        //Keeping the one above, between would otherwise have top as both its larger and smaller item
+        let planned = plan_merge(&surreal_tables, &id("top"), &id("bottom")).unwrap();
+        let merged = apply(surreal_tables.clone(), planned);
+        assert_eq!(
+            find(&merged, "top")
+                .unwrap()
+                .smaller_items_in_priority_order,
+            vec![smaller("between"), smaller("below bottom")]
+        );
+        assert!(
+            find(&merged, "between")
+                .unwrap()
+                .smaller_items_in_priority_order
+                .is_empty()
+        );
+
+        //Keeping the one below, it takes on between which is already larger than it
+        let planned = plan_merge(&surreal_tables, &id("bottom"), &id("top")).unwrap();
+        let merged = apply(surreal_tables, planned);
+        assert_eq!(
+            find(&merged, "bottom")
+                .unwrap()
+                .smaller_items_in_priority_order,
+            vec![smaller("below bottom"), smaller("between")]
+        );
+        assert!(
+            find(&merged, "between")
+                .unwrap()
+                .smaller_items_in_priority_order
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn deleting_or_merging_an_event_changes_what_items_wait_on() {
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                item("waits on both")
+                    .dependencies(vec![
+                        SurrealDependency::AfterEvent(event_id("keep")),
+                        SurrealDependency::AfterEvent(event_id("merge")),
+                    ])
+                    .build()
+                    .unwrap(),
+                item("waits on merge")
+                    .dependencies(vec![SurrealDependency::AfterEvent(event_id("merge"))])
+                    .build()
+                    .unwrap(),
+            ])
+            .surreal_events(vec![event("keep"), event("merge")])
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            plan_merge(&surreal_tables, &event_id("keep"), &id("waits on merge")),
+            None
+        );
+
+        let planned = plan_merge(&surreal_tables, &event_id("keep"), &event_id("merge")).unwrap();
+        let merged = apply(surreal_tables.clone(), planned);
+        assert_eq!(merged.surreal_events, vec![event("keep")]);
+        assert_eq!(
+            find(&merged, "waits on both").unwrap().dependencies,
+            vec![SurrealDependency::AfterEvent(event_id("keep"))]
+        );
+        assert_eq!(
+            find(&merged, "waits on merge").unwrap().dependencies,
+            vec![SurrealDependency::AfterEvent(event_id("keep"))]
+        );
+
+        let planned = plan_delete(&surreal_tables, &event_id("merge")).unwrap();
+        assert_eq!(planned.len(), 3);
+        let deleted = apply(surreal_tables, planned);
+        assert_eq!(deleted.surreal_events, vec![event("keep")]);
+        assert_eq!(
+            find(&deleted, "waits on both").unwrap().dependencies,
+            vec![SurrealDependency::AfterEvent(event_id("keep"))]
+        );
+        assert!(
+            find(&deleted, "waits on merge")
+                .unwrap()
+                .dependencies
+                .is_empty()
+        );
+    }
+}
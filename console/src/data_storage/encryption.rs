@@ -0,0 +1,303 @@
+use std::{
+    error::Error,
+    fmt::{self, Debug, Display, Formatter},
+    fs,
+    path::Path,
+};
+
+use argon2::Argon2;
+use chacha20poly1305::{Key, KeyInit, XChaCha20Poly1305, XNonce, aead::Aead};
+use inquire::{InquireError, Password, PasswordDisplayMode};
+use tokio::sync::mpsc::Sender;
+
+use super::surrealdb_layer::data_layer_commands::DataLayerCommands;
+
+/// Every encrypted file starts with this so it can be told apart from a plain text export or backup
+const MAGIC: &[u8] = b"ON_PURPOSE_ENCRYPTED_1\n";
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const KEY_LENGTH: usize = 32;
+const PASSPHRASE_ATTEMPTS: usize = 3;
+
+/// The key is derived from the passphrase with Argon2 and a random salt. The salt is written at the start of every file
+/// that is encrypted so the key can be derived again from just the passphrase.
+#[derive(PartialEq, Eq, Clone)]
+pub(crate) struct EncryptionKey {
+    salt: [u8; SALT_LENGTH],
+    key: [u8; KEY_LENGTH],
+}
+
+impl Debug for EncryptionKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        //The key itself is left out so it does not end up in a log or a panic message
+        f.debug_struct("EncryptionKey").finish_non_exhaustive()
+    }
+}
+
+impl EncryptionKey {
+    /// A new salt is picked so the key is different even if the same passphrase was used before
+    pub(crate) fn new(passphrase: &str) -> Result<Self, EncryptionError> {
+        Self::derive(passphrase, rand::random())
+    }
+
+    /// The key for something encrypted with `encrypt`, the passphrase is only known to be right once it decrypts
+    pub(crate) fn for_encrypted(
+        passphrase: &str,
+        encrypted: &[u8],
+    ) -> Result<Self, EncryptionError> {
+        Self::derive(passphrase, split(encrypted)?.salt)
+    }
+
+    fn derive(passphrase: &str, salt: [u8; SALT_LENGTH]) -> Result<Self, EncryptionError> {
+        let mut key = [0; KEY_LENGTH];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|err| EncryptionError::KeyDerivation(err.to_string()))?;
+        Ok(EncryptionKey { salt, key })
+    }
+
+    /// If false then `encrypted` was encrypted with a different passphrase, for example one from before the passphrase
+    /// was changed
+    pub(crate) fn made_with_same_salt(&self, encrypted: &[u8]) -> bool {
+        split(encrypted).is_ok_and(|x| x.salt == self.salt)
+    }
+
+    pub(crate) fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let nonce: [u8; NONCE_LENGTH] = rand::random();
+        let ciphertext = self
+            .cipher()
+            .encrypt(XNonce::from_slice(&nonce), plaintext)
+            .map_err(|_| EncryptionError::Encrypt)?;
+        let mut encrypted =
+            Vec::with_capacity(MAGIC.len() + SALT_LENGTH + NONCE_LENGTH + ciphertext.len());
+        encrypted.extend_from_slice(MAGIC);
+        encrypted.extend_from_slice(&self.salt);
+        encrypted.extend_from_slice(&nonce);
+        encrypted.extend_from_slice(&ciphertext);
+        Ok(encrypted)
+    }
+
+    /// Fails with WrongPassphrase if this key did not encrypt it or if it was changed after it was encrypted
+    pub(crate) fn decrypt(&self, encrypted: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let parts = split(encrypted)?;
+        if parts.salt != self.salt {
+            return Err(EncryptionError::WrongPassphrase);
+        }
+        self.cipher()
+            .decrypt(XNonce::from_slice(&parts.nonce), parts.ciphertext)
+            .map_err(|_| EncryptionError::WrongPassphrase)
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(Key::from_slice(&self.key))
+    }
+}
+
+pub(crate) fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// What comes after MAGIC in an encrypted file
+struct EncryptedParts<'e> {
+    salt: [u8; SALT_LENGTH],
+    nonce: [u8; NONCE_LENGTH],
+    ciphertext: &'e [u8],
+}
+
+fn split(encrypted: &[u8]) -> Result<EncryptedParts<'_>, EncryptionError> {
+    let rest = encrypted
+        .strip_prefix(MAGIC)
+        .ok_or(EncryptionError::NotEncrypted)?;
+    if rest.len() < SALT_LENGTH + NONCE_LENGTH {
+        return Err(EncryptionError::Truncated);
+    }
+    let (salt, rest) = rest.split_at(SALT_LENGTH);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+    Ok(EncryptedParts {
+        salt: salt.try_into().expect("Split at the salt length"),
+        nonce: nonce.try_into().expect("Split at the nonce length"),
+        ciphertext,
+    })
+}
+
+#[derive(Debug)]
+pub(crate) enum EncryptionError {
+    NotEncrypted,
+    Truncated,
+    KeyDerivation(String),
+    Encrypt,
+    /// The passphrase is not the one it was encrypted with or the file was changed after it was encrypted, the two
+    /// cannot be told apart
+    WrongPassphrase,
+}
+
+impl Display for EncryptionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptionError::NotEncrypted => write!(f, "The file is not encrypted"),
+            EncryptionError::Truncated => {
+                write!(f, "The encrypted file is cut short, it might be damaged")
+            }
+            EncryptionError::KeyDerivation(err) => {
+                write!(f, "Unable to make a key from the passphrase: {}", err)
+            }
+            EncryptionError::Encrypt => write!(f, "Unable to encrypt the data"),
+            EncryptionError::WrongPassphrase => write!(
+                f,
+                "The passphrase is wrong or the file was changed after it was encrypted"
+            ),
+        }
+    }
+}
+
+impl Error for EncryptionError {}
+
+/// Asks for the passphrase of an existing encrypted database or, if there is not one yet, for the passphrase to create
+/// it with. None if no passphrase was given.
+pub(crate) fn unlock_or_create(path: &Path) -> Result<Option<EncryptionKey>, Box<dyn Error>> {
+    if path.exists() {
+        let encrypted = fs::read(path)?;
+        let what = format!("the database at {}", path.display());
+        prompt_for_key(&encrypted, &what)
+    } else {
+        prompt_for_new_key(&format!(
+            "Pick a passphrase for the new encrypted database at {}|",
+            path.display()
+        ))
+    }
+}
+
+/// Asks until the passphrase decrypts `encrypted`, None if the user gave up
+pub(crate) fn prompt_for_key(
+    encrypted: &[u8],
+    what: &str,
+) -> Result<Option<EncryptionKey>, Box<dyn Error>> {
+    for _ in 0..PASSPHRASE_ATTEMPTS {
+        let passphrase = Password::new(&format!("Passphrase for {}|", what))
+            .without_confirmation()
+            .with_display_mode(PasswordDisplayMode::Masked)
+            .prompt();
+        let passphrase = match passphrase {
+            Ok(passphrase) => passphrase,
+            Err(InquireError::OperationCanceled) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let key = EncryptionKey::for_encrypted(&passphrase, encrypted)?;
+        match key.decrypt(encrypted) {
+            Ok(_) => return Ok(Some(key)),
+            Err(EncryptionError::WrongPassphrase) => {
+                println!("{}", EncryptionError::WrongPassphrase)
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(None)
+}
+
+/// The passphrase is typed twice so a typo does not lock the user out of their data
+pub(crate) fn prompt_for_new_key(prompt: &str) -> Result<Option<EncryptionKey>, Box<dyn Error>> {
+    let passphrase = Password::new(prompt)
+        .with_display_mode(PasswordDisplayMode::Masked)
+        .with_custom_confirmation_message("Type the passphrase again|")
+        .with_custom_confirmation_error_message("The passphrases do not match")
+        .prompt();
+    match passphrase {
+        Ok(passphrase) if passphrase.is_empty() => {
+            println!("The passphrase cannot be empty");
+            Ok(None)
+        }
+        Ok(passphrase) => Ok(Some(EncryptionKey::new(&passphrase)?)),
+        Err(InquireError::OperationCanceled) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Encrypts the database with a new passphrase, the exports and backups that were already made keep the old passphrase
+pub(crate) async fn change_passphrase(
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), Box<dyn Error>> {
+    let Some(key) = prompt_for_new_key("New passphrase|")? else {
+        println!("The passphrase was not changed");
+        return Ok(());
+    };
+    DataLayerCommands::change_passphrase(send_to_data_storage_layer, key).await?;
+    println!("The passphrase was changed, backups made before now still need the old passphrase");
+    Ok(())
+}
+
+/// Reads an export or a backup that might be encrypted. If it was encrypted with a different passphrase than `key`, for
+/// example before the passphrase was changed, then the user is asked for that passphrase.
+pub(crate) fn read_to_string(
+    path: &Path,
+    key: Option<&EncryptionKey>,
+) -> Result<String, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    if !is_encrypted(&bytes) {
+        return Ok(String::from_utf8(bytes)?);
+    }
+    let plaintext = match key.filter(|x| x.made_with_same_salt(&bytes)) {
+        Some(key) => key.decrypt(&bytes)?,
+        None => match prompt_for_key(&bytes, &path.display().to_string())? {
+            Some(key) => key.decrypt(&bytes)?,
+            None => return Err("No passphrase was given so it could not be decrypted".into()),
+        },
+    };
+    Ok(String::from_utf8(plaintext)?)
+}
+
+/// Encrypts the contents if there is a key, otherwise they are given back as they are
+pub(crate) fn encrypt_if_key(
+    contents: Vec<u8>,
+    key: Option<&EncryptionKey>,
+) -> Result<Vec<u8>, EncryptionError> {
+    match key {
+        Some(key) => key.encrypt(&contents),
+        None => Ok(contents),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EncryptionError, EncryptionKey, is_encrypted};
+
+    #[test]
+    fn what_is_encrypted_decrypts_with_the_same_passphrase() {
+        let key = EncryptionKey::new("correct horse battery staple").unwrap();
+        let encrypted = key.encrypt(b"Buy milk").unwrap();
+
+        assert!(is_encrypted(&encrypted));
+        assert!(
+            !encrypted
+                .windows(b"Buy milk".len())
+                .any(|x| x == b"Buy milk")
+        );
+
+        let key_again =
+            EncryptionKey::for_encrypted("correct horse battery staple", &encrypted).unwrap();
+        assert_eq!(key_again, key);
+        assert_eq!(key_again.decrypt(&encrypted).unwrap(), b"Buy milk");
+    }
+
+    #[test]
+    fn a_wrong_passphrase_or_a_changed_file_does_not_decrypt() {
+        let key = EncryptionKey::new("correct horse battery staple").unwrap();
+        let encrypted = key.encrypt(b"Buy milk").unwrap();
+
+        let wrong_key = EncryptionKey::for_encrypted("wrong", &encrypted).unwrap();
+        assert!(matches!(
+            wrong_key.decrypt(&encrypted),
+            Err(EncryptionError::WrongPassphrase)
+        ));
+
+        let mut changed = encrypted.clone();
+        *changed.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            key.decrypt(&changed),
+            Err(EncryptionError::WrongPassphrase)
+        ));
+
+        let other_key = EncryptionKey::new("correct horse battery staple").unwrap();
+        assert!(!other_key.made_with_same_salt(&encrypted));
+        assert!(key.made_with_same_salt(&encrypted));
+    }
+}
@@ -0,0 +1,740 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    mem,
+};
+
+use ahash::{HashMap, HashSet};
+use surrealdb::sql::Thing;
+
+use super::surrealdb_layer::{
+    SurrealItemsInScope, SurrealTrigger,
+    surreal_archive::SurrealArchive,
+    surreal_current_mode::SurrealCurrentMode,
+    surreal_event::SurrealEvent,
+    surreal_in_the_moment_priority::SurrealInTheMomentPriority,
+    surreal_item::{SurrealDependency, SurrealItem, SurrealOrderedSubItem, SurrealUrgencyPlan},
+    surreal_mode::SurrealMode,
+    surreal_tables::SurrealTables,
+    surreal_time_spent::SurrealTimeSpent,
+    surreal_undo_journal::SurrealRecord,
+};
+
+/// The different ways one record refers to another
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub(crate) enum Reference {
+    SmallerItem,
+    Dependency,
+    /// The items a trigger counts, in an urgency plan or an in the moment priority
+    TriggerScope,
+    PriorityChoice,
+    PriorityNotChosen,
+    WorkingOn,
+    ModeParent,
+    /// The user defined modes an item is done in or that are selected in the current mode
+    Mode,
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Reference::SmallerItem => write!(f, "smaller item"),
+            Reference::Dependency => write!(f, "waiting on"),
+            Reference::TriggerScope => write!(f, "items counted by a trigger"),
+            Reference::PriorityChoice => write!(f, "chosen item"),
+            Reference::PriorityNotChosen => write!(f, "item not chosen"),
+            Reference::WorkingOn => write!(f, "worked on"),
+            Reference::ModeParent => write!(f, "parent mode"),
+            Reference::Mode => write!(f, "mode"),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub(crate) enum Problem {
+    /// `record` refers to `missing` which is not in the database
+    DanglingReference {
+        record: SurrealRecord,
+        reference: Reference,
+        missing: Thing,
+    },
+    /// Each record refers to the next one and the last one refers back to the first one
+    Cycle {
+        reference: Reference,
+        records: Vec<SurrealRecord>,
+    },
+    /// `child` is in the smaller items of `parent` more than once
+    DuplicateChild { parent: SurrealItem, child: Thing },
+    /// No item waits on this event so triggering it does nothing
+    OrphanedEvent(SurrealEvent),
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::DanglingReference {
+                record,
+                reference,
+                missing,
+            } => write!(
+                f,
+                "{} refers to {} ({}) which does not exist",
+                describe(record),
+                missing,
+                reference
+            ),
+            Problem::Cycle { reference, records } => {
+                let mut names = records.iter().map(describe).collect::<Vec<_>>();
+                names.push(describe(&records[0]));
+                write!(f, "Circular {}: {}", reference, names.join(" → "))
+            }
+            Problem::DuplicateChild { parent, child } => write!(
+                f,
+                "\"{}\" has {} as a smaller item more than once",
+                parent.summary, child
+            ),
+            Problem::OrphanedEvent(event) => write!(
+                f,
+                "Event \"{}\" is not waited on by any item",
+                event.summary
+            ),
+        }
+    }
+}
+
+/// A fix that needs nothing more from the user, the record is saved as `state` or deleted if `state` is None
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub(crate) struct Repair {
+    pub(crate) description: String,
+    pub(crate) id: Thing,
+    pub(crate) state: Option<SurrealRecord>,
+}
+
+impl Display for Repair {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.description)
+    }
+}
+
+impl Problem {
+    pub(crate) fn repairs(&self) -> Vec<Repair> {
+        match self {
+            Problem::DanglingReference {
+                record,
+                reference,
+                missing,
+            } => {
+                let state = change_reference(record, *reference, missing, None);
+                let description = match state {
+                    Some(_) => format!("Remove the reference from {}", describe(record)),
+                    None => format!("Delete {}", describe(record)),
+                };
+                vec![Repair {
+                    description,
+                    id: record.id().expect("In DB").clone(),
+                    state,
+                }]
+            }
+            Problem::Cycle { reference, records } => records
+                .iter()
+                .zip(records.iter().cycle().skip(1))
+                .map(|(from, to)| {
+                    let to_id = to.id().expect("In DB");
+                    Repair {
+                        description: format!(
+                            "Remove the link from {} to {}",
+                            describe(from),
+                            describe(to)
+                        ),
+                        id: from.id().expect("In DB").clone(),
+                        state: change_reference(from, *reference, to_id, None),
+                    }
+                })
+                .collect(),
+            Problem::DuplicateChild { parent, child } => {
+                let mut parent = parent.clone();
+                let mut seen = false;
+                parent.smaller_items_in_priority_order.retain(|x| match x {
+                    SurrealOrderedSubItem::SubItem { surreal_item_id } => {
+                        surreal_item_id != child || !mem::replace(&mut seen, true)
+                    }
+                });
+                vec![Repair {
+                    description: "Keep only the first, most important, one".to_string(),
+                    id: parent.id.clone().expect("In DB"),
+                    state: Some(SurrealRecord::Item(parent)),
+                }]
+            }
+            Problem::OrphanedEvent(event) => vec![Repair {
+                description: format!("Delete event \"{}\"", event.summary),
+                id: event.id.clone().expect("In DB"),
+                state: None,
+            }],
+        }
+    }
+}
+
+/// A short description of the record for showing to the user
+pub(crate) fn describe(record: &SurrealRecord) -> String {
+    match record {
+        SurrealRecord::Item(item) => format!("\"{}\"", item.summary),
+        SurrealRecord::TimeSpent(time_spent) => {
+            format!("Time spent starting {}", time_spent.when_started)
+        }
+        SurrealRecord::InTheMomentPriority(..) => "An in the moment priority".to_string(),
+        SurrealRecord::CurrentMode(..) => "The current mode".to_string(),
+        SurrealRecord::Mode(mode) => format!("Mode \"{}\"", mode.name),
+        SurrealRecord::Event(event) => format!("Event \"{}\"", event.summary),
+    }
+}
+
+/// Every record that `record` refers to
+pub(crate) fn references(record: &SurrealRecord) -> Vec<(Reference, &Thing)> {
+    let mut references = Vec::default();
+    match record {
+        SurrealRecord::Item(item) => {
+            for smaller in item.smaller_items_in_priority_order.iter() {
+                match smaller {
+                    SurrealOrderedSubItem::SubItem { surreal_item_id } => {
+                        references.push((Reference::SmallerItem, surreal_item_id))
+                    }
+                }
+            }
+            for dependency in item.dependencies.iter() {
+                match dependency {
+                    SurrealDependency::AfterItem(id)
+                    | SurrealDependency::DuringItem(id)
+                    | SurrealDependency::AfterEvent(id) => {
+                        references.push((Reference::Dependency, id))
+                    }
+                    SurrealDependency::AfterDateTime(..) => {}
+                }
+            }
+            if let Some(SurrealUrgencyPlan::WillEscalate { triggers, .. }) = &item.urgency_plan {
+                push_trigger_scopes(&mut references, triggers);
+            }
+            references.extend(item.modes.iter().map(|x| (Reference::Mode, x)));
+        }
+        SurrealRecord::InTheMomentPriority(priority) => {
+            references.push((Reference::PriorityChoice, priority.choice.get_record_id()));
+            for not_chosen in priority.not_chosen.iter() {
+                references.push((Reference::PriorityNotChosen, not_chosen.get_record_id()));
+            }
+            push_trigger_scopes(&mut references, &priority.in_effect_until);
+        }
+        SurrealRecord::TimeSpent(time_spent) => {
+            for working_on in time_spent.working_on.iter() {
+                references.push((Reference::WorkingOn, working_on.get_record_id()));
+            }
+        }
+        SurrealRecord::Mode(mode) => {
+            if let Some(parent) = &mode.parent {
+                references.push((Reference::ModeParent, parent));
+            }
+        }
+        SurrealRecord::CurrentMode(current_mode) => {
+            references.extend(current_mode.modes.iter().map(|x| (Reference::Mode, x)));
+        }
+        SurrealRecord::Event(..) => {}
+    }
+    references
+}
+
+fn push_trigger_scopes<'a>(
+    references: &mut Vec<(Reference, &'a Thing)>,
+    triggers: &'a [SurrealTrigger],
+) {
+    for trigger in triggers.iter() {
+        match trigger {
+            SurrealTrigger::LoggedInvocationCount { items_in_scope, .. }
+            | SurrealTrigger::LoggedAmountOfTime { items_in_scope, .. } => match items_in_scope {
+                SurrealItemsInScope::Include(ids) | SurrealItemsInScope::Exclude(ids) => {
+                    references.extend(ids.iter().map(|x| (Reference::TriggerScope, x)))
+                }
+                SurrealItemsInScope::All => {}
+            },
+            SurrealTrigger::WallClockDateTime(..) => {}
+        }
+    }
+}
+
+/// Gives back `record` with every `reference` to `target` pointed at `replacement` instead, or removed if
+/// `replacement` is None. Gives back None if the record no longer makes sense without the reference and should be
+/// deleted.
+pub(crate) fn change_reference(
+    record: &SurrealRecord,
+    reference: Reference,
+    target: &Thing,
+    replacement: Option<&Thing>,
+) -> Option<SurrealRecord> {
+    let change_ids = |ids: &mut Vec<Thing>| match replacement {
+        Some(replacement) => ids
+            .iter_mut()
+            .filter(|x| **x == *target)
+            .for_each(|x| *x = replacement.clone()),
+        None => ids.retain(|x| x != target),
+    };
+    let mut record = record.clone();
+    match (&mut record, reference) {
+        (SurrealRecord::Item(item), Reference::SmallerItem) => match replacement {
+            Some(replacement) => {
+                for smaller in item.smaller_items_in_priority_order.iter_mut() {
+                    match smaller {
+                        SurrealOrderedSubItem::SubItem { surreal_item_id } => {
+                            if surreal_item_id == target {
+                                *surreal_item_id = replacement.clone();
+                            }
+                        }
+                    }
+                }
+            }
+            None => item.smaller_items_in_priority_order.retain(|x| match x {
+                SurrealOrderedSubItem::SubItem { surreal_item_id } => surreal_item_id != target,
+            }),
+        },
+        (SurrealRecord::Item(item), Reference::Dependency) => match replacement {
+            Some(replacement) => {
+                for dependency in item.dependencies.iter_mut() {
+                    match dependency {
+                        SurrealDependency::AfterItem(id)
+                        | SurrealDependency::DuringItem(id)
+                        | SurrealDependency::AfterEvent(id) => {
+                            if id == target {
+                                *id = replacement.clone();
+                            }
+                        }
+                        SurrealDependency::AfterDateTime(..) => {}
+                    }
+                }
+            }
+            None => item.dependencies.retain(|x| match x {
+                SurrealDependency::AfterItem(id)
+                | SurrealDependency::DuringItem(id)
+                | SurrealDependency::AfterEvent(id) => id != target,
+                SurrealDependency::AfterDateTime(..) => true,
+            }),
+        },
+        (SurrealRecord::Item(item), Reference::TriggerScope) => {
+            if let Some(SurrealUrgencyPlan::WillEscalate { triggers, .. }) = &mut item.urgency_plan
+            {
+                change_trigger_scopes(triggers, change_ids);
+            }
+        }
+        (SurrealRecord::InTheMomentPriority(priority), Reference::TriggerScope) => {
+            change_trigger_scopes(&mut priority.in_effect_until, change_ids);
+        }
+        (SurrealRecord::InTheMomentPriority(priority), Reference::PriorityChoice) => {
+            match replacement {
+                Some(replacement) => *priority.choice.get_record_id_mut() = replacement.clone(),
+                //A priority is about its choice so without it there is nothing left
+                None => return None,
+            }
+        }
+        (SurrealRecord::InTheMomentPriority(priority), Reference::PriorityNotChosen) => {
+            match replacement {
+                Some(replacement) => priority
+                    .not_chosen
+                    .iter_mut()
+                    .filter(|x| x.get_record_id() == target)
+                    .for_each(|x| *x.get_record_id_mut() = replacement.clone()),
+                None => priority.not_chosen.retain(|x| x.get_record_id() != target),
+            }
+        }
+        (SurrealRecord::TimeSpent(time_spent), Reference::WorkingOn) => match replacement {
+            Some(replacement) => time_spent
+                .working_on
+                .iter_mut()
+                .filter(|x| x.get_record_id() == target)
+                .for_each(|x| *x.get_record_id_mut() = replacement.clone()),
+            None => time_spent
+                .working_on
+                .retain(|x| x.get_record_id() != target),
+        },
+        (SurrealRecord::Mode(mode), Reference::ModeParent) => {
+            mode.parent = replacement.cloned();
+        }
+        (SurrealRecord::Item(item), Reference::Mode) => change_ids(&mut item.modes),
+        (SurrealRecord::CurrentMode(current_mode), Reference::Mode) => {
+            change_ids(&mut current_mode.modes)
+        }
+        //This kind of record does not have this kind of reference so there is nothing to change
+        _ => {}
+    }
+    Some(record)
+}
+
+fn change_trigger_scopes(
+    triggers: &mut [SurrealTrigger],
+    mut change_ids: impl FnMut(&mut Vec<Thing>),
+) {
+    for trigger in triggers.iter_mut() {
+        match trigger {
+            SurrealTrigger::LoggedInvocationCount { items_in_scope, .. }
+            | SurrealTrigger::LoggedAmountOfTime { items_in_scope, .. } => match items_in_scope {
+                SurrealItemsInScope::Include(ids) | SurrealItemsInScope::Exclude(ids) => {
+                    change_ids(ids)
+                }
+                SurrealItemsInScope::All => {}
+            },
+            SurrealTrigger::WallClockDateTime(..) => {}
+        }
+    }
+}
+
+/// The tables `find_problems` looks at, so the only tables a repair can change
+pub(crate) const CHECKED_TABLES: [&str; 6] = [
+    SurrealItem::TABLE_NAME,
+    SurrealTimeSpent::TABLE_NAME,
+    SurrealInTheMomentPriority::TABLE_NAME,
+    SurrealCurrentMode::TABLE_NAME,
+    SurrealMode::TABLE_NAME,
+    SurrealEvent::TABLE_NAME,
+];
+
+/// Everything that is wrong with how the records refer to each other, in the order the records are in the tables. A
+/// reference to an archived record is not a problem, for example a parent still lists its archived smaller items.
+pub(crate) fn find_problems(
+    surreal_tables: &SurrealTables,
+    surreal_archive: &SurrealArchive,
+) -> Vec<Problem> {
+    let archived = surreal_archive.archived_ids();
+    let records = surreal_tables.all_records().collect::<Vec<_>>();
+    let by_id: HashMap<&Thing, &SurrealRecord> = records
+        .iter()
+        .map(|x| (x.id().expect("In DB"), x))
+        .collect();
+
+    let mut problems = Vec::default();
+    for record in records.iter() {
+        for (reference, target) in references(record) {
+            if !by_id.contains_key(target) && !archived.contains(target) {
+                problems.push(Problem::DanglingReference {
+                    record: record.clone(),
+                    reference,
+                    missing: target.clone(),
+                });
+            }
+        }
+    }
+
+    for reference in [
+        Reference::SmallerItem,
+        Reference::Dependency,
+        Reference::ModeParent,
+    ] {
+        for cycle in find_cycles(&records, &by_id, reference) {
+            problems.push(Problem::Cycle {
+                reference,
+                records: cycle.into_iter().cloned().collect(),
+            });
+        }
+    }
+
+    for item in surreal_tables.surreal_items.iter() {
+        let mut seen = HashSet::default();
+        let mut reported = HashSet::default();
+        for smaller in item.smaller_items_in_priority_order.iter() {
+            let SurrealOrderedSubItem::SubItem { surreal_item_id } = smaller;
+            if !seen.insert(surreal_item_id) && reported.insert(surreal_item_id) {
+                problems.push(Problem::DuplicateChild {
+                    parent: item.clone(),
+                    child: surreal_item_id.clone(),
+                });
+            }
+        }
+    }
+
+    let waited_on = surreal_tables
+        .surreal_items
+        .iter()
+        .chain(surreal_archive.items.iter().map(|x| &x.item))
+        .flat_map(|x| x.dependencies.iter())
+        .filter_map(|x| match x {
+            SurrealDependency::AfterEvent(id) => Some(id),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    for event in surreal_tables.surreal_events.iter() {
+        if !waited_on.contains(event.id.as_ref().expect("In DB")) {
+            problems.push(Problem::OrphanedEvent(event.clone()));
+        }
+    }
+
+    problems
+}
+
+/// A depth first search, each time a record is reached that is already on the path the records from there to the end of
+/// the path are a cycle. Every record is in at most one of the cycles that are given back.
+fn find_cycles<'a>(
+    records: &'a [SurrealRecord],
+    by_id: &HashMap<&Thing, &'a SurrealRecord>,
+    reference: Reference,
+) -> Vec<Vec<&'a SurrealRecord>> {
+    let mut cycles = Vec::default();
+    let mut finished: HashSet<&Thing> = HashSet::default();
+    for start in records.iter() {
+        let mut path: Vec<&SurrealRecord> = Vec::default();
+        visit(
+            start,
+            by_id,
+            reference,
+            &mut path,
+            &mut finished,
+            &mut cycles,
+        );
+    }
+    cycles
+}
+
+fn visit<'a>(
+    record: &'a SurrealRecord,
+    by_id: &HashMap<&Thing, &'a SurrealRecord>,
+    reference: Reference,
+    path: &mut Vec<&'a SurrealRecord>,
+    finished: &mut HashSet<&'a Thing>,
+    cycles: &mut Vec<Vec<&'a SurrealRecord>>,
+) {
+    let id = record.id().expect("In DB");
+    if finished.contains(id) {
+        return;
+    }
+    if let Some(position) = path.iter().position(|x| x.id() == Some(id)) {
+        cycles.push(path[position..].to_vec());
+        return;
+    }
+    path.push(record);
+    let next = references(record)
+        .into_iter()
+        .filter(|(kind, _)| *kind == reference)
+        .filter_map(|(_, target)| by_id.get(target).copied())
+        .collect::<Vec<_>>();
+    for next in next {
+        visit(next, by_id, reference, path, finished, cycles);
+    }
+    path.pop();
+    finished.insert(id);
+}
+
+#[cfg(test)]
+mod tests {
+    use surrealdb::sql::Datetime;
+
+    use crate::data_storage::surrealdb_layer::{
+        surreal_archive::{SurrealArchive, SurrealArchivedItem},
+        surreal_event::SurrealEvent,
+        surreal_item::{SurrealDependency, SurrealItemBuilder, SurrealOrderedSubItem},
+        surreal_mode::SurrealMode,
+        surreal_tables::{SurrealTables, SurrealTablesBuilder},
+    };
+
+    use super::{Problem, Reference, find_problems};
+
+    fn smaller(id: &str) -> SurrealOrderedSubItem {
+        SurrealOrderedSubItem::SubItem {
+            surreal_item_id: ("item", id).into(),
+        }
+    }
+
+    /// Applies the first repair of each problem until there are no problems left
+    fn repair_everything(mut surreal_tables: SurrealTables) -> SurrealTables {
+        loop {
+            let problems = find_problems(&surreal_tables, &SurrealArchive::default());
+            let Some(problem) = problems.first() else {
+                return surreal_tables;
+            };
+            let repair = problem.repairs().remove(0);
+            surreal_tables.apply(&repair.id, repair.state);
+        }
+    }
+
+    #[test]
+    fn a_reference_to_an_item_that_does_not_exist_is_found_and_removed() {
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                SurrealItemBuilder::default()
+                    .id(Some(("item", "1").into()))
+                    .summary("Parent")
+                    .smaller_items_in_priority_order(vec![smaller("2"), smaller("missing")])
+                    .dependencies(vec![SurrealDependency::AfterItem(("item", "gone").into())])
+                    .build()
+                    .unwrap(),
+                SurrealItemBuilder::default()
+                    .id(Some(("item", "2").into()))
+                    .summary("Child")
+                    .build()
+                    .unwrap(),
+            ])
+            .surreal_modes(vec![SurrealMode {
+                id: Some(("modes", "1").into()),
+                name: "Mode".into(),
+                version: 0,
+                parent: Some(("modes", "missing").into()),
+                display_order: 0,
+            }])
+            .build()
+            .unwrap();
+
+        let problems = find_problems(&surreal_tables, &SurrealArchive::default());
+
+        assert_eq!(problems.len(), 3);
+        assert!(
+            problems
+                .iter()
+                .all(|x| matches!(x, Problem::DanglingReference { .. }))
+        );
+
+        let repaired = repair_everything(surreal_tables);
+        assert_eq!(
+            repaired.surreal_items[0].smaller_items_in_priority_order,
+            vec![smaller("2")]
+        );
+        assert!(repaired.surreal_items[0].dependencies.is_empty());
+        assert_eq!(repaired.surreal_modes[0].parent, None);
+    }
+
+    #[test]
+    fn circular_smaller_items_and_dependencies_are_found() {
+        //The same shapes the create_growing_node tests guard against
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                SurrealItemBuilder::default()
+                    .id(Some(("item", "1").into()))
+                    .summary("1")
+                    .smaller_items_in_priority_order(vec![smaller("3")])
+                    .build()
+                    .unwrap(),
+                SurrealItemBuilder::default()
+                    .id(Some(("item", "2").into()))
+                    .summary("2")
+                    .smaller_items_in_priority_order(vec![smaller("1")])
+                    .build()
+                    .unwrap(),
+                SurrealItemBuilder::default()
+                    .id(Some(("item", "3").into()))
+                    .summary("3")
+                    .smaller_items_in_priority_order(vec![smaller("2")])
+                    .dependencies(vec![SurrealDependency::AfterItem(("item", "3").into())])
+                    .build()
+                    .unwrap(),
+            ])
+            .build()
+            .unwrap();
+
+        let problems = find_problems(&surreal_tables, &SurrealArchive::default());
+
+        assert_eq!(problems.len(), 2);
+        match &problems[0] {
+            Problem::Cycle { reference, records } => {
+                assert_eq!(*reference, Reference::SmallerItem);
+                assert_eq!(records.len(), 3);
+            }
+            problem => panic!("Expected a cycle, found: {}", problem),
+        }
+        match &problems[1] {
+            Problem::Cycle { reference, records } => {
+                assert_eq!(*reference, Reference::Dependency);
+                assert_eq!(records.len(), 1);
+            }
+            problem => panic!("Expected a cycle, found: {}", problem),
+        }
+        //One repair for each link in the cycle
+        assert_eq!(problems[0].repairs().len(), 3);
+
+        assert!(
+            find_problems(
+                &repair_everything(surreal_tables),
+                &SurrealArchive::default()
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn duplicate_children_and_orphaned_events_are_found() {
+        let event = SurrealEvent {
+            id: Some(("events", "1").into()),
+            version: 0,
+            last_updated: Datetime::default(),
+            triggered: false,
+            summary: "Nothing waits on this".into(),
+            untrigger_after: None,
+        };
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                SurrealItemBuilder::default()
+                    .id(Some(("item", "1").into()))
+                    .summary("Parent")
+                    .smaller_items_in_priority_order(vec![smaller("2"), smaller("3"), smaller("2")])
+                    .build()
+                    .unwrap(),
+                SurrealItemBuilder::default()
+                    .id(Some(("item", "2").into()))
+                    .summary("Listed twice")
+                    .build()
+                    .unwrap(),
+                SurrealItemBuilder::default()
+                    .id(Some(("item", "3").into()))
+                    .summary("Listed once")
+                    .build()
+                    .unwrap(),
+            ])
+            .surreal_events(vec![event.clone()])
+            .build()
+            .unwrap();
+
+        let problems = find_problems(&surreal_tables, &SurrealArchive::default());
+
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(problems[0], Problem::DuplicateChild { .. }));
+        assert_eq!(problems[1], Problem::OrphanedEvent(event));
+
+        let repaired = repair_everything(surreal_tables);
+        assert_eq!(
+            repaired.surreal_items[0].smaller_items_in_priority_order,
+            vec![smaller("2"), smaller("3")]
+        );
+        assert!(repaired.surreal_events.is_empty());
+    }
+
+    #[test]
+    fn references_to_archived_items_are_not_problems() {
+        let event = SurrealEvent {
+            id: Some(("events", "1").into()),
+            version: 0,
+            last_updated: Datetime::default(),
+            triggered: true,
+            summary: "Only an archived item waits on this".into(),
+            untrigger_after: None,
+        };
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                SurrealItemBuilder::default()
+                    .id(Some(("item", "1").into()))
+                    .summary("Parent")
+                    .smaller_items_in_priority_order(vec![smaller("archived")])
+                    .build()
+                    .unwrap(),
+            ])
+            .surreal_events(vec![event])
+            .build()
+            .unwrap();
+        let surreal_archive = SurrealArchive {
+            items: vec![SurrealArchivedItem::new(
+                SurrealItemBuilder::default()
+                    .id(Some(("item", "archived").into()))
+                    .summary("Finished long ago")
+                    .finished(Some(Datetime::default()))
+                    .dependencies(vec![SurrealDependency::AfterEvent(("events", "1").into())])
+                    .build()
+                    .unwrap(),
+                Datetime::default(),
+            )],
+            time_spent_log: vec![],
+        };
+
+        assert!(find_problems(&surreal_tables, &surreal_archive).is_empty());
+        assert_eq!(
+            find_problems(&surreal_tables, &SurrealArchive::default()).len(),
+            2
+        );
+    }
+}
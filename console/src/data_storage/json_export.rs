@@ -0,0 +1,353 @@
+use std::{error::Error, fs, io::Write, path::Path, sync::Arc};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Datetime;
+use tokio::sync::mpsc::Sender;
+
+use super::{
+    encryption::{self, EncryptionKey, encrypt_if_key},
+    surrealdb_layer::{
+        data_layer_commands::DataLayerCommands, surreal_archive::SurrealArchive,
+        surreal_tables::SurrealTables,
+    },
+};
+
+const EXPORT_FORMAT: &str = "on_purpose";
+
+/// Increment this when a change is made to the export that an older version of On Purpose would not be able to import
+const EXPORT_FORMAT_VERSION: u32 = 2;
+
+/// One JSON document with every table in it, the record ids are included so the references between records survive a round trip
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct ExportDocument {
+    pub(crate) format: String,
+    pub(crate) format_version: u32,
+    pub(crate) exported_by_version: String,
+    pub(crate) exported: Datetime,
+    pub(crate) tables: SurrealTables,
+    /// Added in format version 2, an export from before then has nothing archived
+    #[serde(default)]
+    pub(crate) archive: SurrealArchive,
+}
+
+#[derive(Deserialize)]
+struct ExportHeader {
+    format: String,
+    format_version: u32,
+}
+
+impl ExportDocument {
+    pub(crate) fn new(tables: SurrealTables, archive: SurrealArchive, exported: Datetime) -> Self {
+        ExportDocument {
+            format: EXPORT_FORMAT.to_string(),
+            format_version: EXPORT_FORMAT_VERSION,
+            exported_by_version: option_env!("CARGO_PKG_VERSION")
+                .unwrap_or("UNKNOWN")
+                .to_string(),
+            exported,
+            tables,
+            archive,
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub(crate) fn from_json(json: &str) -> Result<Self, Box<dyn Error>> {
+        //The header is checked first because a newer format might not deserialize into this version's types
+        let header: ExportHeader = serde_json::from_str(json)?;
+        if header.format != EXPORT_FORMAT {
+            return Err(format!(
+                "This is not an On Purpose export, the format is \"{}\"",
+                header.format
+            )
+            .into());
+        }
+        if header.format_version > EXPORT_FORMAT_VERSION {
+            return Err(format!(
+                "This export is format version {} which is newer than this version of On Purpose understands, version {}. Please upgrade On Purpose.",
+                header.format_version, EXPORT_FORMAT_VERSION
+            )
+            .into());
+        }
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// Writes to standard output if `to` is None so the export can be piped into other tools
+/// When the database is encrypted the export is encrypted too and so it is only ever written to a file
+pub(crate) async fn export_to(
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+    to: Option<&Path>,
+    encryption: Option<&EncryptionKey>,
+) -> Result<(), Box<dyn Error>> {
+    if to.is_none() && encryption.is_some() {
+        return Err(
+            "The database is encrypted so it is not exported to the terminal, give a file to export to".into(),
+        );
+    }
+    let surreal_tables = SurrealTables::new(send_to_data_storage_layer).await?;
+    let surreal_archive = SurrealArchive::new(send_to_data_storage_layer).await?;
+    let document = ExportDocument::new(
+        Arc::unwrap_or_clone(surreal_tables),
+        surreal_archive,
+        Utc::now().into(),
+    );
+    let json = document.to_json()?;
+    match to {
+        Some(to) => {
+            fs::write(to, encrypt_if_key(json.into_bytes(), encryption)?)?;
+            println!(
+                "Exported {} items to {}",
+                document.tables.surreal_items.len(),
+                to.display()
+            );
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(json.as_bytes())?;
+            writeln!(stdout)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn import_from(
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+    from: &Path,
+    replace_existing: bool,
+    encryption: Option<&EncryptionKey>,
+) -> Result<(), Box<dyn Error>> {
+    let json = encryption::read_to_string(from, encryption)?;
+    let document = ExportDocument::from_json(&json)?;
+    let items_count = document.tables.surreal_items.len();
+    DataLayerCommands::import_raw_data(
+        send_to_data_storage_layer,
+        document.tables,
+        document.archive,
+        replace_existing,
+    )
+    .await?;
+    println!("Imported {} items from {}", items_count, from.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use tokio::sync::mpsc;
+
+    use crate::{
+        data_storage::surrealdb_layer::{
+            data_layer_commands::{
+                DataChange, DataLayerCommands, DataLayerError, DataStorageSettings, ImportError,
+                data_storage_start_and_run,
+            },
+            surreal_archive::SurrealArchive,
+            surreal_item::SurrealItemType,
+            surreal_tables::SurrealTables,
+        },
+        new_event::NewEventBuilder,
+        new_item::{NewDependency, NewItem, NewItemBuilder},
+        new_mode::NewModeBuilder,
+    };
+
+    use super::ExportDocument;
+
+    #[tokio::test]
+    async fn export_then_import_gives_an_identical_database() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Parent".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let parent = surreal_tables.surreal_items[0].id.clone().unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithANewChildItem {
+                child: NewItemBuilder::default()
+                    .summary("Child that waits on an event")
+                    .item_type(SurrealItemType::Action)
+                    .dependencies(vec![NewDependency::NewEvent(
+                        NewEventBuilder::default().summary("Event").build().unwrap(),
+                    )])
+                    .build()
+                    .unwrap(),
+                parent,
+                higher_importance_than_this: None,
+            },
+        )
+        .await
+        .unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewMode(NewModeBuilder::default().name("Mode").build().unwrap()),
+        )
+        .await
+        .unwrap();
+        let exported = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(exported.surreal_items.len(), 2);
+        assert_eq!(exported.surreal_events.len(), 1);
+
+        let json = ExportDocument::new(
+            SurrealTables::clone(&exported),
+            SurrealArchive::default(),
+            Utc::now().into(),
+        )
+        .to_json()
+        .unwrap();
+        let document = ExportDocument::from_json(&json).unwrap();
+
+        let (import_sender, import_receiver) = mpsc::channel(1);
+        let import_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(import_receiver, "mem://", DataStorageSettings::default())
+                .await
+        });
+        DataLayerCommands::import_raw_data(
+            &import_sender,
+            document.tables,
+            document.archive,
+            false,
+        )
+        .await
+        .unwrap();
+        let imported = SurrealTables::new(&import_sender).await.unwrap();
+
+        assert!(imported.is_same_data(&exported));
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+        drop(import_sender);
+        import_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn import_into_a_database_with_data_needs_replace() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Existing".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let replacement = SurrealTables::new(&sender).await.unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Added after the export".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let before_replace = SurrealTables::new(&sender)
+            .await
+            .unwrap()
+            .surreal_items
+            .iter()
+            .map(|x| x.id.clone().unwrap())
+            .collect::<Vec<_>>();
+
+        let result = DataLayerCommands::import_raw_data(
+            &sender,
+            SurrealTables::clone(&replacement),
+            SurrealArchive::default(),
+            false,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(DataLayerError::Import(ImportError::DatabaseNotEmpty))
+        ));
+
+        DataLayerCommands::import_raw_data(
+            &sender,
+            SurrealTables::clone(&replacement),
+            SurrealArchive::default(),
+            true,
+        )
+        .await
+        .unwrap();
+        let after_replace = SurrealTables::new(&sender).await.unwrap();
+        assert!(after_replace.is_same_data(&replacement));
+
+        //The history of the item that was imported again is kept, the history of the one that is gone is removed
+        let kept = replacement.surreal_items[0].id.clone().unwrap();
+        let history = DataLayerCommands::get_history(&sender, vec![kept])
+            .await
+            .unwrap();
+        assert!(!history.is_empty());
+        let all_history = DataLayerCommands::get_history(&sender, before_replace)
+            .await
+            .unwrap();
+        assert_eq!(all_history.len(), history.len());
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_replace_that_fails_leaves_the_database_as_it_was() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Existing".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let existing = SurrealTables::new(&sender).await.unwrap();
+
+        //Two items with the same id cannot both be inserted
+        let mut replacement = SurrealTables::clone(&existing);
+        replacement
+            .surreal_items
+            .push(replacement.surreal_items[0].clone());
+        let result = DataLayerCommands::import_raw_data(
+            &sender,
+            replacement,
+            SurrealArchive::default(),
+            true,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(DataLayerError::Import(ImportError::Surreal(_)))
+        ));
+
+        let after = SurrealTables::new(&sender).await.unwrap();
+        assert!(after.is_same_data(&existing));
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[test]
+    fn an_export_from_a_newer_version_is_refused() {
+        let json = r#"{"format":"on_purpose","format_version":9999}"#;
+
+        let error = ExportDocument::from_json(json).unwrap_err();
+        assert!(error.to_string().contains("newer"));
+    }
+
+    #[test]
+    fn an_export_from_before_the_archive_has_nothing_archived() {
+        let json = r#"{"format":"on_purpose","format_version":1,"exported_by_version":"0.0.1","exported":"2024-01-01T00:00:00Z","tables":{"item":[],"time_spent_log":[],"in_the_moment_priorities":[],"current_modes":[],"modes":[],"events":[]}}"#;
+
+        let document = ExportDocument::from_json(json).unwrap();
+        assert!(document.archive.is_empty());
+    }
+}
@@ -5,13 +5,19 @@ use surrealdb::{
 };
 
 pub(crate) mod data_layer_commands;
+pub(crate) mod encrypted_database;
+pub(crate) mod migrations;
+pub(crate) mod surreal_archive;
 pub(crate) mod surreal_current_mode;
 pub(crate) mod surreal_event;
+pub(crate) mod surreal_history;
 pub(crate) mod surreal_in_the_moment_priority;
 pub(crate) mod surreal_item;
 pub(crate) mod surreal_mode;
 pub(crate) mod surreal_tables;
 pub(crate) mod surreal_time_spent;
+pub(crate) mod surreal_undo_journal;
+pub(crate) mod sync_log;
 
 #[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
 pub(crate) enum SurrealTrigger {
@@ -1,43 +1,124 @@
-use chrono::Utc;
-use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    future, mem,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use ahash::HashSet;
+use chrono::{DateTime, Local, Utc};
 use surrealdb::{
     Surreal,
     engine::any::{Any, IntoEndpoint, connect},
-    opt::{PatchOp, RecordId},
-    sql::{Datetime, Thing},
+    method::Query,
+    opt::RecordId,
+    sql::{Datetime, Duration, Id, Thing},
 };
-use tokio::sync::{
-    mpsc::{Receiver, Sender},
-    oneshot::{self, error::RecvError},
+use tokio::{
+    sync::{
+        broadcast,
+        mpsc::{Receiver, Sender},
+        oneshot,
+    },
+    time::{Instant, Interval, MissedTickBehavior, interval_at},
 };
 
 use crate::{
-    data_storage::surrealdb_layer::surreal_mode::SurrealMode,
+    data_storage::{
+        backups::{BackupSettings, make_backup},
+        delete_and_merge::{PlannedChange, plan_delete, plan_merge, without_duplicates},
+        encryption::EncryptionKey,
+        integrity::{CHECKED_TABLES, Reference, change_reference, references},
+        surrealdb_layer::surreal_mode::SurrealMode,
+    },
     new_event::NewEvent,
     new_item::{NewDependency, NewItem},
     new_mode::NewMode,
     new_time_spent::NewTimeSpent,
+    systems::maintenance,
 };
 
 use super::{
     SurrealTrigger,
+    encrypted_database::{EncryptedDatabase, EncryptionSettings},
+    migrations::{MigrationError, migrate_if_needed},
+    surreal_archive::{
+        SurrealArchive, SurrealArchivedItem, SurrealArchivedTimeSpent, to_archive, to_unarchive,
+    },
     surreal_current_mode::{NewCurrentMode, SurrealCurrentMode},
     surreal_event::SurrealEvent,
+    surreal_history::{SurrealHistoryEntry, history_entries},
     surreal_in_the_moment_priority::{
         SurrealAction, SurrealInTheMomentPriority, SurrealPriorityKind,
     },
     surreal_item::{
-        Responsibility, SurrealDependency, SurrealFrequency, SurrealItem, SurrealItemOldVersion,
-        SurrealItemType, SurrealOrderedSubItem, SurrealReviewGuidance, SurrealUrgencyPlan,
+        NotesLocation, Responsibility, SurrealCompletion, SurrealDeadline, SurrealDependency,
+        SurrealFrequency, SurrealItem, SurrealItemType, SurrealLink, SurrealOrderedSubItem,
+        SurrealRecurrence, SurrealReviewGuidance, SurrealUrgencyPlan,
     },
     surreal_mode,
-    surreal_tables::SurrealTables,
-    surreal_time_spent::{SurrealTimeSpent, SurrealTimeSpentVersion0},
+    surreal_tables::{SurrealTables, TablesChanged},
+    surreal_time_spent::{SurrealDedication, SurrealTimeSpent},
+    surreal_undo_journal::{SurrealRecord, SurrealRecordChange, SurrealUndoEntry},
+    sync_log::SyncLog,
 };
 
 pub(crate) enum DataLayerCommands {
-    SendRawData(oneshot::Sender<SurrealTables>),
+    /// Replies with any error from upgrading the database when the data storage layer started. If there was an error
+    /// then every other command is ignored.
+    SendStartupResult(oneshot::Sender<Result<(), MigrationError>>),
+    SendRawData(oneshot::Sender<Result<Arc<SurrealTables>, DataLayerError>>),
+    /// Replies with a receiver that is sent the tables after every change that is saved
+    SubscribeToChanges(oneshot::Sender<broadcast::Receiver<TablesChanged>>),
+    ImportRawData {
+        surreal_tables: SurrealTables,
+        surreal_archive: SurrealArchive,
+        replace_existing: bool,
+        result: oneshot::Sender<Result<(), ImportError>>,
+    },
     SendTimeSpentLog(oneshot::Sender<Vec<SurrealTimeSpent>>),
+    /// Replies once the change is saved or with the reason why it could not be saved
+    Change(DataChange, oneshot::Sender<Result<(), DataLayerError>>),
+    /// Either every change is saved or, if any of them fail, none of them are. Replies once for all of the changes.
+    Transaction(Vec<DataChange>, oneshot::Sender<Result<(), DataLayerError>>),
+    /// Replies with the description of the change that was undone or None if there is nothing to undo
+    Undo(oneshot::Sender<Result<Option<String>, DataLayerError>>),
+    /// Replies with the description of the change that was redone or None if there is nothing to redo
+    Redo(oneshot::Sender<Result<Option<String>, DataLayerError>>),
+    /// Merges in the changes other devices wrote to the sync folder, replies with how many records changed. Replies with 0
+    /// if there is no sync folder.
+    Sync(oneshot::Sender<Result<usize, DataLayerError>>),
+    /// The archive is not kept in memory, it is read from the database each time it is asked for
+    SendArchive(oneshot::Sender<Result<SurrealArchive, DataLayerError>>),
+    /// Moves the items finished before `finished_before`, and the time spent on them, into the archive. Replies with how
+    /// many items were archived.
+    Archive {
+        finished_before: Datetime,
+        reply: oneshot::Sender<Result<usize, DataLayerError>>,
+    },
+    /// Moves the item, and the archived items it waits on, back out of the archive. If `reopen` then the item is also
+    /// marked as not finished.
+    Unarchive {
+        item: RecordId,
+        reopen: bool,
+        reply: oneshot::Sender<Result<(), DataLayerError>>,
+    },
+    /// Replies with the history of each of the records, oldest first. History is not kept in memory.
+    SendHistory {
+        records: Vec<RecordId>,
+        reply: oneshot::Sender<Result<Vec<SurrealHistoryEntry>, DataLayerError>>,
+    },
+    /// Encrypts the database with the new key, replies once it is saved. Fails if the database is not encrypted.
+    ChangePassphrase {
+        key: EncryptionKey,
+        reply: oneshot::Sender<Result<(), DataLayerError>>,
+    },
+}
+
+/// Every change to the data, these are sent with `DataLayerCommands::Change` so the caller finds out if the change was saved
+pub(crate) enum DataChange {
     RecordTimeSpent(NewTimeSpent),
     FinishItem {
         item: RecordId,
@@ -82,6 +163,30 @@ pub(crate) enum DataLayerCommands {
     AddItemDependencyNewEvent(RecordId, NewEvent),
     UpdateSummary(RecordId, String),
     UpdateModeName(RecordId, String),
+    /// None makes it a top level mode. It is not valid to move a mode under itself or one of its own smaller modes.
+    UpdateModeParent(RecordId, Option<RecordId>),
+    /// Every mode with the same parent, in the order they should be shown
+    UpdateModeOrder(Vec<RecordId>),
+    /// The user defined modes the item is done in, see `CurrentMode`
+    UpdateItemModes(RecordId, Vec<RecordId>),
+    /// Markdown
+    UpdateItemNotes(RecordId, String),
+    /// Every link the item has, this replaces the older `notes_location` so it is cleared
+    UpdateItemLinks(RecordId, Vec<SurrealLink>),
+    /// Adding a tag the item already has does nothing, leading and trailing whitespace is not part of the tag
+    AddItemTag(RecordId, String),
+    RemoveItemTag(RecordId, String),
+    /// None stops it recurring, the times it was finished are kept
+    UpdateItemRecurrence(RecordId, Option<SurrealRecurrence>),
+    /// The urgency that comes from the deadline is only used when the item has no urgency plan
+    UpdateItemDeadline(RecordId, Option<SurrealDeadline>),
+    UpdateItemEstimate(RecordId, Option<Duration>),
+    /// The smaller modes of `mode` are deleted along with it if `delete_smaller_modes`, otherwise they move up to take
+    /// its place under its parent
+    DeleteMode {
+        mode: RecordId,
+        delete_smaller_modes: bool,
+    },
     UpdateUrgencyPlan(RecordId, Option<SurrealUrgencyPlan>),
     UpdateItemReviewFrequency(RecordId, SurrealFrequency, SurrealReviewGuidance),
     UpdateItemLastReviewedDate(RecordId, Datetime),
@@ -101,970 +206,4025 @@ pub(crate) enum DataLayerCommands {
         event: RecordId,
         when: Datetime,
     },
+    UpdateEventSummary(RecordId, String),
+    /// None makes it an event that stays triggered, see `SurrealEvent::untrigger_after`
+    UpdateEventUntriggerAfter(RecordId, Option<Duration>),
+    /// Deletes the event and stops every item from waiting on it, see `plan_delete`
+    DeleteEvent(RecordId),
+    /// Items waiting on `merge` wait on `keep` instead and `merge` is deleted, see `plan_merge`
+    MergeEvents {
+        keep: RecordId,
+        merge: RecordId,
+    },
+    /// Saves the record as given, or deletes it if None, for fixing the problems found by the integrity check. The
+    /// record must have the id given and be in one of the tables the integrity check looks at.
+    RepairRecord(RecordId, Option<SurrealRecord>),
+    /// Marks a finished item as not finished
+    ReopenItem(RecordId),
+    /// Deletes the item and removes every reference to it, see `plan_delete`
+    DeleteItem(RecordId),
+    /// Folds `merge` into `keep` and deletes `merge`, see `plan_merge`
+    MergeItems {
+        keep: RecordId,
+        merge: RecordId,
+    },
+    /// Corrects when the time was spent, `when_started` must not be after `when_stopped`
+    UpdateTimeSpentTimes {
+        time_spent: RecordId,
+        when_started: Datetime,
+        when_stopped: Datetime,
+    },
+    UpdateTimeSpentWorkingOn(RecordId, Vec<SurrealAction>),
+    UpdateTimeSpentDedication(RecordId, Option<SurrealDedication>),
+    /// Makes two entries out of one, the first keeps the record id and stops `at` and the second starts `at`
+    SplitTimeSpent {
+        time_spent: RecordId,
+        at: Datetime,
+    },
+    DeleteTimeSpent(RecordId),
 }
 
 impl DataLayerCommands {
     pub(crate) async fn get_raw_data(
         sender: &Sender<DataLayerCommands>,
-    ) -> Result<SurrealTables, RecvError> {
+    ) -> Result<Arc<SurrealTables>, DataLayerError> {
         let (raw_data_sender, raw_data_receiver) = oneshot::channel();
         sender
             .send(DataLayerCommands::SendRawData(raw_data_sender))
             .await
-            .unwrap();
-        raw_data_receiver.await
+            .map_err(|_| DataLayerError::Stopped)?;
+        raw_data_receiver
+            .await
+            .map_err(|_| DataLayerError::Stopped)?
     }
-}
 
-pub(crate) async fn data_storage_start_and_run(
-    mut data_storage_layer_receive_rx: Receiver<DataLayerCommands>,
-    endpoint: impl IntoEndpoint,
-) {
-    let db = connect(endpoint).await.unwrap();
-    db.use_ns("OnPurpose").use_db("Russ").await.unwrap(); //TODO: "Russ" should be a parameter, maybe the username or something
+    pub(crate) async fn subscribe_to_changes(
+        sender: &Sender<DataLayerCommands>,
+    ) -> Result<broadcast::Receiver<TablesChanged>, DataLayerError> {
+        let (subscribe_sender, subscribe_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::SubscribeToChanges(subscribe_sender))
+            .await
+            .map_err(|_| DataLayerError::Stopped)?;
+        subscribe_receiver
+            .await
+            .map_err(|_| DataLayerError::Stopped)
+    }
 
-    // let updated: Option<SurrealItem> = db.update((SurrealItem::TABLE_NAME, "5i5mkemqn0f1716v3ycw"))
-    //     .patch(PatchOp::replace("/urgency_plan", None::<Option<SurrealUrgencyPlan>>)).await.unwrap();
-    // assert!(updated.is_some());
-    // panic!("Finished");
-    loop {
-        let received = data_storage_layer_receive_rx.recv().await;
-        match received {
-            Some(DataLayerCommands::SendRawData(oneshot)) => {
-                let surreal_tables = load_from_surrealdb_upgrade_if_needed(&db).await;
-                oneshot.send(surreal_tables).unwrap();
-            }
-            Some(DataLayerCommands::SendTimeSpentLog(sender)) => send_time_spent(sender, &db).await,
-            Some(DataLayerCommands::RecordTimeSpent(new_time_spent)) => {
-                record_time_spent(new_time_spent, &db).await
-            }
-            Some(DataLayerCommands::FinishItem {
-                item,
-                when_finished,
-            }) => finish_item(item, when_finished, &db).await,
-            Some(DataLayerCommands::NewItem(new_item)) => {
-                create_new_item(new_item, &db).await;
-            }
-            Some(DataLayerCommands::CoverItemWithANewItem {
-                cover_this,
-                cover_with,
-            }) => cover_with_a_new_item(cover_this, cover_with, &db).await,
-            Some(DataLayerCommands::CoverItemWithAnExistingItem {
-                item_to_be_covered,
-                item_that_should_do_the_covering,
-            }) => {
-                cover_item_with_an_existing_item(
-                    item_to_be_covered,
-                    item_that_should_do_the_covering,
-                    &db,
-                )
-                .await
-            }
-            Some(DataLayerCommands::NewMode(new_mode)) => {
-                let mut surreal_mode: SurrealMode = new_mode.into();
-                let created: SurrealMode = db
-                    .create(surreal_mode::SurrealMode::TABLE_NAME)
-                    .content(surreal_mode.clone())
-                    .await
-                    .unwrap()
-                    .into_iter()
-                    .next()
-                    .unwrap();
+    pub(crate) async fn get_startup_result(
+        sender: &Sender<DataLayerCommands>,
+    ) -> Result<(), DataLayerError> {
+        let (startup_sender, startup_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::SendStartupResult(startup_sender))
+            .await
+            .map_err(|_| DataLayerError::Stopped)?;
+        startup_receiver
+            .await
+            .map_err(|_| DataLayerError::Stopped)?
+            .map_err(DataLayerError::Startup)
+    }
 
-                surreal_mode.id = created.id.clone();
-                assert_eq!(surreal_mode, created);
-            }
-            Some(DataLayerCommands::ParentItemWithExistingItem {
-                child,
-                parent,
-                higher_importance_than_this,
-            }) => {
-                parent_item_with_existing_item(child, parent, higher_importance_than_this, &db)
-                    .await
-            }
-            Some(DataLayerCommands::ParentItemWithANewChildItem {
-                child,
-                parent,
-                higher_importance_than_this,
-            }) => {
-                parent_item_with_a_new_child(child, parent, higher_importance_than_this, &db).await
-            }
-            Some(DataLayerCommands::ParentNewItemWithAnExistingChildItem {
-                child,
-                parent_new_item,
-            }) => parent_new_item_with_an_existing_child_item(child, parent_new_item, &db).await,
-            Some(DataLayerCommands::ParentItemRemoveParent {
-                child,
-                parent_to_remove,
-            }) => {
-                let mut parent: SurrealItem =
-                    db.select(parent_to_remove.clone()).await.unwrap().unwrap();
+    /// Sends the change and waits for it to be saved
+    pub(crate) async fn change(
+        sender: &Sender<DataLayerCommands>,
+        change: DataChange,
+    ) -> Result<(), DataLayerError> {
+        let (reply_sender, reply_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::Change(change, reply_sender))
+            .await
+            .map_err(|_| DataLayerError::Stopped)?;
+        reply_receiver.await.map_err(|_| DataLayerError::Stopped)?
+    }
 
-                parent.smaller_items_in_priority_order = parent
-                    .smaller_items_in_priority_order
-                    .into_iter()
-                    .filter(|x| match x {
-                        SurrealOrderedSubItem::SubItem { surreal_item_id } => {
-                            surreal_item_id != &child
-                        }
-                    })
-                    .collect::<Vec<_>>();
-                let saved = db
-                    .update(parent_to_remove)
-                    .content(parent.clone())
-                    .await
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(parent, saved);
-            }
-            Some(DataLayerCommands::AddItemDependency(record_id, new_ready)) => {
-                add_dependency(record_id, new_ready, &db).await
-            }
-            Some(DataLayerCommands::RemoveItemDependency(record_id, to_remove)) => {
-                remove_dependency(record_id, to_remove, &db).await
-            }
-            Some(DataLayerCommands::AddItemDependencyNewEvent(record_id, new_event)) => {
-                add_dependency_new_event(record_id, new_event, &db).await
-            }
-            Some(DataLayerCommands::UpdateRelativeImportance {
-                parent,
-                update_this_child,
-                higher_importance_than_this_child,
-            }) => {
-                parent_item_with_existing_item(
-                    update_this_child,
-                    parent,
-                    higher_importance_than_this_child,
-                    &db,
-                )
-                .await
-            }
-            Some(DataLayerCommands::UpdateItemLastReviewedDate(record_id, new_last_reviewed)) => {
-                //TODO: I should probably fix this so it does the update all as one transaction rather than reading in the data and then changing it and writing it out again. That could cause issues if there are multiple writers. The reason why I didn't do it yet is because I only want to update part of the SurrealItemReview type and I need to experiment with the PatchOp::replace to see if and how to make it work with the nested type. Otherwise I might consider just making review_frequency and last_reviewed separate fields and then I can just update the review_frequency and not have to worry about the last_reviewed field.
-                let mut item: SurrealItem = db.select(record_id.clone()).await.unwrap().unwrap();
-
-                item.last_reviewed = Some(new_last_reviewed);
-                let updated = db
-                    .update(record_id)
-                    .content(item.clone())
-                    .await
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(item, updated);
-            }
-            Some(DataLayerCommands::UpdateItemReviewFrequency(
-                record_id,
-                surreal_frequency,
-                surreal_review_guidance,
-            )) => {
-                //TODO: I should probably fix this so it does the update all as one transaction rather than reading in the data and then changing it and writing it out again. That could cause issues if there are multiple writers. The reason why I didn't do it yet is because I only want to update part of the SurrealItemReview type and I need to experiment with the PatchOp::replace to see if and how to make it work with the nested type. Otherwise I might consider just making review_frequency and last_reviewed separate fields and then I can just update the review_frequency and not have to worry about the last_reviewed field.
-                let previous_value: SurrealItem =
-                    db.select(record_id.clone()).await.unwrap().unwrap();
-                let mut item = previous_value.clone();
-                item.review_frequency = Some(surreal_frequency);
-                item.review_guidance = Some(surreal_review_guidance);
-                let updated = db
-                    .update(record_id)
-                    .content(item.clone())
-                    .await
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(item, updated);
-            }
-            Some(DataLayerCommands::UpdateSummary(item, new_summary)) => {
-                update_item_summary(item, new_summary, &db).await
-            }
-            Some(DataLayerCommands::UpdateModeName(thing, new_name)) => {
-                let updated: SurrealMode = db
-                    .update(thing)
-                    .patch(PatchOp::replace("/name", new_name.clone()))
-                    .await
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(updated.name, new_name);
-            }
-            Some(DataLayerCommands::UpdateResponsibilityAndItemType(
-                item,
-                new_responsibility,
-                new_item_type,
-            )) => {
-                let updated: SurrealItem = db
-                    .update(item.clone())
-                    .patch(PatchOp::replace(
-                        "/responsibility",
-                        new_responsibility.clone(),
-                    ))
-                    .patch(PatchOp::replace("/item_type", new_item_type.clone()))
-                    .await
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(updated.responsibility, new_responsibility);
-                assert_eq!(updated.item_type, new_item_type);
-            }
-            Some(DataLayerCommands::UpdateUrgencyPlan(record_id, new_urgency_plan)) => {
-                let updated: SurrealItem = db
-                    .update(record_id)
-                    .patch(PatchOp::replace("/urgency_plan", new_urgency_plan.clone()))
-                    .await
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(updated.urgency_plan, new_urgency_plan);
-            }
-            Some(DataLayerCommands::DeclareInTheMomentPriority {
-                choice,
-                kind,
-                not_chosen,
-                in_effect_until,
-            }) => {
-                let mut priority = SurrealInTheMomentPriority {
-                    id: None,
-                    not_chosen,
-                    in_effect_until,
-                    created: Utc::now().into(),
-                    choice,
-                    kind,
-                };
-                let updated = db
-                    .create(SurrealInTheMomentPriority::TABLE_NAME)
-                    .content(priority.clone())
-                    .await
-                    .unwrap();
-                assert_eq!(1, updated.len());
-
-                let updated: SurrealInTheMomentPriority = updated.into_iter().next().unwrap();
-                priority.id = updated.id.clone();
-                assert_eq!(priority, updated);
-            }
-            Some(DataLayerCommands::ClearInTheMomentPriority(record_id)) => {
-                let updated: SurrealInTheMomentPriority = db
-                    .delete((SurrealInTheMomentPriority::TABLE_NAME, record_id.clone()))
-                    .await
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(updated.id, Some(record_id));
-            }
-            Some(DataLayerCommands::SetCurrentMode(new_current_mode)) => {
-                let current_mode: SurrealCurrentMode = new_current_mode.into();
-                let mut updated = db
-                    .upsert(SurrealCurrentMode::TABLE_NAME)
-                    .content(current_mode.clone())
-                    .await
-                    .unwrap();
-                if updated.is_empty() {
-                    //Annoyingly SurrealDB's upsert seems to just not work sometimes without giving an explicit error so I have to do this
-                    updated = db
-                        .insert(SurrealCurrentMode::TABLE_NAME)
-                        .content(current_mode.clone())
-                        .await
-                        .unwrap();
-                }
-                assert_eq!(1, updated.len());
-                let updated = updated.into_iter().next().unwrap();
-                assert_eq!(current_mode, updated);
-            }
-            Some(DataLayerCommands::TriggerEvent { event, when }) => {
-                let updated: SurrealEvent = db
-                    .update(event.clone())
-                    .patch(PatchOp::replace("/triggered", true))
-                    .patch(PatchOp::replace("/last_updated", when.clone()))
-                    .await
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(updated.id, Some(event));
-                assert!(updated.triggered);
-                assert_eq!(updated.last_updated, when);
-            }
-            Some(DataLayerCommands::UntriggerEvent { event, when }) => {
-                let updated: SurrealEvent = db
-                    .update(event.clone())
-                    .patch(PatchOp::replace("/triggered", false))
-                    .patch(PatchOp::replace("/last_updated", when.clone()))
-                    .await
-                    .unwrap()
-                    .unwrap();
-                assert_eq!(updated.id, Some(event));
-                assert!(!updated.triggered);
-                assert_eq!(updated.last_updated, when);
-            }
-            None => return, //Channel closed, time to shutdown down, exit
-        }
+    /// Sends the changes as one transaction and waits for them to be saved
+    pub(crate) async fn transaction(
+        sender: &Sender<DataLayerCommands>,
+        changes: Vec<DataChange>,
+    ) -> Result<(), DataLayerError> {
+        let (reply_sender, reply_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::Transaction(changes, reply_sender))
+            .await
+            .map_err(|_| DataLayerError::Stopped)?;
+        reply_receiver.await.map_err(|_| DataLayerError::Stopped)?
     }
-}
 
-pub(crate) async fn load_from_surrealdb_upgrade_if_needed(db: &Surreal<Any>) -> SurrealTables {
-    //TODO: I should do some timings to see if starting all of these get_all requests and then doing awaits on them later really is faster in Rust. Or if they just for sure don't start until the await. For example I could call this function as many times as possible in 10 sec and time that and then see how many times I can call that function written like this and then again with the get_all being right with the await to make sure that code like this is worth it perf wise.
-    let all_items = db.select(SurrealItem::TABLE_NAME);
-    let time_spent_log = db.select(SurrealTimeSpent::TABLE_NAME);
-    let surreal_in_the_moment_priorities = db.select(SurrealInTheMomentPriority::TABLE_NAME);
-    let surreal_current_modes = db.select(SurrealCurrentMode::TABLE_NAME);
-    let surreal_modes = db.select(surreal_mode::SurrealMode::TABLE_NAME);
-    let surreal_events = db.select(SurrealEvent::TABLE_NAME);
+    pub(crate) async fn undo(
+        sender: &Sender<DataLayerCommands>,
+    ) -> Result<Option<String>, DataLayerError> {
+        let (undo_sender, undo_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::Undo(undo_sender))
+            .await
+            .map_err(|_| DataLayerError::Stopped)?;
+        undo_receiver.await.map_err(|_| DataLayerError::Stopped)?
+    }
 
-    let all_items: Vec<SurrealItem> = match all_items.await {
-        Ok(all_items) => {
-            if all_items.iter().any(|x: &SurrealItem| x.version == 1) {
-                upgrade_items_table_version1_to_version2(db).await;
-                db.select(SurrealItem::TABLE_NAME).await.unwrap()
-            } else {
-                all_items
-            }
-        }
-        Err(err) => {
-            println!("Upgrading items table because of issue: {}", err);
-            upgrade_items_table(db).await;
-            db.select(SurrealItem::TABLE_NAME).await.unwrap()
-        }
-    };
+    pub(crate) async fn redo(
+        sender: &Sender<DataLayerCommands>,
+    ) -> Result<Option<String>, DataLayerError> {
+        let (redo_sender, redo_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::Redo(redo_sender))
+            .await
+            .map_err(|_| DataLayerError::Stopped)?;
+        redo_receiver.await.map_err(|_| DataLayerError::Stopped)?
+    }
 
-    let time_spent_log = match time_spent_log.await {
-        Ok(time_spent_log) => time_spent_log,
-        Err(err) => {
-            println!("Time spent log is missing because of issue: {}", err);
-            upgrade_time_spent_log(db).await;
-            db.select(SurrealTimeSpent::TABLE_NAME).await.unwrap()
-        }
-    };
+    pub(crate) async fn sync(sender: &Sender<DataLayerCommands>) -> Result<usize, DataLayerError> {
+        let (sync_sender, sync_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::Sync(sync_sender))
+            .await
+            .map_err(|_| DataLayerError::Stopped)?;
+        sync_receiver.await.map_err(|_| DataLayerError::Stopped)?
+    }
 
-    let surreal_in_the_moment_priorities = surreal_in_the_moment_priorities.await.unwrap();
+    pub(crate) async fn get_archive(
+        sender: &Sender<DataLayerCommands>,
+    ) -> Result<SurrealArchive, DataLayerError> {
+        let (archive_sender, archive_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::SendArchive(archive_sender))
+            .await
+            .map_err(|_| DataLayerError::Stopped)?;
+        archive_receiver
+            .await
+            .map_err(|_| DataLayerError::Stopped)?
+    }
 
-    let surreal_modes = surreal_modes.await.unwrap();
+    pub(crate) async fn archive(
+        sender: &Sender<DataLayerCommands>,
+        finished_before: Datetime,
+    ) -> Result<usize, DataLayerError> {
+        let (reply_sender, reply_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::Archive {
+                finished_before,
+                reply: reply_sender,
+            })
+            .await
+            .map_err(|_| DataLayerError::Stopped)?;
+        reply_receiver.await.map_err(|_| DataLayerError::Stopped)?
+    }
 
-    SurrealTables {
-        surreal_items: all_items,
-        surreal_time_spent_log: time_spent_log,
-        surreal_in_the_moment_priorities,
-        surreal_current_modes: surreal_current_modes.await.unwrap(),
-        surreal_modes,
-        surreal_events: surreal_events.await.unwrap(),
+    pub(crate) async fn unarchive(
+        sender: &Sender<DataLayerCommands>,
+        item: RecordId,
+        reopen: bool,
+    ) -> Result<(), DataLayerError> {
+        let (reply_sender, reply_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::Unarchive {
+                item,
+                reopen,
+                reply: reply_sender,
+            })
+            .await
+            .map_err(|_| DataLayerError::Stopped)?;
+        reply_receiver.await.map_err(|_| DataLayerError::Stopped)?
     }
-}
 
-async fn upgrade_items_table_version1_to_version2(db: &Surreal<Any>) {
-    let a: Vec<SurrealItem> = db.select(SurrealItemOldVersion::TABLE_NAME).await.unwrap();
-    for mut item_old_version in a.into_iter() {
-        let item: SurrealItem =
-            if matches!(item_old_version.item_type, SurrealItemType::Motivation(_)) {
-                item_old_version.responsibility = Responsibility::ReactiveBeAvailableToAct;
-                item_old_version.version = 2;
-                item_old_version
-            } else {
-                item_old_version.version = 2;
-                item_old_version
-            };
-        let updated: SurrealItem = db
-            .update(item.id.clone().unwrap())
-            .content(item.clone())
+    pub(crate) async fn get_history(
+        sender: &Sender<DataLayerCommands>,
+        records: Vec<RecordId>,
+    ) -> Result<Vec<SurrealHistoryEntry>, DataLayerError> {
+        let (reply_sender, reply_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::SendHistory {
+                records,
+                reply: reply_sender,
+            })
             .await
-            .unwrap()
-            .unwrap();
-        assert_eq!(item, updated);
+            .map_err(|_| DataLayerError::Stopped)?;
+        reply_receiver.await.map_err(|_| DataLayerError::Stopped)?
     }
-}
 
-async fn upgrade_items_table(db: &Surreal<Any>) {
-    let a: Vec<SurrealItemOldVersion> = db.select(SurrealItemOldVersion::TABLE_NAME).await.unwrap();
-    for item_old_version in a.into_iter() {
-        let item: SurrealItem = item_old_version.into();
-        let updated: SurrealItem = db
-            .update(item.id.clone().unwrap())
-            .content(item.clone())
+    pub(crate) async fn change_passphrase(
+        sender: &Sender<DataLayerCommands>,
+        key: EncryptionKey,
+    ) -> Result<(), DataLayerError> {
+        let (reply_sender, reply_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::ChangePassphrase {
+                key,
+                reply: reply_sender,
+            })
             .await
-            .unwrap()
-            .unwrap();
-        assert_eq!(item, updated);
+            .map_err(|_| DataLayerError::Stopped)?;
+        reply_receiver.await.map_err(|_| DataLayerError::Stopped)?
+    }
+
+    /// When the database is encrypted it is written out after these, the other commands only read
+    fn changes_data(&self) -> bool {
+        match self {
+            DataLayerCommands::ImportRawData { .. }
+            | DataLayerCommands::Change(..)
+            | DataLayerCommands::Transaction(..)
+            | DataLayerCommands::Undo(..)
+            | DataLayerCommands::Redo(..)
+            | DataLayerCommands::Sync(..)
+            | DataLayerCommands::Archive { .. }
+            | DataLayerCommands::Unarchive { .. } => true,
+            DataLayerCommands::SendStartupResult(..)
+            | DataLayerCommands::SendRawData(..)
+            | DataLayerCommands::SubscribeToChanges(..)
+            | DataLayerCommands::SendTimeSpentLog(..)
+            | DataLayerCommands::SendArchive(..)
+            | DataLayerCommands::SendHistory { .. }
+            //Saves on its own so it can reply with whether it worked
+            | DataLayerCommands::ChangePassphrase { .. } => false,
+        }
     }
-}
 
-async fn upgrade_time_spent_log(db: &Surreal<Any>) {
-    let a: Vec<SurrealTimeSpentVersion0> = db.select(SurrealTimeSpent::TABLE_NAME).await.unwrap();
-    for time_spent_old in a.into_iter() {
-        let time_spent: SurrealTimeSpent = time_spent_old.into();
-        let updated: SurrealTimeSpent = db
-            .update(time_spent.id.clone().expect("In DB"))
-            .content(time_spent.clone())
+    pub(crate) async fn import_raw_data(
+        sender: &Sender<DataLayerCommands>,
+        surreal_tables: SurrealTables,
+        surreal_archive: SurrealArchive,
+        replace_existing: bool,
+    ) -> Result<(), DataLayerError> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        sender
+            .send(DataLayerCommands::ImportRawData {
+                surreal_tables,
+                surreal_archive,
+                replace_existing,
+                result: result_sender,
+            })
             .await
-            .unwrap()
-            .unwrap();
-        assert_eq!(time_spent, updated);
+            .map_err(|_| DataLayerError::Stopped)?;
+        result_receiver
+            .await
+            .map_err(|_| DataLayerError::Stopped)?
+            .map_err(DataLayerError::Import)
     }
 }
 
-#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
-struct SurrealIdOnly {
-    id: Thing,
+impl DataChange {
+    /// The description that is shown when undoing
+    fn undo_description(&self) -> &'static str {
+        match self {
+            DataChange::RecordTimeSpent(..) => "Record time spent",
+            DataChange::FinishItem { .. } => "Finish item",
+            DataChange::NewItem(..) => "Capture new item",
+            DataChange::NewMode(..) => "New mode",
+            DataChange::CoverItemWithANewItem { .. } => "Cover item with a new item",
+            DataChange::CoverItemWithAnExistingItem { .. } => "Cover item with an existing item",
+            DataChange::UpdateRelativeImportance { .. } => "Change relative importance",
+            DataChange::ParentItemWithExistingItem { .. } => "Parent item with an existing item",
+            DataChange::ParentItemWithANewChildItem { .. } => "Parent item with a new child item",
+            DataChange::ParentNewItemWithAnExistingChildItem { .. } => {
+                "Parent item with a new parent item"
+            }
+            DataChange::ParentItemRemoveParent { .. } => "Remove parent",
+            DataChange::UpdateResponsibilityAndItemType(..) => "Change item type",
+            DataChange::AddItemDependency(..) => "Add dependency",
+            DataChange::RemoveItemDependency(..) => "Remove dependency",
+            DataChange::AddItemDependencyNewEvent(..) => "Wait on a new event",
+            DataChange::UpdateSummary(..) => "Change summary",
+            DataChange::UpdateModeName(..) => "Rename mode",
+            DataChange::UpdateModeParent(..) => "Move mode",
+            DataChange::UpdateModeOrder(..) => "Reorder modes",
+            DataChange::DeleteMode { .. } => "Delete mode",
+            DataChange::UpdateItemModes(..) => "Change the modes of an item",
+            DataChange::UpdateItemNotes(..) => "Change notes",
+            DataChange::UpdateItemLinks(..) => "Change links",
+            DataChange::AddItemTag(..) => "Add tag",
+            DataChange::RemoveItemTag(..) => "Remove tag",
+            DataChange::UpdateItemRecurrence(..) => "Change how an item repeats",
+            DataChange::UpdateItemDeadline(..) => "Change deadline",
+            DataChange::UpdateItemEstimate(..) => "Change effort estimate",
+            DataChange::UpdateUrgencyPlan(..) => "Change urgency plan",
+            DataChange::UpdateItemReviewFrequency(..) => "Change review frequency",
+            DataChange::UpdateItemLastReviewedDate(..) => "Review item",
+            DataChange::DeclareInTheMomentPriority { .. } => "Declare in the moment priority",
+            DataChange::ClearInTheMomentPriority(..) => "Clear in the moment priority",
+            DataChange::SetCurrentMode(..) => "Change mode",
+            DataChange::TriggerEvent { .. } => "Trigger event",
+            DataChange::UntriggerEvent { .. } => "Untrigger event",
+            DataChange::UpdateEventSummary(..) => "Rename event",
+            DataChange::UpdateEventUntriggerAfter(..) => "Change how often an event happens",
+            DataChange::DeleteEvent(..) => "Delete event",
+            DataChange::MergeEvents { .. } => "Merge events",
+            DataChange::RepairRecord(..) => "Repair",
+            DataChange::ReopenItem(..) => "Re-open item",
+            DataChange::DeleteItem(..) => "Delete item",
+            DataChange::MergeItems { .. } => "Merge items",
+            DataChange::UpdateTimeSpentTimes { .. } => "Change when time was spent",
+            DataChange::UpdateTimeSpentWorkingOn(..) => "Change what time was spent on",
+            DataChange::UpdateTimeSpentDedication(..) => "Change time spent dedication",
+            DataChange::SplitTimeSpent { .. } => "Split time spent",
+            DataChange::DeleteTimeSpent(..) => "Delete time spent",
+        }
+    }
 }
 
-async fn send_time_spent(sender: oneshot::Sender<Vec<SurrealTimeSpent>>, db: &Surreal<Any>) {
-    let time_spent = db.select(SurrealTimeSpent::TABLE_NAME).await.unwrap();
-    sender.send(time_spent).unwrap();
+#[derive(Debug)]
+pub(crate) enum DataLayerError {
+    /// The record to change is not in the database, for example it was removed by an undo
+    NotFound(RecordId),
+    /// Reading or writing the database failed
+    Io(surrealdb::Error),
+    /// The data could not be converted to or from the way it is stored in the database
+    Serialization(String),
+    /// Reading or writing the sync folder failed
+    Sync(String),
+    /// Saving the encrypted database failed or the database is not encrypted
+    Encryption(String),
+    /// The change itself does not make sense, for example a time spent entry that stops before it starts
+    Invalid(String),
+    /// The data storage layer is no longer running so nothing more can be saved
+    Stopped,
+    /// Opening or upgrading the database when the data storage layer started failed
+    Startup(MigrationError),
+    /// The import was refused or did not work, see `ImportError`
+    Import(ImportError),
 }
 
-async fn record_time_spent(new_time_spent: NewTimeSpent, db: &Surreal<Any>) {
-    let mut new_time_spent: SurrealTimeSpent = new_time_spent.into();
-    let saved: Vec<SurrealTimeSpent> = db
-        .create(SurrealTimeSpent::TABLE_NAME)
-        .content(new_time_spent.clone())
-        .await
-        .unwrap();
-    assert_eq!(1, saved.len());
-    let saved = saved.into_iter().next().unwrap();
-    new_time_spent.id = saved.id.clone();
-    assert_eq!(new_time_spent, saved);
+impl Display for DataLayerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DataLayerError::NotFound(record_id) => write!(
+                f,
+                "{} is not in the database, it might have been removed by an undo",
+                record_id
+            ),
+            DataLayerError::Io(err) => write!(f, "Unable to read or write the database: {}", err),
+            DataLayerError::Serialization(err) => write!(
+                f,
+                "Unable to convert the data to or from the way it is stored: {}",
+                err
+            ),
+            DataLayerError::Sync(err) => {
+                write!(f, "Unable to sync with the other devices: {}", err)
+            }
+            DataLayerError::Encryption(err) => {
+                write!(f, "Unable to save the encrypted database: {}", err)
+            }
+            DataLayerError::Invalid(why) => write!(f, "The change is not valid: {}", why),
+            DataLayerError::Stopped => write!(f, "The data storage layer is no longer running"),
+            DataLayerError::Startup(err) => write!(f, "{}", err),
+            DataLayerError::Import(err) => write!(f, "{}", err),
+        }
+    }
 }
 
-pub(crate) async fn finish_item(finish_this: RecordId, when_finished: Datetime, db: &Surreal<Any>) {
-    let updated: SurrealItem = db
-        .update(finish_this)
-        .patch(PatchOp::replace("/finished", Some(when_finished.clone())))
-        .await
-        .unwrap()
-        .unwrap();
-    assert_eq!(updated.finished, Some(when_finished));
-}
+impl Error for DataLayerError {}
 
-async fn create_new_item(mut new_item: NewItem, db: &Surreal<Any>) -> SurrealItem {
-    for dependency in new_item.dependencies.iter_mut() {
-        match dependency {
-            NewDependency::NewEvent(new_event) => {
-                let created = create_new_event(new_event.clone(), db).await;
-                *dependency = NewDependency::Existing(SurrealDependency::AfterEvent(
-                    created.id.expect("In DB"),
-                ));
+impl From<surrealdb::Error> for DataLayerError {
+    fn from(err: surrealdb::Error) -> Self {
+        match err {
+            surrealdb::Error::Api(surrealdb::error::Api::FromValue { error, .. }) => {
+                DataLayerError::Serialization(error)
             }
-            NewDependency::Existing(_) => {}
+            err => DataLayerError::Io(err),
         }
     }
-    let mut surreal_item: SurrealItem = SurrealItem::new(new_item, vec![])
-        .expect("We fix up NewDependency::NewEvent above so it will never happen here");
-    let created: Vec<SurrealItem> = db
-        .create(SurrealItem::TABLE_NAME)
-        .content(surreal_item.clone())
-        .await
-        .unwrap();
-    assert!(created.len() == 1);
-    let created = created.into_iter().next().unwrap();
-    surreal_item.id = created.id.clone();
-    assert_eq!(surreal_item, created);
-
-    created
 }
 
-async fn cover_with_a_new_item(cover_this: RecordId, cover_with: NewItem, db: &Surreal<Any>) {
-    let cover_with = create_new_item(cover_with, db).await;
+/// `find` gives None when the record is not there
+fn found<T>(record: Option<T>, record_id: &RecordId) -> Result<T, DataLayerError> {
+    record.ok_or_else(|| DataLayerError::NotFound(record_id.clone()))
+}
 
-    let cover_with: Option<Thing> = cover_with.into();
-    let cover_with = cover_with.expect("always exists the .into() wraps it in an option");
-    let new_dependency = SurrealDependency::AfterItem(cover_with);
-    add_dependency(cover_this, new_dependency, db).await;
+#[derive(Debug)]
+pub(crate) enum ImportError {
+    /// Importing on top of existing data would mix two databases together so it must be asked for explicitly
+    DatabaseNotEmpty,
+    Surreal(surrealdb::Error),
+    /// After importing the data was read back in and it did not match what was imported
+    RoundTripMismatch,
 }
 
-async fn cover_item_with_an_existing_item(
-    existing_item_to_be_covered: RecordId,
-    existing_item_that_is_doing_the_covering: RecordId,
-    db: &Surreal<Any>,
-) {
-    let new_dependency = SurrealDependency::AfterItem(existing_item_that_is_doing_the_covering);
-    add_dependency(existing_item_to_be_covered, new_dependency, db).await;
+impl Display for ImportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::DatabaseNotEmpty => write!(
+                f,
+                "The database already has data in it, use --replace to overwrite it or import into a new --profile or --db"
+            ),
+            ImportError::Surreal(err) => write!(f, "Database error while importing: {}", err),
+            ImportError::RoundTripMismatch => write!(
+                f,
+                "After importing, the data in the database did not match the data that was imported"
+            ),
+        }
+    }
 }
 
-async fn parent_item_with_existing_item(
-    child_record_id: RecordId,
-    parent_record_id: RecordId,
-    higher_importance_than_this: Option<RecordId>,
-    db: &Surreal<Any>,
-) {
-    //TODO: This should be refactored so it happens inside of a transaction and ideally as one query because if the data is modified between the time that the data is read and the time that the data is written back out then the data could be lost. I haven't done this yet because I need to figure out how to do this inside of a SurrealDB query and I haven't done that yet.
-    let mut parent: SurrealItem = db.select(parent_record_id.clone()).await.unwrap().unwrap();
-    parent.smaller_items_in_priority_order = parent
-        .smaller_items_in_priority_order
-        .into_iter()
-        .filter(|x| match x {
-            SurrealOrderedSubItem::SubItem { surreal_item_id } => {
-                surreal_item_id != &child_record_id
-            }
-        })
-        .collect::<Vec<_>>();
-    if let Some(higher_priority_than_this) = higher_importance_than_this {
-        let index_of_higher_priority = parent
-            .smaller_items_in_priority_order
-            .iter()
-            .position(|x| match x {
-                //Note that position() is short-circuiting. If there are multiple matches it could be argued that I should panic or assert but
-                //I am just matching the first one and then I just keep going. Because I am still figuring out the design and this is
-                //more in the vein of hardening work I think this is fine but feel free to revisit this.
-                SurrealOrderedSubItem::SubItem { surreal_item_id } => {
-                    surreal_item_id == &higher_priority_than_this
-                }
-            })
-            .expect("Should already be in the list");
-        parent.smaller_items_in_priority_order.insert(
-            index_of_higher_priority,
-            SurrealOrderedSubItem::SubItem {
-                surreal_item_id: child_record_id,
-            },
-        );
-    } else {
-        parent
-            .smaller_items_in_priority_order
-            .push(SurrealOrderedSubItem::SubItem {
-                surreal_item_id: child_record_id,
-            });
+impl Error for ImportError {}
+
+impl From<surrealdb::Error> for ImportError {
+    fn from(err: surrealdb::Error) -> Self {
+        ImportError::Surreal(err)
     }
-    let saved = db
-        .update(parent_record_id)
-        .content(parent.clone())
-        .await
-        .unwrap()
-        .unwrap();
-    assert_eq!(parent, saved);
 }
 
-async fn parent_item_with_a_new_child(
-    child: NewItem,
-    parent: RecordId,
-    higher_importance_than_this: Option<RecordId>,
-    db: &Surreal<Any>,
-) {
-    let child = create_new_item(child, db).await;
-    parent_item_with_existing_item(
-        child.id.expect("In DB"),
-        parent,
-        higher_importance_than_this,
-        db,
-    )
-    .await
+pub(crate) const NAMESPACE: &str = "OnPurpose";
+pub(crate) const DATABASE: &str = "Russ"; //TODO: "Russ" should be a parameter, maybe the username or something
+
+/// Subscribers only ever need the latest tables so if they fall behind the older ones can be dropped
+const CHANGES_CAPACITY: usize = 16;
+
+/// The tables as they are in the database kept in memory so a snapshot can be handed out without reading the database
+struct Cache {
+    /// Read in the first time it is needed and then kept up to date as each change is saved
+    surreal_tables: Option<Arc<SurrealTables>>,
+    changes: broadcast::Sender<TablesChanged>,
+    /// Where the changes made on this device are written for the other devices to merge in, None if not syncing
+    sync_log: Option<SyncLog>,
 }
 
-async fn parent_new_item_with_an_existing_child_item(
-    child: RecordId,
-    mut parent_new_item: NewItem,
-    db: &Surreal<Any>,
-) {
-    for dependency in parent_new_item.dependencies.iter_mut() {
-        match dependency {
-            NewDependency::NewEvent(new_event) => {
-                let created = create_new_event(new_event.clone(), db).await;
-                *dependency = NewDependency::Existing(SurrealDependency::AfterEvent(
-                    created.id.expect("In DB"),
-                ));
+impl Cache {
+    fn new() -> Self {
+        let (changes, _) = broadcast::channel(CHANGES_CAPACITY);
+        Cache {
+            surreal_tables: None,
+            changes,
+            sync_log: None,
+        }
+    }
+
+    async fn get(&mut self, db: &Surreal<Any>) -> Result<Arc<SurrealTables>, DataLayerError> {
+        match &self.surreal_tables {
+            Some(surreal_tables) => Ok(surreal_tables.clone()),
+            None => {
+                let surreal_tables = Arc::new(load_from_surrealdb(db).await?);
+                self.surreal_tables = Some(surreal_tables.clone());
+                Ok(surreal_tables)
             }
-            NewDependency::Existing(_) => {}
         }
     }
 
-    //TODO: Write a Unit Test for this
-    let smaller_items_in_priority_order = vec![SurrealOrderedSubItem::SubItem {
-        surreal_item_id: child,
-    }];
+    /// The tables must have been read in before anything was written so it is known what the records were before
+    fn update_tables(&mut self, written: Written) -> Vec<SurrealRecordChange> {
+        let surreal_tables = self
+            .surreal_tables
+            .as_mut()
+            .expect("Read in before anything was written");
+        //Only copied if a snapshot that was handed out is still being used
+        let tables = Arc::make_mut(surreal_tables);
+        let mut changes: Vec<SurrealRecordChange> = Vec::default();
+        for (id, after) in written.0 {
+            let before = tables.apply(&id, after.clone());
+            match changes.iter_mut().find(|x| x.id == id) {
+                Some(change) => change.after = after,
+                None => changes.push(SurrealRecordChange { id, before, after }),
+            }
+        }
+        changes.retain(|x| x.before != x.after);
+        if !changes.is_empty() {
+            //It is fine if there is no one subscribed
+            let _ = self.changes.send(TablesChanged {
+                surreal_tables: surreal_tables.clone(),
+                changed: Some(changes.iter().map(|x| x.id.clone()).collect()),
+            });
+        }
+        changes
+    }
 
-    let mut parent_surreal_item =
-        SurrealItem::new(parent_new_item, smaller_items_in_priority_order)
-            .expect("We deal with new events above so it will never happen here");
-    let created = db
-        .create(SurrealItem::TABLE_NAME)
-        .content(parent_surreal_item.clone())
-        .await
-        .unwrap();
-    assert_eq!(1, created.len());
-    let created: SurrealItem = created.into_iter().next().unwrap();
-    parent_surreal_item.id = created.id.clone();
-    assert_eq!(parent_surreal_item, created);
+    fn log_for_sync(&mut self, changes: &[SurrealRecordChange]) {
+        if let Some(sync_log) = &mut self.sync_log
+            && let Err(err) = sync_log.append(changes)
+        {
+            println!(
+                "Unable to write to the sync folder, the other devices will not see this change: {}",
+                err
+            );
+        }
+    }
+
+    /// For when it is not known for sure what is in the database, for example a write failed part way through
+    async fn reload(&mut self, db: &Surreal<Any>) {
+        self.surreal_tables = None;
+        //If reading fails then it is tried again and the error reported the next time the tables are needed
+        if let Ok(surreal_tables) = self.get(db).await {
+            let _ = self.changes.send(TablesChanged {
+                surreal_tables,
+                changed: None,
+            });
+        }
+    }
 }
 
-async fn add_dependency(record_id: RecordId, new_dependency: SurrealDependency, db: &Surreal<Any>) {
-    //TODO: This should be refactored so it happens inside of a transaction and ideally as one query because if the data is modified between the time that the data is read and the time that the data is written back out then the data could be lost. I haven't done this yet because I need to figure out how to do this inside of a SurrealDB query and I haven't done that yet.
+/// Every record a change saved or deleted, in the order it happened, so the cache can be updated without reading the
+/// database back in
+#[derive(Default)]
+struct Written(Vec<(RecordId, Option<SurrealRecord>)>);
 
-    let mut surreal_item: SurrealItem = db
-        .select(record_id.clone())
-        .await
-        .unwrap()
-        .expect("Record exists");
-    if surreal_item.dependencies.contains(&new_dependency) {
-        //Is already there, nothing to do
-    } else {
-        surreal_item.dependencies.push(new_dependency);
+impl Written {
+    fn saved(&mut self, record: SurrealRecord) {
+        let id = record.id().expect("Saved records have an id").clone();
+        self.0.push((id, Some(record)));
+    }
 
-        let updated: SurrealItem = db
-            .update(record_id)
-            .content(surreal_item.clone())
-            .await
-            .unwrap()
-            .unwrap();
-        assert_eq!(surreal_item, updated);
+    fn deleted(&mut self, id: RecordId) {
+        self.0.push((id, None));
     }
 }
 
-async fn remove_dependency(record_id: RecordId, to_remove: SurrealDependency, db: &Surreal<Any>) {
-    let mut surreal_item: SurrealItem = db.select(record_id.clone()).await.unwrap().unwrap();
-    surreal_item.dependencies.retain(|x| x != &to_remove);
-
-    let update = db
-        .update(record_id)
-        .content(surreal_item.clone())
-        .await
-        .unwrap()
-        .unwrap();
-    assert_eq!(surreal_item, update);
+/// The records as they will be once a change is saved. A change is worked out against the cached tables rather than the
+/// database so nothing is written until all of it has worked, then it is saved in one transaction.
+struct Staged<'t> {
+    surreal_tables: &'t SurrealTables,
+    written: Written,
 }
 
-async fn add_dependency_new_event(record_id: RecordId, new_event: NewEvent, db: &Surreal<Any>) {
-    let created: SurrealEvent = create_new_event(new_event, db).await;
-    let new_dependency = SurrealDependency::AfterEvent(created.id.expect("In DB"));
-
-    add_dependency(record_id, new_dependency, db).await
-}
+impl<'t> Staged<'t> {
+    fn new(surreal_tables: &'t SurrealTables) -> Self {
+        Staged {
+            surreal_tables,
+            written: Written::default(),
+        }
+    }
 
-async fn create_new_event(new_event: NewEvent, db: &Surreal<Any>) -> SurrealEvent {
-    let event: SurrealEvent = new_event.into();
-    let created = db
-        .create(SurrealEvent::TABLE_NAME)
-        .content(event.clone())
-        .await
-        .unwrap();
-    assert_eq!(1, created.len());
-    let created: SurrealEvent = created.into_iter().next().unwrap();
-    assert_eq!(created.last_updated, event.last_updated);
-    assert_eq!(created.summary, event.summary);
-    created
-}
+    /// The record as it is after what has been staged so far, None if it does not exist
+    fn get(&self, id: &RecordId) -> Option<SurrealRecord> {
+        match self.written.0.iter().rev().find(|(x, _)| x == id) {
+            Some((_, state)) => state.clone(),
+            None => self.surreal_tables.get(id),
+        }
+    }
 
-async fn update_item_summary(item_to_update: RecordId, new_summary: String, db: &Surreal<Any>) {
-    let updated: SurrealItem = db
-        .update(item_to_update.clone())
-        .patch(PatchOp::replace("/summary", new_summary.clone()))
-        .await
-        .unwrap()
-        .unwrap();
-    assert_eq!(updated.summary, new_summary);
-}
+    fn item(&self, id: &RecordId) -> Result<SurrealItem, DataLayerError> {
+        match self.get(id) {
+            Some(SurrealRecord::Item(item)) => Ok(item),
+            _ => Err(DataLayerError::NotFound(id.clone())),
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use tokio::sync::mpsc;
+    fn time_spent(&self, id: &RecordId) -> Result<SurrealTimeSpent, DataLayerError> {
+        match self.get(id) {
+            Some(SurrealRecord::TimeSpent(time_spent)) => Ok(time_spent),
+            _ => Err(DataLayerError::NotFound(id.clone())),
+        }
+    }
 
-    use super::*;
+    fn mode(&self, id: &RecordId) -> Result<SurrealMode, DataLayerError> {
+        match self.get(id) {
+            Some(SurrealRecord::Mode(mode)) => Ok(mode),
+            _ => Err(DataLayerError::NotFound(id.clone())),
+        }
+    }
 
-    use crate::{
-        data_storage::surrealdb_layer::surreal_item::SurrealHowMuchIsInMyControl,
-        new_item::NewItemBuilder,
-    };
+    fn event(&self, id: &RecordId) -> Result<SurrealEvent, DataLayerError> {
+        match self.get(id) {
+            Some(SurrealRecord::Event(event)) => Ok(event),
+            _ => Err(DataLayerError::NotFound(id.clone())),
+        }
+    }
 
-    #[tokio::test]
-    async fn data_starts_empty() {
-        let (sender, receiver) = mpsc::channel(1);
-        let data_storage_join_handle =
-            tokio::spawn(async move { data_storage_start_and_run(receiver, "mem://").await });
+    /// Every record as it is after what has been staged so far, the tables are only copied once something is staged
+    fn tables(&self) -> Cow<'t, SurrealTables> {
+        if self.written.0.is_empty() {
+            return Cow::Borrowed(self.surreal_tables);
+        }
+        let mut surreal_tables = self.surreal_tables.clone();
+        for (id, state) in self.written.0.iter() {
+            surreal_tables.apply(id, state.clone());
+        }
+        Cow::Owned(surreal_tables)
+    }
 
-        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+    fn save(&mut self, record: SurrealRecord) {
+        self.written.saved(record);
+    }
 
-        assert!(surreal_tables.surreal_items.is_empty());
-        assert!(surreal_tables.surreal_time_spent_log.is_empty());
+    /// Puts a record in the given state, None means the record should not exist
+    fn restore(&mut self, id: &RecordId, state: Option<SurrealRecord>) {
+        self.written.0.push((id.clone(), state));
+    }
 
-        drop(sender);
-        data_storage_join_handle.await.unwrap();
+    fn delete(&mut self, id: &RecordId) -> Result<(), DataLayerError> {
+        if self.get(id).is_none() {
+            return Err(DataLayerError::NotFound(id.clone()));
+        }
+        self.written.deleted(id.clone());
+        Ok(())
     }
+}
 
-    #[tokio::test]
-    async fn add_new_item() {
-        let (sender, receiver) = mpsc::channel(1);
-        let data_storage_join_handle =
-            tokio::spawn(async move { data_storage_start_and_run(receiver, "mem://").await });
+/// SurrealDB would make up a random id when a record is created, the id is made up here instead so the new record can be
+/// saved in the same transaction as the rest of the change
+fn new_record_id(table_name: &str) -> RecordId {
+    Thing::from((table_name, Id::rand()))
+}
 
-        let new_item = NewItem::new("New item".into(), Utc::now());
-        sender
-            .send(DataLayerCommands::NewItem(new_item))
-            .await
-            .unwrap();
+/// Saved in the same transaction as the records that changed so the history and the undo journal always match them
+#[derive(Default)]
+struct Journal {
+    /// Only ever added to, undoing a change records the change back as more history
+    history: Vec<SurrealHistoryEntry>,
+    /// Undo entries that are removed, for example the undone ones once there is a new change
+    forgotten: Vec<RecordId>,
+    /// A new undo entry, or one that was just undone or redone
+    undo_entry: Option<SurrealUndoEntry>,
+}
 
-        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+impl Journal {
+    /// `surreal_tables` is what is being saved, it is used to describe the records that a value refers to
+    fn new(changes: &[SurrealRecordChange], surreal_tables: &SurrealTables) -> Self {
+        Journal {
+            history: history_entries(changes, surreal_tables, &Utc::now().into()),
+            ..Journal::default()
+        }
+    }
 
-        assert_eq!(surreal_tables.surreal_items.len(), 1);
-        assert_eq!(
-            SurrealItemType::Undeclared,
-            surreal_tables.surreal_items.first().unwrap().item_type
-        );
+    fn is_empty(&self) -> bool {
+        self.history.is_empty() && self.forgotten.is_empty() && self.undo_entry.is_none()
+    }
 
-        drop(sender);
-        data_storage_join_handle.await.unwrap();
+    /// A new change means the undone changes can no longer be redone, and once there are more than
+    /// `SurrealUndoEntry::MAX_ENTRIES` the oldest are forgotten
+    fn record_undo_entry(
+        &mut self,
+        description: &str,
+        changes: Vec<SurrealRecordChange>,
+        undo_journal: &[SurrealUndoEntry],
+    ) {
+        let kept = undo_journal
+            .iter()
+            .filter(|x| !x.undone)
+            .collect::<Vec<_>>();
+        let remove_count = (kept.len() + 1).saturating_sub(SurrealUndoEntry::MAX_ENTRIES);
+        self.forgotten = undo_journal
+            .iter()
+            .filter(|x| x.undone)
+            .chain(kept.into_iter().take(remove_count))
+            .map(|x| x.id.clone().expect("In DB"))
+            .collect();
+        self.undo_entry = Some(SurrealUndoEntry {
+            id: Some(new_record_id(SurrealUndoEntry::TABLE_NAME)),
+            version: 0,
+            sequence: undo_journal.last().map_or(0, |x| x.sequence + 1),
+            created: Utc::now().into(),
+            description: description.to_string(),
+            undone: false,
+            changes,
+        });
     }
+}
 
-    #[tokio::test]
-    async fn finish_item() {
-        let (sender, receiver) = mpsc::channel(1);
-        let data_storage_join_handle =
-            tokio::spawn(async move { data_storage_start_and_run(receiver, "mem://").await });
+#[derive(Default)]
+pub(crate) struct DataStorageSettings {
+    /// Where and how often backups are made, including before the database is upgraded. None means no backups are made
+    /// which is intended for in memory databases.
+    pub(crate) backups: Option<BackupSettings>,
+    /// A folder shared between devices, for example with OneDrive or Syncthing, that each device writes its changes to
+    /// and merges the other devices' changes from. None means this device does not sync.
+    pub(crate) sync_folder: Option<PathBuf>,
+    /// The database is kept in this encrypted file, the endpoint should then be an in memory database. None means
+    /// SurrealDB stores the database itself, unencrypted.
+    pub(crate) encryption: Option<EncryptionSettings>,
+}
 
-        let new_next_step = NewItemBuilder::default()
-            .summary("New next step")
-            .item_type(SurrealItemType::Action)
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(new_next_step))
-            .await
-            .unwrap();
+pub(crate) async fn data_storage_start_and_run(
+    mut data_storage_layer_receive_rx: Receiver<DataLayerCommands>,
+    endpoint: impl IntoEndpoint,
+    settings: DataStorageSettings,
+) {
+    let db = connect(endpoint).await.unwrap();
+    db.use_ns(NAMESPACE).use_db(DATABASE).await.unwrap();
 
-        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
-        let now = Utc::now();
-        let items = surreal_tables.make_items(&now);
+    let DataStorageSettings {
+        backups,
+        sync_folder,
+        encryption,
+    } = settings;
+    let mut encrypted_database = None;
+    let mut startup_result = match encryption {
+        Some(encryption) => match EncryptedDatabase::open(encryption, &db).await {
+            Ok(opened) => {
+                encrypted_database = Some(opened);
+                Ok(())
+            }
+            Err(err) => Err(MigrationError::OpenEncrypted(err)),
+        },
+        None => Ok(()),
+    };
+    if startup_result.is_ok() {
+        startup_result = migrate_if_needed(
+            &db,
+            backups.as_ref().map(|x| x.directory.as_path()),
+            encrypted_database.as_ref().map(EncryptedDatabase::key),
+        )
+        .await;
+    }
+    let startup_failed = startup_result.is_err();
+    let mut cache = Cache::new();
+    if let Some(sync_folder) = sync_folder
+        && !startup_failed
+    {
+        match start_syncing(sync_folder.clone(), &mut cache, &db).await {
+            Ok(merged) if merged > 0 => {
+                println!("Merged {} changes from the other devices", merged)
+            }
+            Ok(_) => {}
+            Err(err) => println!(
+                "Not syncing with the other devices in {}: {}",
+                sync_folder.display(),
+                err
+            ),
+        }
+    }
+    let mut last_backed_up: Option<Arc<SurrealTables>> = None;
+    let mut backup_timer = None;
+    if let Some(backups) = &backups
+        && !startup_failed
+    {
+        backup_if_changed(
+            &mut cache,
+            &mut last_backed_up,
+            backups,
+            encrypted_database.as_ref().map(EncryptedDatabase::key),
+            &db,
+        )
+        .await;
+        if let Some(period) = backups.schedule.interval {
+            let mut timer = interval_at(Instant::now() + period, period);
+            //If the computer was asleep there is no reason to make several backups in a row when it wakes up
+            timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            backup_timer = Some(timer);
+        }
+    }
+    if !startup_failed {
+        //The upgrade or the merged changes from the other devices are saved straight away
+        save_if_encrypted(&mut encrypted_database, &db).await;
+    }
 
-        assert_eq!(items.len(), 1);
-        let next_step_item = items.iter().next().map(|(_, v)| v).unwrap();
-        assert_eq!(next_step_item.is_finished(), false);
+    // let updated: Option<SurrealItem> = db.update((SurrealItem::TABLE_NAME, "5i5mkemqn0f1716v3ycw"))
+    //     .patch(PatchOp::replace("/urgency_plan", None::<Option<SurrealUrgencyPlan>>)).await.unwrap();
+    // assert!(updated.is_some());
+    // panic!("Finished");
+    loop {
+        let received = tokio::select! {
+            received = data_storage_layer_receive_rx.recv() => received,
+            _ = next_backup(&mut backup_timer) => {
+                let backups = backups.as_ref().expect("Only a timer if there are backups");
+                backup_if_changed(
+                    &mut cache,
+                    &mut last_backed_up,
+                    backups,
+                    encrypted_database.as_ref().map(EncryptedDatabase::key),
+                    &db,
+                )
+                .await;
+                continue;
+            }
+        };
+        if startup_failed
+            && !matches!(
+                received,
+                Some(DataLayerCommands::SendStartupResult(..)) | None
+            )
+        {
+            //The database was not upgraded so it is not safe to read or write it
+            continue;
+        }
+        let changes_data = received
+            .as_ref()
+            .is_some_and(DataLayerCommands::changes_data);
+        //Replies are sent with `let _ =` because if the caller stopped waiting for the reply there is nothing else to do
+        match received {
+            Some(DataLayerCommands::SendStartupResult(sender)) => {
+                let _ = sender.send(mem::replace(&mut startup_result, Ok(())));
+            }
+            Some(DataLayerCommands::SendRawData(oneshot)) => {
+                let _ = oneshot.send(cache.get(&db).await);
+            }
+            Some(DataLayerCommands::SubscribeToChanges(sender)) => {
+                let _ = sender.send(cache.changes.subscribe());
+            }
+            Some(DataLayerCommands::ImportRawData {
+                surreal_tables,
+                surreal_archive,
+                replace_existing,
+                result,
+            }) => {
+                let imported = import_and_log_for_sync(
+                    surreal_tables,
+                    surreal_archive,
+                    replace_existing,
+                    &mut cache,
+                    &db,
+                )
+                .await;
+                let _ = result.send(imported);
+            }
+            Some(DataLayerCommands::SendTimeSpentLog(sender)) => send_time_spent(sender, &db).await,
+            Some(DataLayerCommands::Change(change, reply)) => {
+                let result = change_and_record_undo(change, &mut cache, &db).await;
+                let _ = reply.send(result);
+            }
+            Some(DataLayerCommands::Transaction(changes, reply)) => {
+                let result = transaction_and_record_undo(changes, &mut cache, &db).await;
+                let _ = reply.send(result);
+            }
+            Some(DataLayerCommands::Undo(sender)) => {
+                let undone = undo(&mut cache, &db).await;
+                let _ = sender.send(undone);
+            }
+            Some(DataLayerCommands::Redo(sender)) => {
+                let redone = redo(&mut cache, &db).await;
+                let _ = sender.send(redone);
+            }
+            Some(DataLayerCommands::Sync(sender)) => {
+                let merged = merge_from_other_devices(&mut cache, &db).await;
+                let _ = sender.send(merged);
+            }
+            Some(DataLayerCommands::SendArchive(sender)) => {
+                let _ = sender.send(load_archive(&db).await.map_err(DataLayerError::from));
+            }
+            Some(DataLayerCommands::Archive {
+                finished_before,
+                reply,
+            }) => {
+                let archived = archive_finished(finished_before, &mut cache, &db).await;
+                let _ = reply.send(archived);
+            }
+            Some(DataLayerCommands::Unarchive {
+                item,
+                reopen,
+                reply,
+            }) => {
+                let unarchived = unarchive(item, reopen, &mut cache, &db).await;
+                let _ = reply.send(unarchived);
+            }
+            Some(DataLayerCommands::SendHistory { records, reply }) => {
+                let _ = reply.send(load_history(records, &db).await);
+            }
+            Some(DataLayerCommands::ChangePassphrase { key, reply }) => {
+                let changed = match &mut encrypted_database {
+                    Some(encrypted_database) => encrypted_database
+                        .change_key(key, &db)
+                        .await
+                        .map_err(|err| DataLayerError::Encryption(err.to_string())),
+                    None => Err(DataLayerError::Encryption(
+                        "The database is not encrypted".to_string(),
+                    )),
+                };
+                let _ = reply.send(changed);
+            }
+            None => return, //Channel closed, time to shutdown down, exit
+        }
+        if changes_data {
+            save_if_encrypted(&mut encrypted_database, &db).await;
+        }
+    }
+}
 
-        let when_finished = Utc::now();
-        sender
-            .send(DataLayerCommands::FinishItem {
-                item: next_step_item.get_surreal_record_id().clone().into(),
-                when_finished: when_finished.into(),
-            })
-            .await
-            .unwrap();
+/// The change is already made in memory so if it cannot be saved all that can be done is to say so, it is tried again
+/// after the next change
+async fn save_if_encrypted(encrypted_database: &mut Option<EncryptedDatabase>, db: &Surreal<Any>) {
+    if let Some(encrypted_database) = encrypted_database
+        && let Err(err) = encrypted_database.save_if_changed(db).await
+    {
+        println!("{}", DataLayerError::Encryption(err.to_string()));
+    }
+}
 
-        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
-        let now = Utc::now();
-        let items = surreal_tables.make_items(&now);
+/// The first merge also moves the clock past every change already in the sync folder so the changes made on this device
+/// from now on are ordered after them
+async fn start_syncing(
+    sync_folder: PathBuf,
+    cache: &mut Cache,
+    db: &Surreal<Any>,
+) -> Result<usize, DataLayerError> {
+    let surreal_tables = cache.get(db).await?;
+    let sync_log = SyncLog::open(sync_folder, db, &surreal_tables)
+        .await
+        .map_err(|err| DataLayerError::Sync(err.to_string()))?;
+    cache.sync_log = Some(sync_log);
+    merge_from_other_devices(cache, db).await
+}
 
-        assert_eq!(items.len(), 1);
-        let next_step_item = items.iter().next().map(|(_, v)| v).unwrap();
-        assert_eq!(next_step_item.is_finished(), true);
+/// The merged changes are not logged again, they are already in the other devices' logs, and they are not added to the
+/// undo journal because they were not made on this device
+async fn merge_from_other_devices(
+    cache: &mut Cache,
+    db: &Surreal<Any>,
+) -> Result<usize, DataLayerError> {
+    let surreal_tables = cache.get(db).await?;
+    let Some(sync_log) = &mut cache.sync_log else {
+        return Ok(0);
+    };
+    //Archiving is not logged so the other devices still have the archived records, their changes to them are skipped
+    let archived = load_archive(db).await?.archived_ids();
+    let changes = sync_log
+        .merge(&surreal_tables, &archived)
+        .map_err(|err| DataLayerError::Sync(err.to_string()))?;
+    let mut written = Written::default();
+    for change in changes {
+        written.0.push((change.id, change.after));
+    }
+    let changes = cache.update_tables(written);
+    let journal = Journal::new(&changes, &cache.get(db).await?);
+    save_or_reload(&changes, &journal, cache, db).await?;
+    Ok(changes.len())
+}
 
-        drop(sender);
-        data_storage_join_handle.await.unwrap();
+/// An import replaces what is in the database so every record that ended up different is logged for the other devices
+async fn import_and_log_for_sync(
+    surreal_tables: SurrealTables,
+    surreal_archive: SurrealArchive,
+    replace_existing: bool,
+    cache: &mut Cache,
+    db: &Surreal<Any>,
+) -> Result<(), ImportError> {
+    let before = cache.get(db).await.ok();
+    let imported = import_raw_data(surreal_tables, surreal_archive, replace_existing, db).await;
+    cache.reload(db).await;
+    if let (Some(before), Some(after)) = (before, &cache.surreal_tables) {
+        let changes = SurrealRecordChange::changes_between(&before, after);
+        cache.log_for_sync(&changes);
     }
+    imported
+}
 
-    #[tokio::test]
-    async fn cover_item_with_a_new_proactive_next_step() {
-        let (sender, receiver) = mpsc::channel(1);
-        let data_storage_join_handle =
-            tokio::spawn(async move { data_storage_start_and_run(receiver, "mem://").await });
+async fn next_backup(backup_timer: &mut Option<Interval>) {
+    match backup_timer {
+        Some(backup_timer) => {
+            backup_timer.tick().await;
+        }
+        None => future::pending().await,
+    }
+}
 
-        let new_action = NewItemBuilder::default()
-            .summary("Item to be covered")
-            .item_type(SurrealItemType::Action)
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(new_action))
-            .await
-            .unwrap();
+/// Nothing is backed up if nothing changed since the last backup or if there is no data yet
+async fn backup_if_changed(
+    cache: &mut Cache,
+    last_backed_up: &mut Option<Arc<SurrealTables>>,
+    backups: &BackupSettings,
+    encryption: Option<&EncryptionKey>,
+    db: &Surreal<Any>,
+) {
+    let surreal_tables = match cache.get(db).await {
+        Ok(surreal_tables) => surreal_tables,
+        Err(err) => {
+            println!("Unable to read the database to back it up: {}", err);
+            return;
+        }
+    };
+    //The cached tables are only ever replaced, never changed in place, so if it is the same snapshot nothing changed
+    let unchanged = last_backed_up
+        .as_ref()
+        .is_some_and(|x| Arc::ptr_eq(x, &surreal_tables));
+    if unchanged || surreal_tables.is_empty() {
+        return;
+    }
+    let surreal_archive = match load_archive(db).await {
+        Ok(surreal_archive) => surreal_archive,
+        Err(err) => {
+            println!("Unable to read the archive to back it up: {}", err);
+            return;
+        }
+    };
+    match make_backup(
+        &surreal_tables,
+        &surreal_archive,
+        backups,
+        encryption,
+        Local::now().naive_local(),
+    ) {
+        Ok(_) => *last_backed_up = Some(surreal_tables),
+        Err(err) => println!(
+            "Unable to make a backup in {}: {}",
+            backups.directory.display(),
+            err
+        ),
+    }
+}
 
-        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+/// A change on its own is saved the same way as a transaction with one change in it
+async fn change_and_record_undo(
+    change: DataChange,
+    cache: &mut Cache,
+    db: &Surreal<Any>,
+) -> Result<(), DataLayerError> {
+    transaction_and_record_undo(vec![change], cache, db).await
+}
 
-        assert_eq!(1, surreal_tables.surreal_items.len());
-        assert_eq!(
-            0,
-            surreal_tables
-                .surreal_items
-                .first()
-                .unwrap()
-                .dependencies
-                .len()
-        ); //length of zero means nothing is covered
-        let item_to_cover = surreal_tables.surreal_items.first().unwrap();
+/// Every change is worked out against the cached tables so if any of them fails nothing is saved. Then every record
+/// that ended up different is saved along with its history and the undo entry in one transaction.
+async fn transaction_and_record_undo(
+    changes: Vec<DataChange>,
+    cache: &mut Cache,
+    db: &Surreal<Any>,
+) -> Result<(), DataLayerError> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let undo_description = changes
+        .iter()
+        .map(DataChange::undo_description)
+        .collect::<Vec<_>>()
+        .join(", then ");
+    let written = {
+        let surreal_tables = cache.get(db).await?;
+        let mut staged = Staged::new(&surreal_tables);
+        for change in changes {
+            apply_change(change, &mut staged)?;
+        }
+        staged.written
+    };
+    let undo_journal = load_undo_journal(db).await?;
 
-        let new_item = NewItemBuilder::default()
-            .summary("Covering item")
-            .responsibility(Responsibility::ProactiveActionToTake)
-            .item_type(SurrealItemType::Action)
-            .build()
-            .unwrap();
+    let record_changes = cache.update_tables(written);
+    if record_changes.is_empty() {
+        return Ok(());
+    }
+    let mut journal = Journal::new(&record_changes, &cache.get(db).await?);
+    journal.record_undo_entry(&undo_description, record_changes.clone(), &undo_journal);
+    save_or_reload(&record_changes, &journal, cache, db).await?;
+    cache.log_for_sync(&record_changes);
+    Ok(())
+}
 
-        sender
-            .send(DataLayerCommands::CoverItemWithANewItem {
-                cover_this: item_to_cover.id.clone().expect("In DB"),
-                cover_with: new_item,
-            })
-            .await
-            .unwrap();
+/// The cache is updated before saving so the history can describe the records as they are saved, if saving fails the
+/// cache is read back in so it matches the database again
+async fn save_or_reload(
+    changes: &[SurrealRecordChange],
+    journal: &Journal,
+    cache: &mut Cache,
+    db: &Surreal<Any>,
+) -> Result<(), DataLayerError> {
+    let saved = save_in_one_transaction(changes, journal, db).await;
+    if saved.is_err() {
+        cache.reload(db).await;
+    }
+    saved
+}
 
-        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+async fn save_in_one_transaction(
+    changes: &[SurrealRecordChange],
+    journal: &Journal,
+    db: &Surreal<Any>,
+) -> Result<(), DataLayerError> {
+    if changes.is_empty() && journal.is_empty() {
+        return Ok(());
+    }
+    let mut statements = vec!["BEGIN TRANSACTION;".to_string()];
+    for (index, change) in changes.iter().enumerate() {
+        match change.after {
+            Some(_) => statements.push(format!("UPSERT $id{index} CONTENT $record{index};")),
+            None => statements.push(format!("DELETE $id{index};")),
+        }
+    }
+    if !journal.history.is_empty() {
+        //SurrealDB gives an error when inserting an empty list
+        statements.push(format!(
+            "INSERT INTO {} $history;",
+            SurrealHistoryEntry::TABLE_NAME
+        ));
+    }
+    for index in 0..journal.forgotten.len() {
+        statements.push(format!("DELETE $forgotten{index};"));
+    }
+    if journal.undo_entry.is_some() {
+        statements.push("UPSERT $undo_entry_id CONTENT $undo_entry;".to_string());
+    }
+    statements.push("COMMIT TRANSACTION;".to_string());
 
-        let item_that_should_be_covered = surreal_tables
-            .surreal_items
-            .iter()
-            .find(|x| x.summary == "Item to be covered")
-            .unwrap();
-        assert_eq!(2, surreal_tables.surreal_items.len());
-        assert_eq!(1, item_that_should_be_covered.dependencies.len()); //expect one item to be is covered
-        let item_that_should_cover = surreal_tables
-            .surreal_items
-            .iter()
-            .find(|x| x.summary == "Covering item")
-            .unwrap();
-        let id = match &item_that_should_be_covered.dependencies.first().unwrap() {
-            SurrealDependency::AfterItem(id) => id,
-            _ => panic!("Should be an item"),
-        };
-        assert_eq!(item_that_should_cover.id.as_ref().unwrap(), id,);
+    let mut query = db.query(statements.join("\n"));
+    for (index, change) in changes.iter().enumerate() {
+        query = query.bind((format!("id{}", index), change.id.clone()));
+        if let Some(after) = &change.after {
+            query = bind_record(query, format!("record{}", index), after.clone());
+        }
+    }
+    if !journal.history.is_empty() {
+        query = query.bind(("history", journal.history.clone()));
+    }
+    for (index, id) in journal.forgotten.iter().enumerate() {
+        query = query.bind((format!("forgotten{}", index), id.clone()));
+    }
+    if let Some(undo_entry) = &journal.undo_entry {
+        query = query
+            .bind((
+                "undo_entry_id",
+                undo_entry
+                    .id
+                    .clone()
+                    .expect("Given an id before it is saved"),
+            ))
+            .bind(("undo_entry", undo_entry.clone()));
+    }
+    //check() turns an error from any of the statements into an error, otherwise they are only in the response
+    query.await?.check()?;
+    Ok(())
+}
 
-        drop(sender);
-        data_storage_join_handle.await.unwrap();
+fn bind_record(query: Query<'_, Any>, name: String, record: SurrealRecord) -> Query<'_, Any> {
+    match record {
+        SurrealRecord::Item(item) => query.bind((name, item)),
+        SurrealRecord::TimeSpent(time_spent) => query.bind((name, time_spent)),
+        SurrealRecord::InTheMomentPriority(priority) => query.bind((name, priority)),
+        SurrealRecord::CurrentMode(current_mode) => query.bind((name, current_mode)),
+        SurrealRecord::Mode(mode) => query.bind((name, mode)),
+        SurrealRecord::Event(event) => query.bind((name, event)),
     }
+}
 
-    #[tokio::test]
-    async fn parent_item_with_a_new_item() {
-        let (sender, receiver) = mpsc::channel(1);
-        let data_storage_join_handle =
-            tokio::spawn(async move { data_storage_start_and_run(receiver, "mem://").await });
+/// Archiving only moves records from one table to another on this device so it is not logged for the other devices,
+/// they keep their copies until they are archived there
+async fn archive_finished(
+    finished_before: Datetime,
+    cache: &mut Cache,
+    db: &Surreal<Any>,
+) -> Result<usize, DataLayerError> {
+    let surreal_tables = cache.get(db).await?;
+    let (items, time_spent_log) = to_archive(&surreal_tables, &finished_before);
+    if items.is_empty() {
+        return Ok(0);
+    }
+    let archived: Datetime = Utc::now().into();
+    let surreal_archive = SurrealArchive {
+        items: items
+            .into_iter()
+            .map(|x| SurrealArchivedItem::new(x, archived.clone()))
+            .collect(),
+        time_spent_log: time_spent_log
+            .into_iter()
+            .map(|x| SurrealArchivedTimeSpent::new(x, archived.clone()))
+            .collect(),
+    };
+    let archived_ids = surreal_archive.archived_ids();
+    move_in_one_transaction(&surreal_archive, &[], &archived_ids, db).await?;
 
-        let new_action = NewItemBuilder::default()
-            .summary("Item that needs a parent")
-            .item_type(SurrealItemType::Action)
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(new_action))
-            .await
-            .unwrap();
+    let mut written = Written::default();
+    for id in archived_ids.iter() {
+        written.deleted(id.clone());
+    }
+    cache.update_tables(written);
+    forget_undo_entries_for(&archived_ids, db).await?;
+    Ok(surreal_archive.items.len())
+}
 
-        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+/// Putting the records back is not logged for the other devices either, but re-opening the item is a change like any
+/// other so it is logged and can be undone
+async fn unarchive(
+    item: RecordId,
+    reopen: bool,
+    cache: &mut Cache,
+    db: &Surreal<Any>,
+) -> Result<(), DataLayerError> {
+    let surreal_tables = cache.get(db).await?;
+    let surreal_archive = load_archive(db).await?;
+    let (items, time_spent_log) = to_unarchive(&surreal_archive, &item, &surreal_tables);
+    if items.is_empty() {
+        return Err(DataLayerError::NotFound(item));
+    }
+    let archive_ids: HashSet<RecordId> = items
+        .iter()
+        .map(|x| &x.id)
+        .chain(time_spent_log.iter().map(|x| &x.id))
+        .map(|x| x.clone().expect("In DB"))
+        .collect();
+    let restored = items
+        .into_iter()
+        .map(|x| SurrealRecord::Item(x.item))
+        .chain(
+            time_spent_log
+                .into_iter()
+                .map(|x| SurrealRecord::TimeSpent(x.time_spent)),
+        )
+        .collect::<Vec<_>>();
+    move_in_one_transaction(&SurrealArchive::default(), &restored, &archive_ids, db).await?;
 
-        assert_eq!(1, surreal_tables.surreal_items.len());
+    let mut written = Written::default();
+    for record in restored {
+        written.saved(record);
+    }
+    cache.update_tables(written);
+    if reopen {
+        change_and_record_undo(DataChange::ReopenItem(item), cache, db).await?;
+    }
+    Ok(())
+}
 
-        sender
-            .send(DataLayerCommands::ParentNewItemWithAnExistingChildItem {
-                child: surreal_tables
-                    .surreal_items
-                    .into_iter()
-                    .next()
-                    .unwrap()
-                    .id
-                    .expect("In Db"),
-                parent_new_item: NewItemBuilder::default()
-                    .summary("Parent Item")
-                    .item_type(SurrealItemType::Goal(SurrealHowMuchIsInMyControl::default()))
-                    .build()
-                    .unwrap(),
-            })
-            .await
-            .unwrap();
+/// Saves `to_archive` and `to_tables` and deletes `deleted` all in one transaction so a record is never lost, or found
+/// in both the tables and the archive, if the program is closed part way through
+async fn move_in_one_transaction(
+    to_archive: &SurrealArchive,
+    to_tables: &[SurrealRecord],
+    deleted: &HashSet<RecordId>,
+    db: &Surreal<Any>,
+) -> Result<(), DataLayerError> {
+    let mut statements = vec!["BEGIN TRANSACTION;".to_string()];
+    for index in 0..to_archive.items.len() {
+        statements.push(format!(
+            "UPSERT $archived_item_id{index} CONTENT $archived_item{index};"
+        ));
+    }
+    for index in 0..to_archive.time_spent_log.len() {
+        statements.push(format!(
+            "UPSERT $archived_time_spent_id{index} CONTENT $archived_time_spent{index};"
+        ));
+    }
+    for index in 0..to_tables.len() {
+        statements.push(format!("UPSERT $id{index} CONTENT $record{index};"));
+    }
+    for index in 0..deleted.len() {
+        statements.push(format!("DELETE $deleted{index};"));
+    }
+    statements.push("COMMIT TRANSACTION;".to_string());
 
-        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+    let mut query = db.query(statements.join("\n"));
+    for (index, archived_item) in to_archive.items.iter().enumerate() {
+        query = query
+            .bind((
+                format!("archived_item_id{}", index),
+                archived_item.id.clone(),
+            ))
+            .bind((format!("archived_item{}", index), archived_item.clone()));
+    }
+    for (index, archived_time_spent) in to_archive.time_spent_log.iter().enumerate() {
+        query = query
+            .bind((
+                format!("archived_time_spent_id{}", index),
+                archived_time_spent.id.clone(),
+            ))
+            .bind((
+                format!("archived_time_spent{}", index),
+                archived_time_spent.clone(),
+            ));
+    }
+    for (index, record) in to_tables.iter().enumerate() {
+        query = query.bind((format!("id{}", index), record.id().expect("In DB").clone()));
+        query = bind_record(query, format!("record{}", index), record.clone());
+    }
+    for (index, id) in deleted.iter().enumerate() {
+        query = query.bind((format!("deleted{}", index), id.clone()));
+    }
+    query.await?.check()?;
+    Ok(())
+}
 
-        assert_eq!(2, surreal_tables.surreal_items.len());
-        assert_eq!(
-            1,
-            surreal_tables
-                .surreal_items
+/// Undoing or redoing a change to an archived record would bring back a copy of it while it is still in the archive, so
+/// the changes that touched an archived record can no longer be undone
+async fn forget_undo_entries_for(
+    archived_ids: &HashSet<RecordId>,
+    db: &Surreal<Any>,
+) -> Result<(), DataLayerError> {
+    for undo_entry in load_undo_journal(db).await? {
+        if undo_entry
+            .changes
+            .iter()
+            .any(|x| archived_ids.contains(&x.id))
+        {
+            let _: Option<SurrealUndoEntry> =
+                db.delete(undo_entry.id.clone().expect("In DB")).await?;
+        }
+    }
+    Ok(())
+}
+
+fn apply_change(change: DataChange, staged: &mut Staged<'_>) -> Result<(), DataLayerError> {
+    match change {
+        DataChange::RecordTimeSpent(new_time_spent) => {
+            record_time_spent(new_time_spent, staged);
+            Ok(())
+        }
+        DataChange::FinishItem {
+            item,
+            when_finished,
+        } => finish_item(item, when_finished, staged),
+        DataChange::NewItem(new_item) => {
+            create_new_item(new_item, staged);
+            Ok(())
+        }
+        DataChange::CoverItemWithANewItem {
+            cover_this,
+            cover_with,
+        } => cover_with_a_new_item(cover_this, cover_with, staged),
+        DataChange::CoverItemWithAnExistingItem {
+            item_to_be_covered,
+            item_that_should_do_the_covering,
+        } => cover_item_with_an_existing_item(
+            item_to_be_covered,
+            item_that_should_do_the_covering,
+            staged,
+        ),
+        DataChange::NewMode(new_mode) => {
+            let mut surreal_mode: SurrealMode = new_mode.into();
+            //A new mode goes after the modes that were already there
+            surreal_mode.display_order = staged
+                .tables()
+                .surreal_modes
                 .iter()
-                .find(|x| x.summary == "Parent Item")
-                .unwrap()
-                .smaller_items_in_priority_order
-                .len()
-        );
-        assert_eq!(
-            &SurrealOrderedSubItem::SubItem {
-                surreal_item_id: surreal_tables
-                    .surreal_items
+                .filter(|x| x.parent == surreal_mode.parent)
+                .map(|x| x.display_order + 1)
+                .max()
+                .unwrap_or_default();
+            surreal_mode.id = Some(new_record_id(SurrealMode::TABLE_NAME));
+            staged.save(SurrealRecord::Mode(surreal_mode));
+            Ok(())
+        }
+        DataChange::ParentItemWithExistingItem {
+            child,
+            parent,
+            higher_importance_than_this,
+        } => parent_item_with_existing_item(child, parent, higher_importance_than_this, staged),
+        DataChange::ParentItemWithANewChildItem {
+            child,
+            parent,
+            higher_importance_than_this,
+        } => parent_item_with_a_new_child(child, parent, higher_importance_than_this, staged),
+        DataChange::ParentNewItemWithAnExistingChildItem {
+            child,
+            parent_new_item,
+        } => {
+            parent_new_item_with_an_existing_child_item(child, parent_new_item, staged);
+            Ok(())
+        }
+        DataChange::ParentItemRemoveParent {
+            child,
+            parent_to_remove,
+        } => update_item(
+            &parent_to_remove,
+            |parent| {
+                parent.smaller_items_in_priority_order.retain(|x| match x {
+                    SurrealOrderedSubItem::SubItem { surreal_item_id } => surreal_item_id != &child,
+                })
+            },
+            staged,
+        ),
+        DataChange::AddItemDependency(record_id, new_ready) => {
+            add_dependency(record_id, new_ready, staged)
+        }
+        DataChange::RemoveItemDependency(record_id, to_remove) => {
+            remove_dependency(record_id, to_remove, staged)
+        }
+        DataChange::AddItemDependencyNewEvent(record_id, new_event) => {
+            add_dependency_new_event(record_id, new_event, staged)
+        }
+        DataChange::UpdateRelativeImportance {
+            parent,
+            update_this_child,
+            higher_importance_than_this_child,
+        } => parent_item_with_existing_item(
+            update_this_child,
+            parent,
+            higher_importance_than_this_child,
+            staged,
+        ),
+        DataChange::UpdateItemLastReviewedDate(record_id, new_last_reviewed) => update_item(
+            &record_id,
+            |x| x.last_reviewed = Some(new_last_reviewed),
+            staged,
+        ),
+        DataChange::UpdateItemReviewFrequency(
+            record_id,
+            surreal_frequency,
+            surreal_review_guidance,
+        ) => update_item(
+            &record_id,
+            |x| {
+                x.review_frequency = Some(surreal_frequency);
+                x.review_guidance = Some(surreal_review_guidance);
+            },
+            staged,
+        ),
+        DataChange::UpdateSummary(item, new_summary) => {
+            update_item(&item, |x| x.summary = new_summary, staged)
+        }
+        DataChange::UpdateModeName(thing, new_name) => {
+            let mut surreal_mode = staged.mode(&thing)?;
+            surreal_mode.name = new_name;
+            staged.save(SurrealRecord::Mode(surreal_mode));
+            Ok(())
+        }
+        DataChange::UpdateModeParent(mode, parent) => update_modes(
+            |modes| {
+                let mut ancestor = parent.as_ref();
+                while let Some(ancestor_id) = ancestor {
+                    if *ancestor_id == mode {
+                        return Err(DataLayerError::Invalid(
+                            "A mode cannot be under itself or one of its smaller modes".to_string(),
+                        ));
+                    }
+                    ancestor = modes
+                        .iter()
+                        .find(|x| x.id.as_ref() == Some(ancestor_id))
+                        .ok_or_else(|| DataLayerError::NotFound(ancestor_id.clone()))?
+                        .parent
+                        .as_ref();
+                }
+                let display_order = modes
                     .iter()
-                    .find(|x| x.summary == "Item that needs a parent")
-                    .unwrap()
-                    .id
-                    .as_ref()
-                    .unwrap()
-                    .clone()
+                    .filter(|x| x.parent == parent)
+                    .map(|x| x.display_order + 1)
+                    .max()
+                    .unwrap_or_default();
+                let moved = found(
+                    modes.iter_mut().find(|x| x.id.as_ref() == Some(&mode)),
+                    &mode,
+                )?;
+                moved.parent = parent.clone();
+                moved.display_order = display_order;
+                Ok(())
             },
-            surreal_tables
-                .surreal_items
-                .iter()
-                .find(|x| x.summary == "Parent Item")
-                .unwrap()
-                .smaller_items_in_priority_order
-                .first()
-                .unwrap()
-        );
+            staged,
+        ),
+        DataChange::UpdateModeOrder(in_order) => update_modes(
+            |modes| {
+                check_mode_order(&in_order, modes)?;
+                for (display_order, id) in in_order.iter().enumerate() {
+                    let mode = found(modes.iter_mut().find(|x| x.id.as_ref() == Some(id)), id)?;
+                    mode.display_order = display_order as u32;
+                }
+                Ok(())
+            },
+            staged,
+        ),
+        DataChange::DeleteMode {
+            mode,
+            delete_smaller_modes,
+        } => {
+            let mut deleted = vec![mode.clone()];
+            let mut parent = None;
+            update_modes(
+                |modes| {
+                    parent = found(modes.iter().find(|x| x.id.as_ref() == Some(&mode)), &mode)?
+                        .parent
+                        .clone();
+                    if delete_smaller_modes {
+                        //Each pass finds the next level down
+                        let mut deleted_count = 0;
+                        while deleted_count != deleted.len() {
+                            deleted_count = deleted.len();
+                            let smaller = modes
+                                .iter()
+                                .filter(|x| {
+                                    x.parent.as_ref().is_some_and(|x| deleted.contains(x))
+                                        && x.id.as_ref().is_some_and(|x| !deleted.contains(x))
+                                })
+                                .filter_map(|x| x.id.clone())
+                                .collect::<Vec<_>>();
+                            deleted.extend(smaller);
+                        }
+                    } else {
+                        for smaller in modes
+                            .iter_mut()
+                            .filter(|x| x.parent.as_ref() == Some(&mode))
+                        {
+                            smaller.parent = parent.clone();
+                        }
+                    }
+                    modes.retain(|x| x.id.as_ref().is_none_or(|x| !deleted.contains(x)));
+                    Ok(())
+                },
+                staged,
+            )?;
+            //Items in a mode whose smaller modes moved up go to its parent, like its smaller modes did
+            let replacement = if delete_smaller_modes { None } else { parent };
+            replace_references(&deleted, Reference::Mode, replacement.as_ref(), staged);
+            Ok(())
+        }
+        DataChange::UpdateItemModes(item, modes) => update_item(&item, |x| x.modes = modes, staged),
+        DataChange::UpdateItemNotes(item, notes) => update_item(&item, |x| x.notes = notes, staged),
+        DataChange::UpdateItemLinks(record_id, links) => update_item(
+            &record_id,
+            |x| {
+                x.links = links;
+                x.notes_location = NotesLocation::None;
+            },
+            staged,
+        ),
+        DataChange::AddItemTag(record_id, tag) => {
+            let tag = normalize_tag(&tag)?;
+            let mut item = staged.item(&record_id)?;
+            if item.tags.iter().any(|x| x == tag) {
+                return Ok(());
+            }
+            item.tags.push(tag.to_string());
+            staged.save(SurrealRecord::Item(item));
+            Ok(())
+        }
+        DataChange::UpdateItemRecurrence(item, recurrence) => {
+            update_item(&item, |x| x.recurrence = recurrence, staged)
+        }
+        DataChange::UpdateItemDeadline(item, deadline) => {
+            update_item(&item, |x| x.deadline = deadline, staged)
+        }
+        DataChange::UpdateItemEstimate(item, estimate) => {
+            update_item(&item, |x| x.estimate = estimate, staged)
+        }
+        DataChange::RemoveItemTag(record_id, tag) => {
+            let tag = normalize_tag(&tag)?;
+            update_item(&record_id, |x| x.tags.retain(|x| x != tag), staged)
+        }
+        DataChange::UpdateResponsibilityAndItemType(item, new_responsibility, new_item_type) => {
+            update_item(
+                &item,
+                |x| {
+                    x.responsibility = new_responsibility;
+                    x.item_type = new_item_type;
+                },
+                staged,
+            )
+        }
+        DataChange::UpdateUrgencyPlan(record_id, new_urgency_plan) => {
+            update_item(&record_id, |x| x.urgency_plan = new_urgency_plan, staged)
+        }
+        DataChange::DeclareInTheMomentPriority {
+            choice,
+            kind,
+            not_chosen,
+            in_effect_until,
+        } => {
+            let priority = SurrealInTheMomentPriority {
+                id: Some(new_record_id(SurrealInTheMomentPriority::TABLE_NAME)),
+                not_chosen,
+                in_effect_until,
+                created: Utc::now().into(),
+                choice,
+                kind,
+            };
+            staged.save(SurrealRecord::InTheMomentPriority(priority));
+            Ok(())
+        }
+        DataChange::ClearInTheMomentPriority(record_id) => staged.delete(&record_id),
+        DataChange::SetCurrentMode(new_current_mode) => {
+            let current_mode: SurrealCurrentMode = new_current_mode.into();
+            staged.save(SurrealRecord::CurrentMode(current_mode));
+            Ok(())
+        }
+        DataChange::TriggerEvent { event, when } => set_event_triggered(event, true, when, staged),
+        DataChange::UntriggerEvent { event, when } => {
+            set_event_triggered(event, false, when, staged)
+        }
+        DataChange::UpdateEventSummary(event, new_summary) => {
+            let mut surreal_event = staged.event(&event)?;
+            surreal_event.summary = new_summary;
+            staged.save(SurrealRecord::Event(surreal_event));
+            Ok(())
+        }
+        DataChange::UpdateEventUntriggerAfter(event, untrigger_after) => {
+            let mut surreal_event = staged.event(&event)?;
+            surreal_event.untrigger_after = untrigger_after;
+            staged.save(SurrealRecord::Event(surreal_event));
+            Ok(())
+        }
+        DataChange::DeleteItem(item) => delete_record(item, SurrealItem::TABLE_NAME, staged),
+        DataChange::DeleteEvent(event) => delete_record(event, SurrealEvent::TABLE_NAME, staged),
+        DataChange::MergeItems { keep, merge } => {
+            merge_records(keep, merge, SurrealItem::TABLE_NAME, staged)
+        }
+        DataChange::MergeEvents { keep, merge } => {
+            merge_records(keep, merge, SurrealEvent::TABLE_NAME, staged)
+        }
+        DataChange::RepairRecord(id, state) => {
+            if !CHECKED_TABLES.contains(&id.tb.as_str()) {
+                return Err(DataLayerError::Invalid(format!(
+                    "{} is not in a table the integrity check repairs",
+                    id
+                )));
+            }
+            if let Some(state) = &state
+                && state.id() != Some(&id)
+            {
+                return Err(DataLayerError::Invalid(format!(
+                    "the repaired record is not {}",
+                    id
+                )));
+            }
+            staged.restore(&id, state);
+            Ok(())
+        }
+        DataChange::ReopenItem(item) => reopen_item(&item, staged),
+        DataChange::UpdateTimeSpentTimes {
+            time_spent,
+            when_started,
+            when_stopped,
+        } => {
+            if when_started > when_stopped {
+                return Err(DataLayerError::Invalid(
+                    "the time started is after the time stopped".to_string(),
+                ));
+            }
+            update_time_spent(
+                &time_spent,
+                |x| {
+                    x.when_started = when_started;
+                    x.when_stopped = when_stopped;
+                },
+                staged,
+            )
+        }
+        DataChange::UpdateTimeSpentWorkingOn(time_spent, working_on) => {
+            update_time_spent(&time_spent, |x| x.working_on = working_on, staged)
+        }
+        DataChange::UpdateTimeSpentDedication(time_spent, dedication) => {
+            update_time_spent(&time_spent, |x| x.dedication = dedication, staged)
+        }
+        DataChange::SplitTimeSpent { time_spent, at } => split_time_spent(time_spent, at, staged),
+        DataChange::DeleteTimeSpent(time_spent) => {
+            staged.time_spent(&time_spent)?;
+            staged.delete(&time_spent)
+        }
+    }
+}
 
-        drop(sender);
-        data_storage_join_handle.await.unwrap();
+/// For changes to one item, `change` is given the item as it is after what has been staged so far
+fn update_item(
+    item: &RecordId,
+    change: impl FnOnce(&mut SurrealItem),
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let mut surreal_item = staged.item(item)?;
+    change(&mut surreal_item);
+    staged.save(SurrealRecord::Item(surreal_item));
+    Ok(())
+}
+
+/// Points every `reference` to one of `targets` at `replacement` instead, or removes it if `replacement` is None
+fn replace_references(
+    targets: &[RecordId],
+    reference: Reference,
+    replacement: Option<&RecordId>,
+    staged: &mut Staged<'_>,
+) {
+    for record in staged.tables().all_records() {
+        let refers_to_a_target = references(&record)
+            .iter()
+            .any(|(kind, id)| *kind == reference && targets.contains(id));
+        if !refers_to_a_target {
+            continue;
+        }
+        let state = targets
+            .iter()
+            .try_fold(record.clone(), |record, target| {
+                change_reference(&record, reference, target, replacement)
+            })
+            .map(without_duplicates);
+        staged.restore(record.id().expect("In DB"), state);
     }
+}
 
-    #[tokio::test]
-    async fn parent_item_with_an_existing_item_that_has_no_children() {
-        let (sender, receiver) = mpsc::channel(1);
-        let data_storage_join_handle =
-            tokio::spawn(async move { data_storage_start_and_run(receiver, "mem://").await });
+/// For changes that touch more than one mode. `change` is given every mode and each mode that it changes is saved and
+/// each mode that it removes is deleted.
+fn update_modes(
+    change: impl FnOnce(&mut Vec<SurrealMode>) -> Result<(), DataLayerError>,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let before = staged.tables().surreal_modes.clone();
+    let mut after = before.clone();
+    change(&mut after)?;
+    for mode in before.into_iter() {
+        let id = mode.id.clone().expect("In DB");
+        match after.iter().find(|x| x.id.as_ref() == Some(&id)) {
+            Some(changed) if *changed == mode => {}
+            Some(changed) => staged.save(SurrealRecord::Mode(changed.clone())),
+            None => staged.restore(&id, None),
+        }
+    }
+    Ok(())
+}
 
-        let item_that_needs_a_parent = NewItemBuilder::default()
-            .summary("Item that needs a parent")
-            .item_type(SurrealItemType::Action)
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(item_that_needs_a_parent))
-            .await
-            .unwrap();
+/// `in_order` must be every mode with the same parent, each once, so the new order cannot leave a mode out or move
+/// it to another parent
+fn check_mode_order(in_order: &[RecordId], modes: &[SurrealMode]) -> Result<(), DataLayerError> {
+    let find = |id: &RecordId| modes.iter().find(|x| x.id.as_ref() == Some(id));
+    let parent = match in_order.first().map(find) {
+        Some(Some(first)) => &first.parent,
+        _ => {
+            return Err(DataLayerError::Invalid(
+                "the new order must list modes that exist".to_string(),
+            ));
+        }
+    };
+    let siblings: HashSet<&RecordId> = modes
+        .iter()
+        .filter(|x| &x.parent == parent)
+        .map(|x| x.id.as_ref().expect("In DB"))
+        .collect();
+    let listed: HashSet<&RecordId> = in_order.iter().collect();
+    if listed.len() == in_order.len() && listed == siblings {
+        Ok(())
+    } else {
+        Err(DataLayerError::Invalid(
+            "the new order must list every mode with the same parent once and nothing else"
+                .to_string(),
+        ))
+    }
+}
 
-        let parent_item = NewItemBuilder::default()
-            .summary("Parent Item")
-            .item_type(SurrealItemType::Goal(SurrealHowMuchIsInMyControl::default()))
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(parent_item))
-            .await
-            .unwrap();
+fn update_time_spent(
+    time_spent: &RecordId,
+    change: impl FnOnce(&mut SurrealTimeSpent),
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let mut surreal_time_spent = staged.time_spent(time_spent)?;
+    change(&mut surreal_time_spent);
+    staged.save(SurrealRecord::TimeSpent(surreal_time_spent));
+    Ok(())
+}
 
-        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+fn split_time_spent(
+    time_spent: RecordId,
+    at: Datetime,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let existing = staged.time_spent(&time_spent)?;
+    if at <= existing.when_started || at >= existing.when_stopped {
+        return Err(DataLayerError::Invalid(
+            "the time to split at must be after it started and before it stopped".to_string(),
+        ));
+    }
+    let second = SurrealTimeSpent {
+        id: Some(new_record_id(SurrealTimeSpent::TABLE_NAME)),
+        when_started: at.clone(),
+        ..existing
+    };
+    update_time_spent(&time_spent, |x| x.when_stopped = at, staged)?;
+    staged.save(SurrealRecord::TimeSpent(second));
+    Ok(())
+}
 
-        assert_eq!(2, surreal_tables.surreal_items.len());
+/// Tags are saved trimmed so a tag is added and removed the same way whatever whitespace is typed around it
+fn normalize_tag(tag: &str) -> Result<&str, DataLayerError> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        Err(DataLayerError::Invalid("A tag cannot be empty".to_string()))
+    } else {
+        Ok(tag)
+    }
+}
 
-        sender
-            .send(DataLayerCommands::ParentItemWithExistingItem {
-                child: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Item that needs a parent")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                parent: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Parent Item")
+fn check_table(id: &RecordId, table: &str) -> Result<(), DataLayerError> {
+    if id.tb == table {
+        Ok(())
+    } else {
+        Err(DataLayerError::Invalid(format!(
+            "{} is not in the {} table",
+            id, table
+        )))
+    }
+}
+
+fn delete_record(id: RecordId, table: &str, staged: &mut Staged<'_>) -> Result<(), DataLayerError> {
+    check_table(&id, table)?;
+    let planned = plan_delete(&staged.tables(), &id).ok_or(DataLayerError::NotFound(id))?;
+    apply_planned_changes(planned, staged);
+    Ok(())
+}
+
+fn merge_records(
+    keep: RecordId,
+    merge: RecordId,
+    table: &str,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    check_table(&keep, table)?;
+    check_table(&merge, table)?;
+    if keep == merge {
+        return Err(DataLayerError::Invalid(
+            "a record cannot be merged with itself".to_string(),
+        ));
+    }
+    //Both are the same kind of record and not the same one so plan_merge only gives back None for a missing record
+    let missing = if staged.get(&keep).is_some() {
+        merge.clone()
+    } else {
+        keep.clone()
+    };
+    let planned =
+        plan_merge(&staged.tables(), &keep, &merge).ok_or(DataLayerError::NotFound(missing))?;
+    apply_planned_changes(planned, staged);
+    Ok(())
+}
+
+fn apply_planned_changes(planned: Vec<PlannedChange>, staged: &mut Staged<'_>) {
+    for change in planned {
+        staged.restore(&change.id, change.state);
+    }
+}
+
+fn set_event_triggered(
+    event: RecordId,
+    triggered: bool,
+    when: Datetime,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let mut surreal_event = staged.event(&event)?;
+    surreal_event.triggered = triggered;
+    surreal_event.last_updated = when;
+    staged.save(SurrealRecord::Event(surreal_event));
+    Ok(())
+}
+
+async fn load_undo_journal(db: &Surreal<Any>) -> Result<Vec<SurrealUndoEntry>, DataLayerError> {
+    let mut undo_journal: Vec<SurrealUndoEntry> = db.select(SurrealUndoEntry::TABLE_NAME).await?;
+    undo_journal.sort_by_key(|x| x.sequence);
+    Ok(undo_journal)
+}
+
+async fn load_history(
+    records: Vec<RecordId>,
+    db: &Surreal<Any>,
+) -> Result<Vec<SurrealHistoryEntry>, DataLayerError> {
+    let mut history: Vec<SurrealHistoryEntry> = db
+        .query("SELECT * FROM type::table($table) WHERE record IN $records")
+        .bind(("table", SurrealHistoryEntry::TABLE_NAME))
+        .bind(("records", records))
+        .await?
+        .take(0)?;
+    history.sort_by(|a, b| a.when.cmp(&b.when));
+    Ok(history)
+}
+
+async fn undo(cache: &mut Cache, db: &Surreal<Any>) -> Result<Option<String>, DataLayerError> {
+    let undo_journal = load_undo_journal(db).await?;
+    let Some(mut undo_entry) = undo_journal.into_iter().rev().find(|x| !x.undone) else {
+        return Ok(None);
+    };
+    let mut written = Written::default();
+    for change in undo_entry.changes.iter().rev() {
+        written.0.push((change.id.clone(), change.before.clone()));
+    }
+    undo_entry.undone = true;
+    restore_records(written, &undo_entry, cache, db).await?;
+    Ok(Some(undo_entry.description))
+}
+
+async fn redo(cache: &mut Cache, db: &Surreal<Any>) -> Result<Option<String>, DataLayerError> {
+    let undo_journal = load_undo_journal(db).await?;
+    let Some(mut redo_entry) = undo_journal.into_iter().find(|x| x.undone) else {
+        return Ok(None);
+    };
+    let mut written = Written::default();
+    for change in redo_entry.changes.iter() {
+        written.0.push((change.id.clone(), change.after.clone()));
+    }
+    redo_entry.undone = false;
+    restore_records(written, &redo_entry, cache, db).await?;
+    Ok(Some(redo_entry.description))
+}
+
+/// The records are put back in the same transaction that marks the undo entry as undone or redone, so a change is never
+/// put back without the entry saying so
+async fn restore_records(
+    written: Written,
+    undo_entry: &SurrealUndoEntry,
+    cache: &mut Cache,
+    db: &Surreal<Any>,
+) -> Result<(), DataLayerError> {
+    cache.get(db).await?;
+    let changes = cache.update_tables(written);
+    let mut journal = Journal::new(&changes, &cache.get(db).await?);
+    journal.undo_entry = Some(undo_entry.clone());
+    save_or_reload(&changes, &journal, cache, db).await?;
+    cache.log_for_sync(&changes);
+    Ok(())
+}
+
+pub(crate) async fn load_from_surrealdb(
+    db: &Surreal<Any>,
+) -> Result<SurrealTables, surrealdb::Error> {
+    //TODO: I should do some timings to see if starting all of these get_all requests and then doing awaits on them later really is faster in Rust. Or if they just for sure don't start until the await. For example I could call this function as many times as possible in 10 sec and time that and then see how many times I can call that function written like this and then again with the get_all being right with the await to make sure that code like this is worth it perf wise.
+    let all_items = db.select(SurrealItem::TABLE_NAME);
+    let time_spent_log = db.select(SurrealTimeSpent::TABLE_NAME);
+    let surreal_in_the_moment_priorities = db.select(SurrealInTheMomentPriority::TABLE_NAME);
+    let surreal_current_modes = db.select(SurrealCurrentMode::TABLE_NAME);
+    let surreal_modes = db.select(surreal_mode::SurrealMode::TABLE_NAME);
+    let surreal_events = db.select(SurrealEvent::TABLE_NAME);
+
+    Ok(SurrealTables {
+        surreal_items: all_items.await?,
+        surreal_time_spent_log: time_spent_log.await?,
+        surreal_in_the_moment_priorities: surreal_in_the_moment_priorities.await?,
+        surreal_current_modes: surreal_current_modes.await?,
+        surreal_modes: surreal_modes.await?,
+        surreal_events: surreal_events.await?,
+    })
+}
+
+pub(crate) async fn load_archive(db: &Surreal<Any>) -> Result<SurrealArchive, surrealdb::Error> {
+    let items = db.select(SurrealArchivedItem::TABLE_NAME);
+    let time_spent_log = db.select(SurrealArchivedTimeSpent::TABLE_NAME);
+
+    Ok(SurrealArchive {
+        items: items.await?,
+        time_spent_log: time_spent_log.await?,
+    })
+}
+
+/// Record ids are kept as is so references between records (dependencies, smaller items, events, and so on) still line up after the import
+///
+/// Removing what is there and inserting the imported records is one transaction so a failed import leaves the database
+/// as it was
+async fn import_raw_data(
+    surreal_tables: SurrealTables,
+    surreal_archive: SurrealArchive,
+    replace_existing: bool,
+    db: &Surreal<Any>,
+) -> Result<(), ImportError> {
+    let existing = load_from_surrealdb(db).await?;
+    let existing_archive = load_archive(db).await?;
+    if (!existing.is_empty() || !existing_archive.is_empty()) && !replace_existing {
+        return Err(ImportError::DatabaseNotEmpty);
+    }
+    //History is kept for the records that are imported with the same id, for example when restoring a backup, but the
+    //history of a record that is no longer there would only be found if a record with the same id came back
+    let imported_ids = surreal_tables
+        .all_records()
+        .map(|x| x.id().expect("In DB").clone())
+        .chain(
+            surreal_archive
+                .items
+                .iter()
+                .map(|x| x.item.id.clone().expect("In DB")),
+        )
+        .collect::<Vec<_>>();
+
+    let mut statements = vec!["BEGIN TRANSACTION;".to_string()];
+    for table_name in [
+        SurrealArchivedItem::TABLE_NAME,
+        SurrealArchivedTimeSpent::TABLE_NAME,
+        SurrealItem::TABLE_NAME,
+        SurrealTimeSpent::TABLE_NAME,
+        SurrealInTheMomentPriority::TABLE_NAME,
+        SurrealCurrentMode::TABLE_NAME,
+        SurrealMode::TABLE_NAME,
+        SurrealEvent::TABLE_NAME,
+        //The undo journal refers to records that are being replaced so it no longer makes sense
+        SurrealUndoEntry::TABLE_NAME,
+    ] {
+        statements.push(format!("DELETE {};", table_name));
+    }
+    statements.push(format!(
+        "DELETE {} WHERE record NOT IN $imported_ids;",
+        SurrealHistoryEntry::TABLE_NAME
+    ));
+    //Each table is bound under its own name
+    for (table_name, is_empty) in [
+        (
+            SurrealItem::TABLE_NAME,
+            surreal_tables.surreal_items.is_empty(),
+        ),
+        (
+            SurrealTimeSpent::TABLE_NAME,
+            surreal_tables.surreal_time_spent_log.is_empty(),
+        ),
+        (
+            SurrealInTheMomentPriority::TABLE_NAME,
+            surreal_tables.surreal_in_the_moment_priorities.is_empty(),
+        ),
+        (
+            SurrealCurrentMode::TABLE_NAME,
+            surreal_tables.surreal_current_modes.is_empty(),
+        ),
+        (
+            SurrealMode::TABLE_NAME,
+            surreal_tables.surreal_modes.is_empty(),
+        ),
+        (
+            SurrealEvent::TABLE_NAME,
+            surreal_tables.surreal_events.is_empty(),
+        ),
+        (
+            SurrealArchivedItem::TABLE_NAME,
+            surreal_archive.items.is_empty(),
+        ),
+        (
+            SurrealArchivedTimeSpent::TABLE_NAME,
+            surreal_archive.time_spent_log.is_empty(),
+        ),
+    ] {
+        //SurrealDB gives an error when inserting an empty list
+        if !is_empty {
+            statements.push(format!("INSERT INTO {0} ${0};", table_name));
+        }
+    }
+    statements.push("COMMIT TRANSACTION;".to_string());
+
+    let query = db
+        .query(statements.join("\n"))
+        .bind(("imported_ids", imported_ids))
+        .bind((
+            SurrealItem::TABLE_NAME,
+            surreal_tables.surreal_items.clone(),
+        ))
+        .bind((
+            SurrealTimeSpent::TABLE_NAME,
+            surreal_tables.surreal_time_spent_log.clone(),
+        ))
+        .bind((
+            SurrealInTheMomentPriority::TABLE_NAME,
+            surreal_tables.surreal_in_the_moment_priorities.clone(),
+        ))
+        .bind((
+            SurrealCurrentMode::TABLE_NAME,
+            surreal_tables.surreal_current_modes.clone(),
+        ))
+        .bind((
+            SurrealMode::TABLE_NAME,
+            surreal_tables.surreal_modes.clone(),
+        ))
+        .bind((
+            SurrealEvent::TABLE_NAME,
+            surreal_tables.surreal_events.clone(),
+        ))
+        .bind((
+            SurrealArchivedItem::TABLE_NAME,
+            surreal_archive.items.clone(),
+        ))
+        .bind((
+            SurrealArchivedTimeSpent::TABLE_NAME,
+            surreal_archive.time_spent_log.clone(),
+        ));
+    //check() turns an error from any of the statements into an error, otherwise they are only in the response
+    query.await?.check()?;
+
+    check_round_trip(&surreal_tables, &surreal_archive, db).await
+}
+
+/// Reads back what was imported, both the tables and the archive, to make sure it is exactly what was given
+async fn check_round_trip(
+    surreal_tables: &SurrealTables,
+    surreal_archive: &SurrealArchive,
+    db: &Surreal<Any>,
+) -> Result<(), ImportError> {
+    let imported = load_from_surrealdb(db).await?;
+    let imported_archive = load_archive(db).await?;
+    if imported.is_same_data(surreal_tables) && imported_archive.is_same_data(surreal_archive) {
+        Ok(())
+    } else {
+        Err(ImportError::RoundTripMismatch)
+    }
+}
+
+async fn send_time_spent(sender: oneshot::Sender<Vec<SurrealTimeSpent>>, db: &Surreal<Any>) {
+    match db.select(SurrealTimeSpent::TABLE_NAME).await {
+        Ok(time_spent) => {
+            let _ = sender.send(time_spent);
+        }
+        //Dropping the oneshot without sending lets the caller know that the data could not be read
+        Err(err) => println!("Unable to read the time spent log: {}", err),
+    }
+}
+
+fn record_time_spent(new_time_spent: NewTimeSpent, staged: &mut Staged<'_>) {
+    let mut new_time_spent: SurrealTimeSpent = new_time_spent.into();
+    new_time_spent.id = Some(new_record_id(SurrealTimeSpent::TABLE_NAME));
+    staged.save(SurrealRecord::TimeSpent(new_time_spent));
+}
+
+fn finish_item(
+    finish_this: RecordId,
+    when_finished: Datetime,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let item = staged.item(&finish_this)?;
+    if let Some(recurrence) = &item.recurrence {
+        return finish_recurring_item(item.clone(), recurrence, when_finished, staged);
+    }
+    update_item(&finish_this, |x| x.finished = Some(when_finished), staged)
+}
+
+/// Rather than being finished the item waits until it is due again, its finished smaller items are a checklist that is
+/// re-opened to be done again. Parents, urgency plan, review settings and the dependencies the user added stay as they
+/// are.
+fn finish_recurring_item(
+    mut item: SurrealItem,
+    recurrence: &SurrealRecurrence,
+    when_finished: Datetime,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let finished: DateTime<Utc> = when_finished.clone().into();
+    let last_due = item.completions.last().and_then(|x| x.due_again.clone());
+    if let Some(last_due) = &last_due {
+        let added = SurrealDependency::AfterDateTime(last_due.clone());
+        if let Some(position) = item.dependencies.iter().position(|x| *x == added) {
+            item.dependencies.remove(position);
+        }
+    }
+    let last_due: Option<DateTime<Utc>> = last_due.map(Into::into);
+    let on_time = last_due
+        .as_ref()
+        .is_none_or(|due| maintenance::is_on_time(recurrence, due, &finished, &Local));
+    let next_due = maintenance::next_due(recurrence, last_due.as_ref(), &finished, &Local);
+    item.completions.push(SurrealCompletion {
+        finished: when_finished,
+        on_time,
+        due_again: Some(next_due.into()),
+    });
+    item.dependencies
+        .push(SurrealDependency::AfterDateTime(next_due.into()));
+    staged.save(SurrealRecord::Item(item.clone()));
+
+    for smaller in item.smaller_items_in_priority_order.iter() {
+        let SurrealOrderedSubItem::SubItem { surreal_item_id } = smaller;
+        //A smaller item that was archived is not brought back
+        let is_finished = staged
+            .item(surreal_item_id)
+            .is_ok_and(|x| x.finished.is_some());
+        if is_finished {
+            reopen_item(surreal_item_id, staged)?;
+        }
+    }
+    Ok(())
+}
+
+fn reopen_item(reopen_this: &RecordId, staged: &mut Staged<'_>) -> Result<(), DataLayerError> {
+    update_item(reopen_this, |x| x.finished = None, staged)
+}
+
+/// The events that the new item waits on are created first so it can refer to them
+fn create_new_events(new_item: &mut NewItem, staged: &mut Staged<'_>) {
+    for dependency in new_item.dependencies.iter_mut() {
+        match dependency {
+            NewDependency::NewEvent(new_event) => {
+                let created = create_new_event(new_event.clone(), staged);
+                *dependency = NewDependency::Existing(SurrealDependency::AfterEvent(
+                    created.id.expect("Given an id when it is created"),
+                ));
+            }
+            NewDependency::Existing(_) => {}
+        }
+    }
+}
+
+fn create_new_item(mut new_item: NewItem, staged: &mut Staged<'_>) -> SurrealItem {
+    create_new_events(&mut new_item, staged);
+    let mut surreal_item: SurrealItem = SurrealItem::new(new_item, vec![])
+        .expect("We fix up NewDependency::NewEvent above so it will never happen here");
+    surreal_item.id = Some(new_record_id(SurrealItem::TABLE_NAME));
+    staged.save(SurrealRecord::Item(surreal_item.clone()));
+    surreal_item
+}
+
+fn cover_with_a_new_item(
+    cover_this: RecordId,
+    cover_with: NewItem,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let cover_with = create_new_item(cover_with, staged);
+
+    let cover_with: Option<Thing> = cover_with.into();
+    let cover_with = cover_with.expect("always exists the .into() wraps it in an option");
+    let new_dependency = SurrealDependency::AfterItem(cover_with);
+    add_dependency(cover_this, new_dependency, staged)
+}
+
+fn cover_item_with_an_existing_item(
+    existing_item_to_be_covered: RecordId,
+    existing_item_that_is_doing_the_covering: RecordId,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let new_dependency = SurrealDependency::AfterItem(existing_item_that_is_doing_the_covering);
+    add_dependency(existing_item_to_be_covered, new_dependency, staged)
+}
+
+fn parent_item_with_existing_item(
+    child_record_id: RecordId,
+    parent_record_id: RecordId,
+    higher_importance_than_this: Option<RecordId>,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let mut parent = staged.item(&parent_record_id)?;
+    parent.smaller_items_in_priority_order = parent
+        .smaller_items_in_priority_order
+        .into_iter()
+        .filter(|x| match x {
+            SurrealOrderedSubItem::SubItem { surreal_item_id } => {
+                surreal_item_id != &child_record_id
+            }
+        })
+        .collect::<Vec<_>>();
+    if let Some(higher_priority_than_this) = higher_importance_than_this {
+        let index_of_higher_priority = parent
+            .smaller_items_in_priority_order
+            .iter()
+            .position(|x| match x {
+                //Note that position() is short-circuiting. If there are multiple matches it could be argued that I should panic or assert but
+                //I am just matching the first one and then I just keep going. Because I am still figuring out the design and this is
+                //more in the vein of hardening work I think this is fine but feel free to revisit this.
+                SurrealOrderedSubItem::SubItem { surreal_item_id } => {
+                    surreal_item_id == &higher_priority_than_this
+                }
+            })
+            //The list was read in above so if it is not there then it was removed after the menu was shown
+            .ok_or_else(|| DataLayerError::NotFound(higher_priority_than_this.clone()))?;
+        parent.smaller_items_in_priority_order.insert(
+            index_of_higher_priority,
+            SurrealOrderedSubItem::SubItem {
+                surreal_item_id: child_record_id,
+            },
+        );
+    } else {
+        parent
+            .smaller_items_in_priority_order
+            .push(SurrealOrderedSubItem::SubItem {
+                surreal_item_id: child_record_id,
+            });
+    }
+    staged.save(SurrealRecord::Item(parent));
+    Ok(())
+}
+
+fn parent_item_with_a_new_child(
+    child: NewItem,
+    parent: RecordId,
+    higher_importance_than_this: Option<RecordId>,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let child = create_new_item(child, staged);
+    parent_item_with_existing_item(
+        child.id.expect("Given an id when it is created"),
+        parent,
+        higher_importance_than_this,
+        staged,
+    )
+}
+
+fn parent_new_item_with_an_existing_child_item(
+    child: RecordId,
+    mut parent_new_item: NewItem,
+    staged: &mut Staged<'_>,
+) {
+    create_new_events(&mut parent_new_item, staged);
+
+    //TODO: Write a Unit Test for this
+    let smaller_items_in_priority_order = vec![SurrealOrderedSubItem::SubItem {
+        surreal_item_id: child,
+    }];
+
+    let mut parent_surreal_item =
+        SurrealItem::new(parent_new_item, smaller_items_in_priority_order)
+            .expect("We deal with new events above so it will never happen here");
+    parent_surreal_item.id = Some(new_record_id(SurrealItem::TABLE_NAME));
+    staged.save(SurrealRecord::Item(parent_surreal_item));
+}
+
+fn add_dependency(
+    record_id: RecordId,
+    new_dependency: SurrealDependency,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let mut surreal_item = staged.item(&record_id)?;
+    if surreal_item.dependencies.contains(&new_dependency) {
+        //Is already there, nothing to do
+        Ok(())
+    } else {
+        surreal_item.dependencies.push(new_dependency);
+        staged.save(SurrealRecord::Item(surreal_item));
+        Ok(())
+    }
+}
+
+fn remove_dependency(
+    record_id: RecordId,
+    to_remove: SurrealDependency,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    update_item(
+        &record_id,
+        |x| x.dependencies.retain(|x| x != &to_remove),
+        staged,
+    )
+}
+
+fn add_dependency_new_event(
+    record_id: RecordId,
+    new_event: NewEvent,
+    staged: &mut Staged<'_>,
+) -> Result<(), DataLayerError> {
+    let created = create_new_event(new_event, staged);
+    let new_dependency =
+        SurrealDependency::AfterEvent(created.id.expect("Given an id when it is created"));
+
+    add_dependency(record_id, new_dependency, staged)
+}
+
+fn create_new_event(new_event: NewEvent, staged: &mut Staged<'_>) -> SurrealEvent {
+    let mut event: SurrealEvent = new_event.into();
+    event.id = Some(new_record_id(SurrealEvent::TABLE_NAME));
+    staged.save(SurrealRecord::Event(event.clone()));
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    use crate::{
+        data_storage::surrealdb_layer::{
+            surreal_item::{SurrealHowMuchIsInMyControl, SurrealItemBuilder, SurrealLinkKind},
+            surreal_tables::LatestSurrealTables,
+        },
+        new_item::NewItemBuilder,
+        new_mode::NewModeBuilder,
+    };
+
+    #[tokio::test]
+    async fn data_starts_empty() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+
+        assert!(surreal_tables.surreal_items.is_empty());
+        assert!(surreal_tables.surreal_time_spent_log.is_empty());
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn add_new_item() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        let new_item = NewItem::new("New item".into(), Utc::now());
+        DataLayerCommands::change(&sender, DataChange::NewItem(new_item))
+            .await
+            .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+
+        assert_eq!(surreal_tables.surreal_items.len(), 1);
+        assert_eq!(
+            SurrealItemType::Undeclared,
+            surreal_tables.surreal_items.first().unwrap().item_type
+        );
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn finish_item() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        let new_next_step = NewItemBuilder::default()
+            .summary("New next step")
+            .item_type(SurrealItemType::Action)
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(new_next_step))
+            .await
+            .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let now = Utc::now();
+        let items = surreal_tables.make_items(&now);
+
+        assert_eq!(items.len(), 1);
+        let next_step_item = items.iter().next().map(|(_, v)| v).unwrap();
+        assert_eq!(next_step_item.is_finished(), false);
+
+        let when_finished = Utc::now();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::FinishItem {
+                item: next_step_item.get_surreal_record_id().clone().into(),
+                when_finished: when_finished.into(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let now = Utc::now();
+        let items = surreal_tables.make_items(&now);
+
+        assert_eq!(items.len(), 1);
+        let next_step_item = items.iter().next().map(|(_, v)| v).unwrap();
+        assert_eq!(next_step_item.is_finished(), true);
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn archive_then_unarchive_and_reopen() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Finished long ago".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let item = surreal_tables.surreal_items[0].id.clone().unwrap();
+        let long_ago = Utc::now() - chrono::Duration::days(365);
+        DataLayerCommands::transaction(
+            &sender,
+            vec![
+                DataChange::RecordTimeSpent(NewTimeSpent {
+                    working_on: vec![SurrealAction::MakeProgress(item.clone())],
+                    urgency: None,
+                    why_in_scope: vec![],
+                    when_started: long_ago,
+                    when_stopped: long_ago,
+                    dedication: None,
+                }),
+                DataChange::FinishItem {
+                    item: item.clone(),
+                    when_finished: long_ago.into(),
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+        let archived = DataLayerCommands::archive(&sender, Utc::now().into())
+            .await
+            .unwrap();
+
+        assert_eq!(archived, 1);
+        assert!(SurrealTables::new(&sender).await.unwrap().is_empty());
+        let surreal_archive = SurrealArchive::new(&sender).await.unwrap();
+        assert_eq!(surreal_archive.items.len(), 1);
+        assert_eq!(surreal_archive.time_spent_log.len(), 1);
+        //The finish was undoable but undoing it now would bring back a copy of the archived item
+        assert_eq!(DataLayerCommands::undo(&sender).await.unwrap(), None);
+
+        DataLayerCommands::unarchive(&sender, item.clone(), true)
+            .await
+            .unwrap();
+
+        assert!(SurrealArchive::new(&sender).await.unwrap().is_empty());
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items.len(), 1);
+        assert_eq!(surreal_tables.surreal_items[0].finished, None);
+        assert_eq!(surreal_tables.surreal_time_spent_log.len(), 1);
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn the_import_is_only_accepted_if_the_archive_reads_back_the_same() {
+        let db = connect("mem://").await.unwrap();
+        db.use_ns(NAMESPACE).use_db(DATABASE).await.unwrap();
+        let item = SurrealItemBuilder::default()
+            .id(Some((SurrealItem::TABLE_NAME, "archived").into()))
+            .summary("Archived")
+            .build()
+            .unwrap();
+        let surreal_archive = SurrealArchive {
+            items: vec![SurrealArchivedItem::new(item, Utc::now().into())],
+            time_spent_log: vec![],
+        };
+
+        import_raw_data(
+            SurrealTables::default(),
+            surreal_archive.clone(),
+            false,
+            &db,
+        )
+        .await
+        .unwrap();
+
+        let mut different = surreal_archive.clone();
+        different.items[0].item.summary = "Not what was imported".into();
+        assert!(matches!(
+            check_round_trip(&SurrealTables::default(), &different, &db).await,
+            Err(ImportError::RoundTripMismatch)
+        ));
+        assert!(matches!(
+            check_round_trip(&SurrealTables::default(), &SurrealArchive::default(), &db).await,
+            Err(ImportError::RoundTripMismatch)
+        ));
+    }
+
+    #[tokio::test]
+    async fn every_change_to_an_item_is_in_its_history_including_undo() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("First".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let item = surreal_tables.surreal_items[0].id.clone().unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateSummary(item.clone(), "Second".into()),
+        )
+        .await
+        .unwrap();
+        DataLayerCommands::undo(&sender).await.unwrap();
+
+        let history = DataLayerCommands::get_history(&sender, vec![item.clone()])
+            .await
+            .unwrap();
+
+        let described = history
+            .iter()
+            .map(|x| {
+                (
+                    x.field.as_str(),
+                    x.old_value.as_deref(),
+                    x.new_value.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            described,
+            vec![
+                (SurrealHistoryEntry::EXISTS, None, Some("First")),
+                ("summary", Some("First"), Some("Second")),
+                ("summary", Some("Second"), Some("First")),
+            ]
+        );
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn delete_item_removes_it_from_its_parent_and_undo_brings_it_back() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Parent".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let parent = surreal_tables.surreal_items[0].id.clone().unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithANewChildItem {
+                child: NewItem::new("Child".into(), Utc::now()),
+                parent: parent.clone(),
+                higher_importance_than_this: None,
+            },
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let child = surreal_tables
+            .surreal_items
+            .iter()
+            .find_map(|x| x.id.clone().filter(|x| *x != parent))
+            .unwrap();
+
+        DataLayerCommands::change(&sender, DataChange::DeleteItem(child.clone()))
+            .await
+            .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items.len(), 1);
+        assert!(
+            surreal_tables.surreal_items[0]
+                .smaller_items_in_priority_order
+                .is_empty()
+        );
+
+        assert_eq!(
+            DataLayerCommands::undo(&sender).await.unwrap(),
+            Some("Delete item".to_string())
+        );
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items.len(), 2);
+        assert!(surreal_tables.surreal_items.iter().any(|x| {
+            x.smaller_items_in_priority_order
+                == vec![SurrealOrderedSubItem::SubItem {
+                    surreal_item_id: child.clone(),
+                }]
+        }));
+
+        let missing: RecordId = (SurrealItem::TABLE_NAME, "missing").into();
+        let result = DataLayerCommands::change(&sender, DataChange::DeleteItem(missing)).await;
+        assert!(matches!(result, Err(DataLayerError::NotFound(_))));
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn delete_and_merge_refuse_records_from_the_wrong_table() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Item".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let item = SurrealTables::new(&sender).await.unwrap().surreal_items[0]
+            .id
+            .clone()
+            .unwrap();
+        let new_event = NewEvent {
+            summary: "Event".into(),
+            triggered: false,
+            last_updated: Utc::now(),
+        };
+        DataLayerCommands::change(
+            &sender,
+            DataChange::AddItemDependencyNewEvent(item.clone(), new_event),
+        )
+        .await
+        .unwrap();
+        let before = SurrealTables::new(&sender).await.unwrap();
+        let event = before.surreal_events[0].id.clone().unwrap();
+
+        for change in [
+            DataChange::DeleteItem(event.clone()),
+            DataChange::DeleteEvent(item.clone()),
+            DataChange::MergeItems {
+                keep: item.clone(),
+                merge: event.clone(),
+            },
+            DataChange::MergeEvents {
+                keep: event.clone(),
+                merge: item.clone(),
+            },
+        ] {
+            let result = DataLayerCommands::change(&sender, change).await;
+            assert!(matches!(result, Err(DataLayerError::Invalid(..))));
+        }
+        assert_eq!(SurrealTables::new(&sender).await.unwrap(), before);
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_repair_must_be_of_the_record_given_in_a_table_that_is_checked() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        for summary in ["First", "Second"] {
+            DataLayerCommands::change(
+                &sender,
+                DataChange::NewItem(NewItem::new(summary.into(), Utc::now())),
+            )
+            .await
+            .unwrap();
+        }
+        let before = SurrealTables::new(&sender).await.unwrap();
+        let first = before.surreal_items[0].clone();
+        let second = before.surreal_items[1].id.clone().unwrap();
+        let repaired = SurrealItem {
+            summary: "Repaired".into(),
+            ..first.clone()
+        };
+
+        let result = DataLayerCommands::change(
+            &sender,
+            DataChange::RepairRecord(second, Some(SurrealRecord::Item(repaired.clone()))),
+        )
+        .await;
+        assert!(matches!(result, Err(DataLayerError::Invalid(..))));
+        let result = DataLayerCommands::change(
+            &sender,
+            DataChange::RepairRecord((SurrealUndoEntry::TABLE_NAME, "entry").into(), None),
+        )
+        .await;
+        assert!(matches!(result, Err(DataLayerError::Invalid(..))));
+        assert!(
+            SurrealTables::new(&sender)
+                .await
+                .unwrap()
+                .is_same_data(&before)
+        );
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::RepairRecord(
+                first.id.clone().unwrap(),
+                Some(SurrealRecord::Item(repaired)),
+            ),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert!(
+            surreal_tables
+                .surreal_items
+                .iter()
+                .any(|x| x.summary == "Repaired")
+        );
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_record_cannot_be_merged_with_itself() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Item".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let item = SurrealTables::new(&sender).await.unwrap().surreal_items[0]
+            .id
+            .clone()
+            .unwrap();
+        let new_event = NewEvent {
+            summary: "Event".into(),
+            triggered: false,
+            last_updated: Utc::now(),
+        };
+        DataLayerCommands::change(
+            &sender,
+            DataChange::AddItemDependencyNewEvent(item.clone(), new_event),
+        )
+        .await
+        .unwrap();
+        let before = SurrealTables::new(&sender).await.unwrap();
+        let event = before.surreal_events[0].id.clone().unwrap();
+
+        let result = DataLayerCommands::change(
+            &sender,
+            DataChange::MergeItems {
+                keep: item.clone(),
+                merge: item,
+            },
+        )
+        .await;
+        assert!(matches!(result, Err(DataLayerError::Invalid(..))));
+        let result = DataLayerCommands::change(
+            &sender,
+            DataChange::MergeEvents {
+                keep: event.clone(),
+                merge: event,
+            },
+        )
+        .await;
+        assert!(matches!(result, Err(DataLayerError::Invalid(..))));
+        assert_eq!(SurrealTables::new(&sender).await.unwrap(), before);
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn delete_and_merge_items_in_a_transaction_see_the_changes_before_them() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        for summary in ["Parent", "Delete me", "Keep", "Merge me"] {
+            DataLayerCommands::change(
+                &sender,
+                DataChange::NewItem(NewItem::new(summary.into(), Utc::now())),
+            )
+            .await
+            .unwrap();
+        }
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let find_item = |summary: &str| {
+            surreal_tables
+                .surreal_items
+                .iter()
+                .find(|x| x.summary == summary)
+                .and_then(|x| x.id.clone())
+                .unwrap()
+        };
+        let parent = find_item("Parent");
+        let delete_me = find_item("Delete me");
+        let keep = find_item("Keep");
+        let merge_me = find_item("Merge me");
+
+        //The references are only made earlier in the same transaction so they are not in the database yet when the
+        //delete and merge work out what refers to the item
+        DataLayerCommands::transaction(
+            &sender,
+            vec![
+                DataChange::ParentItemWithExistingItem {
+                    child: delete_me.clone(),
+                    parent: parent.clone(),
+                    higher_importance_than_this: None,
+                },
+                DataChange::AddItemDependency(
+                    parent.clone(),
+                    SurrealDependency::AfterItem(merge_me.clone()),
+                ),
+                DataChange::DeleteItem(delete_me.clone()),
+                DataChange::MergeItems {
+                    keep: keep.clone(),
+                    merge: merge_me.clone(),
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items.len(), 2);
+        let parent = surreal_tables
+            .surreal_items
+            .iter()
+            .find(|x| x.id.as_ref() == Some(&parent))
+            .unwrap();
+        assert!(parent.smaller_items_in_priority_order.is_empty());
+        assert_eq!(
+            parent.dependencies,
+            vec![SurrealDependency::AfterItem(keep)]
+        );
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn finishing_an_item_that_does_not_exist_is_an_error_and_the_data_layer_keeps_running() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        let missing: RecordId = (SurrealItem::TABLE_NAME, "missing").into();
+        let result = DataLayerCommands::change(
+            &sender,
+            DataChange::FinishItem {
+                item: missing.clone(),
+                when_finished: Utc::now().into(),
+            },
+        )
+        .await;
+        assert!(matches!(result, Err(DataLayerError::NotFound(id)) if id == missing));
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("After the error".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items.len(), 1);
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn transaction_saves_every_change_and_undoes_them_together() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Parent".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let parent = surreal_tables.surreal_items[0].id.clone().unwrap();
+
+        DataLayerCommands::transaction(
+            &sender,
+            vec![
+                DataChange::FinishItem {
+                    item: parent.clone(),
+                    when_finished: Utc::now().into(),
+                },
+                DataChange::ParentItemWithANewChildItem {
+                    child: NewItem::new("Child".into(), Utc::now()),
+                    parent: parent.clone(),
+                    higher_importance_than_this: None,
+                },
+            ],
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items.len(), 2);
+        let saved_parent = surreal_tables
+            .surreal_items
+            .iter()
+            .find(|x| x.id.as_ref() == Some(&parent))
+            .unwrap();
+        assert!(saved_parent.finished.is_some());
+        assert_eq!(saved_parent.smaller_items_in_priority_order.len(), 1);
+
+        assert_eq!(
+            DataLayerCommands::undo(&sender).await.unwrap(),
+            Some("Finish item, then Parent item with a new child item".to_string())
+        );
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items.len(), 1);
+        assert!(surreal_tables.surreal_items[0].finished.is_none());
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn transaction_saves_nothing_if_any_change_fails() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        let missing: RecordId = (SurrealItem::TABLE_NAME, "missing").into();
+        let result = DataLayerCommands::transaction(
+            &sender,
+            vec![
+                DataChange::NewItem(NewItem::new("Should not be saved".into(), Utc::now())),
+                DataChange::FinishItem {
+                    item: missing,
+                    when_finished: Utc::now().into(),
+                },
+            ],
+        )
+        .await;
+        assert!(matches!(result, Err(DataLayerError::NotFound(_))));
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert!(surreal_tables.surreal_items.is_empty());
+        assert_eq!(DataLayerCommands::undo(&sender).await.unwrap(), None);
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn subscribers_are_sent_the_tables_after_every_change() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        let mut latest = LatestSurrealTables::new(&sender).await.unwrap();
+        assert!(latest.get().is_empty());
+        //Nothing has been handed out before so everything is new to the caller
+        assert_eq!(latest.take_changed(), None);
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Item".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let after_new_item = latest.get();
+        assert_eq!(after_new_item.surreal_items.len(), 1);
+        assert_eq!(after_new_item, SurrealTables::new(&sender).await.unwrap());
+        let new_item_id = after_new_item.surreal_items[0].id.clone().unwrap();
+        let changed = latest.take_changed().unwrap();
+        assert!(changed.contains(&new_item_id));
+        assert_eq!(latest.take_changed(), Some(Vec::default()));
+
+        //Nothing changed so the same snapshot is handed out again
+        assert!(Arc::ptr_eq(&after_new_item, &latest.get()));
+
+        DataLayerCommands::undo(&sender).await.unwrap();
+        assert!(latest.get().is_empty());
+        //The snapshot that was handed out before the undo is not changed by it
+        assert_eq!(after_new_item.surreal_items.len(), 1);
+
+        DataLayerCommands::redo(&sender).await.unwrap();
+        assert_eq!(latest.get(), after_new_item);
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn undo_and_redo_finish_item() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        assert_eq!(DataLayerCommands::undo(&sender).await.unwrap(), None);
+
+        let new_item = NewItem::new("Finish me".into(), Utc::now());
+        DataLayerCommands::change(&sender, DataChange::NewItem(new_item))
+            .await
+            .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let item = surreal_tables
+            .surreal_items
+            .first()
+            .unwrap()
+            .id
+            .clone()
+            .unwrap();
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::FinishItem {
+                item,
+                when_finished: Utc::now().into(),
+            },
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert!(
+            surreal_tables
+                .surreal_items
+                .first()
+                .unwrap()
+                .finished
+                .is_some()
+        );
+
+        assert_eq!(
+            DataLayerCommands::undo(&sender).await.unwrap(),
+            Some("Finish item".to_string())
+        );
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert!(
+            surreal_tables
+                .surreal_items
+                .first()
+                .unwrap()
+                .finished
+                .is_none()
+        );
+
+        assert_eq!(
+            DataLayerCommands::redo(&sender).await.unwrap(),
+            Some("Finish item".to_string())
+        );
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert!(
+            surreal_tables
+                .surreal_items
+                .first()
+                .unwrap()
+                .finished
+                .is_some()
+        );
+        assert_eq!(DataLayerCommands::redo(&sender).await.unwrap(), None);
+
+        //Undoing the capture should remove the item completely
+        DataLayerCommands::undo(&sender).await.unwrap();
+        assert_eq!(
+            DataLayerCommands::undo(&sender).await.unwrap(),
+            Some("Capture new item".to_string())
+        );
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert!(surreal_tables.surreal_items.is_empty());
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cover_item_with_a_new_proactive_next_step() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        let new_action = NewItemBuilder::default()
+            .summary("Item to be covered")
+            .item_type(SurrealItemType::Action)
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(new_action))
+            .await
+            .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+
+        assert_eq!(1, surreal_tables.surreal_items.len());
+        assert_eq!(
+            0,
+            surreal_tables
+                .surreal_items
+                .first()
+                .unwrap()
+                .dependencies
+                .len()
+        ); //length of zero means nothing is covered
+        let item_to_cover = surreal_tables.surreal_items.first().unwrap();
+
+        let new_item = NewItemBuilder::default()
+            .summary("Covering item")
+            .responsibility(Responsibility::ProactiveActionToTake)
+            .item_type(SurrealItemType::Action)
+            .build()
+            .unwrap();
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::CoverItemWithANewItem {
+                cover_this: item_to_cover.id.clone().expect("In DB"),
+                cover_with: new_item,
+            },
+        )
+        .await
+        .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+
+        let item_that_should_be_covered = surreal_tables
+            .surreal_items
+            .iter()
+            .find(|x| x.summary == "Item to be covered")
+            .unwrap();
+        assert_eq!(2, surreal_tables.surreal_items.len());
+        assert_eq!(1, item_that_should_be_covered.dependencies.len()); //expect one item to be is covered
+        let item_that_should_cover = surreal_tables
+            .surreal_items
+            .iter()
+            .find(|x| x.summary == "Covering item")
+            .unwrap();
+        let id = match &item_that_should_be_covered.dependencies.first().unwrap() {
+            SurrealDependency::AfterItem(id) => id,
+            _ => panic!("Should be an item"),
+        };
+        assert_eq!(item_that_should_cover.id.as_ref().unwrap(), id,);
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn parent_item_with_a_new_item() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        let new_action = NewItemBuilder::default()
+            .summary("Item that needs a parent")
+            .item_type(SurrealItemType::Action)
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(new_action))
+            .await
+            .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+
+        assert_eq!(1, surreal_tables.surreal_items.len());
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentNewItemWithAnExistingChildItem {
+                child: surreal_tables
+                    .surreal_items
+                    .into_iter()
+                    .next()
+                    .unwrap()
+                    .id
+                    .expect("In Db"),
+                parent_new_item: NewItemBuilder::default()
+                    .summary("Parent Item")
+                    .item_type(SurrealItemType::Goal(SurrealHowMuchIsInMyControl::default()))
+                    .build()
+                    .unwrap(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+
+        assert_eq!(2, surreal_tables.surreal_items.len());
+        assert_eq!(
+            1,
+            surreal_tables
+                .surreal_items
+                .iter()
+                .find(|x| x.summary == "Parent Item")
+                .unwrap()
+                .smaller_items_in_priority_order
+                .len()
+        );
+        assert_eq!(
+            &SurrealOrderedSubItem::SubItem {
+                surreal_item_id: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Item that needs a parent")
+                    .unwrap()
+                    .id
+                    .as_ref()
+                    .unwrap()
+                    .clone()
+            },
+            surreal_tables
+                .surreal_items
+                .iter()
+                .find(|x| x.summary == "Parent Item")
+                .unwrap()
+                .smaller_items_in_priority_order
+                .first()
+                .unwrap()
+        );
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn parent_item_with_an_existing_item_that_has_no_children() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        let item_that_needs_a_parent = NewItemBuilder::default()
+            .summary("Item that needs a parent")
+            .item_type(SurrealItemType::Action)
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(item_that_needs_a_parent))
+            .await
+            .unwrap();
+
+        let parent_item = NewItemBuilder::default()
+            .summary("Parent Item")
+            .item_type(SurrealItemType::Goal(SurrealHowMuchIsInMyControl::default()))
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(parent_item))
+            .await
+            .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+
+        assert_eq!(2, surreal_tables.surreal_items.len());
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithExistingItem {
+                child: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Item that needs a parent")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                parent: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Parent Item")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                higher_importance_than_this: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+
+        assert_eq!(2, surreal_tables.surreal_items.len());
+        assert_eq!(
+            1,
+            surreal_tables
+                .surreal_items
+                .iter()
+                .find(|x| x.summary == "Parent Item")
+                .unwrap()
+                .smaller_items_in_priority_order
+                .len()
+        );
+        assert_eq!(
+            &SurrealOrderedSubItem::SubItem {
+                surreal_item_id: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Item that needs a parent")
+                    .unwrap()
+                    .id
+                    .as_ref()
+                    .unwrap()
+                    .clone()
+            },
+            surreal_tables
+                .surreal_items
+                .iter()
+                .find(|x| x.summary == "Parent Item")
+                .unwrap()
+                .smaller_items_in_priority_order
+                .first()
+                .unwrap()
+        );
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn parent_item_with_an_existing_item_that_has_children() {
+        // SETUP
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        let child_item = NewItemBuilder::default()
+            .summary("Child Item at the top of the list")
+            .item_type(SurrealItemType::Action)
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(child_item))
+            .await
+            .unwrap();
+
+        let child_item = NewItemBuilder::default()
+            .summary("Child Item 2nd position")
+            .item_type(SurrealItemType::Action)
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(child_item))
+            .await
+            .unwrap();
+
+        let child_item = NewItemBuilder::default()
+            .summary("Child Item 3rd position")
+            .item_type(SurrealItemType::Action)
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(child_item))
+            .await
+            .unwrap();
+
+        let child_item = NewItemBuilder::default()
+            .summary("Child Item bottom position")
+            .item_type(SurrealItemType::Action)
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(child_item))
+            .await
+            .unwrap();
+
+        let parent_item = NewItemBuilder::default()
+            .summary("Parent Item")
+            .item_type(SurrealItemType::Goal(SurrealHowMuchIsInMyControl::default()))
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(parent_item))
+            .await
+            .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+
+        assert_eq!(5, surreal_tables.surreal_items.len());
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithExistingItem {
+                child: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Child Item at the top of the list")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                parent: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Parent Item")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                higher_importance_than_this: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        // TEST - The order of adding the items is meant to cause the higher_priority_than_this to be used
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithExistingItem {
+                child: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Child Item bottom position")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                parent: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Parent Item")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                higher_importance_than_this: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithExistingItem {
+                child: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Child Item 2nd position")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                parent: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Parent Item")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                higher_importance_than_this: Some(
+                    surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| x.summary == "Child Item bottom position")
+                        .unwrap()
+                        .id
+                        .clone()
+                        .expect("In DB"),
+                ),
+            },
+        )
+        .await
+        .unwrap();
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithExistingItem {
+                child: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Child Item 3rd position")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                parent: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Parent Item")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                higher_importance_than_this: Some(
+                    surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| x.summary == "Child Item bottom position")
+                        .unwrap()
+                        .id
+                        .clone()
+                        .expect("In DB"),
+                ),
+            },
+        )
+        .await
+        .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+
+        assert_eq!(
+            vec![
+                SurrealOrderedSubItem::SubItem {
+                    surreal_item_id: surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| x.summary == "Child Item at the top of the list")
+                        .unwrap()
+                        .id
+                        .as_ref()
+                        .unwrap()
+                        .clone()
+                },
+                SurrealOrderedSubItem::SubItem {
+                    surreal_item_id: surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| x.summary == "Child Item 2nd position")
+                        .unwrap()
+                        .id
+                        .as_ref()
+                        .unwrap()
+                        .clone()
+                },
+                SurrealOrderedSubItem::SubItem {
+                    surreal_item_id: surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| x.summary == "Child Item 3rd position")
+                        .unwrap()
+                        .id
+                        .as_ref()
+                        .unwrap()
+                        .clone()
+                },
+                SurrealOrderedSubItem::SubItem {
+                    surreal_item_id: surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| x.summary == "Child Item bottom position")
+                        .unwrap()
+                        .id
+                        .as_ref()
+                        .unwrap()
+                        .clone()
+                },
+            ],
+            surreal_tables
+                .surreal_items
+                .iter()
+                .find(|x| x.summary == "Parent Item")
+                .unwrap()
+                .smaller_items_in_priority_order
+        );
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn change_order_of_children() {
+        // SETUP
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        let child_item = NewItemBuilder::default()
+            .summary("Child Item at the top of the list")
+            .item_type(SurrealItemType::Action)
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(child_item))
+            .await
+            .unwrap();
+
+        let child_item = NewItemBuilder::default()
+            .summary("Child Item 2nd position")
+            .item_type(SurrealItemType::Action)
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(child_item))
+            .await
+            .unwrap();
+
+        let child_item = NewItemBuilder::default()
+            .summary("Child Item 3rd position")
+            .item_type(SurrealItemType::Action)
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(child_item))
+            .await
+            .unwrap();
+
+        let child_item = NewItemBuilder::default()
+            .summary("Child Item bottom position, then moved to above 2nd position")
+            .item_type(SurrealItemType::Action)
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(child_item))
+            .await
+            .unwrap();
+
+        let parent_item = NewItemBuilder::default()
+            .summary("Parent Item")
+            .item_type(SurrealItemType::Goal(SurrealHowMuchIsInMyControl::default()))
+            .build()
+            .expect("Filled out required fields");
+        DataLayerCommands::change(&sender, DataChange::NewItem(parent_item))
+            .await
+            .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+
+        assert_eq!(5, surreal_tables.surreal_items.len());
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithExistingItem {
+                child: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Child Item at the top of the list")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                parent: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Parent Item")
                     .unwrap()
                     .id
                     .clone()
                     .expect("In DB"),
                 higher_importance_than_this: None,
-            })
+            },
+        )
+        .await
+        .unwrap();
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithExistingItem {
+                child: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| {
+                        x.summary == "Child Item bottom position, then moved to above 2nd position"
+                    })
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                parent: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Parent Item")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                higher_importance_than_this: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithExistingItem {
+                child: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Child Item 2nd position")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                parent: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Parent Item")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                higher_importance_than_this: Some(
+                    surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| {
+                            x.summary
+                                == "Child Item bottom position, then moved to above 2nd position"
+                        })
+                        .unwrap()
+                        .id
+                        .clone()
+                        .expect("In DB"),
+                ),
+            },
+        )
+        .await
+        .unwrap();
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithExistingItem {
+                child: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Child Item 3rd position")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                parent: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Parent Item")
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                higher_importance_than_this: Some(
+                    surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| {
+                            x.summary
+                                == "Child Item bottom position, then moved to above 2nd position"
+                        })
+                        .unwrap()
+                        .id
+                        .clone()
+                        .expect("In DB"),
+                ),
+            },
+        )
+        .await
+        .unwrap();
+
+        // TEST - Move the bottom item to the 2nd position
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithExistingItem {
+                child: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| {
+                        x.summary == "Child Item bottom position, then moved to above 2nd position"
+                    })
+                    .unwrap()
+                    .id
+                    .clone()
+                    .expect("In DB"),
+                parent: surreal_tables
+                    .surreal_items
+                    .iter()
+                    .find(|x| x.summary == "Parent Item")
+                    .unwrap()
+                    .id
+                    .as_ref()
+                    .expect("In DB")
+                    .clone(),
+                higher_importance_than_this: Some(
+                    surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| x.summary == "Child Item 2nd position")
+                        .unwrap()
+                        .id
+                        .clone()
+                        .expect("In DB"),
+                ),
+            },
+        )
+        .await
+        .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+
+        assert_eq!(
+            vec![
+                SurrealOrderedSubItem::SubItem {
+                    surreal_item_id: surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| x.summary == "Child Item at the top of the list")
+                        .unwrap()
+                        .id
+                        .as_ref()
+                        .unwrap()
+                        .clone()
+                },
+                SurrealOrderedSubItem::SubItem {
+                    surreal_item_id: surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| x.summary
+                            == "Child Item bottom position, then moved to above 2nd position")
+                        .unwrap()
+                        .id
+                        .as_ref()
+                        .unwrap()
+                        .clone()
+                },
+                SurrealOrderedSubItem::SubItem {
+                    surreal_item_id: surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| x.summary == "Child Item 2nd position")
+                        .unwrap()
+                        .id
+                        .as_ref()
+                        .unwrap()
+                        .clone()
+                },
+                SurrealOrderedSubItem::SubItem {
+                    surreal_item_id: surreal_tables
+                        .surreal_items
+                        .iter()
+                        .find(|x| x.summary == "Child Item 3rd position")
+                        .unwrap()
+                        .id
+                        .as_ref()
+                        .unwrap()
+                        .clone()
+                },
+            ],
+            surreal_tables
+                .surreal_items
+                .iter()
+                .find(|x| x.summary == "Parent Item")
+                .unwrap()
+                .smaller_items_in_priority_order
+        );
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn time_spent_can_be_corrected_split_and_deleted() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        let started = Utc::now() - chrono::Duration::hours(3);
+        let stopped = Utc::now();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::RecordTimeSpent(NewTimeSpent {
+                working_on: vec![],
+                urgency: None,
+                why_in_scope: vec![],
+                when_started: started,
+                when_stopped: stopped,
+                dedication: None,
+            }),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let time_spent = surreal_tables.surreal_time_spent_log[0].id.clone().unwrap();
+
+        let result = DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateTimeSpentTimes {
+                time_spent: time_spent.clone(),
+                when_started: stopped.into(),
+                when_stopped: started.into(),
+            },
+        )
+        .await;
+        assert!(matches!(result, Err(DataLayerError::Invalid(..))));
+
+        let corrected_start = started + chrono::Duration::hours(1);
+        DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateTimeSpentTimes {
+                time_spent: time_spent.clone(),
+                when_started: corrected_start.into(),
+                when_stopped: stopped.into(),
+            },
+        )
+        .await
+        .unwrap();
+        let split_at = corrected_start + chrono::Duration::hours(1);
+        DataLayerCommands::change(
+            &sender,
+            DataChange::SplitTimeSpent {
+                time_spent: time_spent.clone(),
+                at: split_at.into(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let mut log = surreal_tables.surreal_time_spent_log.clone();
+        log.sort_by(|a, b| a.when_started.cmp(&b.when_started));
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].id, Some(time_spent.clone()));
+        assert_eq!(log[0].when_started, corrected_start.into());
+        assert_eq!(log[0].when_stopped, split_at.into());
+        assert_eq!(log[1].when_started, split_at.into());
+        assert_eq!(log[1].when_stopped, stopped.into());
+
+        DataLayerCommands::change(&sender, DataChange::DeleteTimeSpent(time_spent))
             .await
             .unwrap();
-
         let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_time_spent_log.len(), 1);
+        assert_eq!(
+            DataLayerCommands::undo(&sender).await.unwrap(),
+            Some("Delete time spent".to_string())
+        );
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_time_spent_log.len(), 2);
 
-        assert_eq!(2, surreal_tables.surreal_items.len());
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    async fn new_mode_for_test(
+        sender: &Sender<DataLayerCommands>,
+        name: &str,
+        parent: Option<&RecordId>,
+    ) -> RecordId {
+        DataLayerCommands::change(
+            sender,
+            DataChange::NewMode(
+                NewModeBuilder::default()
+                    .name(name)
+                    .parent(parent.cloned())
+                    .build()
+                    .unwrap(),
+            ),
+        )
+        .await
+        .unwrap();
+        find_mode_for_test(&SurrealTables::new(sender).await.unwrap(), name)
+            .unwrap()
+            .id
+            .unwrap()
+    }
+
+    fn find_mode_for_test(surreal_tables: &SurrealTables, name: &str) -> Option<SurrealMode> {
+        surreal_tables
+            .surreal_modes
+            .iter()
+            .find(|x| x.name == name)
+            .cloned()
+    }
+
+    #[tokio::test]
+    async fn a_mode_can_be_moved_under_another_mode_but_not_under_its_own_smaller_mode() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+        let work = new_mode_for_test(&sender, "Work", None).await;
+        let home = new_mode_for_test(&sender, "Home", None).await;
+        let meetings = new_mode_for_test(&sender, "Meetings", Some(&home)).await;
+
+        //Meetings was put under Home by mistake
+        DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateModeParent(meetings.clone(), Some(work.clone())),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
         assert_eq!(
-            1,
-            surreal_tables
-                .surreal_items
-                .iter()
-                .find(|x| x.summary == "Parent Item")
+            find_mode_for_test(&surreal_tables, "Meetings")
                 .unwrap()
-                .smaller_items_in_priority_order
-                .len()
+                .parent,
+            Some(work.clone())
         );
+
+        let result = DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateModeParent(work.clone(), Some(meetings)),
+        )
+        .await;
+        assert!(matches!(result, Err(DataLayerError::Invalid(..))));
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
         assert_eq!(
-            &SurrealOrderedSubItem::SubItem {
-                surreal_item_id: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Item that needs a parent")
-                    .unwrap()
-                    .id
-                    .as_ref()
-                    .unwrap()
-                    .clone()
-            },
-            surreal_tables
-                .surreal_items
-                .iter()
-                .find(|x| x.summary == "Parent Item")
-                .unwrap()
-                .smaller_items_in_priority_order
-                .first()
+            find_mode_for_test(&surreal_tables, "Work").unwrap().parent,
+            None
+        );
+
+        assert_eq!(
+            DataLayerCommands::undo(&sender).await.unwrap(),
+            Some("Move mode".to_string())
+        );
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(
+            find_mode_for_test(&surreal_tables, "Meetings")
                 .unwrap()
+                .parent,
+            Some(home)
         );
 
         drop(sender);
@@ -1072,513 +4232,822 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn parent_item_with_an_existing_item_that_has_children() {
-        // SETUP
+    async fn modes_can_be_reordered() {
         let (sender, receiver) = mpsc::channel(1);
-        let data_storage_join_handle =
-            tokio::spawn(async move { data_storage_start_and_run(receiver, "mem://").await });
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+        let work = new_mode_for_test(&sender, "Work", None).await;
+        let home = new_mode_for_test(&sender, "Home", None).await;
+        let display_order = |surreal_tables: &SurrealTables, name: &str| {
+            find_mode_for_test(surreal_tables, name)
+                .unwrap()
+                .display_order
+        };
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(display_order(&surreal_tables, "Work"), 0);
+        assert_eq!(display_order(&surreal_tables, "Home"), 1);
 
-        let child_item = NewItemBuilder::default()
-            .summary("Child Item at the top of the list")
-            .item_type(SurrealItemType::Action)
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(child_item))
+        DataLayerCommands::change(&sender, DataChange::UpdateModeOrder(vec![home, work]))
             .await
             .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(display_order(&surreal_tables, "Home"), 0);
+        assert_eq!(display_order(&surreal_tables, "Work"), 1);
 
-        let child_item = NewItemBuilder::default()
-            .summary("Child Item 2nd position")
-            .item_type(SurrealItemType::Action)
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(child_item))
-            .await
-            .unwrap();
+        DataLayerCommands::undo(&sender).await.unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(display_order(&surreal_tables, "Work"), 0);
+        assert_eq!(display_order(&surreal_tables, "Home"), 1);
 
-        let child_item = NewItemBuilder::default()
-            .summary("Child Item 3rd position")
-            .item_type(SurrealItemType::Action)
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(child_item))
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_mode_order_that_is_not_exactly_the_modes_with_one_parent_is_not_valid() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+        let work = new_mode_for_test(&sender, "Work", None).await;
+        let home = new_mode_for_test(&sender, "Home", None).await;
+        let kitchen = new_mode_for_test(&sender, "Kitchen", Some(&home)).await;
+        let missing: RecordId = (SurrealMode::TABLE_NAME, "missing").into();
+
+        for in_order in [
+            vec![],
+            vec![home.clone()],
+            vec![home.clone(), work.clone(), home.clone()],
+            vec![home.clone(), kitchen.clone()],
+            vec![home.clone(), work.clone(), kitchen.clone()],
+            vec![missing.clone(), home.clone(), work.clone()],
+            vec![home.clone(), work.clone(), missing],
+        ] {
+            let result =
+                DataLayerCommands::change(&sender, DataChange::UpdateModeOrder(in_order)).await;
+            assert!(matches!(result, Err(DataLayerError::Invalid(..))));
+        }
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(
+            find_mode_for_test(&surreal_tables, "Work")
+                .unwrap()
+                .display_order,
+            0
+        );
+        assert_eq!(
+            find_mode_for_test(&surreal_tables, "Home")
+                .unwrap()
+                .display_order,
+            1
+        );
+
+        DataLayerCommands::change(&sender, DataChange::UpdateModeOrder(vec![kitchen]))
             .await
             .unwrap();
 
-        let child_item = NewItemBuilder::default()
-            .summary("Child Item bottom position")
-            .item_type(SurrealItemType::Action)
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(child_item))
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn deleting_a_mode_with_smaller_modes_and_items_either_moves_them_up_or_deletes_them_too()
+    {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+        let home = new_mode_for_test(&sender, "Home", None).await;
+        let kitchen = new_mode_for_test(&sender, "Kitchen", Some(&home)).await;
+        let pantry = new_mode_for_test(&sender, "Pantry", Some(&kitchen)).await;
+        for summary in ["Clean the oven", "Count the cans"] {
+            DataLayerCommands::change(
+                &sender,
+                DataChange::NewItem(NewItem::new(summary.into(), Utc::now())),
+            )
             .await
             .unwrap();
+        }
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let item_id = |summary: &str| {
+            surreal_tables
+                .surreal_items
+                .iter()
+                .find(|x| x.summary == summary)
+                .and_then(|x| x.id.clone())
+                .unwrap()
+        };
+        let oven = item_id("Clean the oven");
+        let cans = item_id("Count the cans");
+        DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateItemModes(oven.clone(), vec![kitchen.clone()]),
+        )
+        .await
+        .unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateItemModes(cans.clone(), vec![pantry.clone()]),
+        )
+        .await
+        .unwrap();
+        let modes_of = |surreal_tables: &SurrealTables, item: &RecordId| {
+            surreal_tables
+                .surreal_items
+                .iter()
+                .find(|x| x.id.as_ref() == Some(item))
+                .unwrap()
+                .modes
+                .clone()
+        };
 
-        let parent_item = NewItemBuilder::default()
-            .summary("Parent Item")
-            .item_type(SurrealItemType::Goal(SurrealHowMuchIsInMyControl::default()))
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(parent_item))
-            .await
+        //Keeping the smaller modes moves them, and the items in the deleted mode, up to its parent
+        DataLayerCommands::change(
+            &sender,
+            DataChange::DeleteMode {
+                mode: kitchen.clone(),
+                delete_smaller_modes: false,
+            },
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert!(find_mode_for_test(&surreal_tables, "Kitchen").is_none());
+        assert_eq!(
+            find_mode_for_test(&surreal_tables, "Pantry")
+                .unwrap()
+                .parent,
+            Some(home.clone())
+        );
+        assert_eq!(modes_of(&surreal_tables, &oven), vec![home.clone()]);
+        assert_eq!(modes_of(&surreal_tables, &cans), vec![pantry.clone()]);
+
+        assert_eq!(
+            DataLayerCommands::undo(&sender).await.unwrap(),
+            Some("Delete mode".to_string())
+        );
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(
+            find_mode_for_test(&surreal_tables, "Pantry")
+                .unwrap()
+                .parent,
+            Some(kitchen.clone())
+        );
+        assert_eq!(modes_of(&surreal_tables, &oven), vec![kitchen.clone()]);
+
+        //Deleting the smaller modes as well takes every item out of all of them
+        DataLayerCommands::change(
+            &sender,
+            DataChange::DeleteMode {
+                mode: kitchen.clone(),
+                delete_smaller_modes: true,
+            },
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_modes.len(), 1);
+        assert!(find_mode_for_test(&surreal_tables, "Home").is_some());
+        assert!(modes_of(&surreal_tables, &oven).is_empty());
+        assert!(modes_of(&surreal_tables, &cans).is_empty());
+
+        DataLayerCommands::undo(&sender).await.unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_modes.len(), 3);
+        assert_eq!(modes_of(&surreal_tables, &oven), vec![kitchen]);
+        assert_eq!(modes_of(&surreal_tables, &cans), vec![pantry]);
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn items_and_the_current_mode_keep_their_place_when_their_mode_is_deleted() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewMode(NewModeBuilder::default().name("Home").build().unwrap()),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let home = surreal_tables.surreal_modes[0].id.clone().unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewMode(
+                NewModeBuilder::default()
+                    .name("Kitchen")
+                    .parent(Some(home.clone()))
+                    .build()
+                    .unwrap(),
+            ),
+        )
+        .await
+        .unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Clean the oven".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let kitchen = surreal_tables
+            .surreal_modes
+            .iter()
+            .find(|x| x.name == "Kitchen")
+            .and_then(|x| x.id.clone())
             .unwrap();
+        let item = surreal_tables.surreal_items[0].id.clone().unwrap();
 
+        DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateItemModes(item.clone(), vec![kitchen.clone()]),
+        )
+        .await
+        .unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::SetCurrentMode(NewCurrentMode::new(
+                Vec::default(),
+                Vec::default(),
+                vec![kitchen.clone()],
+                Vec::default(),
+            )),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items[0].modes, vec![kitchen.clone()]);
+        assert_eq!(
+            surreal_tables.surreal_current_modes[0].modes,
+            vec![kitchen.clone()]
+        );
+
+        //Like its smaller modes, what was in Kitchen moves up to Home
+        DataLayerCommands::change(
+            &sender,
+            DataChange::DeleteMode {
+                mode: kitchen,
+                delete_smaller_modes: false,
+            },
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items[0].modes, vec![home.clone()]);
+        assert_eq!(
+            surreal_tables.surreal_current_modes[0].modes,
+            vec![home.clone()]
+        );
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::DeleteMode {
+                mode: home.clone(),
+                delete_smaller_modes: true,
+            },
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert!(surreal_tables.surreal_items[0].modes.is_empty());
+        assert!(surreal_tables.surreal_current_modes[0].modes.is_empty());
+
+        DataLayerCommands::undo(&sender).await.unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items[0].modes, vec![home.clone()]);
+        assert_eq!(surreal_tables.surreal_current_modes[0].modes, vec![home]);
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn notes_and_links_can_be_changed_and_undone() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Plan the trip".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let item = surreal_tables.surreal_items[0].id.clone().unwrap();
+        assert!(surreal_tables.surreal_items[0].notes.is_empty());
+        assert!(surreal_tables.surreal_items[0].links.is_empty());
+
+        let notes = "# Packing\n- Passport\n- Charger".to_string();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateItemNotes(item.clone(), notes.clone()),
+        )
+        .await
+        .unwrap();
+        let links = vec![
+            SurrealLink {
+                kind: SurrealLinkKind::Web,
+                target: "https://example.com/itinerary".into(),
+            },
+            SurrealLink {
+                kind: SurrealLinkKind::FilePath,
+                target: "/home/me/trip/tickets.pdf".into(),
+            },
+        ];
+        DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateItemLinks(item.clone(), links.clone()),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items[0].notes, notes);
+        assert_eq!(surreal_tables.surreal_items[0].links, links);
+
+        assert_eq!(
+            DataLayerCommands::undo(&sender).await.unwrap(),
+            Some("Change links".to_string())
+        );
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items[0].notes, notes);
+        assert!(surreal_tables.surreal_items[0].links.is_empty());
+
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn tags_can_be_added_and_removed() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Mow the lawn".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let item = surreal_tables.surreal_items[0].id.clone().unwrap();
+
+        for tag in [" outside ", "weekend", "outside"] {
+            DataLayerCommands::change(&sender, DataChange::AddItemTag(item.clone(), tag.into()))
+                .await
+                .unwrap();
+        }
+        let result =
+            DataLayerCommands::change(&sender, DataChange::AddItemTag(item.clone(), "  ".into()))
+                .await;
+        assert!(matches!(result, Err(DataLayerError::Invalid(..))));
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(
+            surreal_tables.surreal_items[0].tags,
+            vec!["outside".to_string(), "weekend".to_string()]
+        );
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::RemoveItemTag(item.clone(), "outside".into()),
+        )
+        .await
+        .unwrap();
         let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(
+            surreal_tables.surreal_items[0].tags,
+            vec!["weekend".to_string()]
+        );
 
-        assert_eq!(5, surreal_tables.surreal_items.len());
+        DataLayerCommands::change(
+            &sender,
+            DataChange::RemoveItemTag(item.clone(), "  weekend ".into()),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert!(surreal_tables.surreal_items[0].tags.is_empty());
+        let result =
+            DataLayerCommands::change(&sender, DataChange::RemoveItemTag(item.clone(), " ".into()))
+                .await;
+        assert!(matches!(result, Err(DataLayerError::Invalid(..))));
 
-        sender
-            .send(DataLayerCommands::ParentItemWithExistingItem {
-                child: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Child Item at the top of the list")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                parent: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Parent Item")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                higher_importance_than_this: None,
-            })
-            .await
-            .unwrap();
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
 
-        // TEST - The order of adding the items is meant to cause the higher_priority_than_this to be used
+    #[tokio::test]
+    async fn finishing_a_recurring_item_waits_for_it_to_be_due_again() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
 
-        sender
-            .send(DataLayerCommands::ParentItemWithExistingItem {
-                child: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Child Item bottom position")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                parent: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Parent Item")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Water the plants".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let item = surreal_tables.surreal_items[0].id.clone().unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithANewChildItem {
+                child: NewItem::new("Fill the watering can".into(), Utc::now()),
+                parent: item.clone(),
                 higher_importance_than_this: None,
-            })
-            .await
-            .unwrap();
-
-        sender
-            .send(DataLayerCommands::ParentItemWithExistingItem {
-                child: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Child Item 2nd position")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                parent: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Parent Item")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                higher_importance_than_this: Some(
-                    surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| x.summary == "Child Item bottom position")
-                        .unwrap()
-                        .id
-                        .clone()
-                        .expect("In DB"),
-                ),
-            })
-            .await
+            },
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let child = surreal_tables
+            .surreal_items
+            .iter()
+            .find_map(|x| x.id.clone().filter(|x| *x != item))
             .unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::FinishItem {
+                item: child.clone(),
+                when_finished: Utc::now().into(),
+            },
+        )
+        .await
+        .unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateItemRecurrence(item.clone(), Some(SurrealRecurrence::EveryDays(1))),
+        )
+        .await
+        .unwrap();
 
-        sender
-            .send(DataLayerCommands::ParentItemWithExistingItem {
-                child: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Child Item 3rd position")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                parent: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Parent Item")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                higher_importance_than_this: Some(
-                    surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| x.summary == "Child Item bottom position")
-                        .unwrap()
-                        .id
-                        .clone()
-                        .expect("In DB"),
-                ),
-            })
-            .await
-            .unwrap();
+        let when_finished = Utc::now();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::FinishItem {
+                item: item.clone(),
+                when_finished: when_finished.into(),
+            },
+        )
+        .await
+        .unwrap();
 
         let surreal_tables = SurrealTables::new(&sender).await.unwrap();
-
-        assert_eq!(
-            vec![
-                SurrealOrderedSubItem::SubItem {
-                    surreal_item_id: surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| x.summary == "Child Item at the top of the list")
-                        .unwrap()
-                        .id
-                        .as_ref()
-                        .unwrap()
-                        .clone()
-                },
-                SurrealOrderedSubItem::SubItem {
-                    surreal_item_id: surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| x.summary == "Child Item 2nd position")
-                        .unwrap()
-                        .id
-                        .as_ref()
-                        .unwrap()
-                        .clone()
-                },
-                SurrealOrderedSubItem::SubItem {
-                    surreal_item_id: surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| x.summary == "Child Item 3rd position")
-                        .unwrap()
-                        .id
-                        .as_ref()
-                        .unwrap()
-                        .clone()
-                },
-                SurrealOrderedSubItem::SubItem {
-                    surreal_item_id: surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| x.summary == "Child Item bottom position")
-                        .unwrap()
-                        .id
-                        .as_ref()
-                        .unwrap()
-                        .clone()
-                },
-            ],
+        let find = |surreal_tables: &SurrealTables, id: &RecordId| {
             surreal_tables
                 .surreal_items
                 .iter()
-                .find(|x| x.summary == "Parent Item")
+                .find(|x| x.id.as_ref() == Some(id))
+                .cloned()
                 .unwrap()
-                .smaller_items_in_priority_order
-        );
+        };
+        let recurring = find(&surreal_tables, &item);
+        assert_eq!(recurring.finished, None);
+        assert_eq!(recurring.completions.len(), 1);
+        assert!(recurring.completions[0].on_time);
+        let due = recurring
+            .dependencies
+            .iter()
+            .find_map(|x| match x {
+                SurrealDependency::AfterDateTime(due) => Some(DateTime::<Utc>::from(due.clone())),
+                _ => None,
+            })
+            .unwrap();
+        assert!(due > when_finished);
+        assert_eq!(find(&surreal_tables, &child).finished, None);
+
+        assert!(DataLayerCommands::undo(&sender).await.unwrap().is_some());
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let recurring = find(&surreal_tables, &item);
+        assert!(recurring.completions.is_empty());
+        assert!(recurring.dependencies.is_empty());
+        assert!(find(&surreal_tables, &child).finished.is_some());
 
         drop(sender);
         data_storage_join_handle.await.unwrap();
     }
 
     #[tokio::test]
-    async fn change_order_of_children() {
-        // SETUP
+    async fn finishing_a_recurring_item_keeps_the_not_before_dates_the_user_added() {
         let (sender, receiver) = mpsc::channel(1);
-        let data_storage_join_handle =
-            tokio::spawn(async move { data_storage_start_and_run(receiver, "mem://").await });
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
 
-        let child_item = NewItemBuilder::default()
-            .summary("Child Item at the top of the list")
-            .item_type(SurrealItemType::Action)
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(child_item))
-            .await
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Pay the rent".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let item = SurrealTables::new(&sender).await.unwrap().surreal_items[0]
+            .id
+            .clone()
             .unwrap();
+        let not_before =
+            SurrealDependency::AfterDateTime((Utc::now() - chrono::Duration::days(3)).into());
+        DataLayerCommands::transaction(
+            &sender,
+            vec![
+                DataChange::AddItemDependency(item.clone(), not_before.clone()),
+                DataChange::UpdateItemRecurrence(
+                    item.clone(),
+                    Some(SurrealRecurrence::EveryDays(1)),
+                ),
+            ],
+        )
+        .await
+        .unwrap();
 
-        let child_item = NewItemBuilder::default()
-            .summary("Child Item 2nd position")
-            .item_type(SurrealItemType::Action)
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(child_item))
+        for finished_count in 1..=2 {
+            DataLayerCommands::change(
+                &sender,
+                DataChange::FinishItem {
+                    item: item.clone(),
+                    when_finished: Utc::now().into(),
+                },
+            )
             .await
             .unwrap();
 
-        let child_item = NewItemBuilder::default()
-            .summary("Child Item 3rd position")
-            .item_type(SurrealItemType::Action)
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(child_item))
-            .await
-            .unwrap();
+            let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+            let recurring = &surreal_tables.surreal_items[0];
+            assert_eq!(recurring.completions.len(), finished_count);
+            let due_again = recurring.completions[finished_count - 1]
+                .due_again
+                .clone()
+                .unwrap();
+            assert_eq!(
+                recurring.dependencies,
+                vec![
+                    not_before.clone(),
+                    SurrealDependency::AfterDateTime(due_again)
+                ]
+            );
+        }
 
-        let child_item = NewItemBuilder::default()
-            .summary("Child Item bottom position, then moved to above 2nd position")
-            .item_type(SurrealItemType::Action)
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(child_item))
-            .await
-            .unwrap();
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
 
-        let parent_item = NewItemBuilder::default()
-            .summary("Parent Item")
-            .item_type(SurrealItemType::Goal(SurrealHowMuchIsInMyControl::default()))
-            .build()
-            .expect("Filled out required fields");
-        sender
-            .send(DataLayerCommands::NewItem(parent_item))
+    #[tokio::test]
+    async fn events_can_be_renamed_made_recurring_merged_and_deleted() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Take out the bin".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let item = surreal_tables.surreal_items[0].id.clone().unwrap();
+        for summary in ["Bin is full", "Bin is ful"] {
+            let new_event = NewEvent {
+                summary: summary.into(),
+                triggered: false,
+                last_updated: Utc::now(),
+            };
+            DataLayerCommands::change(
+                &sender,
+                DataChange::AddItemDependencyNewEvent(item.clone(), new_event),
+            )
             .await
             .unwrap();
+        }
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let find_event = |surreal_tables: &SurrealTables, summary: &str| {
+            surreal_tables
+                .surreal_events
+                .iter()
+                .find(|x| x.summary == summary)
+                .and_then(|x| x.id.clone())
+                .unwrap()
+        };
+        let keep = find_event(&surreal_tables, "Bin is full");
+        let merge = find_event(&surreal_tables, "Bin is ful");
+
+        DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateEventSummary(keep.clone(), "The bin is full".into()),
+        )
+        .await
+        .unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::UpdateEventUntriggerAfter(
+                keep.clone(),
+                Some(std::time::Duration::from_secs(60 * 60).into()),
+            ),
+        )
+        .await
+        .unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::MergeEvents {
+                keep: keep.clone(),
+                merge,
+            },
+        )
+        .await
+        .unwrap();
 
         let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_events.len(), 1);
+        assert_eq!(surreal_tables.surreal_events[0].summary, "The bin is full");
+        assert_eq!(
+            surreal_tables.surreal_items[0].dependencies,
+            vec![SurrealDependency::AfterEvent(keep.clone())]
+        );
 
-        assert_eq!(5, surreal_tables.surreal_items.len());
+        //A recurring event goes back to waiting once it has been triggered for longer than untrigger_after
+        DataLayerCommands::change(
+            &sender,
+            DataChange::TriggerEvent {
+                event: keep.clone(),
+                when: Utc::now().into(),
+            },
+        )
+        .await
+        .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let now = Utc::now();
+        let events = surreal_tables.make_events(&now);
+        assert!(!events.get(&keep).unwrap().is_active());
+        let two_hours_later = now + chrono::Duration::hours(2);
+        let events = surreal_tables.make_events(&two_hours_later);
+        assert!(events.get(&keep).unwrap().is_active());
 
-        sender
-            .send(DataLayerCommands::ParentItemWithExistingItem {
-                child: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Child Item at the top of the list")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                parent: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Parent Item")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                higher_importance_than_this: None,
-            })
+        DataLayerCommands::change(&sender, DataChange::DeleteEvent(keep))
             .await
             .unwrap();
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert!(surreal_tables.surreal_events.is_empty());
+        assert!(surreal_tables.surreal_items[0].dependencies.is_empty());
+        assert_eq!(
+            DataLayerCommands::undo(&sender).await.unwrap(),
+            Some("Delete event".to_string())
+        );
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_events.len(), 1);
 
-        sender
-            .send(DataLayerCommands::ParentItemWithExistingItem {
-                child: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| {
-                        x.summary == "Child Item bottom position, then moved to above 2nd position"
-                    })
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                parent: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Parent Item")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                higher_importance_than_this: None,
-            })
-            .await
-            .unwrap();
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+    }
 
-        sender
-            .send(DataLayerCommands::ParentItemWithExistingItem {
-                child: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Child Item 2nd position")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                parent: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Parent Item")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                higher_importance_than_this: Some(
-                    surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| {
-                            x.summary
-                                == "Child Item bottom position, then moved to above 2nd position"
-                        })
-                        .unwrap()
-                        .id
-                        .clone()
-                        .expect("In DB"),
-                ),
-            })
-            .await
-            .unwrap();
+    #[tokio::test]
+    async fn delete_and_merge_events_in_a_transaction_see_the_changes_before_them() {
+        let (sender, receiver) = mpsc::channel(1);
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
 
-        sender
-            .send(DataLayerCommands::ParentItemWithExistingItem {
-                child: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Child Item 3rd position")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                parent: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Parent Item")
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                higher_importance_than_this: Some(
-                    surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| {
-                            x.summary
-                                == "Child Item bottom position, then moved to above 2nd position"
-                        })
-                        .unwrap()
-                        .id
-                        .clone()
-                        .expect("In DB"),
-                ),
-            })
+        for summary in ["Has the events", "Waiting"] {
+            DataLayerCommands::change(
+                &sender,
+                DataChange::NewItem(NewItem::new(summary.into(), Utc::now())),
+            )
             .await
             .unwrap();
-
-        // TEST - Move the bottom item to the 2nd position
-        sender
-            .send(DataLayerCommands::ParentItemWithExistingItem {
-                child: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| {
-                        x.summary == "Child Item bottom position, then moved to above 2nd position"
-                    })
-                    .unwrap()
-                    .id
-                    .clone()
-                    .expect("In DB"),
-                parent: surreal_tables
-                    .surreal_items
-                    .iter()
-                    .find(|x| x.summary == "Parent Item")
-                    .unwrap()
-                    .id
-                    .as_ref()
-                    .expect("In DB")
-                    .clone(),
-                higher_importance_than_this: Some(
-                    surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| x.summary == "Child Item 2nd position")
-                        .unwrap()
-                        .id
-                        .clone()
-                        .expect("In DB"),
-                ),
-            })
+        }
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let find_item = |summary: &str| {
+            surreal_tables
+                .surreal_items
+                .iter()
+                .find(|x| x.summary == summary)
+                .and_then(|x| x.id.clone())
+                .unwrap()
+        };
+        let has_the_events = find_item("Has the events");
+        let waiting = find_item("Waiting");
+        for summary in ["Keep", "Merge me", "Delete me"] {
+            let new_event = NewEvent {
+                summary: summary.into(),
+                triggered: false,
+                last_updated: Utc::now(),
+            };
+            DataLayerCommands::change(
+                &sender,
+                DataChange::AddItemDependencyNewEvent(has_the_events.clone(), new_event),
+            )
             .await
             .unwrap();
-
+        }
         let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        let find_event = |summary: &str| {
+            surreal_tables
+                .surreal_events
+                .iter()
+                .find(|x| x.summary == summary)
+                .and_then(|x| x.id.clone())
+                .unwrap()
+        };
+        let keep = find_event("Keep");
+        let merge_me = find_event("Merge me");
+        let delete_me = find_event("Delete me");
 
-        assert_eq!(
+        //The item only starts waiting on the events earlier in the same transaction so that is not in the database
+        //yet when the delete and merge work out what waits on the event
+        DataLayerCommands::transaction(
+            &sender,
             vec![
-                SurrealOrderedSubItem::SubItem {
-                    surreal_item_id: surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| x.summary == "Child Item at the top of the list")
-                        .unwrap()
-                        .id
-                        .as_ref()
-                        .unwrap()
-                        .clone()
-                },
-                SurrealOrderedSubItem::SubItem {
-                    surreal_item_id: surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| x.summary
-                            == "Child Item bottom position, then moved to above 2nd position")
-                        .unwrap()
-                        .id
-                        .as_ref()
-                        .unwrap()
-                        .clone()
-                },
-                SurrealOrderedSubItem::SubItem {
-                    surreal_item_id: surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| x.summary == "Child Item 2nd position")
-                        .unwrap()
-                        .id
-                        .as_ref()
-                        .unwrap()
-                        .clone()
-                },
-                SurrealOrderedSubItem::SubItem {
-                    surreal_item_id: surreal_tables
-                        .surreal_items
-                        .iter()
-                        .find(|x| x.summary == "Child Item 3rd position")
-                        .unwrap()
-                        .id
-                        .as_ref()
-                        .unwrap()
-                        .clone()
+                DataChange::AddItemDependency(
+                    waiting.clone(),
+                    SurrealDependency::AfterEvent(merge_me.clone()),
+                ),
+                DataChange::AddItemDependency(
+                    waiting.clone(),
+                    SurrealDependency::AfterEvent(delete_me.clone()),
+                ),
+                DataChange::MergeEvents {
+                    keep: keep.clone(),
+                    merge: merge_me,
                 },
+                DataChange::DeleteEvent(delete_me),
             ],
-            surreal_tables
-                .surreal_items
-                .iter()
-                .find(|x| x.summary == "Parent Item")
-                .unwrap()
-                .smaller_items_in_priority_order
+        )
+        .await
+        .unwrap();
+
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_events.len(), 1);
+        let waiting = surreal_tables
+            .surreal_items
+            .iter()
+            .find(|x| x.id.as_ref() == Some(&waiting))
+            .unwrap();
+        assert_eq!(
+            waiting.dependencies,
+            vec![SurrealDependency::AfterEvent(keep)]
         );
 
         drop(sender);
         data_storage_join_handle.await.unwrap();
     }
+
+    #[tokio::test]
+    async fn an_encrypted_database_is_saved_encrypted_and_opens_with_a_new_passphrase() {
+        let file = std::env::temp_dir().join(format!(
+            "on_purpose_encrypted_{}.encrypted",
+            std::process::id()
+        ));
+        let key = EncryptionKey::new("correct horse battery staple").unwrap();
+        let start = |key: EncryptionKey| {
+            let (sender, receiver) = mpsc::channel(1);
+            let settings = DataStorageSettings {
+                encryption: Some(EncryptionSettings {
+                    file: file.clone(),
+                    key,
+                }),
+                ..Default::default()
+            };
+            let join_handle = tokio::spawn(async move {
+                data_storage_start_and_run(receiver, "mem://", settings).await
+            });
+            (sender, join_handle)
+        };
+
+        let (sender, data_storage_join_handle) = start(key.clone());
+        DataLayerCommands::get_startup_result(&sender)
+            .await
+            .unwrap();
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(NewItem::new("Secret item".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let new_key = EncryptionKey::new("a new passphrase").unwrap();
+        DataLayerCommands::change_passphrase(&sender, new_key.clone())
+            .await
+            .unwrap();
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+
+        let encrypted = std::fs::read(&file).unwrap();
+        assert!(!encrypted.windows(b"Secret".len()).any(|x| x == b"Secret"));
+        assert!(key.decrypt(&encrypted).is_err());
+
+        let (sender, data_storage_join_handle) = start(new_key);
+        let surreal_tables = SurrealTables::new(&sender).await.unwrap();
+        assert_eq!(surreal_tables.surreal_items.len(), 1);
+        assert_eq!(surreal_tables.surreal_items[0].summary, "Secret item");
+        drop(sender);
+        data_storage_join_handle.await.unwrap();
+
+        std::fs::remove_file(file).unwrap();
+    }
 }
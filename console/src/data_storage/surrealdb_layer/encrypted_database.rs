@@ -0,0 +1,101 @@
+use std::{
+    error::Error,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    mem,
+    path::PathBuf,
+};
+
+use futures::StreamExt;
+use surrealdb::{Surreal, engine::any::Any};
+
+use crate::data_storage::encryption::EncryptionKey;
+
+/// Where the encrypted database is kept and the key it is encrypted with
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub(crate) struct EncryptionSettings {
+    pub(crate) file: PathBuf,
+    pub(crate) key: EncryptionKey,
+}
+
+/// SurrealDB has no encryption of its own so when the database is encrypted SurrealDB runs in memory and all of it is
+/// exported, encrypted, and written to one file after every change
+pub(crate) struct EncryptedDatabase {
+    settings: EncryptionSettings,
+    /// So the file is only written when something changed
+    saved_hash: Option<u64>,
+}
+
+impl EncryptedDatabase {
+    /// Loads what is in the file, if there is one yet, into `db` which should be an empty in memory database
+    pub(crate) async fn open(
+        settings: EncryptionSettings,
+        db: &Surreal<Any>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut saved_hash = None;
+        if settings.file.exists() {
+            let encrypted = fs::read(&settings.file)?;
+            let export = settings.key.decrypt(&encrypted)?;
+            saved_hash = Some(hash_of(&export));
+            db.query(String::from_utf8(export)?).await?.check()?;
+        }
+        Ok(EncryptedDatabase {
+            settings,
+            saved_hash,
+        })
+    }
+
+    pub(crate) fn key(&self) -> &EncryptionKey {
+        &self.settings.key
+    }
+
+    pub(crate) async fn save_if_changed(
+        &mut self,
+        db: &Surreal<Any>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let export = export_to_memory(db).await?;
+        let hash = hash_of(&export);
+        if self.saved_hash == Some(hash) {
+            return Ok(());
+        }
+        let encrypted = self.settings.key.encrypt(&export)?;
+        //Written to a temporary file first so the database is never left half written if the program is closed part way through
+        let temporary_path = self.settings.file.with_extension("partial");
+        fs::write(&temporary_path, encrypted)?;
+        fs::rename(&temporary_path, &self.settings.file)?;
+        self.saved_hash = Some(hash);
+        Ok(())
+    }
+
+    /// The file is written again straight away so the old passphrase stops working
+    pub(crate) async fn change_key(
+        &mut self,
+        key: EncryptionKey,
+        db: &Surreal<Any>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let old_key = mem::replace(&mut self.settings.key, key);
+        self.saved_hash = None;
+        let saved = self.save_if_changed(db).await;
+        if saved.is_err() {
+            //The file is still encrypted with the old passphrase
+            self.settings.key = old_key;
+        }
+        saved
+    }
+}
+
+/// SurrealDB's own export of every table as SurrealQL statements, which is what is encrypted
+pub(crate) async fn export_to_memory(db: &Surreal<Any>) -> Result<Vec<u8>, surrealdb::Error> {
+    let mut chunks = db.export(()).await?;
+    let mut export = Vec::default();
+    while let Some(chunk) = chunks.next().await {
+        export.extend(chunk?);
+    }
+    Ok(export)
+}
+
+fn hash_of(export: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    export.hash(&mut hasher);
+    hasher.finish()
+}
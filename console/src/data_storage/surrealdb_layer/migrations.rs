@@ -0,0 +1,508 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use surrealdb::{
+    Surreal,
+    engine::any::{Any, connect},
+    sql::{Datetime, Thing},
+};
+
+use crate::data_storage::encryption::EncryptionKey;
+
+use super::{
+    data_layer_commands::{DATABASE, NAMESPACE, load_archive, load_from_surrealdb},
+    encrypted_database::export_to_memory,
+    surreal_archive::SurrealArchive,
+    surreal_item::{Responsibility, SurrealItem, SurrealItemOldVersion, SurrealItemType},
+    surreal_tables::SurrealTables,
+    surreal_time_spent::{SurrealTimeSpent, SurrealTimeSpentVersion0},
+    surreal_undo_journal::SurrealUndoEntry,
+};
+
+/// The migrations in the order that they must be run. The schema version of a database is how many of these have been
+/// run so new migrations must only ever be added to the end of this list.
+const MIGRATIONS: [MigrationStep; 3] = [
+    MigrationStep::ItemsWithoutVersion,
+    MigrationStep::ItemsVersion1ToVersion2,
+    MigrationStep::TimeSpentWithoutWhyInScope,
+];
+
+pub(crate) const CURRENT_SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// The start of the file name of the backup that is made before upgrading
+pub(crate) const BEFORE_MIGRATION_PREFIX: &str = "before_schema_version_";
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SurrealSchemaVersion {
+    pub(crate) id: Option<Thing>,
+    pub(crate) version: u32,
+    pub(crate) schema_version: u32,
+    pub(crate) last_migrated: Datetime,
+}
+
+impl SurrealSchemaVersion {
+    pub(crate) const TABLE_NAME: &'static str = "schema_version";
+    const RECORD_ID: &'static str = "schema_version";
+}
+
+/// Each step only changes the records that still need it so running a step a second time does nothing. This means
+/// a step that failed part way through can just be run again.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum MigrationStep {
+    /// Items from before the version field was added stored the review frequency and last reviewed together in item_review
+    ItemsWithoutVersion,
+    /// Motivations became reactive rather than proactive
+    ItemsVersion1ToVersion2,
+    /// Time spent entries from before why_in_scope was recorded
+    TimeSpentWithoutWhyInScope,
+}
+
+impl MigrationStep {
+    fn table_name(&self) -> &'static str {
+        match self {
+            MigrationStep::ItemsWithoutVersion | MigrationStep::ItemsVersion1ToVersion2 => {
+                SurrealItem::TABLE_NAME
+            }
+            MigrationStep::TimeSpentWithoutWhyInScope => SurrealTimeSpent::TABLE_NAME,
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            MigrationStep::ItemsWithoutVersion => {
+                "Split item_review into last_reviewed and review_frequency"
+            }
+            MigrationStep::ItemsVersion1ToVersion2 => "Make motivations reactive",
+            MigrationStep::TimeSpentWithoutWhyInScope => "Record why_in_scope for time spent",
+        }
+    }
+
+    /// Returns how many records were changed
+    async fn run(&self, db: &Surreal<Any>) -> Result<usize, MigrationStepError> {
+        match self {
+            MigrationStep::ItemsWithoutVersion => {
+                let old_items: Vec<SurrealItemOldVersion> =
+                    select_where(db, self.table_name(), "version = NONE").await?;
+                let count = old_items.len();
+                for item_old_version in old_items.into_iter() {
+                    let item: SurrealItem = item_old_version.into();
+                    save(db, item.id.clone(), item).await?;
+                }
+                Ok(count)
+            }
+            MigrationStep::ItemsVersion1ToVersion2 => {
+                let items: Vec<SurrealItem> =
+                    select_where(db, self.table_name(), "version = 1").await?;
+                let count = items.len();
+                for mut item in items.into_iter() {
+                    if matches!(item.item_type, SurrealItemType::Motivation(_)) {
+                        item.responsibility = Responsibility::ReactiveBeAvailableToAct;
+                    }
+                    item.version = 2;
+                    save(db, item.id.clone(), item).await?;
+                }
+                Ok(count)
+            }
+            MigrationStep::TimeSpentWithoutWhyInScope => {
+                let old_time_spent: Vec<SurrealTimeSpentVersion0> =
+                    select_where(db, self.table_name(), "why_in_scope = NONE").await?;
+                let count = old_time_spent.len();
+                for time_spent_old in old_time_spent.into_iter() {
+                    let time_spent: SurrealTimeSpent = time_spent_old.into();
+                    save(db, time_spent.id.clone(), time_spent).await?;
+                }
+                Ok(count)
+            }
+        }
+    }
+}
+
+async fn select_where<T: DeserializeOwned>(
+    db: &Surreal<Any>,
+    table_name: &'static str,
+    condition: &'static str,
+) -> Result<Vec<T>, MigrationStepError> {
+    let mut response = db
+        .query(format!("SELECT * FROM {} WHERE {}", table_name, condition))
+        .await?;
+    Ok(response.take(0)?)
+}
+
+async fn save<T>(db: &Surreal<Any>, id: Option<Thing>, record: T) -> Result<(), MigrationStepError>
+where
+    T: Serialize + DeserializeOwned + PartialEq + Clone + 'static,
+{
+    let id = id.ok_or(MigrationStepError::RecordWithoutId)?;
+    let saved: Option<T> = db.update(id.clone()).content(record.clone()).await?;
+    match saved {
+        Some(saved) if saved == record => Ok(()),
+        _ => Err(MigrationStepError::NotSaved(id)),
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum MigrationStepError {
+    Surreal(surrealdb::Error),
+    RecordWithoutId,
+    /// The record read back after saving did not match what was saved
+    NotSaved(Thing),
+}
+
+impl Display for MigrationStepError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationStepError::Surreal(err) => write!(f, "{}", err),
+            MigrationStepError::RecordWithoutId => write!(f, "A record was found without an id"),
+            MigrationStepError::NotSaved(id) => {
+                write!(f, "The record {} did not save correctly", id)
+            }
+        }
+    }
+}
+
+impl From<surrealdb::Error> for MigrationStepError {
+    fn from(err: surrealdb::Error) -> Self {
+        MigrationStepError::Surreal(err)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum MigrationError {
+    /// The encrypted database could not be read or decrypted so there was nothing to upgrade
+    OpenEncrypted(Box<dyn Error + Send + Sync>),
+    /// The database was last used by a newer version of On Purpose
+    NewerSchemaVersion {
+        found: u32,
+        supported: u32,
+    },
+    ReadSchemaVersion(surrealdb::Error),
+    Backup {
+        path: PathBuf,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    StepFailed {
+        to_schema_version: u32,
+        table_name: &'static str,
+        description: &'static str,
+        source: MigrationStepError,
+        backup: Option<PathBuf>,
+    },
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::OpenEncrypted(err) => {
+                write!(f, "Unable to open the encrypted database: {}", err)
+            }
+            MigrationError::NewerSchemaVersion { found, supported } => write!(
+                f,
+                "The database is schema version {} but this version of On Purpose only understands up to version {}. Please upgrade On Purpose.",
+                found, supported
+            ),
+            MigrationError::ReadSchemaVersion(err) => {
+                write!(
+                    f,
+                    "Unable to read the schema version of the database: {}",
+                    err
+                )
+            }
+            MigrationError::Backup { path, source } => write!(
+                f,
+                "Unable to make a backup at {} before upgrading the database, nothing was changed: {}",
+                path.display(),
+                source
+            ),
+            MigrationError::StepFailed {
+                to_schema_version,
+                table_name,
+                description,
+                source,
+                backup,
+            } => {
+                write!(
+                    f,
+                    "Upgrading the database to schema version {} failed on the {} table ({}): {}",
+                    to_schema_version, table_name, description, source
+                )?;
+                match backup {
+                    Some(backup) => write!(
+                        f,
+                        "\nA backup from before the upgrade was started is at {}",
+                        backup.display()
+                    ),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl Error for MigrationError {}
+
+/// Brings the database up to CURRENT_SCHEMA_VERSION, a backup is made first if there is data to migrate and a backup directory is given.
+/// If there is an encryption key then the backup is encrypted with it.
+pub(crate) async fn migrate_if_needed(
+    db: &Surreal<Any>,
+    backup_directory: Option<&Path>,
+    encryption: Option<&EncryptionKey>,
+) -> Result<(), MigrationError> {
+    let stored: Option<SurrealSchemaVersion> = db
+        .select((
+            SurrealSchemaVersion::TABLE_NAME,
+            SurrealSchemaVersion::RECORD_ID,
+        ))
+        .await
+        .map_err(MigrationError::ReadSchemaVersion)?;
+    let schema_version = stored.map_or(0, |x| x.schema_version);
+    if schema_version > CURRENT_SCHEMA_VERSION {
+        return Err(MigrationError::NewerSchemaVersion {
+            found: schema_version,
+            supported: CURRENT_SCHEMA_VERSION,
+        });
+    }
+    if schema_version == CURRENT_SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    let has_data = has_data_to_migrate(db)
+        .await
+        .map_err(MigrationError::ReadSchemaVersion)?;
+    let backup = match backup_directory {
+        Some(backup_directory) if has_data => {
+            Some(backup_before_migration(db, backup_directory, schema_version, encryption).await?)
+        }
+        _ => None,
+    };
+
+    for (index, step) in MIGRATIONS.iter().enumerate().skip(schema_version as usize) {
+        let to_schema_version = index as u32 + 1;
+        let step_failed = |source| MigrationError::StepFailed {
+            to_schema_version,
+            table_name: step.table_name(),
+            description: step.description(),
+            source,
+            backup: backup.clone(),
+        };
+        let changed = step.run(db).await.map_err(step_failed)?;
+        if changed > 0 {
+            println!(
+                "Upgraded {} records in the {} table: {}",
+                changed,
+                step.table_name(),
+                step.description()
+            );
+        }
+        save_schema_version(db, to_schema_version)
+            .await
+            .map_err(step_failed)?;
+    }
+
+    if has_data {
+        //The undo journal holds copies of records from before the migration so it can no longer be safely applied
+        let _: Vec<SurrealUndoEntry> =
+            db.delete(SurrealUndoEntry::TABLE_NAME)
+                .await
+                .map_err(|err| MigrationError::StepFailed {
+                    to_schema_version: CURRENT_SCHEMA_VERSION,
+                    table_name: SurrealUndoEntry::TABLE_NAME,
+                    description: "Clear the undo journal",
+                    source: err.into(),
+                    backup: backup.clone(),
+                })?;
+    }
+
+    Ok(())
+}
+
+async fn has_data_to_migrate(db: &Surreal<Any>) -> Result<bool, surrealdb::Error> {
+    let mut response = db
+        .query(format!(
+            "SELECT VALUE id FROM {} LIMIT 1; SELECT VALUE id FROM {} LIMIT 1",
+            SurrealItem::TABLE_NAME,
+            SurrealTimeSpent::TABLE_NAME
+        ))
+        .await?;
+    let items: Vec<Thing> = response.take(0)?;
+    let time_spent: Vec<Thing> = response.take(1)?;
+    Ok(!items.is_empty() || !time_spent.is_empty())
+}
+
+async fn backup_before_migration(
+    db: &Surreal<Any>,
+    backup_directory: &Path,
+    schema_version: u32,
+    encryption: Option<&EncryptionKey>,
+) -> Result<PathBuf, MigrationError> {
+    //SurrealDB's own export is used rather than the JSON export because records that need to be migrated do not
+    //deserialize into the current types
+    let path = backup_directory.join(format!(
+        "{}{}_{}.{}",
+        BEFORE_MIGRATION_PREFIX,
+        schema_version + 1,
+        Utc::now().format("%Y-%m-%d_%H%M%S"),
+        if encryption.is_some() {
+            "encrypted"
+        } else {
+            "surql"
+        }
+    ));
+    let backup_failed = |source| MigrationError::Backup {
+        path: path.clone(),
+        source,
+    };
+    std::fs::create_dir_all(backup_directory).map_err(|err| backup_failed(err.into()))?;
+    match encryption {
+        Some(key) => {
+            let export = export_to_memory(db)
+                .await
+                .map_err(|err| backup_failed(err.into()))?;
+            let encrypted = key
+                .encrypt(&export)
+                .map_err(|err| backup_failed(err.into()))?;
+            std::fs::write(&path, encrypted).map_err(|err| backup_failed(err.into()))?;
+        }
+        None => db
+            .export(&path)
+            .await
+            .map_err(|err| backup_failed(err.into()))?,
+    }
+    println!(
+        "Upgrading the database, a backup was made first at {}",
+        path.display()
+    );
+    Ok(path)
+}
+
+/// Reads back a backup made by `backup_before_migration`. It is loaded into a database in memory and upgraded there
+/// first, for the same reason it was not written as the JSON export.
+pub(crate) async fn read_before_migration_backup(
+    export: String,
+) -> Result<(SurrealTables, SurrealArchive), Box<dyn Error>> {
+    let db = connect("mem://").await?;
+    db.use_ns(NAMESPACE).use_db(DATABASE).await?;
+    db.query(export).await?.check()?;
+    migrate_if_needed(&db, None, None).await?;
+    Ok((load_from_surrealdb(&db).await?, load_archive(&db).await?))
+}
+
+async fn save_schema_version(
+    db: &Surreal<Any>,
+    schema_version: u32,
+) -> Result<(), MigrationStepError> {
+    let record = SurrealSchemaVersion {
+        id: Some(
+            (
+                SurrealSchemaVersion::TABLE_NAME,
+                SurrealSchemaVersion::RECORD_ID,
+            )
+                .into(),
+        ),
+        version: 0,
+        schema_version,
+        last_migrated: Utc::now().into(),
+    };
+    let saved: Option<SurrealSchemaVersion> = db
+        .upsert((
+            SurrealSchemaVersion::TABLE_NAME,
+            SurrealSchemaVersion::RECORD_ID,
+        ))
+        .content(record.clone())
+        .await?;
+    match saved {
+        Some(saved) if saved == record => Ok(()),
+        _ => Err(MigrationStepError::NotSaved(record.id.expect("Set above"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use surrealdb::engine::any::connect;
+
+    use crate::data_storage::surrealdb_layer::surreal_item::{
+        Responsibility, SurrealItem, SurrealItemOldVersion, SurrealItemOldVersionBuilder,
+        SurrealItemType, SurrealMotivationKind,
+    };
+
+    use super::{CURRENT_SCHEMA_VERSION, MigrationError, SurrealSchemaVersion, migrate_if_needed};
+
+    #[tokio::test]
+    async fn old_items_are_migrated_and_running_again_changes_nothing() {
+        let db = connect("mem://").await.unwrap();
+        db.use_ns("OnPurpose").use_db("Russ").await.unwrap();
+
+        let old_item = SurrealItemOldVersionBuilder::default()
+            .id(Some((SurrealItem::TABLE_NAME, "1").into()))
+            .summary("Old motivation")
+            .item_type(SurrealItemType::Motivation(SurrealMotivationKind::CoreWork))
+            .review_guidance(None)
+            .build()
+            .unwrap();
+        let _: Option<SurrealItemOldVersion> = db
+            .create((SurrealItem::TABLE_NAME, "1"))
+            .content(old_item)
+            .await
+            .unwrap();
+
+        migrate_if_needed(&db, None, None).await.unwrap();
+
+        let items: Vec<SurrealItem> = db.select(SurrealItem::TABLE_NAME).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].version, 2);
+        assert_eq!(
+            items[0].responsibility,
+            Responsibility::ReactiveBeAvailableToAct
+        );
+        let schema_version: Option<SurrealSchemaVersion> = db
+            .select((
+                SurrealSchemaVersion::TABLE_NAME,
+                SurrealSchemaVersion::RECORD_ID,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            schema_version.unwrap().schema_version,
+            CURRENT_SCHEMA_VERSION
+        );
+
+        //Pretend the schema version was never saved, all steps run again but nothing should change
+        let _: Option<SurrealSchemaVersion> = db
+            .delete((
+                SurrealSchemaVersion::TABLE_NAME,
+                SurrealSchemaVersion::RECORD_ID,
+            ))
+            .await
+            .unwrap();
+        migrate_if_needed(&db, None, None).await.unwrap();
+        let items_after_second_run: Vec<SurrealItem> =
+            db.select(SurrealItem::TABLE_NAME).await.unwrap();
+        assert_eq!(items, items_after_second_run);
+    }
+
+    #[tokio::test]
+    async fn a_newer_schema_version_is_an_error_not_a_panic() {
+        let db = connect("mem://").await.unwrap();
+        db.use_ns("OnPurpose").use_db("Russ").await.unwrap();
+        migrate_if_needed(&db, None, None).await.unwrap();
+        let _: Option<SurrealSchemaVersion> = db
+            .query("UPDATE type::thing($table, $id) SET schema_version = 9999")
+            .bind(("table", SurrealSchemaVersion::TABLE_NAME))
+            .bind(("id", SurrealSchemaVersion::RECORD_ID))
+            .await
+            .unwrap()
+            .take(0)
+            .unwrap();
+
+        let result = migrate_if_needed(&db, None, None).await;
+
+        assert!(matches!(
+            result,
+            Err(MigrationError::NewerSchemaVersion { found: 9999, .. })
+        ));
+    }
+}
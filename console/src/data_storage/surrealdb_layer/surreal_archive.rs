@@ -0,0 +1,380 @@
+use ahash::HashSet;
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Datetime, Thing};
+use tokio::sync::mpsc::Sender;
+
+use crate::data_storage::integrity::{Reference, references};
+
+use super::{
+    data_layer_commands::{DataLayerCommands, DataLayerError},
+    surreal_item::SurrealItem,
+    surreal_tables::{SurrealTables, same_records},
+    surreal_time_spent::SurrealTimeSpent,
+    surreal_undo_journal::SurrealRecord,
+};
+
+/// An item that was finished long enough ago that it is kept out of the tables that are loaded on every refresh. The
+/// item is stored exactly as it was, including its original id, so it can be put back as is.
+///
+/// The migrations only upgrade the item and time spent tables, a migration that changes `SurrealItem` or
+/// `SurrealTimeSpent` needs to upgrade the records in the archive tables as well.
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SurrealArchivedItem {
+    pub(crate) id: Option<Thing>,
+    pub(crate) archived: Datetime,
+    pub(crate) item: SurrealItem,
+}
+
+impl SurrealArchivedItem {
+    pub(crate) const TABLE_NAME: &'static str = "archived_item";
+
+    pub(crate) fn new(item: SurrealItem, archived: Datetime) -> Self {
+        SurrealArchivedItem {
+            id: Some(archive_id(
+                Self::TABLE_NAME,
+                item.id.as_ref().expect("In DB"),
+            )),
+            archived,
+            item,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SurrealArchivedTimeSpent {
+    pub(crate) id: Option<Thing>,
+    pub(crate) archived: Datetime,
+    pub(crate) time_spent: SurrealTimeSpent,
+}
+
+impl SurrealArchivedTimeSpent {
+    pub(crate) const TABLE_NAME: &'static str = "archived_time_spent_log";
+
+    pub(crate) fn new(time_spent: SurrealTimeSpent, archived: Datetime) -> Self {
+        SurrealArchivedTimeSpent {
+            id: Some(archive_id(
+                Self::TABLE_NAME,
+                time_spent.id.as_ref().expect("In DB"),
+            )),
+            archived,
+            time_spent,
+        }
+    }
+}
+
+/// The archived record keeps the key of the record it came from so it is easy to find again
+pub(crate) fn archive_id(table_name: &str, original: &Thing) -> Thing {
+    Thing {
+        tb: table_name.to_string(),
+        id: original.id.clone(),
+    }
+}
+
+/// The archive tables, these are only read when they are asked for, for example by reflection or search
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug, Default)]
+pub(crate) struct SurrealArchive {
+    #[serde(rename = "archived_item")]
+    pub(crate) items: Vec<SurrealArchivedItem>,
+
+    #[serde(rename = "archived_time_spent_log")]
+    pub(crate) time_spent_log: Vec<SurrealArchivedTimeSpent>,
+}
+
+impl SurrealArchive {
+    /// Unlike the tables this is read from the database every time
+    pub(crate) async fn new(sender: &Sender<DataLayerCommands>) -> Result<Self, DataLayerError> {
+        DataLayerCommands::get_archive(sender).await
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.time_spent_log.is_empty()
+    }
+
+    /// Compares the archived records while ignoring the order they are listed in, like `SurrealTables::is_same_data`
+    pub(crate) fn is_same_data(&self, other: &SurrealArchive) -> bool {
+        same_records(&self.items, &other.items, |x| &x.id)
+            && same_records(&self.time_spent_log, &other.time_spent_log, |x| &x.id)
+    }
+
+    /// The ids the archived records had before they were archived
+    pub(crate) fn archived_ids(&self) -> HashSet<Thing> {
+        let items = self.items.iter().map(|x| &x.item.id);
+        let time_spent_log = self.time_spent_log.iter().map(|x| &x.time_spent.id);
+        items
+            .chain(time_spent_log)
+            .map(|x| x.clone().expect("In DB"))
+            .collect()
+    }
+
+    /// For when everything is needed, for example reflecting on what was done before the items were archived
+    pub(crate) fn add_to(&self, surreal_tables: &mut SurrealTables) {
+        surreal_tables
+            .surreal_items
+            .extend(self.items.iter().map(|x| x.item.clone()));
+        surreal_tables
+            .surreal_time_spent_log
+            .extend(self.time_spent_log.iter().map(|x| x.time_spent.clone()));
+    }
+}
+
+/// The items that were finished before `finished_before`, and the time spent on only those items, that can be archived
+/// without anything that is left behind needing them. Something that stays behind can still list an archived item as
+/// a smaller item because finished children are already skipped over, but nothing else that stays may refer to an
+/// archived item. An item also stays if any of its smaller items stay, so an archived item never hides live work.
+pub(crate) fn to_archive(
+    surreal_tables: &SurrealTables,
+    finished_before: &Datetime,
+) -> (Vec<SurrealItem>, Vec<SurrealTimeSpent>) {
+    let mut archiving: HashSet<&Thing> = surreal_tables
+        .surreal_items
+        .iter()
+        .filter(|x| x.finished.as_ref().is_some_and(|x| x < finished_before))
+        .map(|x| x.id.as_ref().expect("In DB"))
+        .collect();
+
+    //Keeping one item can mean another item has to be kept as well, so this is repeated until nothing else is kept
+    loop {
+        let time_spent_archiving = time_spent_to_archive(surreal_tables, &archiving)
+            .map(|x| x.id.as_ref().expect("In DB"))
+            .collect::<HashSet<_>>();
+        let mut keep: HashSet<Thing> = HashSet::default();
+        for record in surreal_tables.all_records() {
+            let id = record.id().expect("In DB");
+            let archived = archiving.contains(id) || time_spent_archiving.contains(id);
+            for (reference, target) in references(&record) {
+                if !archiving.contains(target) {
+                    if archived && reference == Reference::SmallerItem {
+                        keep.insert(id.clone());
+                    }
+                    continue;
+                }
+                if !archived && reference != Reference::SmallerItem {
+                    keep.insert(target.clone());
+                }
+            }
+        }
+        if keep.is_empty() {
+            break;
+        }
+        archiving.retain(|x| !keep.contains(*x));
+    }
+
+    let items = surreal_tables
+        .surreal_items
+        .iter()
+        .filter(|x| archiving.contains(x.id.as_ref().expect("In DB")))
+        .cloned()
+        .collect();
+    let time_spent_log = time_spent_to_archive(surreal_tables, &archiving)
+        .cloned()
+        .collect();
+    (items, time_spent_log)
+}
+
+/// Time spent is archived once everything it was spent on is archived
+fn time_spent_to_archive<'a>(
+    surreal_tables: &'a SurrealTables,
+    archiving: &HashSet<&Thing>,
+) -> impl Iterator<Item = &'a SurrealTimeSpent> {
+    surreal_tables
+        .surreal_time_spent_log
+        .iter()
+        .filter(move |x| {
+            !x.working_on.is_empty()
+                && x.working_on
+                    .iter()
+                    .all(|x| archiving.contains(x.get_record_id()))
+        })
+}
+
+/// The archived item and the archived items it needs to be able to work, that is what it waits on, and the time spent
+/// that is only on items that will be back in the tables once these are. Smaller items are not brought back because
+/// they are finished and are skipped over anyway.
+pub(crate) fn to_unarchive(
+    archive: &SurrealArchive,
+    item: &Thing,
+    surreal_tables: &SurrealTables,
+) -> (Vec<SurrealArchivedItem>, Vec<SurrealArchivedTimeSpent>) {
+    let mut unarchiving: HashSet<&Thing> = HashSet::default();
+    let mut to_visit = vec![item];
+    while let Some(visiting) = to_visit.pop() {
+        let Some(archived) = archive
+            .items
+            .iter()
+            .find(|x| x.item.id.as_ref() == Some(visiting))
+        else {
+            continue;
+        };
+        if !unarchiving.insert(visiting) {
+            continue;
+        }
+        for (reference, target) in references(&SurrealRecord::Item(archived.item.clone())) {
+            if reference != Reference::SmallerItem {
+                let target = archive
+                    .items
+                    .iter()
+                    .find_map(|x| x.item.id.as_ref().filter(|x| *x == target));
+                to_visit.extend(target);
+            }
+        }
+    }
+
+    let live = surreal_tables
+        .surreal_items
+        .iter()
+        .map(|x| x.id.as_ref().expect("In DB"))
+        .collect::<HashSet<_>>();
+    let items = archive
+        .items
+        .iter()
+        .filter(|x| unarchiving.contains(x.item.id.as_ref().expect("In DB")))
+        .cloned()
+        .collect();
+    let time_spent_log = archive
+        .time_spent_log
+        .iter()
+        .filter(|x| {
+            let working_on = &x.time_spent.working_on;
+            working_on
+                .iter()
+                .any(|x| unarchiving.contains(x.get_record_id()))
+                && working_on.iter().all(|x| {
+                    unarchiving.contains(x.get_record_id()) || live.contains(x.get_record_id())
+                })
+        })
+        .cloned()
+        .collect();
+    (items, time_spent_log)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use surrealdb::sql::{Datetime, Thing};
+
+    use crate::data_storage::surrealdb_layer::{
+        surreal_in_the_moment_priority::SurrealAction,
+        surreal_item::{SurrealDependency, SurrealItem, SurrealItemBuilder, SurrealOrderedSubItem},
+        surreal_tables::SurrealTablesBuilder,
+        surreal_time_spent::SurrealTimeSpent,
+    };
+
+    use super::{
+        SurrealArchive, SurrealArchivedItem, SurrealArchivedTimeSpent, to_archive, to_unarchive,
+    };
+
+    fn id(id: &str) -> Thing {
+        (SurrealItem::TABLE_NAME, id).into()
+    }
+
+    fn item(key: &str, finished: Option<Datetime>) -> SurrealItemBuilder {
+        let mut builder = SurrealItemBuilder::default();
+        builder.id(Some(id(key))).summary(key).finished(finished);
+        builder
+    }
+
+    fn time_spent(key: &str, working_on: &[&str]) -> SurrealTimeSpent {
+        SurrealTimeSpent {
+            id: Some(("time_spent_log", key).into()),
+            version: 1,
+            working_on: working_on
+                .iter()
+                .map(|x| SurrealAction::MakeProgress(id(x)))
+                .collect(),
+            why_in_scope: vec![],
+            urgency: None,
+            when_started: Utc::now().into(),
+            when_stopped: Utc::now().into(),
+            dedication: None,
+        }
+    }
+
+    fn summaries(items: &[SurrealItem]) -> Vec<&str> {
+        let mut summaries = items.iter().map(|x| x.summary.as_str()).collect::<Vec<_>>();
+        summaries.sort();
+        summaries
+    }
+
+    #[test]
+    fn only_items_finished_long_enough_ago_that_nothing_needs_are_archived() {
+        let long_ago: Datetime = (Utc::now() - Duration::days(365)).into();
+        let recently: Datetime = Utc::now().into();
+        let finished_before: Datetime = (Utc::now() - Duration::days(90)).into();
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                item("parent", None)
+                    .smaller_items_in_priority_order(vec![SurrealOrderedSubItem::SubItem {
+                        surreal_item_id: id("old child"),
+                    }])
+                    .build()
+                    .unwrap(),
+                item("old child", Some(long_ago.clone())).build().unwrap(),
+                item("recent", Some(recently)).build().unwrap(),
+                item("not finished", None).build().unwrap(),
+                item("waited on", Some(long_ago.clone())).build().unwrap(),
+                item("waits", None)
+                    .dependencies(vec![SurrealDependency::AfterItem(id("waited on"))])
+                    .build()
+                    .unwrap(),
+                item("old parent of live child", Some(long_ago.clone()))
+                    .smaller_items_in_priority_order(vec![SurrealOrderedSubItem::SubItem {
+                        surreal_item_id: id("not finished"),
+                    }])
+                    .build()
+                    .unwrap(),
+                item("shares time", Some(long_ago)).build().unwrap(),
+            ])
+            .surreal_time_spent_log(vec![
+                time_spent("only old", &["old child"]),
+                time_spent("shared", &["shares time", "not finished"]),
+            ])
+            .build()
+            .unwrap();
+
+        let (items, time_spent_log) = to_archive(&surreal_tables, &finished_before);
+
+        assert_eq!(summaries(&items), vec!["old child"]);
+        assert_eq!(time_spent_log, vec![time_spent("only old", &["old child"])]);
+    }
+
+    #[test]
+    fn unarchive_brings_back_what_the_item_waits_on() {
+        let long_ago: Datetime = (Utc::now() - Duration::days(365)).into();
+        let archive = SurrealArchive {
+            items: vec![
+                item("waits", Some(long_ago.clone()))
+                    .dependencies(vec![SurrealDependency::AfterItem(id("waited on"))])
+                    .build()
+                    .unwrap(),
+                item("waited on", Some(long_ago.clone())).build().unwrap(),
+                item("unrelated", Some(long_ago.clone())).build().unwrap(),
+            ]
+            .into_iter()
+            .map(|x| SurrealArchivedItem::new(x, long_ago.clone()))
+            .collect(),
+            time_spent_log: vec![
+                time_spent("waits", &["waits"]),
+                time_spent("with unrelated", &["waits", "unrelated"]),
+            ]
+            .into_iter()
+            .map(|x| SurrealArchivedTimeSpent::new(x, long_ago.clone()))
+            .collect(),
+        };
+
+        let (items, time_spent_log) = to_unarchive(
+            &archive,
+            &id("waits"),
+            &SurrealTablesBuilder::default().build().unwrap(),
+        );
+
+        let items = items.into_iter().map(|x| x.item).collect::<Vec<_>>();
+        assert_eq!(summaries(&items), vec!["waited on", "waits"]);
+        assert_eq!(
+            time_spent_log
+                .into_iter()
+                .map(|x| x.time_spent)
+                .collect::<Vec<_>>(),
+            vec![time_spent("waits", &["waits"])]
+        );
+    }
+}
@@ -7,6 +7,13 @@ pub(crate) struct SurrealCurrentMode {
     pub(crate) version: u32,
     pub(crate) urgency_in_scope: Vec<SurrealSelectedSingleMode>,
     pub(crate) importance_in_scope: Vec<SurrealSelectedSingleMode>,
+    /// The user defined modes that are selected, see `CurrentMode`. Empty means the do now list is not filtered by user
+    /// defined modes, which is also how a current mode from before these existed is read.
+    #[serde(default)]
+    pub(crate) modes: Vec<Thing>,
+    /// Only items with one of these tags are shown, empty means the do now list is not filtered by tag
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
 }
 
 impl SurrealCurrentMode {
@@ -22,6 +29,8 @@ pub(crate) enum SurrealSelectedSingleMode {
 pub(crate) struct NewCurrentMode {
     urgency_in_scope: Vec<SurrealSelectedSingleMode>,
     importance_in_scope: Vec<SurrealSelectedSingleMode>,
+    modes: Vec<Thing>,
+    tags: Vec<String>,
 }
 
 impl From<NewCurrentMode> for SurrealCurrentMode {
@@ -31,6 +40,8 @@ impl From<NewCurrentMode> for SurrealCurrentMode {
             version: 0,
             urgency_in_scope: new_current_mode.urgency_in_scope,
             importance_in_scope: new_current_mode.importance_in_scope,
+            modes: new_current_mode.modes,
+            tags: new_current_mode.tags,
         }
     }
 }
@@ -39,10 +50,14 @@ impl NewCurrentMode {
     pub(crate) fn new(
         urgency_in_scope: Vec<SurrealSelectedSingleMode>,
         importance_in_scope: Vec<SurrealSelectedSingleMode>,
+        modes: Vec<Thing>,
+        tags: Vec<String>,
     ) -> Self {
         NewCurrentMode {
             urgency_in_scope,
             importance_in_scope,
+            modes,
+            tags,
         }
     }
 }
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use surrealdb::sql::{Datetime, Thing};
+use surrealdb::sql::{Datetime, Duration, Thing};
 
 use crate::new_event::NewEvent;
 
@@ -10,6 +10,11 @@ pub(crate) struct SurrealEvent {
     pub(crate) last_updated: Datetime,
     pub(crate) triggered: bool,
     pub(crate) summary: String,
+    /// For an event that keeps happening, like "the bin is full". Once it has been triggered this long it counts as
+    /// untriggered again so the items waiting on it wait for the next time. Events from before this was added do not
+    /// have it so it is None for them.
+    #[serde(default)]
+    pub(crate) untrigger_after: Option<Duration>,
 }
 
 impl From<NewEvent> for SurrealEvent {
@@ -20,6 +25,7 @@ impl From<NewEvent> for SurrealEvent {
             last_updated: new_event.last_updated.into(),
             triggered: new_event.triggered,
             summary: new_event.summary,
+            untrigger_after: None,
         }
     }
 }
@@ -0,0 +1,559 @@
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Datetime, Duration, Thing};
+
+use super::{
+    surreal_event::SurrealEvent,
+    surreal_item::{SurrealDependency, SurrealItem, SurrealOrderedSubItem},
+    surreal_tables::SurrealTables,
+    surreal_undo_journal::{SurrealRecord, SurrealRecordChange},
+};
+
+/// One field of an item or event changing. Entries are recorded for every change that is saved, including undo, redo
+/// and changes merged in from other devices, and they are kept forever so an item's whole history can be shown. The
+/// values are stored already described for showing to the user because the records they mention might not exist
+/// anymore by the time the history is looked at.
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SurrealHistoryEntry {
+    pub(crate) id: Option<Thing>,
+    pub(crate) version: u32,
+    /// The item or event that changed
+    pub(crate) record: Thing,
+    pub(crate) when: Datetime,
+    pub(crate) field: String,
+    /// None means there was no value, for example before the item was created
+    pub(crate) old_value: Option<String>,
+    /// None means there is no value anymore, for example after the item was deleted
+    pub(crate) new_value: Option<String>,
+}
+
+impl SurrealHistoryEntry {
+    pub(crate) const TABLE_NAME: &'static str = "history";
+
+    /// Being created or deleted is recorded as a change to this field
+    pub(crate) const EXISTS: &'static str = "exists";
+
+    /// Being added to or removed from a parent is recorded on the smaller item as a change to this field, the value is
+    /// the parent's summary
+    pub(crate) const PARENT: &'static str = "parent";
+
+    fn new(
+        record: &Thing,
+        when: &Datetime,
+        field: &str,
+        old_value: Option<String>,
+        new_value: Option<String>,
+    ) -> Self {
+        SurrealHistoryEntry {
+            id: None,
+            version: 0,
+            record: record.clone(),
+            when: when.clone(),
+            field: field.to_string(),
+            old_value,
+            new_value,
+        }
+    }
+}
+
+/// The history entries for `changes` which were just saved, `surreal_tables` is what was saved and is used to describe
+/// the records that a value refers to
+pub(crate) fn history_entries(
+    changes: &[SurrealRecordChange],
+    surreal_tables: &SurrealTables,
+    when: &Datetime,
+) -> Vec<SurrealHistoryEntry> {
+    let mut entries = Vec::default();
+    for change in changes.iter() {
+        match (&change.before, &change.after) {
+            (Some(SurrealRecord::Item(_)), _) | (_, Some(SurrealRecord::Item(_))) => {
+                let before = as_item(&change.before);
+                let after = as_item(&change.after);
+                push_item_entries(
+                    &mut entries,
+                    &change.id,
+                    before,
+                    after,
+                    surreal_tables,
+                    when,
+                );
+            }
+            (Some(SurrealRecord::Event(_)), _) | (_, Some(SurrealRecord::Event(_))) => {
+                let before = as_event(&change.before);
+                let after = as_event(&change.after);
+                push_event_entries(&mut entries, &change.id, before, after, when);
+            }
+            //Time spent is its own history and the rest of the records are not about one item
+            _ => {}
+        }
+    }
+    entries
+}
+
+fn as_item(record: &Option<SurrealRecord>) -> Option<&SurrealItem> {
+    match record {
+        Some(SurrealRecord::Item(item)) => Some(item),
+        _ => None,
+    }
+}
+
+fn as_event(record: &Option<SurrealRecord>) -> Option<&SurrealEvent> {
+    match record {
+        Some(SurrealRecord::Event(event)) => Some(event),
+        _ => None,
+    }
+}
+
+fn push_item_entries(
+    entries: &mut Vec<SurrealHistoryEntry>,
+    id: &Thing,
+    before: Option<&SurrealItem>,
+    after: Option<&SurrealItem>,
+    surreal_tables: &SurrealTables,
+    when: &Datetime,
+) {
+    match (before, after) {
+        (Some(before), Some(after)) => {
+            for (field, old_value, new_value) in item_field_changes(before, after, surreal_tables) {
+                entries.push(SurrealHistoryEntry::new(
+                    id,
+                    when,
+                    field,
+                    Some(old_value),
+                    Some(new_value),
+                ));
+            }
+        }
+        (before, after) => entries.push(SurrealHistoryEntry::new(
+            id,
+            when,
+            SurrealHistoryEntry::EXISTS,
+            before.map(|x| x.summary.clone()),
+            after.map(|x| x.summary.clone()),
+        )),
+    }
+
+    //Being re-parented is a change to the parent so it is also recorded on the smaller item where it is looked for
+    let parent_summary = after.or(before).map(|x| x.summary.clone());
+    let smaller_before = smaller_item_ids(before);
+    let smaller_after = smaller_item_ids(after);
+    for removed in smaller_before
+        .iter()
+        .filter(|x| !smaller_after.contains(*x))
+    {
+        entries.push(SurrealHistoryEntry::new(
+            removed,
+            when,
+            SurrealHistoryEntry::PARENT,
+            parent_summary.clone(),
+            None,
+        ));
+    }
+    for added in smaller_after
+        .iter()
+        .filter(|x| !smaller_before.contains(*x))
+    {
+        entries.push(SurrealHistoryEntry::new(
+            added,
+            when,
+            SurrealHistoryEntry::PARENT,
+            None,
+            parent_summary.clone(),
+        ));
+    }
+}
+
+fn smaller_item_ids(item: Option<&SurrealItem>) -> Vec<&Thing> {
+    item.iter()
+        .flat_map(|x| x.smaller_items_in_priority_order.iter())
+        .map(|x| match x {
+            SurrealOrderedSubItem::SubItem { surreal_item_id } => surreal_item_id,
+        })
+        .collect()
+}
+
+/// Every field is listed, without `..`, so a new field can't be added without deciding whether it has history
+fn item_field_changes(
+    before: &SurrealItem,
+    after: &SurrealItem,
+    surreal_tables: &SurrealTables,
+) -> Vec<(&'static str, String, String)> {
+    let SurrealItem {
+        id: _,
+        summary,
+        //The version only changes when the database is upgraded which is not something the user did
+        version: _,
+        finished,
+        responsibility,
+        item_type,
+        notes_location,
+        lap,
+        dependencies,
+        last_reviewed,
+        review_frequency,
+        review_guidance,
+        smaller_items_in_priority_order,
+        created: _,
+        urgency_plan,
+        modes,
+        notes,
+        links,
+        tags,
+        recurrence,
+        completions,
+        deadline,
+        estimate,
+    } = after;
+    let mut changes = Vec::default();
+    let mut push = |field: &'static str, old_value: String, new_value: String| {
+        if old_value != new_value {
+            changes.push((field, old_value, new_value));
+        }
+    };
+    push("summary", before.summary.clone(), summary.clone());
+    push(
+        "finished",
+        describe_datetime(&before.finished, "Not finished"),
+        describe_datetime(finished, "Not finished"),
+    );
+    push(
+        "responsibility",
+        format!("{:?}", before.responsibility),
+        format!("{:?}", responsibility),
+    );
+    push(
+        "item type",
+        format!("{:?}", before.item_type),
+        format!("{:?}", item_type),
+    );
+    push(
+        "notes location",
+        format!("{:?}", before.notes_location),
+        format!("{:?}", notes_location),
+    );
+    push("lap", format!("{:?}", before.lap), format!("{:?}", lap));
+    push(
+        "waiting on",
+        describe_dependencies(&before.dependencies, surreal_tables),
+        describe_dependencies(dependencies, surreal_tables),
+    );
+    push(
+        "last reviewed",
+        describe_datetime(&before.last_reviewed, "Never"),
+        describe_datetime(last_reviewed, "Never"),
+    );
+    push(
+        "review frequency",
+        format!("{:?}", before.review_frequency),
+        format!("{:?}", review_frequency),
+    );
+    push(
+        "review guidance",
+        format!("{:?}", before.review_guidance),
+        format!("{:?}", review_guidance),
+    );
+    push(
+        "smaller items",
+        describe_smaller_items(&before.smaller_items_in_priority_order, surreal_tables),
+        describe_smaller_items(smaller_items_in_priority_order, surreal_tables),
+    );
+    push(
+        "urgency plan",
+        format!("{:?}", before.urgency_plan),
+        format!("{:?}", urgency_plan),
+    );
+    push(
+        "modes",
+        describe_modes(&before.modes, surreal_tables),
+        describe_modes(modes, surreal_tables),
+    );
+    push("notes", before.notes.clone(), notes.clone());
+    push(
+        "links",
+        format!("{:?}", before.links),
+        format!("{:?}", links),
+    );
+    push("tags", before.tags.join(", "), tags.join(", "));
+    push(
+        "repeats",
+        format!("{:?}", before.recurrence),
+        format!("{:?}", recurrence),
+    );
+    //Finishing a recurring item adds to its completions rather than setting finished
+    push(
+        "times finished",
+        before.completions.len().to_string(),
+        completions.len().to_string(),
+    );
+    push(
+        "deadline",
+        describe_datetime(
+            &before.deadline.as_ref().map(|x| x.due.clone()),
+            "No deadline",
+        ),
+        describe_datetime(&deadline.as_ref().map(|x| x.due.clone()), "No deadline"),
+    );
+    push(
+        "deadline lead times",
+        format!("{:?}", before.deadline.as_ref().map(|x| &x.lead_times)),
+        format!("{:?}", deadline.as_ref().map(|x| &x.lead_times)),
+    );
+    push(
+        "effort estimate",
+        format!("{:?}", before.estimate),
+        format!("{:?}", estimate),
+    );
+    changes
+}
+
+fn push_event_entries(
+    entries: &mut Vec<SurrealHistoryEntry>,
+    id: &Thing,
+    before: Option<&SurrealEvent>,
+    after: Option<&SurrealEvent>,
+    when: &Datetime,
+) {
+    match (before, after) {
+        (Some(before), Some(after)) => {
+            if before.summary != after.summary {
+                entries.push(SurrealHistoryEntry::new(
+                    id,
+                    when,
+                    "summary",
+                    Some(before.summary.clone()),
+                    Some(after.summary.clone()),
+                ));
+            }
+            if before.triggered != after.triggered {
+                entries.push(SurrealHistoryEntry::new(
+                    id,
+                    when,
+                    "triggered",
+                    Some(before.triggered.to_string()),
+                    Some(after.triggered.to_string()),
+                ));
+            }
+            if before.untrigger_after != after.untrigger_after {
+                let describe =
+                    |x: Option<Duration>| x.map_or("never".to_string(), |x| x.to_string());
+                entries.push(SurrealHistoryEntry::new(
+                    id,
+                    when,
+                    "untrigger after",
+                    Some(describe(before.untrigger_after)),
+                    Some(describe(after.untrigger_after)),
+                ));
+            }
+        }
+        (before, after) => entries.push(SurrealHistoryEntry::new(
+            id,
+            when,
+            SurrealHistoryEntry::EXISTS,
+            before.map(|x| x.summary.clone()),
+            after.map(|x| x.summary.clone()),
+        )),
+    }
+}
+
+fn describe_datetime(datetime: &Option<Datetime>, none: &str) -> String {
+    match datetime {
+        Some(datetime) => {
+            let datetime: DateTime<Utc> = datetime.clone().into();
+            let datetime: DateTime<Local> = datetime.into();
+            datetime.format("%a %d %b %Y %I:%M%p").to_string()
+        }
+        None => none.to_string(),
+    }
+}
+
+/// The summary of the item or event, or the id if it does not exist anymore
+fn name(id: &Thing, surreal_tables: &SurrealTables) -> String {
+    surreal_tables
+        .surreal_items
+        .iter()
+        .find(|x| x.id.as_ref() == Some(id))
+        .map(|x| format!("\"{}\"", x.summary))
+        .or_else(|| {
+            surreal_tables
+                .surreal_events
+                .iter()
+                .find(|x| x.id.as_ref() == Some(id))
+                .map(|x| format!("\"{}\"", x.summary))
+        })
+        .unwrap_or_else(|| id.to_string())
+}
+
+fn describe_modes(modes: &[Thing], surreal_tables: &SurrealTables) -> String {
+    if modes.is_empty() {
+        return "None".to_string();
+    }
+    modes
+        .iter()
+        .map(|id| {
+            surreal_tables
+                .surreal_modes
+                .iter()
+                .find(|x| x.id.as_ref() == Some(id))
+                .map(|x| format!("\"{}\"", x.name))
+                .unwrap_or_else(|| id.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn describe_dependencies(
+    dependencies: &[SurrealDependency],
+    surreal_tables: &SurrealTables,
+) -> String {
+    if dependencies.is_empty() {
+        return "Nothing".to_string();
+    }
+    dependencies
+        .iter()
+        .map(|x| match x {
+            SurrealDependency::AfterDateTime(after) => {
+                format!("After {}", describe_datetime(&Some(after.clone()), ""))
+            }
+            SurrealDependency::AfterItem(id) | SurrealDependency::DuringItem(id) => {
+                format!("After {}", name(id, surreal_tables))
+            }
+            SurrealDependency::AfterEvent(id) => {
+                format!("After event {}", name(id, surreal_tables))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn describe_smaller_items(
+    smaller_items: &[SurrealOrderedSubItem],
+    surreal_tables: &SurrealTables,
+) -> String {
+    if smaller_items.is_empty() {
+        return "None".to_string();
+    }
+    smaller_items
+        .iter()
+        .map(|x| match x {
+            SurrealOrderedSubItem::SubItem { surreal_item_id } => {
+                name(surreal_item_id, surreal_tables)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use surrealdb::sql::{Datetime, Thing};
+
+    use crate::data_storage::surrealdb_layer::{
+        surreal_item::{SurrealItem, SurrealItemBuilder, SurrealOrderedSubItem},
+        surreal_tables::SurrealTablesBuilder,
+        surreal_undo_journal::{SurrealRecord, SurrealRecordChange},
+    };
+
+    use super::{SurrealHistoryEntry, history_entries};
+
+    fn id(id: &str) -> Thing {
+        (SurrealItem::TABLE_NAME, id).into()
+    }
+
+    fn item(key: &str) -> SurrealItem {
+        SurrealItemBuilder::default()
+            .id(Some(id(key)))
+            .summary(key)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn each_changed_field_is_recorded_and_reparenting_is_recorded_on_the_child() {
+        let before = item("parent");
+        let mut after = before.clone();
+        after.summary = "renamed".to_string();
+        after.smaller_items_in_priority_order = vec![SurrealOrderedSubItem::SubItem {
+            surreal_item_id: id("child"),
+        }];
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(vec![after.clone(), item("child")])
+            .build()
+            .unwrap();
+        let when: Datetime = Utc::now().into();
+
+        let entries = history_entries(
+            &[SurrealRecordChange {
+                id: id("parent"),
+                before: Some(SurrealRecord::Item(before)),
+                after: Some(SurrealRecord::Item(after)),
+            }],
+            &surreal_tables,
+            &when,
+        );
+
+        let described = entries
+            .iter()
+            .map(|x| {
+                (
+                    x.record.clone(),
+                    x.field.as_str(),
+                    x.old_value.as_deref(),
+                    x.new_value.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            described,
+            vec![
+                (id("parent"), "summary", Some("parent"), Some("renamed")),
+                (
+                    id("parent"),
+                    "smaller items",
+                    Some("None"),
+                    Some("\"child\"")
+                ),
+                (
+                    id("child"),
+                    SurrealHistoryEntry::PARENT,
+                    None,
+                    Some("renamed")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn creating_and_deleting_an_item_is_recorded() {
+        let surreal_tables = SurrealTablesBuilder::default().build().unwrap();
+        let when: Datetime = Utc::now().into();
+
+        let entries = history_entries(
+            &[
+                SurrealRecordChange {
+                    id: id("new"),
+                    before: None,
+                    after: Some(SurrealRecord::Item(item("new"))),
+                },
+                SurrealRecordChange {
+                    id: id("old"),
+                    before: Some(SurrealRecord::Item(item("old"))),
+                    after: None,
+                },
+            ],
+            &surreal_tables,
+            &when,
+        );
+
+        assert_eq!(entries.len(), 2);
+        assert!(
+            entries
+                .iter()
+                .all(|x| x.field == SurrealHistoryEntry::EXISTS)
+        );
+        assert_eq!(entries[0].old_value, None);
+        assert_eq!(entries[0].new_value, Some("new".to_string()));
+        assert_eq!(entries[1].old_value, Some("old".to_string()));
+        assert_eq!(entries[1].new_value, None);
+    }
+}
@@ -43,6 +43,17 @@ impl SurrealAction {
             | SurrealAction::MakeProgress(record_id) => record_id,
         }
     }
+
+    pub(crate) fn get_record_id_mut(&mut self) -> &mut RecordId {
+        match self {
+            SurrealAction::SetReadyAndUrgency(record_id)
+            | SurrealAction::ParentBackToAMotivation(record_id)
+            | SurrealAction::ReviewItem(record_id)
+            | SurrealAction::ItemNeedsAClassification(record_id)
+            | SurrealAction::PickItemReviewFrequency(record_id)
+            | SurrealAction::MakeProgress(record_id) => record_id,
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
@@ -69,6 +69,49 @@ pub(crate) struct SurrealItem {
 
     #[cfg_attr(test, builder(default))]
     pub(crate) urgency_plan: Option<SurrealUrgencyPlan>,
+
+    /// The user defined modes that this item, and so also its smaller items, is done in. Items from before modes could
+    /// be picked do not have this so it is empty for them.
+    #[serde(default)]
+    #[cfg_attr(test, builder(default))]
+    pub(crate) modes: Vec<Thing>,
+
+    /// Markdown written by the user about the item
+    #[serde(default)]
+    #[cfg_attr(test, builder(default))]
+    pub(crate) notes: String,
+
+    /// Where more about the item can be found. An item from before these existed may still have its one link in
+    /// `notes_location`, see `Item::get_links`.
+    #[serde(default)]
+    #[cfg_attr(test, builder(default))]
+    pub(crate) links: Vec<SurrealLink>,
+
+    /// Free-form, smaller items have the tags of their larger items as well, see `ItemNode::get_tags`
+    #[serde(default)]
+    #[cfg_attr(test, builder(default))]
+    pub(crate) tags: Vec<String>,
+
+    /// A recurring item is never finished, finishing it waits for the next time it is due instead. See `finish_item`.
+    #[serde(default)]
+    #[cfg_attr(test, builder(default))]
+    pub(crate) recurrence: Option<SurrealRecurrence>,
+
+    /// Each time a recurring item was finished, oldest first
+    #[serde(default)]
+    #[cfg_attr(test, builder(default))]
+    pub(crate) completions: Vec<SurrealCompletion>,
+
+    /// When there is no `urgency_plan` the urgency escalates as the deadline gets close, see `ItemNode::new`
+    #[serde(default)]
+    #[cfg_attr(test, builder(default))]
+    pub(crate) deadline: Option<SurrealDeadline>,
+
+    /// How long the item itself is expected to take, not counting its smaller items as they have their own estimates.
+    /// See `ItemNode::get_estimate_rolled_up`.
+    #[serde(default)]
+    #[cfg_attr(test, builder(default))]
+    pub(crate) estimate: Option<Duration>,
 }
 
 impl From<SurrealItem> for Option<Thing> {
@@ -124,6 +167,14 @@ impl SurrealItem {
             last_reviewed,
             review_frequency: new_item.review_frequency,
             review_guidance: new_item.review_guidance,
+            modes: Vec::default(),
+            notes: String::default(),
+            links: Vec::default(),
+            tags: Vec::default(),
+            recurrence: None,
+            completions: Vec::default(),
+            deadline: None,
+            estimate: None,
         })
     }
 
@@ -144,17 +195,6 @@ pub(crate) enum SurrealItemType {
     PersonOrGroup,
 }
 
-#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug, Default)]
-pub(crate) enum SurrealItemTypeOld {
-    #[default]
-    Undeclared,
-    Action,
-    Goal(SurrealHowMuchIsInMyControl),
-    IdeaOrThought,
-    Motivation,
-    PersonOrGroup,
-}
-
 #[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug, Default)]
 pub(crate) enum SurrealMotivationKind {
     #[default]
@@ -164,17 +204,6 @@ pub(crate) enum SurrealMotivationKind {
     DoesNotFitInCoreOrNonCore,
 }
 
-#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug, Default)]
-pub(crate) enum ItemTypeOld {
-    #[default]
-    Undeclared,
-    Action,
-    Goal(SurrealHowMuchIsInMyControl),
-    IdeaOrThought,
-    Motivation,
-    PersonOrGroup,
-}
-
 #[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug, Default)]
 pub(crate) enum SurrealHowMuchIsInMyControl {
     #[default]
@@ -184,14 +213,6 @@ pub(crate) enum SurrealHowMuchIsInMyControl {
     LargelyOutOfMyControl,
 }
 
-#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug, Default)]
-pub(crate) enum GoalType {
-    #[default]
-    NotSpecified,
-    AspirationalHope,
-    TangibleMilestone,
-}
-
 #[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug, Default)]
 pub(crate) enum Responsibility {
     #[default]
@@ -209,6 +230,67 @@ pub(crate) enum Permanence {
     NotSet,
 }
 
+/// When a recurring item is due again after it is finished, see `systems::maintenance::next_due`
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) enum SurrealRecurrence {
+    /// Counted from when it was last due, not when it was finished. Times that were missed are skipped.
+    EveryDays(u32),
+    EveryWeeks(u32),
+    OnWeekdays(Vec<SurrealWeekday>),
+    /// On the last day of the month for months that are too short
+    DayOfMonth(u32),
+    DaysAfterFinished(u32),
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Copy, Debug)]
+pub(crate) enum SurrealWeekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SurrealCompletion {
+    pub(crate) finished: Datetime,
+    /// Finished before the time after it was due came around, this is what keeps a streak going
+    pub(crate) on_time: bool,
+    /// The `AfterDateTime` dependency this finish added to wait until it is due again, so the next finish replaces
+    /// only that one and leaves any the user added. None if it was finished before this was kept.
+    #[serde(default)]
+    pub(crate) due_again: Option<Datetime>,
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SurrealDeadline {
+    pub(crate) due: Datetime,
+    #[serde(default)]
+    pub(crate) lead_times: SurrealLeadTimes,
+}
+
+/// How long before the deadline each step up in urgency happens. Before the first of these it is by importance.
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SurrealLeadTimes {
+    pub(crate) maybe_urgent: Duration,
+    pub(crate) definitely_urgent: Duration,
+    pub(crate) more_urgent_than_mode: Duration,
+}
+
+impl Default for SurrealLeadTimes {
+    fn default() -> Self {
+        const HOUR: u64 = 60 * 60;
+        const DAY: u64 = 24 * HOUR;
+        SurrealLeadTimes {
+            maybe_urgent: std::time::Duration::from_secs(7 * DAY).into(),
+            definitely_urgent: std::time::Duration::from_secs(2 * DAY).into(),
+            more_urgent_than_mode: std::time::Duration::from_secs(4 * HOUR).into(),
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
 pub(crate) enum SurrealDependency {
     AfterDateTime(Datetime),
@@ -247,14 +329,6 @@ pub(crate) enum SurrealFrequency {
     Yearly,
 }
 
-#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
-pub(crate) enum EnterListReasonOldVersion {
-    DateTime(Datetime),
-    HighestUncovered {
-        earliest: Datetime,
-        review_after: Datetime,
-    },
-}
 //This is a newtype pattern for f32 that implements PartialEq and Eq
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub(crate) struct EqF32(f32);
@@ -393,84 +467,6 @@ impl Mul<&EqF32> for TimeDelta {
     }
 }
 
-#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
-pub(crate) enum InRelationToRatioType {
-    AmountOfTimeSpent { multiplier: EqF32 },
-    IterationCount { multiplier: EqF32 },
-}
-
-#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug, Default)]
-pub(crate) enum SurrealStaging {
-    #[default]
-    NotSet,
-    MentallyResident {
-        enter_list: EnterListReasonOldVersion,
-        lap: SurrealLap,
-    },
-    OnDeck {
-        enter_list: EnterListReasonOldVersion,
-        lap: SurrealLap,
-    },
-    Planned,
-    ThinkingAbout,
-    Released,
-    InRelationTo {
-        start: Datetime,
-        other_item: RecordId,
-        ratio: InRelationToRatioType,
-    },
-}
-
-impl PartialOrd for SurrealStaging {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl Ord for SurrealStaging {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        match self {
-            SurrealStaging::NotSet => match other {
-                SurrealStaging::NotSet => Ordering::Equal,
-                _ => Ordering::Less,
-            },
-            SurrealStaging::MentallyResident { .. } => match other {
-                SurrealStaging::NotSet => Ordering::Greater,
-                SurrealStaging::MentallyResident { .. } => Ordering::Equal,
-                _ => Ordering::Less,
-            },
-            SurrealStaging::InRelationTo { .. } => match other {
-                SurrealStaging::NotSet | SurrealStaging::MentallyResident { .. } => {
-                    Ordering::Greater
-                }
-                SurrealStaging::InRelationTo { .. } => Ordering::Equal,
-                _ => Ordering::Less,
-            },
-            SurrealStaging::OnDeck { .. } => match other {
-                SurrealStaging::NotSet
-                | SurrealStaging::MentallyResident { .. }
-                | SurrealStaging::InRelationTo { .. } => Ordering::Greater,
-                SurrealStaging::OnDeck { .. } => Ordering::Equal,
-                _ => Ordering::Less,
-            },
-            SurrealStaging::Planned => match other {
-                SurrealStaging::Released | SurrealStaging::ThinkingAbout => Ordering::Less,
-                SurrealStaging::Planned => Ordering::Equal,
-                _ => Ordering::Greater,
-            },
-            SurrealStaging::ThinkingAbout => match other {
-                SurrealStaging::Released => Ordering::Less,
-                SurrealStaging::ThinkingAbout => Ordering::Equal,
-                _ => Ordering::Greater,
-            },
-            SurrealStaging::Released => match other {
-                SurrealStaging::Released => Ordering::Equal,
-                _ => Ordering::Greater,
-            },
-        }
-    }
-}
-
 #[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
 pub(crate) enum SurrealLap {
     AlwaysTimer(Duration),
@@ -490,6 +486,7 @@ pub(crate) enum SurrealOrderedSubItem {
     //This could be expanded to state multiple items that are at the same priority meaning you would go with lap count or something else to determine which to work on first.
 }
 
+/// The one link an item could have before `SurrealItem::links`, it is moved into `links` the first time they are changed
 #[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug, Default)]
 pub(crate) enum NotesLocation {
     #[default]
@@ -498,6 +495,36 @@ pub(crate) enum NotesLocation {
     WebLink(String),
 }
 
+impl NotesLocation {
+    pub(crate) fn as_link(&self) -> Option<SurrealLink> {
+        match self {
+            NotesLocation::None => None,
+            NotesLocation::OneNoteLink(target) => Some(SurrealLink {
+                kind: SurrealLinkKind::OneNote,
+                target: target.clone(),
+            }),
+            NotesLocation::WebLink(target) => Some(SurrealLink {
+                kind: SurrealLinkKind::Web,
+                target: target.clone(),
+            }),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SurrealLink {
+    pub(crate) kind: SurrealLinkKind,
+    /// The address, path, or OneNote link to open
+    pub(crate) target: String,
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Copy, Debug)]
+pub(crate) enum SurrealLinkKind {
+    Web,
+    FilePath,
+    OneNote,
+}
+
 #[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
 pub(crate) enum SurrealScheduled {
     Exact {
@@ -634,22 +661,6 @@ pub(crate) struct SurrealItemOldVersion {
     pub(crate) urgency_plan: Option<SurrealUrgencyPlan>,
 }
 
-#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug, Default)]
-pub(crate) enum SurrealScheduledOldVersion {
-    #[default]
-    NotScheduled,
-    ScheduledExact {
-        start: Datetime,
-        duration: Duration,
-        priority: SurrealScheduledPriority,
-    },
-    ScheduledRange {
-        start_range: (Datetime, Datetime),
-        duration: Duration,
-        priority: SurrealScheduledPriority,
-    },
-}
-
 impl From<SurrealItemOldVersion> for SurrealItem {
     fn from(value: SurrealItemOldVersion) -> Self {
         let (last_reviewed, review_frequency) = match value.item_review {
@@ -676,6 +687,14 @@ impl From<SurrealItemOldVersion> for SurrealItem {
             review_guidance: value.review_guidance,
             last_reviewed,
             review_frequency,
+            modes: Vec::default(),
+            notes: String::default(),
+            links: Vec::default(),
+            tags: Vec::default(),
+            recurrence: None,
+            completions: Vec::default(),
+            deadline: None,
+            estimate: None,
         }
     }
 }
@@ -9,6 +9,9 @@ pub(crate) struct SurrealMode {
     pub(crate) name: String,
     pub(crate) version: u32,
     pub(crate) parent: Option<Thing>,
+    /// Modes with the same parent are shown in this order and then by name, modes from before this was added are all 0
+    #[serde(default)]
+    pub(crate) display_order: u32,
 }
 
 impl From<NewMode> for SurrealMode {
@@ -18,6 +21,7 @@ impl From<NewMode> for SurrealMode {
             name: new_mode.name,
             version: 0,
             parent: new_mode.parent,
+            display_order: 0,
         }
     }
 }
@@ -1,7 +1,13 @@
+use std::{mem, sync::Arc};
+
 use ahash::HashMap;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use surrealdb::opt::RecordId;
-use tokio::sync::mpsc::Sender;
+use tokio::sync::{
+    broadcast::{self, error::TryRecvError},
+    mpsc::Sender,
+};
 
 #[cfg(test)]
 use derive_builder::Builder;
@@ -9,40 +15,217 @@ use derive_builder::Builder;
 use crate::base_data::{event::Event, item::Item, mode::Mode, time_spent::TimeSpent};
 
 use super::{
-    data_layer_commands::DataLayerCommands, surreal_current_mode::SurrealCurrentMode,
-    surreal_event::SurrealEvent, surreal_in_the_moment_priority::SurrealInTheMomentPriority,
-    surreal_item::SurrealItem, surreal_mode::SurrealMode, surreal_time_spent::SurrealTimeSpent,
+    data_layer_commands::{DataLayerCommands, DataLayerError},
+    surreal_current_mode::SurrealCurrentMode,
+    surreal_event::SurrealEvent,
+    surreal_in_the_moment_priority::SurrealInTheMomentPriority,
+    surreal_item::SurrealItem,
+    surreal_mode::SurrealMode,
+    surreal_time_spent::SurrealTimeSpent,
+    surreal_undo_journal::SurrealRecord,
 };
 
-#[derive(Clone, Debug)]
+/// The serde names match the table names so an export is easy to understand when viewed with other tools
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug, Default)]
 #[cfg_attr(test, derive(Builder), builder(setter(into)))]
 pub(crate) struct SurrealTables {
     #[cfg_attr(test, builder(default))]
+    #[serde(rename = "item")]
     pub(crate) surreal_items: Vec<SurrealItem>,
 
     #[cfg_attr(test, builder(default))]
+    #[serde(rename = "time_spent_log")]
     pub(crate) surreal_time_spent_log: Vec<SurrealTimeSpent>,
 
     #[cfg_attr(test, builder(default))]
+    #[serde(rename = "in_the_moment_priorities")]
     pub(crate) surreal_in_the_moment_priorities: Vec<SurrealInTheMomentPriority>,
 
     #[cfg_attr(test, builder(default))]
+    #[serde(rename = "current_modes")]
     pub(crate) surreal_current_modes: Vec<SurrealCurrentMode>,
 
     #[cfg_attr(test, builder(default))]
+    #[serde(rename = "modes")]
     pub(crate) surreal_modes: Vec<SurrealMode>,
 
     #[cfg_attr(test, builder(default))]
+    #[serde(rename = "events")]
     pub(crate) surreal_events: Vec<SurrealEvent>,
 }
 
 impl SurrealTables {
+    /// The data storage layer keeps the tables in memory so this is a cheap snapshot that is shared, not a copy
     pub(crate) async fn new(
         sender: &Sender<DataLayerCommands>,
-    ) -> Result<Self, tokio::sync::oneshot::error::RecvError> {
+    ) -> Result<Arc<Self>, DataLayerError> {
         DataLayerCommands::get_raw_data(sender).await
     }
 
+    pub(crate) fn is_empty(&self) -> bool {
+        self.surreal_items.is_empty()
+            && self.surreal_time_spent_log.is_empty()
+            && self.surreal_in_the_moment_priorities.is_empty()
+            && self.surreal_current_modes.is_empty()
+            && self.surreal_modes.is_empty()
+            && self.surreal_events.is_empty()
+    }
+
+    /// Compares the data while ignoring the order that records are listed in, the database does not promise to keep the order
+    pub(crate) fn is_same_data(&self, other: &SurrealTables) -> bool {
+        same_records(&self.surreal_items, &other.surreal_items, |x| &x.id)
+            && same_records(
+                &self.surreal_time_spent_log,
+                &other.surreal_time_spent_log,
+                |x| &x.id,
+            )
+            && same_records(
+                &self.surreal_in_the_moment_priorities,
+                &other.surreal_in_the_moment_priorities,
+                |x| &x.id,
+            )
+            && same_records(
+                &self.surreal_current_modes,
+                &other.surreal_current_modes,
+                |x| &x.id,
+            )
+            && same_records(&self.surreal_modes, &other.surreal_modes, |x| &x.id)
+            && same_records(&self.surreal_events, &other.surreal_events, |x| &x.id)
+    }
+
+    /// Every record in every table, copied
+    pub(crate) fn all_records(&self) -> impl Iterator<Item = SurrealRecord> + '_ {
+        let items = self.surreal_items.iter().cloned().map(SurrealRecord::Item);
+        let time_spent_log = self
+            .surreal_time_spent_log
+            .iter()
+            .cloned()
+            .map(SurrealRecord::TimeSpent);
+        let priorities = self
+            .surreal_in_the_moment_priorities
+            .iter()
+            .cloned()
+            .map(SurrealRecord::InTheMomentPriority);
+        let current_modes = self
+            .surreal_current_modes
+            .iter()
+            .cloned()
+            .map(SurrealRecord::CurrentMode);
+        let modes = self.surreal_modes.iter().cloned().map(SurrealRecord::Mode);
+        let events = self
+            .surreal_events
+            .iter()
+            .cloned()
+            .map(SurrealRecord::Event);
+        items
+            .chain(time_spent_log)
+            .chain(priorities)
+            .chain(current_modes)
+            .chain(modes)
+            .chain(events)
+    }
+
+    /// A copy of the record, None if it is not in the table its id says it belongs in
+    pub(crate) fn get(&self, id: &RecordId) -> Option<SurrealRecord> {
+        match id.tb.as_str() {
+            SurrealItem::TABLE_NAME => {
+                find_record(&self.surreal_items, id, |x| &x.id).map(SurrealRecord::Item)
+            }
+            SurrealTimeSpent::TABLE_NAME => {
+                find_record(&self.surreal_time_spent_log, id, |x| &x.id)
+                    .map(SurrealRecord::TimeSpent)
+            }
+            SurrealInTheMomentPriority::TABLE_NAME => {
+                find_record(&self.surreal_in_the_moment_priorities, id, |x| &x.id)
+                    .map(SurrealRecord::InTheMomentPriority)
+            }
+            SurrealCurrentMode::TABLE_NAME => {
+                find_record(&self.surreal_current_modes, id, |x| &x.id)
+                    .map(SurrealRecord::CurrentMode)
+            }
+            SurrealMode::TABLE_NAME => {
+                find_record(&self.surreal_modes, id, |x| &x.id).map(SurrealRecord::Mode)
+            }
+            SurrealEvent::TABLE_NAME => {
+                find_record(&self.surreal_events, id, |x| &x.id).map(SurrealRecord::Event)
+            }
+            //Not one of the tables that is kept in memory, for example the undo journal
+            _ => None,
+        }
+    }
+
+    /// Puts the record in the table it belongs in, replacing the record with the same id, or removes the record if
+    /// `state` is None. Gives back what the record was before.
+    pub(crate) fn apply(
+        &mut self,
+        id: &RecordId,
+        state: Option<SurrealRecord>,
+    ) -> Option<SurrealRecord> {
+        match (id.tb.as_str(), state) {
+            (_, Some(SurrealRecord::Item(item))) => {
+                replace_record(&mut self.surreal_items, id, Some(item), |x| &x.id)
+                    .map(SurrealRecord::Item)
+            }
+            (_, Some(SurrealRecord::TimeSpent(time_spent))) => replace_record(
+                &mut self.surreal_time_spent_log,
+                id,
+                Some(time_spent),
+                |x| &x.id,
+            )
+            .map(SurrealRecord::TimeSpent),
+            (_, Some(SurrealRecord::InTheMomentPriority(priority))) => replace_record(
+                &mut self.surreal_in_the_moment_priorities,
+                id,
+                Some(priority),
+                |x| &x.id,
+            )
+            .map(SurrealRecord::InTheMomentPriority),
+            (_, Some(SurrealRecord::CurrentMode(current_mode))) => replace_record(
+                &mut self.surreal_current_modes,
+                id,
+                Some(current_mode),
+                |x| &x.id,
+            )
+            .map(SurrealRecord::CurrentMode),
+            (_, Some(SurrealRecord::Mode(mode))) => {
+                replace_record(&mut self.surreal_modes, id, Some(mode), |x| &x.id)
+                    .map(SurrealRecord::Mode)
+            }
+            (_, Some(SurrealRecord::Event(event))) => {
+                replace_record(&mut self.surreal_events, id, Some(event), |x| &x.id)
+                    .map(SurrealRecord::Event)
+            }
+            (SurrealItem::TABLE_NAME, None) => {
+                replace_record(&mut self.surreal_items, id, None, |x| &x.id)
+                    .map(SurrealRecord::Item)
+            }
+            (SurrealTimeSpent::TABLE_NAME, None) => {
+                replace_record(&mut self.surreal_time_spent_log, id, None, |x| &x.id)
+                    .map(SurrealRecord::TimeSpent)
+            }
+            (SurrealInTheMomentPriority::TABLE_NAME, None) => {
+                replace_record(&mut self.surreal_in_the_moment_priorities, id, None, |x| {
+                    &x.id
+                })
+                .map(SurrealRecord::InTheMomentPriority)
+            }
+            (SurrealCurrentMode::TABLE_NAME, None) => {
+                replace_record(&mut self.surreal_current_modes, id, None, |x| &x.id)
+                    .map(SurrealRecord::CurrentMode)
+            }
+            (SurrealMode::TABLE_NAME, None) => {
+                replace_record(&mut self.surreal_modes, id, None, |x| &x.id)
+                    .map(SurrealRecord::Mode)
+            }
+            (SurrealEvent::TABLE_NAME, None) => {
+                replace_record(&mut self.surreal_events, id, None, |x| &x.id)
+                    .map(SurrealRecord::Event)
+            }
+            //Not one of the tables that is kept in memory, for example the undo journal
+            (_, None) => None,
+        }
+    }
+
     pub(crate) fn make_items<'a>(
         &'a self,
         now: &'a DateTime<Utc>,
@@ -53,10 +236,10 @@ impl SurrealTables {
             .collect()
     }
 
-    pub(crate) fn make_events(&self) -> HashMap<&RecordId, Event<'_>> {
+    pub(crate) fn make_events(&self, now: &DateTime<Utc>) -> HashMap<&RecordId, Event<'_>> {
         self.surreal_events
             .iter()
-            .map(|x| (x.id.as_ref().expect("In DB"), Event::new(x)))
+            .map(|x| (x.id.as_ref().expect("In DB"), Event::new(x, now)))
             .collect()
     }
 
@@ -76,3 +259,96 @@ impl SurrealTables {
         &self.surreal_current_modes
     }
 }
+
+/// Sent by the data storage layer after every change
+#[derive(Clone, Debug)]
+pub(crate) struct TablesChanged {
+    pub(crate) surreal_tables: Arc<SurrealTables>,
+    /// The records that were saved or deleted, None when it is not known which ones
+    pub(crate) changed: Option<Vec<RecordId>>,
+}
+
+/// The data storage layer sends the tables after every change, this holds on to the latest ones so they never need to
+/// be asked for
+pub(crate) struct LatestSurrealTables {
+    surreal_tables: Arc<SurrealTables>,
+    changed: broadcast::Receiver<TablesChanged>,
+    /// Every record changed since `take_changed` was last called, None when it is not known which ones
+    changed_records: Option<Vec<RecordId>>,
+}
+
+impl LatestSurrealTables {
+    pub(crate) async fn new(sender: &Sender<DataLayerCommands>) -> Result<Self, DataLayerError> {
+        //Subscribing first means a change made while the tables are being sent is not missed
+        let changed = DataLayerCommands::subscribe_to_changes(sender).await?;
+        let surreal_tables = SurrealTables::new(sender).await?;
+        Ok(LatestSurrealTables {
+            surreal_tables,
+            changed,
+            changed_records: None,
+        })
+    }
+
+    pub(crate) fn get(&mut self) -> Arc<SurrealTables> {
+        loop {
+            match self.changed.try_recv() {
+                Ok(tables_changed) => {
+                    self.surreal_tables = tables_changed.surreal_tables;
+                    match (&mut self.changed_records, tables_changed.changed) {
+                        (Some(changed_records), Some(changed)) => changed_records.extend(changed),
+                        (changed_records, _) => *changed_records = None,
+                    }
+                }
+                //Skipped over some older changes, the newer ones are still there to be received but what the
+                //skipped ones changed is not known
+                Err(TryRecvError::Lagged(_)) => self.changed_records = None,
+                Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => break,
+            }
+        }
+        self.surreal_tables.clone()
+    }
+
+    /// The records changed in the tables handed out by `get` since this was last called, None the first time or when
+    /// it is not known which ones
+    pub(crate) fn take_changed(&mut self) -> Option<Vec<RecordId>> {
+        self.changed_records.replace(Vec::default())
+    }
+}
+
+fn replace_record<T>(
+    records: &mut Vec<T>,
+    id: &RecordId,
+    state: Option<T>,
+    get_id: impl Fn(&T) -> &Option<RecordId>,
+) -> Option<T> {
+    let position = records.iter().position(|x| get_id(x).as_ref() == Some(id));
+    match (position, state) {
+        (Some(position), Some(state)) => Some(mem::replace(&mut records[position], state)),
+        (Some(position), None) => Some(records.remove(position)),
+        (None, Some(state)) => {
+            records.push(state);
+            None
+        }
+        (None, None) => None,
+    }
+}
+
+fn find_record<T: Clone>(
+    records: &[T],
+    id: &RecordId,
+    get_id: impl Fn(&T) -> &Option<RecordId>,
+) -> Option<T> {
+    records
+        .iter()
+        .find(|x| get_id(x).as_ref() == Some(id))
+        .cloned()
+}
+
+pub(crate) fn same_records<T: PartialEq>(
+    left: &[T],
+    right: &[T],
+    get_id: impl Fn(&T) -> &Option<RecordId>,
+) -> bool {
+    let right: HashMap<&Option<RecordId>, &T> = right.iter().map(|x| (get_id(x), x)).collect();
+    left.len() == right.len() && left.iter().all(|x| right.get(get_id(x)) == Some(&x))
+}
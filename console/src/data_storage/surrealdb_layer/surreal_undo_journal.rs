@@ -0,0 +1,222 @@
+use ahash::HashMap;
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Datetime, Thing};
+
+use super::{
+    surreal_current_mode::SurrealCurrentMode, surreal_event::SurrealEvent,
+    surreal_in_the_moment_priority::SurrealInTheMomentPriority, surreal_item::SurrealItem,
+    surreal_mode::SurrealMode, surreal_tables::SurrealTables, surreal_time_spent::SurrealTimeSpent,
+};
+
+/// One entry is recorded for each DataLayerCommands that changes data. It stores what every record that was
+/// touched looked like before and after so the change can be undone and redone, even after a restart.
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SurrealUndoEntry {
+    pub(crate) id: Option<Thing>,
+    pub(crate) version: u32,
+    /// Entries are undone from the highest sequence down and redone from the lowest undone sequence up
+    pub(crate) sequence: u64,
+    pub(crate) created: Datetime,
+    pub(crate) description: String,
+    pub(crate) undone: bool,
+    pub(crate) changes: Vec<SurrealRecordChange>,
+}
+
+impl SurrealUndoEntry {
+    pub(crate) const TABLE_NAME: &'static str = "undo_journal";
+
+    /// How many changes are kept, once there are more than this the oldest are removed
+    pub(crate) const MAX_ENTRIES: usize = 100;
+}
+
+/// `None` means the record did not exist, for example `before` is None when a record is created
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SurrealRecordChange {
+    pub(crate) id: Thing,
+    pub(crate) before: Option<SurrealRecord>,
+    pub(crate) after: Option<SurrealRecord>,
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) enum SurrealRecord {
+    Item(SurrealItem),
+    TimeSpent(SurrealTimeSpent),
+    InTheMomentPriority(SurrealInTheMomentPriority),
+    CurrentMode(SurrealCurrentMode),
+    Mode(SurrealMode),
+    Event(SurrealEvent),
+}
+
+impl SurrealRecord {
+    pub(crate) fn id(&self) -> Option<&Thing> {
+        match self {
+            SurrealRecord::Item(item) => item.id.as_ref(),
+            SurrealRecord::TimeSpent(time_spent) => time_spent.id.as_ref(),
+            SurrealRecord::InTheMomentPriority(priority) => priority.id.as_ref(),
+            SurrealRecord::CurrentMode(current_mode) => current_mode.id.as_ref(),
+            SurrealRecord::Mode(mode) => mode.id.as_ref(),
+            SurrealRecord::Event(event) => event.id.as_ref(),
+        }
+    }
+}
+
+impl SurrealRecordChange {
+    /// Every record that is different between `before` and `after`, records that did not change are not included
+    pub(crate) fn changes_between(before: &SurrealTables, after: &SurrealTables) -> Vec<Self> {
+        let mut changes = Vec::default();
+        push_changes(
+            &mut changes,
+            &before.surreal_items,
+            &after.surreal_items,
+            |x| &x.id,
+            SurrealRecord::Item,
+        );
+        push_changes(
+            &mut changes,
+            &before.surreal_time_spent_log,
+            &after.surreal_time_spent_log,
+            |x| &x.id,
+            SurrealRecord::TimeSpent,
+        );
+        push_changes(
+            &mut changes,
+            &before.surreal_in_the_moment_priorities,
+            &after.surreal_in_the_moment_priorities,
+            |x| &x.id,
+            SurrealRecord::InTheMomentPriority,
+        );
+        push_changes(
+            &mut changes,
+            &before.surreal_current_modes,
+            &after.surreal_current_modes,
+            |x| &x.id,
+            SurrealRecord::CurrentMode,
+        );
+        push_changes(
+            &mut changes,
+            &before.surreal_modes,
+            &after.surreal_modes,
+            |x| &x.id,
+            SurrealRecord::Mode,
+        );
+        push_changes(
+            &mut changes,
+            &before.surreal_events,
+            &after.surreal_events,
+            |x| &x.id,
+            SurrealRecord::Event,
+        );
+        changes
+    }
+}
+
+fn push_changes<T: PartialEq + Clone>(
+    changes: &mut Vec<SurrealRecordChange>,
+    before: &[T],
+    after: &[T],
+    get_id: impl Fn(&T) -> &Option<Thing>,
+    to_record: impl Fn(T) -> SurrealRecord,
+) {
+    let after_by_id: HashMap<&Thing, &T> = after
+        .iter()
+        .map(|x| (get_id(x).as_ref().expect("In DB"), x))
+        .collect();
+    let before_by_id: HashMap<&Thing, &T> = before
+        .iter()
+        .map(|x| (get_id(x).as_ref().expect("In DB"), x))
+        .collect();
+
+    for (id, before) in before_by_id.iter() {
+        match after_by_id.get(id) {
+            Some(after) if after == before => {}
+            after => changes.push(SurrealRecordChange {
+                id: (*id).clone(),
+                before: Some(to_record((*before).clone())),
+                after: after.map(|x| to_record((*x).clone())),
+            }),
+        }
+    }
+    for (id, after) in after_by_id.iter() {
+        if !before_by_id.contains_key(id) {
+            changes.push(SurrealRecordChange {
+                id: (*id).clone(),
+                before: None,
+                after: Some(to_record((*after).clone())),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::data_storage::surrealdb_layer::{
+        surreal_item::SurrealItemBuilder, surreal_tables::SurrealTablesBuilder,
+    };
+
+    use super::{SurrealRecord, SurrealRecordChange};
+
+    #[test]
+    fn only_records_that_changed_are_included() {
+        let unchanged = SurrealItemBuilder::default()
+            .id(Some(("item", "1").into()))
+            .summary("Unchanged")
+            .build()
+            .unwrap();
+        let finished_before = SurrealItemBuilder::default()
+            .id(Some(("item", "2").into()))
+            .summary("Finished")
+            .build()
+            .unwrap();
+        let mut finished_after = finished_before.clone();
+        finished_after.finished = Some(Utc::now().into());
+        let removed = SurrealItemBuilder::default()
+            .id(Some(("item", "3").into()))
+            .summary("Removed")
+            .build()
+            .unwrap();
+        let created = SurrealItemBuilder::default()
+            .id(Some(("item", "4").into()))
+            .summary("Created")
+            .build()
+            .unwrap();
+
+        let before = SurrealTablesBuilder::default()
+            .surreal_items(vec![
+                unchanged.clone(),
+                finished_before.clone(),
+                removed.clone(),
+            ])
+            .build()
+            .unwrap();
+        let after = SurrealTablesBuilder::default()
+            .surreal_items(vec![unchanged, finished_after.clone(), created.clone()])
+            .build()
+            .unwrap();
+
+        let mut changes = SurrealRecordChange::changes_between(&before, &after);
+        changes.sort_by(|a, b| a.id.to_string().cmp(&b.id.to_string()));
+
+        assert_eq!(
+            changes,
+            vec![
+                SurrealRecordChange {
+                    id: ("item", "2").into(),
+                    before: Some(SurrealRecord::Item(finished_before)),
+                    after: Some(SurrealRecord::Item(finished_after)),
+                },
+                SurrealRecordChange {
+                    id: ("item", "3").into(),
+                    before: Some(SurrealRecord::Item(removed)),
+                    after: None,
+                },
+                SurrealRecordChange {
+                    id: ("item", "4").into(),
+                    before: None,
+                    after: Some(SurrealRecord::Item(created)),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,546 @@
+use std::{
+    error::Error,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use ahash::{HashMap, HashSet};
+use chrono::Utc;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use surrealdb::{Surreal, engine::any::Any, sql::Thing};
+
+use super::{
+    surreal_current_mode::SurrealCurrentMode,
+    surreal_event::SurrealEvent,
+    surreal_in_the_moment_priority::SurrealInTheMomentPriority,
+    surreal_item::SurrealItem,
+    surreal_mode::SurrealMode,
+    surreal_tables::SurrealTables,
+    surreal_time_spent::SurrealTimeSpent,
+    surreal_undo_journal::{SurrealRecord, SurrealRecordChange},
+};
+
+const LOG_EXTENSION: &str = "jsonl";
+
+/// Which device this database is, each device only ever appends to its own log so the logs can be copied around by
+/// OneDrive, Syncthing, and so on without two devices ever writing to the same file
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SurrealSyncDevice {
+    pub(crate) id: Option<Thing>,
+    pub(crate) device_id: String,
+}
+
+impl SurrealSyncDevice {
+    pub(crate) const TABLE_NAME: &'static str = "sync_device";
+    const RECORD_ID: &'static str = "this_device";
+}
+
+/// A hybrid logical clock, it follows the wall clock but never goes backwards and always moves past any time seen from
+/// another device so a change made after seeing another device's change is always ordered after it
+#[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub(crate) struct Hlc {
+    pub(crate) wall_ms: i64,
+    pub(crate) counter: u32,
+}
+
+impl Hlc {
+    fn tick(&mut self, now_ms: i64) -> Hlc {
+        if now_ms > self.wall_ms {
+            self.wall_ms = now_ms;
+            self.counter = 0;
+        } else {
+            self.counter += 1;
+        }
+        *self
+    }
+
+    fn observe(&mut self, seen: Hlc) {
+        if seen > *self {
+            *self = seen;
+        }
+    }
+}
+
+/// One line of a device's log. Changes are per field so two devices changing different fields of the same item both
+/// keep their change, when both change the same field the one with the later stamp wins.
+#[derive(PartialEq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SyncEntry {
+    pub(crate) hlc: Hlc,
+    pub(crate) device: String,
+    pub(crate) id: Thing,
+    pub(crate) change: SyncChange,
+}
+
+#[derive(PartialEq, Serialize, Deserialize, Clone, Debug)]
+pub(crate) enum SyncChange {
+    /// Only the fields that changed, every field when the record is created
+    Fields(Map<String, Value>),
+    Deleted,
+}
+
+impl SyncEntry {
+    /// Ties between devices are broken by the device id so every device picks the same winner
+    fn stamp(&self) -> (Hlc, &str) {
+        (self.hlc, &self.device)
+    }
+}
+
+pub(crate) struct SyncLog {
+    folder: PathBuf,
+    device_id: String,
+    clock: Hlc,
+    /// How long each of the other devices' logs was when it was last merged so nothing is done if nothing was added
+    merged_lengths: HashMap<PathBuf, u64>,
+}
+
+impl SyncLog {
+    /// The first time a database is synced every record is written to its log so the other devices get the data that
+    /// was there before syncing was turned on
+    pub(crate) async fn open(
+        folder: PathBuf,
+        db: &Surreal<Any>,
+        surreal_tables: &SurrealTables,
+    ) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(&folder)?;
+        let mut sync_log = SyncLog {
+            folder,
+            device_id: load_or_create_device_id(db).await?,
+            clock: Hlc::default(),
+            merged_lengths: HashMap::default(),
+        };
+        let own_entries = read_log(&sync_log.own_log_path())?;
+        //So a change made now is ordered after the changes made before even if the wall clock has gone backwards
+        for entry in own_entries.iter() {
+            sync_log.clock.observe(entry.hlc);
+        }
+        if own_entries.is_empty() {
+            let everything =
+                SurrealRecordChange::changes_between(&SurrealTables::default(), surreal_tables);
+            sync_log.append(&everything)?;
+        }
+        Ok(sync_log)
+    }
+
+    fn own_log_path(&self) -> PathBuf {
+        self.folder
+            .join(format!("{}.{}", self.device_id, LOG_EXTENSION))
+    }
+
+    /// Appends the changes made on this device, all of them get the same time stamp
+    pub(crate) fn append(&mut self, changes: &[SurrealRecordChange]) -> Result<(), Box<dyn Error>> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let hlc = self.clock.tick(Utc::now().timestamp_millis());
+        let mut lines = String::default();
+        for record_change in changes {
+            let change = match (&record_change.before, &record_change.after) {
+                (_, None) => SyncChange::Deleted,
+                (before, Some(after)) => {
+                    let before = before.as_ref().map(to_fields).transpose()?;
+                    let mut fields = to_fields(after)?;
+                    if let Some(before) = before {
+                        fields.retain(|name, value| before.get(name) != Some(value));
+                    }
+                    SyncChange::Fields(fields)
+                }
+            };
+            let entry = SyncEntry {
+                hlc,
+                device: self.device_id.clone(),
+                id: record_change.id.clone(),
+                change,
+            };
+            lines.push_str(&serde_json::to_string(&entry)?);
+            lines.push('\n');
+        }
+        //Written all at once so another device never sees only part of a change
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.own_log_path())?;
+        file.write_all(lines.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// True if another device added to its log since the last merge, this only looks at the length of the files so it
+    /// is cheap enough to check every time the list is refreshed
+    fn has_new_entries(&self) -> Result<bool, Box<dyn Error>> {
+        for (path, length) in self.other_logs()? {
+            if self.merged_lengths.get(&path) != Some(&length) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn other_logs(&self) -> Result<Vec<(PathBuf, u64)>, Box<dyn Error>> {
+        let own_log = self.own_log_path();
+        let mut logs = Vec::default();
+        for entry in fs::read_dir(&self.folder)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_some_and(|x| x == LOG_EXTENSION) && path != own_log {
+                logs.push((path, entry.metadata()?.len()));
+            }
+        }
+        Ok(logs)
+    }
+
+    /// Reads every device's log, including this one's, and gives back the changes needed to bring `current` up to date.
+    /// Because the result only depends on the logs every device ends up with the same data once it has the same logs.
+    /// The records in `archived` were archived on this device and are left alone.
+    pub(crate) fn merge(
+        &mut self,
+        current: &SurrealTables,
+        archived: &HashSet<Thing>,
+    ) -> Result<Vec<SurrealRecordChange>, Box<dyn Error>> {
+        if !self.has_new_entries()? {
+            return Ok(Vec::default());
+        }
+        let mut entries = read_log(&self.own_log_path())?;
+        for (path, length) in self.other_logs()? {
+            entries.extend(read_log(&path)?);
+            self.merged_lengths.insert(path, length);
+        }
+        for entry in entries.iter() {
+            self.clock.observe(entry.hlc);
+        }
+        entries.retain(|x| !archived.contains(&x.id));
+        Ok(changes_to_apply(&entries, current))
+    }
+}
+
+async fn load_or_create_device_id(db: &Surreal<Any>) -> Result<String, surrealdb::Error> {
+    let existing: Option<SurrealSyncDevice> = db
+        .select((SurrealSyncDevice::TABLE_NAME, SurrealSyncDevice::RECORD_ID))
+        .await?;
+    if let Some(existing) = existing {
+        return Ok(existing.device_id);
+    }
+    let device_id = format!("{:016x}", rand::rng().random::<u64>());
+    let _: Option<SurrealSyncDevice> = db
+        .upsert((SurrealSyncDevice::TABLE_NAME, SurrealSyncDevice::RECORD_ID))
+        .content(SurrealSyncDevice {
+            id: None,
+            device_id: device_id.clone(),
+        })
+        .await?;
+    Ok(device_id)
+}
+
+/// A line that does not parse at the end of a log is skipped because it is most likely still being copied over and it
+/// will be read in full the next time
+fn read_log(path: &Path) -> Result<Vec<SyncEntry>, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(Vec::default());
+    }
+    let contents = fs::read_to_string(path)?;
+    let mut entries = Vec::default();
+    for line in contents.lines().filter(|x| !x.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(err) => println!("Skipping a sync log entry in {}: {}", path.display(), err),
+        }
+    }
+    Ok(entries)
+}
+
+#[derive(Default)]
+struct FoldedRecord<'e> {
+    fields: HashMap<&'e str, (&'e Value, (Hlc, &'e str))>,
+    deleted: Option<(Hlc, &'e str)>,
+}
+
+/// For each field the value with the latest stamp wins. The order the entries are in does not matter.
+fn changes_to_apply(entries: &[SyncEntry], current: &SurrealTables) -> Vec<SurrealRecordChange> {
+    let mut folded: HashMap<&Thing, FoldedRecord> = HashMap::default();
+    for entry in entries.iter() {
+        let stamp = entry.stamp();
+        let record = folded.entry(&entry.id).or_default();
+        match &entry.change {
+            SyncChange::Fields(fields) => {
+                for (name, value) in fields.iter() {
+                    let newer = record
+                        .fields
+                        .get(name.as_str())
+                        .is_none_or(|(_, existing)| stamp > *existing);
+                    if newer {
+                        record.fields.insert(name, (value, stamp));
+                    }
+                }
+            }
+            SyncChange::Deleted => {
+                if record.deleted.is_none_or(|existing| stamp > existing) {
+                    record.deleted = Some(stamp);
+                }
+            }
+        }
+    }
+
+    let mut current_records: HashMap<Thing, SurrealRecord> = current
+        .all_records()
+        .map(|x| (x.id().expect("In DB").clone(), x))
+        .collect();
+    let mut changes = Vec::default();
+    for (id, record) in folded {
+        let before = current_records.remove(id);
+        let last_field_change = record.fields.values().map(|(_, stamp)| *stamp).max();
+        //A change made after the delete, on a device that had not seen the delete yet, brings the record back
+        let is_deleted = record
+            .deleted
+            .is_some_and(|deleted| last_field_change.is_none_or(|changed| deleted > changed));
+        let after = if is_deleted {
+            None
+        } else {
+            let mut fields = match &before {
+                Some(before) => match to_fields(before) {
+                    Ok(fields) => fields,
+                    Err(err) => {
+                        println!("Unable to sync {}: {}", id, err);
+                        continue;
+                    }
+                },
+                None => Map::default(),
+            };
+            for (name, (value, _)) in record.fields {
+                fields.insert(name.to_string(), value.clone());
+            }
+            match from_fields(id, fields) {
+                Ok(after) => Some(after),
+                //Most likely a record from a newer version of On Purpose, it is left for that version to sync
+                Err(err) => {
+                    println!("Unable to sync {}: {}", id, err);
+                    continue;
+                }
+            }
+        };
+        if before != after {
+            changes.push(SurrealRecordChange {
+                id: id.clone(),
+                before,
+                after,
+            });
+        }
+    }
+    changes
+}
+
+/// Every field except the id, the id is kept separately in the entry
+fn to_fields(record: &SurrealRecord) -> Result<Map<String, Value>, serde_json::Error> {
+    let value = match record {
+        SurrealRecord::Item(item) => serde_json::to_value(item)?,
+        SurrealRecord::TimeSpent(time_spent) => serde_json::to_value(time_spent)?,
+        SurrealRecord::InTheMomentPriority(priority) => serde_json::to_value(priority)?,
+        SurrealRecord::CurrentMode(current_mode) => serde_json::to_value(current_mode)?,
+        SurrealRecord::Mode(mode) => serde_json::to_value(mode)?,
+        SurrealRecord::Event(event) => serde_json::to_value(event)?,
+    };
+    let mut fields = match value {
+        Value::Object(fields) => fields,
+        _ => Map::default(),
+    };
+    fields.remove("id");
+    Ok(fields)
+}
+
+fn from_fields(
+    id: &Thing,
+    mut fields: Map<String, Value>,
+) -> Result<SurrealRecord, serde_json::Error> {
+    fields.insert("id".to_string(), serde_json::to_value(Some(id))?);
+    let fields = Value::Object(fields);
+    match id.tb.as_str() {
+        SurrealItem::TABLE_NAME => Ok(SurrealRecord::Item(serde_json::from_value(fields)?)),
+        SurrealTimeSpent::TABLE_NAME => {
+            Ok(SurrealRecord::TimeSpent(serde_json::from_value(fields)?))
+        }
+        SurrealInTheMomentPriority::TABLE_NAME => Ok(SurrealRecord::InTheMomentPriority(
+            serde_json::from_value(fields)?,
+        )),
+        SurrealCurrentMode::TABLE_NAME => {
+            Ok(SurrealRecord::CurrentMode(serde_json::from_value(fields)?))
+        }
+        SurrealMode::TABLE_NAME => Ok(SurrealRecord::Mode(serde_json::from_value(fields)?)),
+        SurrealEvent::TABLE_NAME => Ok(SurrealRecord::Event(serde_json::from_value(fields)?)),
+        table => Err(serde::de::Error::custom(format!(
+            "{} is not a table that is synced",
+            table
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, process};
+
+    use chrono::Utc;
+    use tokio::{
+        sync::mpsc::{self, Sender},
+        task::JoinHandle,
+    };
+
+    use crate::{
+        data_storage::surrealdb_layer::{
+            data_layer_commands::{
+                DataChange, DataLayerCommands, DataStorageSettings, data_storage_start_and_run,
+            },
+            surreal_item::{Responsibility, SurrealItemType},
+            surreal_tables::SurrealTables,
+        },
+        new_item::NewItem,
+    };
+
+    /// Each device has its own in memory database, only the sync folder is shared
+    fn start_device(sync_folder: &Path) -> (Sender<DataLayerCommands>, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::channel(1);
+        let settings = DataStorageSettings {
+            backups: None,
+            sync_folder: Some(sync_folder.to_path_buf()),
+            encryption: None,
+        };
+        let join_handle =
+            tokio::spawn(
+                async move { data_storage_start_and_run(receiver, "mem://", settings).await },
+            );
+        (sender, join_handle)
+    }
+
+    async fn stop_device((sender, join_handle): (Sender<DataLayerCommands>, JoinHandle<()>)) {
+        drop(sender);
+        join_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn changes_on_one_device_are_merged_into_the_other() {
+        let sync_folder =
+            std::env::temp_dir().join(format!("on_purpose_sync_merged_{}", process::id()));
+        let laptop = start_device(&sync_folder);
+        DataLayerCommands::change(
+            &laptop.0,
+            DataChange::NewItem(NewItem::new("Made on the laptop".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+
+        //Merged when the phone starts
+        let phone = start_device(&sync_folder);
+        let on_phone = SurrealTables::new(&phone.0).await.unwrap();
+        let on_laptop = SurrealTables::new(&laptop.0).await.unwrap();
+        assert!(on_phone.is_same_data(&on_laptop));
+
+        let item = on_phone.surreal_items[0].id.clone().unwrap();
+        DataLayerCommands::change(
+            &phone.0,
+            DataChange::UpdateSummary(item, "Changed on the phone".into()),
+        )
+        .await
+        .unwrap();
+
+        //Merged when the laptop refreshes
+        assert_eq!(DataLayerCommands::sync(&laptop.0).await.unwrap(), 1);
+        let on_laptop = SurrealTables::new(&laptop.0).await.unwrap();
+        assert_eq!(on_laptop.surreal_items[0].summary, "Changed on the phone");
+        assert_eq!(DataLayerCommands::sync(&laptop.0).await.unwrap(), 0);
+
+        stop_device(laptop).await;
+        stop_device(phone).await;
+        fs::remove_dir_all(sync_folder).unwrap();
+    }
+
+    #[tokio::test]
+    async fn changes_made_on_both_devices_at_the_same_time_end_up_the_same_on_both() {
+        let sync_folder =
+            std::env::temp_dir().join(format!("on_purpose_sync_conflict_{}", process::id()));
+        let laptop = start_device(&sync_folder);
+        DataLayerCommands::change(
+            &laptop.0,
+            DataChange::NewItem(NewItem::new("Made on the laptop".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let phone = start_device(&sync_folder);
+        let item = SurrealTables::new(&phone.0).await.unwrap().surreal_items[0]
+            .id
+            .clone()
+            .unwrap();
+
+        //Neither device has seen the other's changes yet
+        DataLayerCommands::change(
+            &laptop.0,
+            DataChange::UpdateSummary(item.clone(), "Laptop summary".into()),
+        )
+        .await
+        .unwrap();
+        DataLayerCommands::change(
+            &phone.0,
+            DataChange::UpdateSummary(item.clone(), "Phone summary".into()),
+        )
+        .await
+        .unwrap();
+        DataLayerCommands::change(
+            &phone.0,
+            DataChange::UpdateResponsibilityAndItemType(
+                item,
+                Responsibility::ReactiveBeAvailableToAct,
+                SurrealItemType::Action,
+            ),
+        )
+        .await
+        .unwrap();
+
+        DataLayerCommands::sync(&laptop.0).await.unwrap();
+        DataLayerCommands::sync(&phone.0).await.unwrap();
+        let on_laptop = SurrealTables::new(&laptop.0).await.unwrap();
+        let on_phone = SurrealTables::new(&phone.0).await.unwrap();
+
+        assert!(on_laptop.is_same_data(&on_phone));
+        //Both summaries were changed so only one is kept, the phone's changes to other fields are kept either way
+        let item = &on_laptop.surreal_items[0];
+        assert_eq!(
+            item.responsibility,
+            Responsibility::ReactiveBeAvailableToAct
+        );
+        assert_eq!(item.item_type, SurrealItemType::Action);
+
+        stop_device(laptop).await;
+        stop_device(phone).await;
+        fs::remove_dir_all(sync_folder).unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_delete_on_one_device_is_merged_into_the_other() {
+        let sync_folder =
+            std::env::temp_dir().join(format!("on_purpose_sync_delete_{}", process::id()));
+        let laptop = start_device(&sync_folder);
+        DataLayerCommands::change(
+            &laptop.0,
+            DataChange::NewItem(NewItem::new("Made on the laptop".into(), Utc::now())),
+        )
+        .await
+        .unwrap();
+        let phone = start_device(&sync_folder);
+        assert_eq!(
+            SurrealTables::new(&phone.0)
+                .await
+                .unwrap()
+                .surreal_items
+                .len(),
+            1
+        );
+
+        //Undoing the new item deletes it
+        DataLayerCommands::undo(&laptop.0).await.unwrap();
+        DataLayerCommands::sync(&phone.0).await.unwrap();
+
+        assert!(SurrealTables::new(&phone.0).await.unwrap().is_empty());
+
+        stop_device(laptop).await;
+        stop_device(phone).await;
+        fs::remove_dir_all(sync_folder).unwrap();
+    }
+}
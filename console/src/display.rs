@@ -1,4 +1,5 @@
 pub(crate) mod display_action_with_item_status;
+pub(crate) mod display_deadline;
 pub(crate) mod display_dependencies_with_item_node;
 pub(crate) mod display_duration;
 pub(crate) mod display_duration_one_unit;
@@ -8,6 +9,7 @@ pub(crate) mod display_item_status;
 pub(crate) mod display_item_type;
 pub(crate) mod display_mode;
 pub(crate) mod display_mode_node;
+pub(crate) mod display_recurrence;
 pub(crate) mod display_scheduled_item;
 pub(crate) mod display_urgency_level_item_with_item_status;
 pub(crate) mod display_urgency_plan;
@@ -0,0 +1,44 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Local, Utc};
+
+use crate::{
+    data_storage::surrealdb_layer::surreal_item::SurrealDeadline,
+    display::display_duration_one_unit::DisplayDurationOneUnit,
+};
+
+/// A countdown to the deadline, or how long ago it passed
+pub(crate) struct DisplayDeadline<'s> {
+    deadline: &'s SurrealDeadline,
+    now: &'s DateTime<Utc>,
+}
+
+impl Display for DisplayDeadline<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let due: DateTime<Utc> = self.deadline.due.clone().into();
+        let due_local = due.with_timezone(&Local).format("%a %d %b %Y %I:%M%p");
+        match (due - *self.now).to_std() {
+            Ok(left) => write!(
+                f,
+                "⏰ Due in {} ({})",
+                DisplayDurationOneUnit::new(&left),
+                due_local
+            ),
+            Err(_) => {
+                let overdue = (*self.now - due).to_std().unwrap_or_default();
+                write!(
+                    f,
+                    "❗ Overdue by {} ({})",
+                    DisplayDurationOneUnit::new(&overdue),
+                    due_local
+                )
+            }
+        }
+    }
+}
+
+impl<'s> DisplayDeadline<'s> {
+    pub(crate) fn new(deadline: &'s SurrealDeadline, now: &'s DateTime<Utc>) -> Self {
+        DisplayDeadline { deadline, now }
+    }
+}
@@ -0,0 +1,54 @@
+use std::fmt::Display;
+
+use itertools::Itertools;
+
+use crate::data_storage::surrealdb_layer::surreal_item::{SurrealRecurrence, SurrealWeekday};
+
+pub(crate) struct DisplayRecurrence<'s> {
+    recurrence: &'s SurrealRecurrence,
+}
+
+impl Display for DisplayRecurrence<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.recurrence {
+            SurrealRecurrence::EveryDays(1) => write!(f, "every day"),
+            SurrealRecurrence::EveryDays(days) => write!(f, "every {} days", days),
+            SurrealRecurrence::EveryWeeks(1) => write!(f, "every week"),
+            SurrealRecurrence::EveryWeeks(weeks) => write!(f, "every {} weeks", weeks),
+            SurrealRecurrence::OnWeekdays(weekdays) => write!(
+                f,
+                "every {}",
+                weekdays.iter().map(|x| DisplayWeekday(*x)).join(", ")
+            ),
+            SurrealRecurrence::DayOfMonth(day) => {
+                write!(f, "on day {} of every month", day)
+            }
+            SurrealRecurrence::DaysAfterFinished(1) => write!(f, "the day after it is finished"),
+            SurrealRecurrence::DaysAfterFinished(days) => {
+                write!(f, "{} days after it is finished", days)
+            }
+        }
+    }
+}
+
+impl<'s> DisplayRecurrence<'s> {
+    pub(crate) fn new(recurrence: &'s SurrealRecurrence) -> Self {
+        Self { recurrence }
+    }
+}
+
+pub(crate) struct DisplayWeekday(pub(crate) SurrealWeekday);
+
+impl Display for DisplayWeekday {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            SurrealWeekday::Monday => write!(f, "Monday"),
+            SurrealWeekday::Tuesday => write!(f, "Tuesday"),
+            SurrealWeekday::Wednesday => write!(f, "Wednesday"),
+            SurrealWeekday::Thursday => write!(f, "Thursday"),
+            SurrealWeekday::Friday => write!(f, "Friday"),
+            SurrealWeekday::Saturday => write!(f, "Saturday"),
+            SurrealWeekday::Sunday => write!(f, "Sunday"),
+        }
+    }
+}
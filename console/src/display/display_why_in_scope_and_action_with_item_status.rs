@@ -7,7 +7,10 @@ use crate::{
     data_storage::surrealdb_layer::{
         surreal_in_the_moment_priority::SurrealAction, surreal_item::SurrealUrgency,
     },
-    display::display_action_with_item_status::DisplayActionWithItemStatus,
+    display::{
+        display_action_with_item_status::DisplayActionWithItemStatus,
+        display_deadline::DisplayDeadline,
+    },
     node::{
         Filter,
         action_with_item_status::ActionWithItemStatus,
@@ -45,7 +48,13 @@ impl Display for DisplayWhyInScopeAndActionWithItemStatus<'_> {
             f,
             "{}",
             DisplayActionWithItemStatus::new(self.get_action(), self.filter, self.display_format)
-        )
+        )?;
+
+        let item = self.get_action().get_item_node().get_item();
+        if let Some(deadline) = item.get_deadline() {
+            write!(f, " {}", DisplayDeadline::new(deadline, item.get_now()))?;
+        }
+        Ok(())
     }
 }
 
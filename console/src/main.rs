@@ -1,5 +1,6 @@
 pub(crate) mod base_data;
 pub(crate) mod calculated_data;
+pub(crate) mod command_line;
 pub(crate) mod data_storage;
 pub(crate) mod display;
 pub(crate) mod menu;
@@ -21,8 +22,27 @@ use mimalloc::MiMalloc;
 use tokio::sync::mpsc;
 
 use crate::{
-    data_storage::surrealdb_layer::data_layer_commands::data_storage_start_and_run,
-    menu::inquire::do_now_list_menu::present_normal_do_now_list_menu,
+    calculated_data::node_shapes::NodeShapes,
+    command_line::{Command, CommandLine, DatabaseLocation, help_string},
+    data_storage::{
+        backups::{BackupSettings, plain_text_backups, restore_from},
+        encryption::{change_passphrase, unlock_or_create},
+        json_export::{export_to, import_from},
+        surrealdb_layer::{
+            data_layer_commands::{
+                DataLayerCommands, DataStorageSettings, data_storage_start_and_run,
+            },
+            encrypted_database::EncryptionSettings,
+            surreal_tables::LatestSurrealTables,
+        },
+    },
+    menu::inquire::{
+        back_menu::{
+            archive_finished::archive_finished_older_than,
+            check_integrity::present_check_integrity_menu,
+        },
+        do_now_list_menu::present_do_now_list_menu_for_surreal_tables,
+    },
 };
 
 #[global_allocator]
@@ -32,28 +52,172 @@ static GLOBAL: MiMalloc = MiMalloc;
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     const CARGO_PKG_VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
 
-    println!("{}Welcome to 🕜 Task On Purpose 🕜", Clear(ClearType::All));
-    println!("Version {}", CARGO_PKG_VERSION.unwrap_or("UNKNOWN"));
+    let command_line = match CommandLine::from_env() {
+        Ok(command_line) => command_line,
+        Err(err) => {
+            println!("{}", err);
+            std::process::exit(2);
+        }
+    };
+    if command_line.command == Command::PrintHelp {
+        println!("{}", help_string());
+        return Ok(());
+    }
+
+    if let DatabaseLocation::File(path) = &command_line.database
+        && let Some(parent) = path.parent()
+    {
+        std::fs::create_dir_all(parent)?;
+    }
 
     let commands_in_flight_limit = 20;
     let (send_to_data_storage_layer_tx, have_data_storage_layer_use_to_receive_rx) =
         mpsc::channel(commands_in_flight_limit);
 
-    let args: Vec<String> = env::args().collect();
-
-    let data_storage_join_handle = if args.len() > 1 && args[1] == "inmemorydb" {
-        tokio::spawn(async move {
-            data_storage_start_and_run(have_data_storage_layer_use_to_receive_rx, "mem://").await
-        })
-    } else {
-        tokio::spawn(async move {
-            data_storage_start_and_run(
-                have_data_storage_layer_use_to_receive_rx,
-                "file://c:/.on_purpose.db", //TODO: Get a default file location that works for both Linux and Windows
-            )
-            .await
-        })
+    let encryption = match command_line.database.encrypted_file() {
+        Some(file) if command_line.encrypted || file.exists() => match unlock_or_create(&file)? {
+            Some(key) => Some(EncryptionSettings { file, key }),
+            None => {
+                println!("No passphrase was given so the encrypted database was not opened");
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+    if command_line.command == Command::ChangePassphrase && encryption.is_none() {
+        println!("The database is not encrypted, use --encrypted to create an encrypted database");
+        std::process::exit(2);
+    }
+    if encryption.is_some() && command_line.sync_folder.is_some() {
+        //Each device derives its key with its own salt so the other devices could not read encrypted changes either
+        println!(
+            "--sync-folder cannot be used with an encrypted database, the changes written to it are not encrypted"
+        );
+        std::process::exit(2);
+    }
+    if encryption.is_some() {
+        //An existing database is encrypted by exporting and importing it so what was there before is left as it was
+        let mut plain_text = command_line
+            .database
+            .backup_directory()
+            .map(|x| plain_text_backups(&x))
+            .unwrap_or_default();
+        if let DatabaseLocation::File(path) = &command_line.database
+            && path.exists()
+        {
+            plain_text.insert(0, path.clone());
+        }
+        if !plain_text.is_empty() {
+            println!("These were made before the database was encrypted and are not encrypted:");
+            for path in plain_text {
+                println!("  {}", path.display());
+            }
+            println!(
+                "Delete them once you have checked that the encrypted database has everything"
+            );
+        }
+    }
+    let encryption_key = encryption.as_ref().map(|x| x.key.clone());
+    let database_file = match (&encryption, &command_line.database) {
+        (Some(encryption), _) => Some(encryption.file.clone()),
+        (None, DatabaseLocation::File(path)) => Some(path.clone()),
+        (None, DatabaseLocation::InMemory) => None,
+    };
+
+    //SurrealDB cannot encrypt the database itself so an encrypted database is loaded into memory and written out encrypted
+    let endpoint = match &encryption {
+        Some(_) => DatabaseLocation::InMemory.to_endpoint(),
+        None => command_line.database.to_endpoint(),
     };
+    let backup_directory = command_line.database.backup_directory();
+    let settings = DataStorageSettings {
+        backups: backup_directory.clone().map(|directory| BackupSettings {
+            directory,
+            schedule: command_line.backup_schedule.clone(),
+        }),
+        sync_folder: command_line.sync_folder.clone(),
+        encryption,
+    };
+    let data_storage_join_handle = tokio::spawn(async move {
+        data_storage_start_and_run(
+            have_data_storage_layer_use_to_receive_rx,
+            endpoint,
+            settings,
+        )
+        .await
+    });
+
+    if let Err(err) = DataLayerCommands::get_startup_result(&send_to_data_storage_layer_tx).await {
+        println!("{}", err);
+        drop(send_to_data_storage_layer_tx);
+        data_storage_join_handle.await.unwrap();
+        std::process::exit(1);
+    }
+
+    match command_line.command {
+        Command::Export { to } => {
+            let result = export_to(
+                &send_to_data_storage_layer_tx,
+                to.as_deref(),
+                encryption_key.as_ref(),
+            )
+            .await;
+            drop(send_to_data_storage_layer_tx);
+            data_storage_join_handle.await.unwrap();
+            return result;
+        }
+        Command::Import { from, replace } => {
+            let result = import_from(
+                &send_to_data_storage_layer_tx,
+                &from,
+                replace,
+                encryption_key.as_ref(),
+            )
+            .await;
+            drop(send_to_data_storage_layer_tx);
+            data_storage_join_handle.await.unwrap();
+            return result;
+        }
+        Command::Restore { from } => {
+            let result = restore_from(
+                &send_to_data_storage_layer_tx,
+                &from,
+                backup_directory.as_deref(),
+                encryption_key.as_ref(),
+            )
+            .await;
+            drop(send_to_data_storage_layer_tx);
+            data_storage_join_handle.await.unwrap();
+            return result;
+        }
+        Command::Check => {
+            //Err means the user asked to exit part way through or the data could not be read, which is already printed
+            let _ = present_check_integrity_menu(&send_to_data_storage_layer_tx).await;
+            drop(send_to_data_storage_layer_tx);
+            data_storage_join_handle.await.unwrap();
+            return Ok(());
+        }
+        Command::Archive { older_than_days } => {
+            let result =
+                archive_finished_older_than(&send_to_data_storage_layer_tx, older_than_days).await;
+            drop(send_to_data_storage_layer_tx);
+            data_storage_join_handle.await.unwrap();
+            return Ok(result?);
+        }
+        Command::ChangePassphrase => {
+            let result = change_passphrase(&send_to_data_storage_layer_tx).await;
+            drop(send_to_data_storage_layer_tx);
+            data_storage_join_handle.await.unwrap();
+            return result;
+        }
+        Command::Run | Command::PrintHelp => {}
+    }
+
+    println!("{}Welcome to 🕜 Task On Purpose 🕜", Clear(ClearType::All));
+    println!("Version {}", CARGO_PKG_VERSION.unwrap_or("UNKNOWN"));
+    if let Some(path) = &database_file {
+        println!("Database: {}", path.display());
+    }
 
     //If the current executable is more than 3 months old print a message that there is probably a newer version available
     let exe_path = env::current_exe().unwrap();
@@ -67,8 +231,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         );
     }
 
+    let mut latest_surreal_tables = LatestSurrealTables::new(&send_to_data_storage_layer_tx)
+        .await
+        .unwrap();
+    let mut node_shapes = NodeShapes::default();
     loop {
-        match present_normal_do_now_list_menu(&send_to_data_storage_layer_tx).await {
+        let surreal_tables = latest_surreal_tables.get();
+        let changed = latest_surreal_tables.take_changed();
+        match present_do_now_list_menu_for_surreal_tables(
+            surreal_tables,
+            &mut node_shapes,
+            changed.as_deref(),
+            &send_to_data_storage_layer_tx,
+        )
+        .await
+        {
             Result::Ok(..) => (),
             Result::Err(..) => break,
         };
@@ -4,11 +4,23 @@ use lazy_static::lazy_static;
 use chrono::{DateTime, Datelike, Duration, Local, NaiveTime, TimeZone};
 use regex::{Regex, RegexBuilder};
 
+use crate::data_storage::surrealdb_layer::data_layer_commands::DataLayerError;
+
 pub(crate) mod back_menu;
 pub(crate) mod do_now_list_menu;
 pub(crate) mod select_higher_importance_than_this;
 pub(crate) mod update_item_summary;
 
+/// Shows the user that their change was not saved. The menus keep running so they can try again, unless the data storage
+/// layer has stopped in which case Err is returned to exit.
+pub(crate) fn report_not_saved(err: DataLayerError) -> Result<(), ()> {
+    println!("Unable to save this change: {}", err);
+    match err {
+        DataLayerError::Stopped => Err(()),
+        _ => Ok(()),
+    }
+}
+
 #[must_use]
 fn parse_exact_or_relative_datetime_help_string() -> &'static str {
     concat!(
@@ -1,27 +1,41 @@
+pub(crate) mod archive_finished;
+pub(crate) mod check_integrity;
 pub(crate) mod configure_modes;
 pub(crate) mod configure_settings;
+pub(crate) mod events;
+pub(crate) mod time_log;
 
-use std::{cmp::Ordering, fmt::Display, vec};
+use std::{cmp::Ordering, fmt::Display, sync::Arc, vec};
 
 use ahash::HashMap;
+use archive_finished::present_archive_finished_menu;
+use check_integrity::present_check_integrity_menu;
 use chrono::{DateTime, Local, Utc};
 use configure_settings::configure_settings;
+use events::present_events_menu;
 use inquire::{InquireError, Select, Text};
 use surrealdb::opt::RecordId;
+use time_log::present_time_log;
 use tokio::sync::mpsc::Sender;
 
 use crate::{
     base_data::{BaseData, item::Item, time_spent::TimeSpent},
     calculated_data::{CalculatedData, parent_lookup::ParentLookup},
     data_storage::surrealdb_layer::{
-        data_layer_commands::DataLayerCommands, surreal_tables::SurrealTables,
+        data_layer_commands::{DataChange, DataLayerCommands},
+        surreal_archive::SurrealArchive,
+        surreal_tables::SurrealTables,
     },
     display::{
+        DisplayStyle,
         display_duration::DisplayDuration,
         display_item::DisplayItem,
         display_item_node::{DisplayFormat, DisplayItemNode},
         display_item_status::DisplayItemStatus,
+        display_item_type::DisplayItemType,
+        display_recurrence::DisplayRecurrence,
     },
+    menu::inquire::report_not_saved,
     menu::inquire::{
         back_menu::configure_modes::configure_modes, parse_exact_or_relative_datetime,
         parse_exact_or_relative_datetime_help_string,
@@ -29,9 +43,10 @@ use crate::{
     new_item::NewItem,
     node::{
         Filter,
-        item_node::{ItemNode, ShrinkingItemNode},
+        item_node::{ItemNode, ShrinkingItemNode, get_logged_time},
         item_status::ItemStatus,
     },
+    systems::maintenance::streak,
 };
 
 use super::{
@@ -45,6 +60,11 @@ enum TopMenuSelection {
     ClearInTheMomentPriorities,
     ConfigureModes,
     ConfigureSettings,
+    ManageEvents,
+    UndoLastChange,
+    RedoLastUndoneChange,
+    CheckForProblems,
+    ArchiveFinishedItems,
     DebugViewAllItems,
 }
 
@@ -63,9 +83,16 @@ impl Display for TopMenuSelection {
             }
             TopMenuSelection::ConfigureSettings => write!(f, "⚙️  Configure Settings"),
             TopMenuSelection::ConfigureModes => write!(f, "😊  Configure Modes"),
+            TopMenuSelection::ManageEvents => write!(f, "⚡  Events, what items are waiting on"),
             TopMenuSelection::ClearInTheMomentPriorities => {
                 write!(f, "🗑️  Clear In The Moment Priorities")
             }
+            TopMenuSelection::UndoLastChange => write!(f, "↩️  Undo last change"),
+            TopMenuSelection::RedoLastUndoneChange => write!(f, "↪️  Redo last undone change"),
+            TopMenuSelection::CheckForProblems => write!(f, "🩺  Check for problems in the data"),
+            TopMenuSelection::ArchiveFinishedItems => {
+                write!(f, "📦  Archive items finished long ago")
+            }
         }
     }
 }
@@ -78,7 +105,12 @@ impl TopMenuSelection {
             Self::Reflection,
             Self::ConfigureModes,
             Self::ConfigureSettings,
+            Self::ManageEvents,
             Self::ViewDoNowList,
+            Self::UndoLastChange,
+            Self::RedoLastUndoneChange,
+            Self::CheckForProblems,
+            Self::ArchiveFinishedItems,
             Self::DebugViewAllItems,
         ]
     }
@@ -104,6 +136,17 @@ pub(crate) async fn present_back_menu(
         }
         Ok(TopMenuSelection::ConfigureSettings) => configure_settings().await,
         Ok(TopMenuSelection::ConfigureModes) => configure_modes(send_to_data_storage_layer).await,
+        Ok(TopMenuSelection::ManageEvents) => present_events_menu(send_to_data_storage_layer).await,
+        Ok(TopMenuSelection::UndoLastChange) => undo_last_change(send_to_data_storage_layer).await,
+        Ok(TopMenuSelection::RedoLastUndoneChange) => {
+            redo_last_undone_change(send_to_data_storage_layer).await
+        }
+        Ok(TopMenuSelection::CheckForProblems) => {
+            present_check_integrity_menu(send_to_data_storage_layer).await
+        }
+        Ok(TopMenuSelection::ArchiveFinishedItems) => {
+            present_archive_finished_menu(send_to_data_storage_layer).await
+        }
         Ok(TopMenuSelection::DebugViewAllItems) => {
             debug_view_all_items(send_to_data_storage_layer).await
         }
@@ -121,11 +164,9 @@ pub(crate) async fn capture(
     match new_item_summary {
         Ok(new_item_summary) => {
             let new_item = NewItem::new(new_item_summary, Utc::now());
-            send_to_data_storage_layer
-                .send(DataLayerCommands::NewItem(new_item))
+            DataLayerCommands::change(send_to_data_storage_layer, DataChange::NewItem(new_item))
                 .await
-                .unwrap();
-            Ok(())
+                .or_else(report_not_saved)
         }
         Err(InquireError::OperationCanceled) => Ok(()),
         Err(InquireError::OperationInterrupted) => Err(()),
@@ -133,6 +174,28 @@ pub(crate) async fn capture(
     }
 }
 
+pub(crate) async fn undo_last_change(
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    match DataLayerCommands::undo(send_to_data_storage_layer).await {
+        Ok(Some(undone)) => println!("Undid: {}", undone),
+        Ok(None) => println!("There is nothing to undo"),
+        Err(err) => return report_not_saved(err),
+    }
+    Ok(())
+}
+
+async fn redo_last_undone_change(
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    match DataLayerCommands::redo(send_to_data_storage_layer).await {
+        Ok(Some(redone)) => println!("Redid: {}", redone),
+        Ok(None) => println!("There is nothing to redo"),
+        Err(err) => return report_not_saved(err),
+    }
+    Ok(())
+}
+
 enum ClearInTheMomentPrioritiesChoice {
     ClearAll,
     Back,
@@ -167,12 +230,14 @@ async fn clear_in_the_moment_priorities(
                 print!(".");
                 let in_the_moment_priority =
                     in_the_moment_priority.id.as_ref().expect("In DB").clone();
-                send_to_data_storage_layer
-                    .send(DataLayerCommands::ClearInTheMomentPriority(
-                        in_the_moment_priority,
-                    ))
-                    .await
-                    .unwrap();
+                if let Err(err) = DataLayerCommands::change(
+                    send_to_data_storage_layer,
+                    DataChange::ClearInTheMomentPriority(in_the_moment_priority),
+                )
+                .await
+                {
+                    return report_not_saved(err);
+                }
             }
             println!("Done");
             Ok(())
@@ -357,14 +422,15 @@ async fn view_priorities_single_item_no_children(
     match selection {
         Ok(ViewPrioritiesSingleItemNoChildrenChoice::Finish) => {
             let now = Utc::now();
-            send_to_data_storage_layer
-                .send(DataLayerCommands::FinishItem {
+            DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::FinishItem {
                     item: item_status.get_surreal_record_id().clone(),
                     when_finished: now.into(),
-                })
-                .await
-                .unwrap();
-            Ok(())
+                },
+            )
+            .await
+            .or_else(report_not_saved)
         }
         Ok(ViewPrioritiesSingleItemNoChildrenChoice::EditSummary) => {
             update_item_summary(item_status.get_item(), send_to_data_storage_layer).await
@@ -427,9 +493,14 @@ async fn present_reflection(
 
     println!("Time spent between {} and {}", start, end);
 
-    let surreal_tables = SurrealTables::new(send_to_data_storage_layer)
+    let mut surreal_tables = SurrealTables::new(send_to_data_storage_layer)
+        .await
+        .unwrap();
+    //What was done can include items that have since been archived
+    let surreal_archive = SurrealArchive::new(send_to_data_storage_layer)
         .await
         .unwrap();
+    surreal_archive.add_to(Arc::make_mut(&mut surreal_tables));
 
     let start_utc = start.with_timezone(&Utc);
     let end_utc = end.with_timezone(&Utc);
@@ -616,6 +687,28 @@ async fn present_reflection(
         );
     }
 
+    let time_by_tag = time_spent_by_tag(&logs_in_range, items_status);
+    if !time_by_tag.is_empty() && total_time_num_seconds != 0 {
+        println!();
+        println!("By tag, time spent on an item with more than one tag counts toward each of them");
+        for (tag, time_spent) in time_by_tag.iter() {
+            println!(
+                "\t{}: {} ({}%)",
+                tag,
+                DisplayDuration::new(&time_spent.to_std().expect("valid")),
+                time_spent.num_seconds() * 100 / total_time_num_seconds
+            );
+        }
+    }
+
+    print_recurring_items(items_status, &start_utc, &end_utc);
+    print_estimate_accuracy(
+        items_status,
+        calculated_data.get_time_spent_log(),
+        &start_utc,
+        &end_utc,
+    );
+
     let urgent_time = logs_in_range
         .iter()
         .filter(|x| x.is_urgent())
@@ -662,13 +755,185 @@ async fn present_reflection(
     }
 
     println!();
-    match Text::new("Press Enter to continue...").prompt() {
-        Ok(_) | Err(InquireError::OperationCanceled) => Ok(()),
+    let selection = Select::new(
+        "Select from the below list|",
+        vec![ReflectionNext::Done, ReflectionNext::CorrectTimeSpent],
+    )
+    .prompt();
+    match selection {
+        Ok(ReflectionNext::Done) | Err(InquireError::OperationCanceled) => Ok(()),
+        Ok(ReflectionNext::CorrectTimeSpent) => {
+            present_time_log(start_utc, end_utc, None, send_to_data_storage_layer).await
+        }
         Err(InquireError::OperationInterrupted) => Err(()),
         Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
     }
 }
 
+/// Most time spent first. Tags are inherited so time spent on a smaller item counts toward the tags of its larger items,
+/// even finished ones. Time that does not count toward any tag is listed as "Untagged".
+#[allow(clippy::mutable_key_type)]
+fn time_spent_by_tag<'s>(
+    logs: &[TimeSpent<'_>],
+    items_status: &'s HashMap<&RecordId, ItemStatus<'s>>,
+) -> Vec<(&'s str, chrono::Duration)> {
+    let mut time_by_tag: HashMap<&'s str, chrono::Duration> = HashMap::default();
+    let mut untagged = chrono::Duration::default();
+    for log in logs.iter() {
+        let mut tags: Vec<&'s str> = Vec::default();
+        for item_status in log
+            .worked_towards()
+            .iter()
+            .filter_map(|x| items_status.get(x))
+        {
+            for tag in item_status.get_item_node().get_tags(Filter::All) {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+        }
+        if tags.is_empty() {
+            untagged += log.get_time_delta();
+        }
+        for tag in tags {
+            *time_by_tag.entry(tag).or_default() += log.get_time_delta();
+        }
+    }
+    let mut time_by_tag = time_by_tag.into_iter().collect::<Vec<_>>();
+    if time_by_tag.is_empty() {
+        //Nothing is tagged so there is nothing to break down
+        return time_by_tag;
+    }
+    time_by_tag.sort_by(|(a_tag, a), (b_tag, b)| b.cmp(a).then_with(|| a_tag.cmp(b_tag)));
+    if !untagged.is_zero() {
+        time_by_tag.push(("Untagged", untagged));
+    }
+    time_by_tag
+}
+
+/// Each repeating item with its streak and the times it was finished during the reflection, ✅ when on time
+#[allow(clippy::mutable_key_type)]
+fn print_recurring_items(
+    items_status: &HashMap<&RecordId, ItemStatus<'_>>,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) {
+    let mut recurring = items_status
+        .values()
+        .map(|x| x.get_item())
+        .filter_map(|x| x.get_recurrence().map(|recurrence| (x, recurrence)))
+        .collect::<Vec<_>>();
+    if recurring.is_empty() {
+        return;
+    }
+    recurring.sort_by(|(a, _), (b, _)| a.get_summary().cmp(b.get_summary()));
+
+    println!();
+    println!("Repeating items");
+    for (item, recurrence) in recurring.into_iter() {
+        let completions = item.get_completions();
+        let in_range = completions
+            .iter()
+            .filter_map(|x| {
+                let finished: DateTime<Utc> = x.finished.clone().into();
+                if &finished >= start && &finished <= end {
+                    Some((finished, x.on_time))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        println!(
+            "\t{} repeats {}, finished {} times, a streak of {}",
+            DisplayItem::new(item),
+            DisplayRecurrence::new(recurrence),
+            in_range.len(),
+            streak(completions)
+        );
+        for (finished, on_time) in in_range.into_iter() {
+            println!(
+                "\t\t{} {}",
+                if on_time { "✅" } else { "⚠️" },
+                finished.with_timezone(&Local).format("%a %d %b %Y")
+            );
+        }
+    }
+}
+
+/// For each item type, how the time logged on items finished during the reflection compares with their estimates
+#[allow(clippy::mutable_key_type)]
+fn print_estimate_accuracy(
+    items_status: &HashMap<&RecordId, ItemStatus<'_>>,
+    time_spent_log: &[TimeSpent<'_>],
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) {
+    //Item type, total estimated, total logged, count of items
+    let mut by_item_type: Vec<(String, std::time::Duration, std::time::Duration, usize)> =
+        Vec::default();
+    for item in items_status.values().map(|x| x.get_item()) {
+        let (Some(estimate), Some(finished)) = (item.get_estimate(), item.get_finished_at()) else {
+            continue;
+        };
+        let finished: DateTime<Utc> = finished.clone().into();
+        if &finished < start || &finished > end {
+            continue;
+        }
+        let logged = get_logged_time(item, time_spent_log);
+        let item_type = DisplayItemType::new(DisplayStyle::Full, item.get_item_type()).to_string();
+        match by_item_type.iter_mut().find(|(x, ..)| *x == item_type) {
+            Some((_, total_estimate, total_logged, count)) => {
+                *total_estimate += estimate;
+                *total_logged += logged;
+                *count += 1;
+            }
+            None => by_item_type.push((item_type, estimate, logged, 1)),
+        }
+    }
+    if by_item_type.is_empty() {
+        return;
+    }
+    by_item_type.sort_by(|a, b| a.0.cmp(&b.0));
+
+    println!();
+    println!(
+        "Estimates of items finished, the time logged on them compared with what was estimated"
+    );
+    for (item_type, estimate, logged, count) in by_item_type.into_iter() {
+        print!(
+            "\t{}: {} items estimated at {} took {}",
+            item_type,
+            count,
+            DisplayDuration::new(&estimate),
+            DisplayDuration::new(&logged)
+        );
+        if estimate.is_zero() {
+            println!();
+        } else {
+            println!(
+                " ({}% of the estimate)",
+                logged.as_secs() * 100 / estimate.as_secs().max(1)
+            );
+        }
+    }
+}
+
+enum ReflectionNext {
+    Done,
+    CorrectTimeSpent,
+}
+
+impl Display for ReflectionNext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReflectionNext::Done => write!(f, "Done"),
+            ReflectionNext::CorrectTimeSpent => {
+                write!(f, "🕜  Correct the time spent in this range")
+            }
+        }
+    }
+}
+
 fn print_children_time_spent(
     record_id: RecordId,
     items_in_range: &mut HashMap<&RecordId, ItemTimeSpent>,
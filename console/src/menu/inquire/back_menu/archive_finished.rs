@@ -0,0 +1,52 @@
+use chrono::{Duration, Utc};
+use inquire::{InquireError, Text};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    command_line::DEFAULT_ARCHIVE_OLDER_THAN_DAYS,
+    data_storage::surrealdb_layer::data_layer_commands::{DataLayerCommands, DataLayerError},
+    menu::inquire::report_not_saved,
+};
+
+pub(crate) async fn archive_finished_older_than(
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+    older_than_days: u32,
+) -> Result<(), DataLayerError> {
+    let finished_before = Utc::now() - Duration::days(older_than_days.into());
+    let archived =
+        DataLayerCommands::archive(send_to_data_storage_layer, finished_before.into()).await?;
+    match archived {
+        0 => println!(
+            "Nothing that was finished more than {} days ago can be archived",
+            older_than_days
+        ),
+        archived => println!(
+            "Archived {} items that were finished more than {} days ago, search can still find them",
+            archived, older_than_days
+        ),
+    }
+    Ok(())
+}
+
+pub(crate) async fn present_archive_finished_menu(
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let older_than_days = loop {
+        let older_than_days =
+            Text::new("Archive the items that were finished more than how many days ago?|")
+                .with_default(&DEFAULT_ARCHIVE_OLDER_THAN_DAYS.to_string())
+                .prompt();
+        match older_than_days {
+            Ok(older_than_days) => match older_than_days.trim().parse::<u32>() {
+                Ok(older_than_days) => break older_than_days,
+                Err(_) => println!("Enter a whole number of days"),
+            },
+            Err(InquireError::OperationCanceled) => return Ok(()),
+            Err(InquireError::OperationInterrupted) => return Err(()),
+            Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+        }
+    };
+    archive_finished_older_than(send_to_data_storage_layer, older_than_days)
+        .await
+        .or_else(report_not_saved)
+}
@@ -0,0 +1,154 @@
+use std::fmt::{self, Display, Formatter};
+
+use inquire::{InquireError, Select};
+use surrealdb::sql::Thing;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    data_storage::{
+        integrity::{Problem, Repair, change_reference, describe, find_problems},
+        surrealdb_layer::{
+            data_layer_commands::{DataChange, DataLayerCommands, DataLayerError},
+            surreal_archive::SurrealArchive,
+            surreal_tables::SurrealTables,
+        },
+    },
+    menu::inquire::report_not_saved,
+};
+
+enum RepairChoice {
+    Repair(Repair),
+    /// Point the reference at another record that the user picks
+    PointAtAnother,
+    Skip,
+}
+
+impl Display for RepairChoice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RepairChoice::Repair(repair) => write!(f, "{}", repair),
+            RepairChoice::PointAtAnother => write!(f, "Point it at something else"),
+            RepairChoice::Skip => write!(f, "Leave it for now"),
+        }
+    }
+}
+
+struct Candidate {
+    description: String,
+    id: Thing,
+}
+
+impl Display for Candidate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.description)
+    }
+}
+
+/// Lists every problem and then goes through them one at a time offering to repair each one. The data is checked again
+/// after each repair because one repair can fix, or reveal, another problem.
+pub(crate) async fn present_check_integrity_menu(
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let mut skipped: Vec<Problem> = Vec::default();
+    let mut listed = false;
+    //Repairs never touch the archive so it only needs to be read once
+    let surreal_archive = SurrealArchive::new(send_to_data_storage_layer)
+        .await
+        .map_err(report_not_read)?;
+    loop {
+        let surreal_tables = SurrealTables::new(send_to_data_storage_layer)
+            .await
+            .map_err(report_not_read)?;
+        let problems = find_problems(&surreal_tables, &surreal_archive);
+        if problems.is_empty() {
+            println!("No problems found");
+            return Ok(());
+        }
+        if !listed {
+            println!("Found {} problem(s):", problems.len());
+            for problem in problems.iter() {
+                println!("  {}", problem);
+            }
+            println!();
+            listed = true;
+        }
+        let Some(problem) = problems.into_iter().find(|x| !skipped.contains(x)) else {
+            println!("Nothing was changed for the remaining problem(s)");
+            return Ok(());
+        };
+
+        let mut choices = problem
+            .repairs()
+            .into_iter()
+            .map(RepairChoice::Repair)
+            .collect::<Vec<_>>();
+        if matches!(problem, Problem::DanglingReference { .. }) {
+            choices.push(RepairChoice::PointAtAnother);
+        }
+        choices.push(RepairChoice::Skip);
+
+        let selection = Select::new(&format!("{}|", problem), choices).prompt();
+        let repair = match selection {
+            Ok(RepairChoice::Repair(repair)) => Some(repair),
+            Ok(RepairChoice::PointAtAnother) => point_at_another(&problem, &surreal_tables)?,
+            Ok(RepairChoice::Skip) => None,
+            Err(InquireError::OperationCanceled) => return Ok(()),
+            Err(InquireError::OperationInterrupted) => return Err(()),
+            Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+        };
+        match repair {
+            Some(repair) => DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::RepairRecord(repair.id, repair.state),
+            )
+            .await
+            .or_else(report_not_saved)?,
+            None => skipped.push(problem),
+        }
+    }
+}
+
+/// Nothing can be checked without the data so the check stops
+fn report_not_read(err: DataLayerError) {
+    println!("Unable to read the data to check: {}", err);
+}
+
+/// Gives back None if the user changed their mind
+fn point_at_another(
+    problem: &Problem,
+    surreal_tables: &SurrealTables,
+) -> Result<Option<Repair>, ()> {
+    let Problem::DanglingReference {
+        record,
+        reference,
+        missing,
+    } = problem
+    else {
+        return Ok(None);
+    };
+    let id = record.id().expect("In DB");
+    let candidates = surreal_tables
+        .all_records()
+        .filter(|x| x.id().is_some_and(|x| x.tb == missing.tb && x != id))
+        .map(|x| Candidate {
+            description: describe(&x),
+            id: x.id().expect("In DB").clone(),
+        })
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        println!("There is nothing else to point it at");
+        return Ok(None);
+    }
+
+    let selection = Select::new("Point it at|", candidates).prompt();
+    match selection {
+        Ok(candidate) => Ok(Some(Repair {
+            description: format!("Point it at {}", candidate.description),
+            id: id.clone(),
+            state: change_reference(record, *reference, missing, Some(&candidate.id)),
+        })),
+        Err(InquireError::OperationCanceled) => Ok(None),
+        Err(InquireError::OperationInterrupted) => Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    }
+}
@@ -4,7 +4,7 @@ use std::{
 };
 
 use chrono::Utc;
-use inquire::{InquireError, Select};
+use inquire::{Confirm, InquireError, Select};
 use tokio::sync::mpsc::Sender;
 
 use crate::{
@@ -12,11 +12,12 @@ use crate::{
     calculated_data::CalculatedData,
     display::display_mode_node::DisplayModeNode,
     menu::inquire::back_menu::{SurrealTables, present_back_menu},
+    menu::inquire::report_not_saved,
     new_mode::NewModeBuilder,
     node::mode_node::ModeNode,
 };
 
-use super::{DataLayerCommands, DisplayFormat};
+use super::{DataChange, DataLayerCommands, DisplayFormat};
 
 enum ConfigureModesOptions<'e> {
     Add,
@@ -41,6 +42,10 @@ impl Display for ConfigureModesOptions<'_> {
 enum ConfigureModesOptionsSelected<'e> {
     AddWithParent(&'e ModeNode<'e>),
     EditName(&'e ModeNode<'e>),
+    MoveUnderADifferentParent(&'e ModeNode<'e>),
+    MoveUp(&'e ModeNode<'e>),
+    MoveDown(&'e ModeNode<'e>),
+    Delete(&'e ModeNode<'e>),
     Back,
     Done,
 }
@@ -58,6 +63,26 @@ impl Display for ConfigureModesOptionsSelected<'_> {
                 "Edit Name of {}",
                 DisplayModeNode::new(mode, DisplayFormat::SingleLine)
             ),
+            ConfigureModesOptionsSelected::MoveUnderADifferentParent(mode) => write!(
+                f,
+                "Move {} Under A Different Parent",
+                DisplayModeNode::new(mode, DisplayFormat::SingleLine)
+            ),
+            ConfigureModesOptionsSelected::MoveUp(mode) => write!(
+                f,
+                "Move {} Up",
+                DisplayModeNode::new(mode, DisplayFormat::SingleLine)
+            ),
+            ConfigureModesOptionsSelected::MoveDown(mode) => write!(
+                f,
+                "Move {} Down",
+                DisplayModeNode::new(mode, DisplayFormat::SingleLine)
+            ),
+            ConfigureModesOptionsSelected::Delete(mode) => write!(
+                f,
+                "🗑️  Delete {}",
+                DisplayModeNode::new(mode, DisplayFormat::SingleLine)
+            ),
             ConfigureModesOptionsSelected::Back => write!(f, "Back"),
             ConfigureModesOptionsSelected::Done => write!(f, "Done (Return to \"Do Now\" List)"),
         }
@@ -95,9 +120,7 @@ pub(crate) async fn configure_modes(
                     a_parent_chain_last.expect("Earlier if statement guarantees this is_some()");
                 let b_parent_chain_last =
                     b_parent_chain_last.expect("Earlier if statement guarantees this is_some()");
-                let ordering = a_parent_chain_last
-                    .get_name()
-                    .cmp(b_parent_chain_last.get_name());
+                let ordering = compare_siblings(a_parent_chain_last, b_parent_chain_last);
                 if let Ordering::Equal = ordering {
                     a_parent_chain.pop();
                     b_parent_chain.pop();
@@ -125,10 +148,14 @@ pub(crate) async fn configure_modes(
             match name {
                 Ok(name) => {
                     let new_mode = NewModeBuilder::default().name(name).build().unwrap();
-                    send_to_data_storage_layer
-                        .send(DataLayerCommands::NewMode(new_mode))
-                        .await
-                        .unwrap();
+                    if let Err(err) = DataLayerCommands::change(
+                        send_to_data_storage_layer,
+                        DataChange::NewMode(new_mode),
+                    )
+                    .await
+                    {
+                        return report_not_saved(err);
+                    }
 
                     Box::pin(configure_modes(send_to_data_storage_layer)).await
                 }
@@ -136,15 +163,17 @@ pub(crate) async fn configure_modes(
                     Box::pin(configure_modes(send_to_data_storage_layer)).await
                 }
                 Err(InquireError::OperationInterrupted) => Err(()),
-                Err(_) => {
-                    todo!()
-                }
+                Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
             }
         }
         Ok(ConfigureModesOptions::Mode(mode)) => {
             let options = vec![
                 ConfigureModesOptionsSelected::AddWithParent(mode),
                 ConfigureModesOptionsSelected::EditName(mode),
+                ConfigureModesOptionsSelected::MoveUnderADifferentParent(mode),
+                ConfigureModesOptionsSelected::MoveUp(mode),
+                ConfigureModesOptionsSelected::MoveDown(mode),
+                ConfigureModesOptionsSelected::Delete(mode),
                 ConfigureModesOptionsSelected::Back,
                 ConfigureModesOptionsSelected::Done,
             ];
@@ -161,10 +190,14 @@ pub(crate) async fn configure_modes(
                                 .parent(Some(parent.get_surreal_id().clone()))
                                 .build()
                                 .unwrap();
-                            send_to_data_storage_layer
-                                .send(DataLayerCommands::NewMode(new_mode))
-                                .await
-                                .unwrap();
+                            if let Err(err) = DataLayerCommands::change(
+                                send_to_data_storage_layer,
+                                DataChange::NewMode(new_mode),
+                            )
+                            .await
+                            {
+                                return report_not_saved(err);
+                            }
 
                             Box::pin(configure_modes(send_to_data_storage_layer)).await
                         }
@@ -172,8 +205,8 @@ pub(crate) async fn configure_modes(
                             Box::pin(configure_modes(send_to_data_storage_layer)).await
                         }
                         Err(InquireError::OperationInterrupted) => Err(()),
-                        Err(_) => {
-                            todo!()
+                        Err(err) => {
+                            panic!("Unexpected error, try restarting the terminal: {}", err)
                         }
                     }
                 }
@@ -183,13 +216,14 @@ pub(crate) async fn configure_modes(
                         .prompt();
                     match name {
                         Ok(name) => {
-                            send_to_data_storage_layer
-                                .send(DataLayerCommands::UpdateModeName(
-                                    mode.get_surreal_id().clone(),
-                                    name,
-                                ))
-                                .await
-                                .unwrap();
+                            if let Err(err) = DataLayerCommands::change(
+                                send_to_data_storage_layer,
+                                DataChange::UpdateModeName(mode.get_surreal_id().clone(), name),
+                            )
+                            .await
+                            {
+                                return report_not_saved(err);
+                            }
 
                             Box::pin(configure_modes(send_to_data_storage_layer)).await
                         }
@@ -197,19 +231,55 @@ pub(crate) async fn configure_modes(
                             Box::pin(configure_modes(send_to_data_storage_layer)).await
                         }
                         Err(InquireError::OperationInterrupted) => Err(()),
-                        Err(_) => {
-                            todo!()
+                        Err(err) => {
+                            panic!("Unexpected error, try restarting the terminal: {}", err)
                         }
                     }
                 }
+                Ok(ConfigureModesOptionsSelected::MoveUnderADifferentParent(mode)) => {
+                    move_under_a_different_parent(
+                        mode,
+                        calculated_data.get_mode_nodes(),
+                        send_to_data_storage_layer,
+                    )
+                    .await?;
+                    Box::pin(configure_modes(send_to_data_storage_layer)).await
+                }
+                Ok(ConfigureModesOptionsSelected::MoveUp(mode)) => {
+                    move_among_siblings(
+                        mode,
+                        Direction::Up,
+                        calculated_data.get_mode_nodes(),
+                        send_to_data_storage_layer,
+                    )
+                    .await?;
+                    Box::pin(configure_modes(send_to_data_storage_layer)).await
+                }
+                Ok(ConfigureModesOptionsSelected::MoveDown(mode)) => {
+                    move_among_siblings(
+                        mode,
+                        Direction::Down,
+                        calculated_data.get_mode_nodes(),
+                        send_to_data_storage_layer,
+                    )
+                    .await?;
+                    Box::pin(configure_modes(send_to_data_storage_layer)).await
+                }
+                Ok(ConfigureModesOptionsSelected::Delete(mode)) => {
+                    delete_mode(
+                        mode,
+                        calculated_data.get_mode_nodes(),
+                        send_to_data_storage_layer,
+                    )
+                    .await?;
+                    Box::pin(configure_modes(send_to_data_storage_layer)).await
+                }
                 Ok(ConfigureModesOptionsSelected::Done) => Ok(()),
                 Ok(ConfigureModesOptionsSelected::Back) | Err(InquireError::OperationCanceled) => {
                     Box::pin(configure_modes(send_to_data_storage_layer)).await
                 }
                 Err(InquireError::OperationInterrupted) => Err(()),
-                Err(_) => {
-                    todo!()
-                }
+                Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
             }
         }
         Ok(ConfigureModesOptions::Done) => Ok(()),
@@ -217,8 +287,175 @@ pub(crate) async fn configure_modes(
             Box::pin(present_back_menu(send_to_data_storage_layer)).await
         }
         Err(InquireError::OperationInterrupted) => Err(()),
-        Err(_) => {
-            todo!()
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    }
+}
+
+/// Modes with the same parent are shown in their display order and then by name
+fn compare_siblings(a: &Mode<'_>, b: &Mode<'_>) -> Ordering {
+    a.get_display_order()
+        .cmp(&b.get_display_order())
+        .then_with(|| a.get_name().cmp(b.get_name()))
+}
+
+fn is_under(mode: &ModeNode<'_>, ancestor: &ModeNode<'_>) -> bool {
+    mode.create_parent_chain()
+        .iter()
+        .any(|x| x.get_surreal_id() == ancestor.get_surreal_id())
+}
+
+enum NewParent<'e> {
+    TopLevel,
+    Mode(&'e ModeNode<'e>),
+}
+
+impl Display for NewParent<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NewParent::TopLevel => write!(f, "Make it a top level mode"),
+            NewParent::Mode(mode) => write!(
+                f,
+                "{}",
+                DisplayModeNode::new(mode, DisplayFormat::SingleLine)
+            ),
+        }
+    }
+}
+
+async fn move_under_a_different_parent(
+    mode: &ModeNode<'_>,
+    all_mode_nodes: &[ModeNode<'_>],
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    //A mode cannot be moved under itself or one of its own smaller modes
+    let mut list = vec![NewParent::TopLevel];
+    list.extend(
+        all_mode_nodes
+            .iter()
+            .filter(|x| !is_under(x, mode))
+            .map(NewParent::Mode),
+    );
+    let selection = Select::new("Select the new parent mode|", list).prompt();
+    let parent = match selection {
+        Ok(NewParent::TopLevel) => None,
+        Ok(NewParent::Mode(parent)) => Some(parent.get_surreal_id().clone()),
+        Err(InquireError::OperationCanceled) => return Ok(()),
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    };
+    DataLayerCommands::change(
+        send_to_data_storage_layer,
+        DataChange::UpdateModeParent(mode.get_surreal_id().clone(), parent),
+    )
+    .await
+    .or_else(report_not_saved)
+}
+
+enum Direction {
+    Up,
+    Down,
+}
+
+async fn move_among_siblings(
+    mode: &ModeNode<'_>,
+    direction: Direction,
+    all_mode_nodes: &[ModeNode<'_>],
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let parent = mode.get_mode().get_parent();
+    let mut siblings = all_mode_nodes
+        .iter()
+        .map(|x| x.get_mode())
+        .filter(|x| x.get_parent() == parent)
+        .collect::<Vec<_>>();
+    siblings.sort_by(|a, b| compare_siblings(a, b));
+    let index = siblings
+        .iter()
+        .position(|x| x.get_surreal_id() == mode.get_surreal_id())
+        .expect("A mode is one of its own siblings");
+    let swap_with = match direction {
+        Direction::Up => index.checked_sub(1),
+        Direction::Down => Some(index + 1).filter(|x| *x < siblings.len()),
+    };
+    let Some(swap_with) = swap_with else {
+        println!("It is already as far as it can go");
+        return Ok(());
+    };
+    siblings.swap(index, swap_with);
+    DataLayerCommands::change(
+        send_to_data_storage_layer,
+        DataChange::UpdateModeOrder(
+            siblings
+                .into_iter()
+                .map(|x| x.get_surreal_id().clone())
+                .collect(),
+        ),
+    )
+    .await
+    .or_else(report_not_saved)
+}
+
+enum SmallerModes {
+    MoveUp,
+    Delete,
+}
+
+impl Display for SmallerModes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SmallerModes::MoveUp => write!(f, "Move its smaller modes up to take its place"),
+            SmallerModes::Delete => write!(f, "Delete its smaller modes as well"),
+        }
+    }
+}
+
+async fn delete_mode(
+    mode: &ModeNode<'_>,
+    all_mode_nodes: &[ModeNode<'_>],
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let smaller_modes = all_mode_nodes
+        .iter()
+        .filter(|x| x.get_surreal_id() != mode.get_surreal_id() && is_under(x, mode))
+        .collect::<Vec<_>>();
+    let delete_smaller_modes = if smaller_modes.is_empty() {
+        false
+    } else {
+        println!("Smaller modes:");
+        for smaller_mode in smaller_modes.iter() {
+            println!(
+                "  {}",
+                DisplayModeNode::new(smaller_mode, DisplayFormat::SingleLine)
+            );
+        }
+        let list = vec![SmallerModes::MoveUp, SmallerModes::Delete];
+        match Select::new("What should happen to its smaller modes?|", list).prompt() {
+            Ok(SmallerModes::MoveUp) => false,
+            Ok(SmallerModes::Delete) => true,
+            Err(InquireError::OperationCanceled) => return Ok(()),
+            Err(InquireError::OperationInterrupted) => return Err(()),
+            Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
         }
+    };
+    match Confirm::new(&format!(
+        "Permanently delete the mode \"{}\"?",
+        mode.get_name()
+    ))
+    .with_default(false)
+    .prompt()
+    {
+        Ok(true) => {}
+        Ok(false) | Err(InquireError::OperationCanceled) => return Ok(()),
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
     }
+    DataLayerCommands::change(
+        send_to_data_storage_layer,
+        DataChange::DeleteMode {
+            mode: mode.get_surreal_id().clone(),
+            delete_smaller_modes,
+        },
+    )
+    .await
+    .or_else(report_not_saved)
 }
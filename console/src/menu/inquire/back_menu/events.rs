@@ -0,0 +1,280 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use fundu::{CustomDurationParser, CustomTimeUnit, SaturatingInto, TimeUnit};
+use inquire::{InquireError, Select, Text};
+use lazy_static::lazy_static;
+use surrealdb::opt::RecordId;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    base_data::{BaseData, event::Event, item::Item},
+    data_storage::{
+        delete_and_merge::{plan_delete, plan_merge},
+        surrealdb_layer::{
+            data_layer_commands::{DataChange, DataLayerCommands},
+            surreal_item::SurrealDependency,
+            surreal_tables::SurrealTables,
+        },
+    },
+    display::{display_duration::DisplayDuration, display_item::DisplayItem},
+    menu::inquire::{
+        do_now_list_menu::do_now_list_single_item::delete_or_merge::confirm_planned_changes,
+        report_not_saved,
+    },
+};
+
+struct EventListEntry<'e> {
+    event: &'e Event<'e>,
+    waiting: usize,
+}
+
+impl Display for EventListEntry<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let state = if self.event.is_active() {
+            "waiting to be triggered"
+        } else {
+            "triggered"
+        };
+        write!(f, "{} ({}", self.event.get_summary(), state)?;
+        if let Some(untrigger_after) = self.event.get_untrigger_after() {
+            write!(
+                f,
+                ", happens again after {}",
+                DisplayDuration::new(&untrigger_after)
+            )?;
+        }
+        write!(f, ", {} waiting)", self.waiting)
+    }
+}
+
+enum EventAction {
+    ListWaiting,
+    Rename,
+    MakeRecurring,
+    StopRecurring,
+    Merge,
+    Delete,
+}
+
+impl Display for EventAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EventAction::ListWaiting => write!(f, "List the items waiting on this"),
+            EventAction::Rename => write!(f, "Rename"),
+            EventAction::MakeRecurring => {
+                write!(f, "🔁  Set how long until it can happen again")
+            }
+            EventAction::StopRecurring => write!(f, "Stop it happening again, once is enough"),
+            EventAction::Merge => write!(f, "🔀  Merge with a duplicate event"),
+            EventAction::Delete => write!(f, "🗑️  Delete"),
+        }
+    }
+}
+
+impl EventAction {
+    fn make_list(event: &Event) -> Vec<Self> {
+        let mut list = vec![Self::ListWaiting, Self::Rename, Self::MakeRecurring];
+        if event.get_untrigger_after().is_some() {
+            list.push(Self::StopRecurring);
+        }
+        list.push(Self::Merge);
+        list.push(Self::Delete);
+        list
+    }
+}
+
+/// Lists every event with the number of items waiting on it so each one can be renamed, made recurring, merged with a
+/// duplicate or deleted. The list is shown again after each change until it is canceled.
+pub(crate) async fn present_events_menu(
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    loop {
+        let surreal_tables = SurrealTables::new(send_to_data_storage_layer)
+            .await
+            .unwrap();
+        let base_data = BaseData::new_from_surreal_tables(surreal_tables.clone(), Utc::now());
+        let mut list = base_data
+            .get_events()
+            .values()
+            .map(|event| EventListEntry {
+                event,
+                waiting: items_waiting_on(event.get_surreal_record_id(), &base_data).len(),
+            })
+            .collect::<Vec<_>>();
+        if list.is_empty() {
+            println!("There are no events, an event is made when an item is set to wait on one");
+            return Ok(());
+        }
+        list.sort_by_key(|x| x.event.get_summary().to_lowercase());
+
+        let selection = Select::new("Select an event|", list)
+            .with_page_size(16)
+            .prompt();
+        match selection {
+            Ok(selected) => {
+                present_event_selected(
+                    selected.event,
+                    &base_data,
+                    &surreal_tables,
+                    send_to_data_storage_layer,
+                )
+                .await?
+            }
+            Err(InquireError::OperationCanceled) => return Ok(()),
+            Err(InquireError::OperationInterrupted) => return Err(()),
+            Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+        }
+    }
+}
+
+/// Finished items are left out, they are not waiting anymore
+fn items_waiting_on<'b>(event: &RecordId, base_data: &'b BaseData) -> Vec<&'b Item<'b>> {
+    let mut waiting = base_data
+        .get_active_items()
+        .iter()
+        .filter(|x| {
+            x.get_surreal_dependencies()
+                .iter()
+                .any(|x| matches!(x, SurrealDependency::AfterEvent(id) if id == event))
+        })
+        .copied()
+        .collect::<Vec<_>>();
+    waiting.sort_by_key(|x| x.get_summary().to_lowercase());
+    waiting
+}
+
+async fn present_event_selected(
+    event: &Event<'_>,
+    base_data: &BaseData,
+    surreal_tables: &Arc<SurrealTables>,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let id = event.get_surreal_record_id().clone();
+    let selection = Select::new("What should be done?|", EventAction::make_list(event)).prompt();
+    let change = match selection {
+        Ok(EventAction::ListWaiting) => {
+            let waiting = items_waiting_on(&id, base_data);
+            println!();
+            if waiting.is_empty() {
+                println!("No items are waiting on \"{}\"", event.get_summary());
+            } else {
+                println!("Waiting on \"{}\":", event.get_summary());
+                for item in waiting {
+                    println!("  {}", DisplayItem::new(item));
+                }
+            }
+            println!();
+            return Ok(());
+        }
+        Ok(EventAction::Rename) => {
+            match Text::new("Enter the new name for this event|")
+                .with_initial_value(event.get_summary())
+                .prompt()
+            {
+                Ok(summary) if summary.trim().is_empty() => {
+                    println!("The name cannot be empty");
+                    return Ok(());
+                }
+                Ok(summary) => DataChange::UpdateEventSummary(id, summary),
+                Err(InquireError::OperationCanceled) => return Ok(()),
+                Err(InquireError::OperationInterrupted) => return Err(()),
+                Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+            }
+        }
+        Ok(EventAction::MakeRecurring) => match ask_for_untrigger_after()? {
+            Some(untrigger_after) => {
+                DataChange::UpdateEventUntriggerAfter(id, Some(untrigger_after.into()))
+            }
+            None => return Ok(()),
+        },
+        Ok(EventAction::StopRecurring) => DataChange::UpdateEventUntriggerAfter(id, None),
+        Ok(EventAction::Merge) => {
+            let list = base_data
+                .get_events()
+                .values()
+                .filter(|x| *x.get_surreal_record_id() != id)
+                .map(|event| EventListEntry {
+                    event,
+                    waiting: items_waiting_on(event.get_surreal_record_id(), base_data).len(),
+                })
+                .collect::<Vec<_>>();
+            let selection = Select::new(
+                "Which event is a duplicate of this one? This event keeps its name|",
+                list,
+            )
+            .prompt();
+            let merge = match selection {
+                Ok(merge) => merge.event.get_surreal_record_id().clone(),
+                Err(InquireError::OperationCanceled | InquireError::InvalidConfiguration(_)) => {
+                    return Ok(());
+                }
+                Err(InquireError::OperationInterrupted) => return Err(()),
+                Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+            };
+            let Some(planned) = plan_merge(surreal_tables, &id, &merge) else {
+                println!("One of these events is no longer in the database");
+                return Ok(());
+            };
+            if !confirm_planned_changes(&planned, "Merge these events?")? {
+                return Ok(());
+            }
+            DataChange::MergeEvents { keep: id, merge }
+        }
+        Ok(EventAction::Delete) => {
+            let Some(planned) = plan_delete(surreal_tables, &id) else {
+                println!("This event is no longer in the database");
+                return Ok(());
+            };
+            if !confirm_planned_changes(&planned, "Permanently delete this event?")? {
+                return Ok(());
+            }
+            DataChange::DeleteEvent(id)
+        }
+        Err(InquireError::OperationCanceled) => return Ok(()),
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    };
+    DataLayerCommands::change(send_to_data_storage_layer, change)
+        .await
+        .or_else(report_not_saved)
+}
+
+/// None if the user canceled
+fn ask_for_untrigger_after() -> Result<Option<Duration>, ()> {
+    lazy_static! {
+        static ref relative_parser: CustomDurationParser<'static> = CustomDurationParser::builder()
+            .allow_time_unit_delimiter()
+            .number_is_optional()
+            .time_units(&[
+                CustomTimeUnit::with_default(
+                    TimeUnit::Minute,
+                    &["m", "min", "mins", "minute", "minutes"]
+                ),
+                CustomTimeUnit::with_default(TimeUnit::Hour, &["h", "hour", "hours"]),
+                CustomTimeUnit::with_default(TimeUnit::Day, &["d", "day", "days"]),
+                CustomTimeUnit::with_default(TimeUnit::Week, &["w", "week", "weeks"]),
+            ])
+            .build();
+    }
+
+    loop {
+        let untrigger_after = Text::new("How long after it is triggered does it go back to waiting to happen again? (Examples: \"12h\", \"1day\", \"2weeks\")\n|").prompt();
+        match untrigger_after {
+            Ok(untrigger_after) => match relative_parser.parse(&untrigger_after) {
+                Ok(untrigger_after) => return Ok(Some(untrigger_after.saturating_into())),
+                Err(_) => {
+                    println!("Invalid duration, please try again");
+                    println!();
+                }
+            },
+            Err(InquireError::OperationCanceled) => return Ok(None),
+            Err(InquireError::OperationInterrupted) => return Err(()),
+            Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+        }
+    }
+}
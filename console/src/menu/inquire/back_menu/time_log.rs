@@ -0,0 +1,270 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Local, Utc};
+use inquire::{Confirm, InquireError, Select, Text};
+use surrealdb::{opt::RecordId, sql::Datetime};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    base_data::{BaseData, time_spent::TimeSpent},
+    calculated_data::CalculatedData,
+    data_storage::surrealdb_layer::{
+        data_layer_commands::{DataChange, DataLayerCommands},
+        surreal_tables::SurrealTables,
+        surreal_time_spent::{SurrealDedication, SurrealTimeSpent},
+    },
+    display::{display_duration::DisplayDuration, display_item::DisplayItem},
+    menu::inquire::{
+        do_now_list_menu::do_now_list_single_item::log_worked_on_this::{
+            ask_about_dedication, create_working_on_list,
+        },
+        parse_exact_or_relative_datetime, parse_exact_or_relative_datetime_help_string,
+        report_not_saved,
+    },
+};
+
+const TIME_FORMAT: &str = "%a %d %b %Y %I:%M%p";
+
+struct TimeLogEntry<'e> {
+    time_spent: &'e SurrealTimeSpent,
+    surreal_tables: &'e SurrealTables,
+}
+
+impl Display for TimeLogEntry<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let when_started: DateTime<Local> = self.time_spent.when_started.clone().into();
+        let when_stopped: DateTime<Local> = self.time_spent.when_stopped.clone().into();
+        write!(
+            f,
+            "{} to {} ({})",
+            when_started.format(TIME_FORMAT),
+            when_stopped.format("%I:%M%p"),
+            DisplayDuration::new(TimeSpent::new(self.time_spent).get_duration())
+        )?;
+        match self.time_spent.dedication {
+            Some(SurrealDedication::PrimaryTask) => write!(f, " primary")?,
+            Some(SurrealDedication::BackgroundTask) => write!(f, " background")?,
+            None => {}
+        }
+        //The first one is what was worked on, the rest are its parents
+        let worked_on = self.time_spent.working_on.first().map(|x| {
+            self.surreal_tables
+                .surreal_items
+                .iter()
+                .find(|item| item.id.as_ref() == Some(x.get_record_id()))
+                .map_or("Item no longer exists", |item| item.summary.as_str())
+        });
+        write!(f, ": {}", worked_on.unwrap_or("Nothing"))
+    }
+}
+
+enum TimeLogCorrection {
+    ChangeWhenStarted,
+    ChangeWhenStopped,
+    ChangeWhatItWasSpentOn,
+    ChangeDedication,
+    SplitInTwo,
+    Delete,
+}
+
+impl Display for TimeLogCorrection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeLogCorrection::ChangeWhenStarted => write!(f, "Change when it started"),
+            TimeLogCorrection::ChangeWhenStopped => write!(f, "Change when it stopped"),
+            TimeLogCorrection::ChangeWhatItWasSpentOn => write!(f, "Change what it was spent on"),
+            TimeLogCorrection::ChangeDedication => write!(f, "Change the dedication"),
+            TimeLogCorrection::SplitInTwo => write!(f, "Split it in two"),
+            TimeLogCorrection::Delete => write!(f, "🗑️  Delete it"),
+        }
+    }
+}
+
+impl TimeLogCorrection {
+    fn make_list() -> Vec<Self> {
+        vec![
+            Self::ChangeWhenStarted,
+            Self::ChangeWhenStopped,
+            Self::ChangeWhatItWasSpentOn,
+            Self::ChangeDedication,
+            Self::SplitInTwo,
+            Self::Delete,
+        ]
+    }
+}
+
+/// Lists the time spent that started between `start` and `end`, only on `item` if one is given, so that it can be
+/// corrected. The list is shown again after each correction until it is canceled.
+pub(crate) async fn present_time_log(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    item: Option<&RecordId>,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    loop {
+        let surreal_tables = SurrealTables::new(send_to_data_storage_layer)
+            .await
+            .unwrap();
+        let mut list = surreal_tables
+            .surreal_time_spent_log
+            .iter()
+            .filter(|x| {
+                let when_started: DateTime<Utc> = x.when_started.clone().into();
+                when_started >= start && when_started <= end
+            })
+            .filter(|x| {
+                item.is_none_or(|item| {
+                    x.working_on
+                        .iter()
+                        .any(|action| action.get_record_id() == item)
+                })
+            })
+            .map(|time_spent| TimeLogEntry {
+                time_spent,
+                surreal_tables: &surreal_tables,
+            })
+            .collect::<Vec<_>>();
+        if list.is_empty() {
+            println!("No time was logged in this range");
+            return Ok(());
+        }
+        list.sort_by(|a, b| a.time_spent.when_started.cmp(&b.time_spent.when_started));
+
+        let selection = Select::new("Select the time spent to correct|", list)
+            .with_page_size(16)
+            .prompt();
+        match selection {
+            Ok(selected) => {
+                correct_time_spent(
+                    selected.time_spent,
+                    &surreal_tables,
+                    send_to_data_storage_layer,
+                )
+                .await?
+            }
+            Err(InquireError::OperationCanceled) => return Ok(()),
+            Err(InquireError::OperationInterrupted) => return Err(()),
+            Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+        }
+    }
+}
+
+async fn correct_time_spent(
+    time_spent: &SurrealTimeSpent,
+    surreal_tables: &Arc<SurrealTables>,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let id = time_spent.id.clone().expect("Read from the database");
+    let selection =
+        Select::new("What should be corrected?|", TimeLogCorrection::make_list()).prompt();
+    let change = match selection {
+        Ok(TimeLogCorrection::ChangeWhenStarted) => {
+            let Some(when_started) = ask_for_time("When did it start?", &time_spent.when_started)?
+            else {
+                return Ok(());
+            };
+            DataChange::UpdateTimeSpentTimes {
+                time_spent: id,
+                when_started,
+                when_stopped: time_spent.when_stopped.clone(),
+            }
+        }
+        Ok(TimeLogCorrection::ChangeWhenStopped) => {
+            let Some(when_stopped) = ask_for_time("When did it stop?", &time_spent.when_stopped)?
+            else {
+                return Ok(());
+            };
+            DataChange::UpdateTimeSpentTimes {
+                time_spent: id,
+                when_started: time_spent.when_started.clone(),
+                when_stopped,
+            }
+        }
+        Ok(TimeLogCorrection::ChangeWhatItWasSpentOn) => {
+            let base_data = BaseData::new_from_surreal_tables(surreal_tables.clone(), Utc::now());
+            let calculated_data = CalculatedData::new_from_base_data(base_data);
+            let items_status = calculated_data.get_items_status();
+            let mut items = items_status.values().collect::<Vec<_>>();
+            items.sort_by_key(|x| x.get_item().get_summary().to_lowercase());
+            let list = items
+                .into_iter()
+                .map(|x| DisplayItem::new(x.get_item()))
+                .collect::<Vec<_>>();
+            let selection = Select::new("What was the time spent on?|", list)
+                .with_page_size(16)
+                .prompt();
+            match selection {
+                Ok(selected) => {
+                    let item_status = items_status
+                        .get(selected.get_surreal_record_id())
+                        .expect("It was just picked from these items");
+                    DataChange::UpdateTimeSpentWorkingOn(id, create_working_on_list(item_status))
+                }
+                Err(InquireError::OperationCanceled | InquireError::InvalidConfiguration(_)) => {
+                    return Ok(());
+                }
+                Err(InquireError::OperationInterrupted) => return Err(()),
+                Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+            }
+        }
+        Ok(TimeLogCorrection::ChangeDedication) => match ask_about_dedication()? {
+            Some(dedication) => DataChange::UpdateTimeSpentDedication(id, Some(dedication)),
+            None => return Ok(()),
+        },
+        Ok(TimeLogCorrection::SplitInTwo) => {
+            let Some(at) = ask_for_time(
+                "When should it be split? The first part is everything before this time and the second part everything after.",
+                &time_spent.when_started,
+            )?
+            else {
+                return Ok(());
+            };
+            DataChange::SplitTimeSpent { time_spent: id, at }
+        }
+        Ok(TimeLogCorrection::Delete) => {
+            match Confirm::new("Permanently delete this time spent?")
+                .with_default(false)
+                .prompt()
+            {
+                Ok(true) => DataChange::DeleteTimeSpent(id),
+                Ok(false) | Err(InquireError::OperationCanceled) => return Ok(()),
+                Err(InquireError::OperationInterrupted) => return Err(()),
+                Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+            }
+        }
+        Err(InquireError::OperationCanceled) => return Ok(()),
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    };
+    DataLayerCommands::change(send_to_data_storage_layer, change)
+        .await
+        .or_else(report_not_saved)
+}
+
+/// None if the user canceled
+fn ask_for_time(question: &str, currently: &Datetime) -> Result<Option<Datetime>, ()> {
+    let currently: DateTime<Local> = currently.clone().into();
+    loop {
+        let prompt = format!(
+            "{} Currently {} (\"?\" for help)\n|",
+            question,
+            currently.format(TIME_FORMAT)
+        );
+        match Text::new(&prompt).prompt() {
+            Ok(input) => match parse_exact_or_relative_datetime(&input) {
+                Some(when) => return Ok(Some(when.with_timezone(&Utc).into())),
+                None => {
+                    println!("Invalid input. Please try again.");
+                    println!();
+                    println!("{}", parse_exact_or_relative_datetime_help_string());
+                }
+            },
+            Err(InquireError::OperationCanceled) => return Ok(None),
+            Err(InquireError::OperationInterrupted) => return Err(()),
+            Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub(crate) mod agenda;
 pub(crate) mod change_mode;
 pub(crate) mod classify_item;
 pub(crate) mod do_now_list_single_item;
@@ -7,8 +8,9 @@ pub(crate) mod pick_what_should_be_done_first;
 pub(crate) mod review_item;
 pub(crate) mod search;
 
-use std::{fmt::Display, iter::once};
+use std::{fmt::Display, iter::once, sync::Arc};
 
+use agenda::present_agenda_menu;
 use ahash::{HashMap, HashSet};
 use better_term::Style;
 use change_mode::present_change_mode_menu;
@@ -27,9 +29,10 @@ use tokio::sync::mpsc::Sender;
 
 use crate::{
     base_data::{BaseData, event::Event},
-    calculated_data::CalculatedData,
+    calculated_data::{CalculatedData, node_shapes::NodeShapes},
     data_storage::surrealdb_layer::{
-        data_layer_commands::DataLayerCommands, surreal_item::SurrealDependency,
+        data_layer_commands::{DataChange, DataLayerCommands, DataLayerError},
+        surreal_item::SurrealDependency,
         surreal_tables::SurrealTables,
     },
     display::{
@@ -38,6 +41,7 @@ use crate::{
         display_urgency_level_item_with_item_status::DisplayUrgencyLevelItemWithItemStatus,
     },
     menu::inquire::back_menu::present_back_menu,
+    menu::inquire::report_not_saved,
     node::{
         Filter,
         action_with_item_status::ActionWithItemStatus,
@@ -55,15 +59,17 @@ use self::do_now_list_single_item::{
     present_do_now_list_item_selected, present_is_person_or_group_around_menu,
 };
 
-use super::back_menu::capture;
+use super::back_menu::{capture, undo_last_change};
 
 pub(crate) enum InquireDoNowListItem<'e> {
     CaptureNewItem,
     Search,
+    Agenda,
     ChangeMode(&'e CurrentMode),
     DeclareEvent { waiting_on: Vec<&'e Event<'e>> },
     DoNowListSingleItem(&'e UrgencyLevelItemWithItemStatus<'e>),
     RefreshList(DateTime<Local>),
+    UndoLastChange,
     BackMenu,
     Help,
 }
@@ -73,6 +79,7 @@ impl Display for InquireDoNowListItem<'_> {
         match self {
             Self::CaptureNewItem => write!(f, "🗬   Capture New Item"),
             Self::Search => write!(f, "🔍  Search"),
+            Self::Agenda => write!(f, "📅  Agenda"),
             Self::DoNowListSingleItem(item) => {
                 let display = DisplayUrgencyLevelItemWithItemStatus::new(
                     item,
@@ -89,6 +96,15 @@ impl Display for InquireDoNowListItem<'_> {
                 mode_icons.push_str("U:");
                 let urgency_mode_icons = turn_to_icons(current_mode.get_urgency_in_scope());
                 mode_icons.push_str(&urgency_mode_icons);
+                match current_mode.get_selected_modes().len() {
+                    0 => {}
+                    1 => mode_icons.push_str("  & 1 of your modes"),
+                    count => mode_icons.push_str(&format!("  & {} of your modes", count)),
+                }
+                if !current_mode.get_selected_tags().is_empty() {
+                    mode_icons.push_str("  & 🏷️ ");
+                    mode_icons.push_str(&current_mode.get_selected_tags().join(", "));
+                }
                 write!(f, "🧭  Change Mode - Currently: {}", mode_icons)
             }
             Self::RefreshList(bullet_list_created) => write!(
@@ -109,6 +125,7 @@ impl Display for InquireDoNowListItem<'_> {
                     write!(f, "⚡  Waiting on: {} events", waiting_on.len())
                 }
             }
+            Self::UndoLastChange => write!(f, "↩️  Undo last change"),
             Self::BackMenu => write!(f, "🏠  Back Menu"),
             Self::Help => write!(f, "❓  Help"),
         }
@@ -154,6 +171,7 @@ impl<'a> InquireDoNowListItem<'a> {
                 do_now_list_created.into()
             )),
             once(InquireDoNowListItem::Search),
+            once(InquireDoNowListItem::Agenda),
         );
         let iter: Box<dyn Iterator<Item = InquireDoNowListItem<'a>>> = if !waiting_on.is_empty() {
             Box::new(iter.chain(once(InquireDoNowListItem::DeclareEvent { waiting_on })))
@@ -167,6 +185,7 @@ impl<'a> InquireDoNowListItem<'a> {
             item_action
                 .iter()
                 .map(InquireDoNowListItem::DoNowListSingleItem),
+            once(InquireDoNowListItem::UndoLastChange),
             once(InquireDoNowListItem::BackMenu),
             once(InquireDoNowListItem::Help),
         )
@@ -185,10 +204,28 @@ pub(crate) async fn present_normal_do_now_list_menu(
     if elapsed > chrono::Duration::try_seconds(1).expect("valid") {
         println!("Slow to get data from database. Time taken: {}", elapsed);
     }
+    present_do_now_list_menu_for_surreal_tables(
+        surreal_tables,
+        &mut NodeShapes::default(),
+        None,
+        send_to_data_storage_layer,
+    )
+    .await
+}
+
+/// For when the caller already has the latest tables, for example from `LatestSurrealTables`, and keeps the shapes of
+/// the items from one call to the next with `changed` being the records changed since the last call
+pub(crate) async fn present_do_now_list_menu_for_surreal_tables(
+    surreal_tables: Arc<SurrealTables>,
+    node_shapes: &mut NodeShapes,
+    changed: Option<&[RecordId]>,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
     let now = Utc::now();
     let base_data = BaseData::new_from_surreal_tables(surreal_tables, now);
     let base_data_checkpoint = Utc::now();
-    let calculated_data = CalculatedData::new_from_base_data(base_data);
+    let calculated_data =
+        CalculatedData::new_from_base_data_and_shapes(base_data, node_shapes, changed);
     let calculated_data_checkpoint = Utc::now();
     let do_now_list = DoNowList::new_do_now_list(calculated_data, &now);
     let finish_checkpoint = Utc::now();
@@ -220,7 +257,8 @@ pub(crate) fn present_upcoming(do_now_list: &DoNowList) {
             let display_scheduled_item = DisplayScheduledItem::new(scheduled_item);
             println!("{}", display_scheduled_item);
         }
-    } else if upcoming.has_conflicts() {
+    }
+    if upcoming.has_conflicts() {
         let bold_text = Style::new().bold();
         let not_bold_text = Style::new();
         println!(
@@ -293,9 +331,9 @@ pub(crate) async fn present_do_now_list_menu(
             .iter()
             .any(|x| matches!(x, InquireDoNowListItem::DeclareEvent { .. }))
     {
-        5
+        6
     } else {
-        4
+        5
     };
     let selected = Select::new(
         "Select from this \"Do Now\" list (default choice is recommended)|",
@@ -311,8 +349,16 @@ pub(crate) async fn present_do_now_list_menu(
         Ok(InquireDoNowListItem::Search) => {
             present_search_menu(do_now_list, send_to_data_storage_layer).await
         }
+        Ok(InquireDoNowListItem::Agenda) => {
+            present_agenda_menu(do_now_list, send_to_data_storage_layer).await
+        }
         Ok(InquireDoNowListItem::ChangeMode(current_mode)) => {
-            present_change_mode_menu(current_mode, send_to_data_storage_layer).await
+            present_change_mode_menu(
+                current_mode,
+                do_now_list.get_base_data(),
+                send_to_data_storage_layer,
+            )
+            .await
         }
         Ok(InquireDoNowListItem::DeclareEvent { mut waiting_on }) => {
             waiting_on.sort_by(|a, b| b.get_last_updated().cmp(a.get_last_updated()));
@@ -358,26 +404,25 @@ pub(crate) async fn present_do_now_list_menu(
                         Ok(EventTrigger::TriggerEvent {
                             all_items_waiting_on_event,
                         }) => {
-                            //Clear the event before clearing the trigger in case it is cancelled part way through
-                            for item_waiting_on_event in all_items_waiting_on_event {
-                                send_to_data_storage_layer
-                                    .send(DataLayerCommands::RemoveItemDependency(
-                                        item_waiting_on_event.get_surreal_record_id().clone(),
+                            //A recurring event is left in place so the items wait on it again once it untriggers
+                            let mut changes = Vec::default();
+                            if event.get_untrigger_after().is_none() {
+                                changes.extend(all_items_waiting_on_event.into_iter().map(|x| {
+                                    DataChange::RemoveItemDependency(
+                                        x.get_surreal_record_id().clone(),
                                         SurrealDependency::AfterEvent(
                                             event.get_surreal_record_id().clone(),
                                         ),
-                                    ))
-                                    .await
-                                    .unwrap();
+                                    )
+                                }));
                             }
-                            send_to_data_storage_layer
-                                .send(DataLayerCommands::TriggerEvent {
-                                    event: event.get_surreal_record_id().clone(),
-                                    when: Utc::now().into(),
-                                })
+                            changes.push(DataChange::TriggerEvent {
+                                event: event.get_surreal_record_id().clone(),
+                                when: Utc::now().into(),
+                            });
+                            DataLayerCommands::transaction(send_to_data_storage_layer, changes)
                                 .await
-                                .unwrap();
-                            Ok(())
+                                .or_else(report_not_saved)
                         }
                         Ok(EventTrigger::ItemDependentOnThisEvent(item_status)) => {
                             let mut why_in_scope = HashSet::default();
@@ -481,10 +526,27 @@ pub(crate) async fn present_do_now_list_menu(
                 }
             }
         },
-        Ok(InquireDoNowListItem::RefreshList(..)) | Err(InquireError::OperationCanceled) => {
+        Ok(InquireDoNowListItem::RefreshList(..)) => {
+            match DataLayerCommands::sync(send_to_data_storage_layer).await {
+                Ok(0) => Ok(()),
+                Ok(merged) => {
+                    println!("Merged {} changes from the other devices", merged);
+                    Ok(())
+                }
+                Err(DataLayerError::Stopped) => Err(()),
+                Err(err) => {
+                    println!("{}", err);
+                    Ok(())
+                }
+            }
+        }
+        Err(InquireError::OperationCanceled) => {
             println!("Press Ctrl+C to exit");
             Ok(())
         }
+        Ok(InquireDoNowListItem::UndoLastChange) => {
+            undo_last_change(send_to_data_storage_layer).await
+        }
         Ok(InquireDoNowListItem::BackMenu) => {
             Box::pin(present_back_menu(send_to_data_storage_layer)).await
         }
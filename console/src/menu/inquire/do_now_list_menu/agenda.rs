@@ -0,0 +1,192 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    iter::once,
+};
+
+use ahash::HashSet;
+use better_term::Style;
+use chrono::{DateTime, Local, Utc};
+use inquire::{InquireError, Select};
+use itertools::chain;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    data_storage::surrealdb_layer::data_layer_commands::DataLayerCommands,
+    display::{display_duration::DisplayDuration, display_item::DisplayItem},
+    menu::inquire::do_now_list_menu::do_now_list_single_item::present_do_now_list_item_selected,
+    node::why_in_scope_and_action_with_item_status::WhyInScope,
+    systems::{
+        agenda::{Agenda, AgendaDay, AgendaEntry},
+        do_now_list::DoNowList,
+    },
+};
+
+enum AgendaSelection<'e> {
+    ReturnToDoNowList,
+    Entry(&'e AgendaEntry<'e>),
+}
+
+impl Display for AgendaSelection<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AgendaSelection::ReturnToDoNowList => write!(f, "🔙 Return to Do Now List"),
+            AgendaSelection::Entry(entry) => {
+                let start = entry.get_start().with_timezone(&Local);
+                write!(
+                    f,
+                    "{} {}",
+                    start.format("%a %d %b %I:%M%p"),
+                    DisplayEntry::new(entry)
+                )
+            }
+        }
+    }
+}
+
+/// Everything about an entry except for when it starts
+struct DisplayEntry<'e> {
+    entry: &'e AgendaEntry<'e>,
+}
+
+impl Display for DisplayEntry<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.entry {
+            AgendaEntry::Scheduled {
+                item_status,
+                end,
+                is_conflict,
+                ..
+            } => {
+                if *is_conflict {
+                    write!(f, "⚠️ ")?;
+                }
+                write!(
+                    f,
+                    "until {} {}",
+                    end.with_timezone(&Local).format("%I:%M%p"),
+                    DisplayItem::new(item_status.get_item())
+                )?;
+                if let Some((window_start, window_end)) = self.entry.get_range_window() {
+                    write!(
+                        f,
+                        " (can start from {} to {})",
+                        window_start.with_timezone(&Local).format("%a %I:%M%p"),
+                        window_end.with_timezone(&Local).format("%a %I:%M%p")
+                    )?;
+                }
+                Ok(())
+            }
+            AgendaEntry::BecomesReady { item_status, .. } => {
+                write!(
+                    f,
+                    "⏳ becomes ready {}",
+                    DisplayItem::new(item_status.get_item())
+                )
+            }
+        }
+    }
+}
+
+impl<'e> DisplayEntry<'e> {
+    fn new(entry: &'e AgendaEntry<'e>) -> Self {
+        Self { entry }
+    }
+}
+
+/// Hour by hour what is scheduled for today and the next 7 days, an entry can be selected to work on it
+pub(crate) async fn present_agenda_menu(
+    do_now_list: &DoNowList,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let now = do_now_list.get_now();
+    let upcoming = do_now_list.get_upcoming();
+    let agenda = Agenda::new(upcoming, do_now_list.get_all_items_status(), now);
+
+    if upcoming.has_conflicts() {
+        let bold_text = Style::new().bold();
+        let not_bold_text = Style::new();
+        println!(
+            "{}Scheduled items don't fit, the ones marked with ⚠️ are shown at the time that they asked for{}",
+            bold_text, not_bold_text
+        );
+    }
+    for day in agenda.get_days() {
+        print_day(day, now);
+    }
+
+    let list = chain!(
+        once(AgendaSelection::ReturnToDoNowList),
+        agenda
+            .get_days()
+            .iter()
+            .flat_map(|x| x.get_entries())
+            .map(AgendaSelection::Entry)
+    )
+    .collect::<Vec<_>>();
+    let selected = Select::new("Select an item from the agenda|", list)
+        .with_page_size(10)
+        .prompt();
+    match selected {
+        Ok(AgendaSelection::Entry(entry)) => {
+            let mut why_in_scope = HashSet::default();
+            why_in_scope.insert(WhyInScope::MenuNavigation);
+            Box::pin(present_do_now_list_item_selected(
+                entry.get_item_status(),
+                &why_in_scope,
+                Utc::now(),
+                do_now_list,
+                send_to_data_storage_layer,
+            ))
+            .await
+        }
+        Ok(AgendaSelection::ReturnToDoNowList) | Err(InquireError::OperationCanceled) => Ok(()),
+        Err(InquireError::OperationInterrupted) => Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    }
+}
+
+fn print_day(day: &AgendaDay<'_>, now: &DateTime<Utc>) {
+    let bold_text = Style::new().bold();
+    let not_bold_text = Style::new();
+    let date = day.get_date().format("%a %d %b %Y");
+    let date = if *day.get_date() == now.with_timezone(&Local).date_naive() {
+        format!("Today, {}", date)
+    } else {
+        date.to_string()
+    };
+    let committed = day.get_committed_time();
+    if committed.is_zero() {
+        println!("{}{}{}, nothing scheduled", bold_text, date, not_bold_text);
+    } else {
+        println!(
+            "{}{}{}, {} scheduled",
+            bold_text,
+            date,
+            not_bold_text,
+            DisplayDuration::new(&committed)
+        );
+    }
+
+    //Free blocks are listed in between the entries they are between
+    let mut free_blocks = day.get_free_blocks().into_iter().peekable();
+    for entry in day.get_entries() {
+        while let Some((start, end)) = free_blocks.next_if(|(start, _)| start <= entry.get_start())
+        {
+            let free = (end - start)
+                .to_std()
+                .expect("Free blocks are not negative");
+            println!(
+                "\t{} until {} free for {}",
+                start.with_timezone(&Local).format("%I:%M%p"),
+                end.with_timezone(&Local).format("%I:%M%p"),
+                DisplayDuration::new(&free)
+            );
+        }
+        println!(
+            "\t{} {}",
+            entry.get_start().with_timezone(&Local).format("%I:%M%p"),
+            DisplayEntry::new(entry)
+        );
+    }
+    println!();
+}
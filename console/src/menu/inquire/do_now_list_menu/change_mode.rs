@@ -1,17 +1,24 @@
 use std::fmt::{Display, Formatter};
 
-use inquire::InquireError;
+use inquire::{InquireError, MultiSelect};
+use surrealdb::opt::RecordId;
 use tokio::sync::mpsc::Sender;
 
 use crate::{
+    base_data::{BaseData, mode::Mode},
     data_storage::surrealdb_layer::{
-        data_layer_commands::DataLayerCommands,
+        data_layer_commands::{DataChange, DataLayerCommands},
         surreal_current_mode::{NewCurrentMode, SurrealSelectedSingleMode},
     },
+    display::display_mode_node::DisplayModeNode,
     menu::inquire::do_now_list_menu::present_normal_do_now_list_menu,
+    menu::inquire::report_not_saved,
+    node::mode_node::ModeNode,
     systems::do_now_list::current_mode::{CurrentMode, SelectedSingleMode},
 };
 
+use super::DisplayFormat;
+
 #[derive(PartialEq, Eq)]
 pub(crate) enum InTheModeChoices {
     CoreWork,
@@ -27,8 +34,98 @@ impl Display for InTheModeChoices {
     }
 }
 
+struct ModeChoice<'s>(ModeNode<'s>);
+
+impl Display for ModeChoice<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            DisplayModeNode::new(&self.0, DisplayFormat::SingleLine)
+        )
+    }
+}
+
+/// Picks from the modes made on the mode configuration screen, `selected` starts out picked. None if the user canceled.
+pub(crate) fn select_modes(
+    message: &str,
+    all_modes: &[Mode<'_>],
+    selected: &[RecordId],
+) -> Result<Option<Vec<RecordId>>, ()> {
+    let mut choices = all_modes
+        .iter()
+        .map(|mode| ModeChoice(ModeNode::new(mode, all_modes)))
+        .collect::<Vec<_>>();
+    if choices.is_empty() {
+        return Ok(Some(Vec::default()));
+    }
+    choices.sort_by_cached_key(|x| x.to_string());
+    let default = choices
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| selected.contains(x.0.get_surreal_id()))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let selections = MultiSelect::new(message, choices)
+        .with_default(&default)
+        .with_page_size(16)
+        .prompt();
+    match selections {
+        Ok(selections) => Ok(Some(
+            selections
+                .into_iter()
+                .map(|x| x.0.get_surreal_id().clone())
+                .collect(),
+        )),
+        Err(InquireError::OperationCanceled) => Ok(None),
+        Err(InquireError::OperationInterrupted) => Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    }
+}
+
+/// None if the user canceled
+fn select_tags(
+    current_mode: &CurrentMode,
+    base_data: &BaseData,
+) -> Result<Option<Vec<String>>, ()> {
+    //A selected tag stays a choice even when nothing unfinished has it anymore
+    let mut choices = base_data
+        .get_tags_in_use()
+        .into_iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    for selected in current_mode.get_selected_tags() {
+        if !choices.contains(selected) {
+            choices.push(selected.clone());
+        }
+    }
+    if choices.is_empty() {
+        return Ok(Some(Vec::default()));
+    }
+    let default = choices
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| current_mode.get_selected_tags().contains(x))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let selections = MultiSelect::new(
+        "Only show items with one of these tags? Select none to show items with any tag or no tag",
+        choices,
+    )
+    .with_default(&default)
+    .with_page_size(16)
+    .prompt();
+    match selections {
+        Ok(selections) => Ok(Some(selections)),
+        Err(InquireError::OperationCanceled) => Ok(None),
+        Err(InquireError::OperationInterrupted) => Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    }
+}
+
 pub(crate) async fn present_change_mode_menu(
     current_mode: &CurrentMode,
+    base_data: &BaseData,
     send_to_data_storage_layer: &Sender<DataLayerCommands>,
 ) -> Result<(), ()> {
     let choices = vec![InTheModeChoices::CoreWork, InTheModeChoices::NonCoreWork];
@@ -113,16 +210,30 @@ pub(crate) async fn present_change_mode_menu(
         println!("Something must be selected. Try again.");
         return Box::pin(present_change_mode_menu(
             current_mode,
+            base_data,
             send_to_data_storage_layer,
         ))
         .await;
     };
 
-    let new_current_mode = NewCurrentMode::new(urgency_choice, importance_choice);
-    send_to_data_storage_layer
-        .send(DataLayerCommands::SetCurrentMode(new_current_mode))
-        .await
-        .unwrap();
+    let Some(modes) = select_modes(
+        "Which of your modes are you in? Select none to show items from every mode",
+        base_data.get_modes(),
+        current_mode.get_selected_modes(),
+    )?
+    else {
+        return Box::pin(present_normal_do_now_list_menu(send_to_data_storage_layer)).await;
+    };
+
+    let Some(tags) = select_tags(current_mode, base_data)? else {
+        return Box::pin(present_normal_do_now_list_menu(send_to_data_storage_layer)).await;
+    };
 
-    Ok(())
+    let new_current_mode = NewCurrentMode::new(urgency_choice, importance_choice, modes, tags);
+    DataLayerCommands::change(
+        send_to_data_storage_layer,
+        DataChange::SetCurrentMode(new_current_mode),
+    )
+    .await
+    .or_else(report_not_saved)
 }
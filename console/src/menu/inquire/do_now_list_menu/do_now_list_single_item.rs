@@ -1,7 +1,14 @@
+mod deadline;
+pub(crate) mod delete_or_merge;
+mod effort;
 pub(crate) mod give_this_item_a_parent;
+mod item_history;
 pub(crate) mod log_worked_on_this;
+mod notes;
+mod recurrence;
 mod something_else_should_be_done_first;
 pub(crate) mod state_a_smaller_action;
+mod tags;
 pub(crate) mod urgency_plan;
 
 use std::fmt::Display;
@@ -18,7 +25,7 @@ use crate::{
     base_data::{BaseData, item::Item},
     calculated_data::{CalculatedData, parent_lookup::ParentLookup},
     data_storage::surrealdb_layer::{
-        data_layer_commands::DataLayerCommands,
+        data_layer_commands::{DataChange, DataLayerCommands},
         surreal_item::{
             Responsibility, SurrealHowMuchIsInMyControl, SurrealItemType, SurrealMotivationKind,
         },
@@ -27,15 +34,24 @@ use crate::{
     display::{
         DisplayStyle, display_duration::DisplayDuration, display_item::DisplayItem,
         display_item_node::DisplayItemNode, display_item_type::DisplayItemType,
-        display_urgency_plan::DisplayUrgency,
+        display_recurrence::DisplayRecurrence, display_urgency_plan::DisplayUrgency,
     },
+    menu::inquire::report_not_saved,
     menu::inquire::{
-        back_menu::capture,
+        back_menu::{capture, time_log::present_time_log},
         do_now_list_menu::{
+            change_mode::select_modes,
             do_now_list_single_item::{
+                deadline::{present_deadline_menu, print_deadline},
+                delete_or_merge::{delete_this_item, merge_this_item},
+                effort::{present_estimate_menu, print_effort},
                 give_this_item_a_parent::give_this_item_a_parent,
+                item_history::present_item_history,
+                notes::{present_notes_and_links_menu, print_notes_and_links},
+                recurrence::{present_recurrence_menu, print_recurrence},
                 something_else_should_be_done_first::something_else_should_be_done_first,
-                state_a_smaller_action::state_a_smaller_action,
+                state_a_smaller_action::{pick_a_smaller_action, state_a_smaller_action},
+                tags::{present_tags_menu, print_tags},
             },
             review_item,
         },
@@ -59,6 +75,12 @@ enum DoNowListSingleItemSelection<'e> {
     CaptureNewItem,
     GiveThisItemAParent,
     ChangeReadyAndUrgencyPlan,
+    ChangeModes,
+    Deadline,
+    Estimate,
+    NotesAndLinks,
+    Tags,
+    Repeat,
     UnableToDoThisRightNow,
     SomethingElseShouldBeDoneFirst,
     ReviewItem,
@@ -72,6 +94,10 @@ enum DoNowListSingleItemSelection<'e> {
     RemoveParent(DisplayItem<'e>, &'e ItemStatus<'e>),
     SwitchToChildItem(DisplayItem<'e>, &'e ItemStatus<'e>),
     RemoveChild(DisplayItem<'e>, &'e ItemStatus<'e>),
+    History,
+    TimeLog,
+    MergeWithADuplicate,
+    DeleteThisItem,
     DebugPrintItem,
 }
 
@@ -95,6 +121,10 @@ impl Display for DoNowListSingleItemSelection<'_> {
             }
             Self::RemoveChild(child_item, _) => write!(f, "🚫 Remove action: {}", child_item),
             Self::RemoveParent(parent_item, _) => write!(f, "🚫 Remove reason: {}", parent_item),
+            Self::History => write!(f, "History"),
+            Self::TimeLog => write!(f, "Correct the time spent on this"),
+            Self::MergeWithADuplicate => write!(f, "Merge with a duplicate item"),
+            Self::DeleteThisItem => write!(f, "🗑️  Permanently delete this item"),
             Self::DebugPrintItem => write!(f, "Debug Print Item"),
             Self::SomethingElseShouldBeDoneFirst => {
                 write!(f, "Something else should be done first")
@@ -109,6 +139,12 @@ impl Display for DoNowListSingleItemSelection<'_> {
             Self::Finished => write!(f, "I finished"),
             Self::ReturnToDoNowList => write!(f, "Return to the Do Now Menu"),
             Self::ChangeReadyAndUrgencyPlan => write!(f, "Change Ready & Urgency Plan"),
+            Self::ChangeModes => write!(f, "🧭  Set which of your modes this is done in"),
+            Self::Deadline => write!(f, "⏰  Deadline"),
+            Self::Estimate => write!(f, "⏱️  Estimate effort"),
+            Self::NotesAndLinks => write!(f, "📝  Notes & Links"),
+            Self::Tags => write!(f, "🏷️  Tags"),
+            Self::Repeat => write!(f, "🔁  Repeat on a schedule"),
         }
     }
 }
@@ -181,9 +217,19 @@ impl<'e> DoNowListSingleItemSelection<'e> {
             current: item_node.get_type(),
         });
         list.push(Self::ChangeReadyAndUrgencyPlan);
+        list.push(Self::ChangeModes);
+        list.push(Self::Deadline);
+        list.push(Self::Estimate);
 
         list.extend(vec![
             Self::UpdateSummary,
+            Self::NotesAndLinks,
+            Self::Tags,
+            Self::Repeat,
+            Self::History,
+            Self::TimeLog,
+            Self::MergeWithADuplicate,
+            Self::DeleteThisItem,
             Self::DebugPrintItem,
             Self::ReturnToDoNowList,
         ]);
@@ -210,6 +256,11 @@ pub(crate) async fn present_do_now_list_item_selected(
             DisplayFormat::MultiLineTree
         )
     );
+    print_tags(menu_for.get_item_node());
+    print_deadline(menu_for.get_item());
+    print_effort(menu_for.get_item_node(), do_now_list.get_time_spent_log());
+    print_recurrence(menu_for.get_item());
+    print_notes_and_links(menu_for.get_item());
     print_completed_children(menu_for);
     print_in_progress_children(menu_for, do_now_list.get_all_items_status());
     println!();
@@ -266,26 +317,24 @@ pub(crate) async fn present_do_now_list_item_selected(
         Ok(DoNowListSingleItemSelection::GiveThisItemAParent) => {
             give_this_item_a_parent(menu_for.get_item(), false, send_to_data_storage_layer).await
         }
-        Ok(DoNowListSingleItemSelection::RemoveParent(_, selected)) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::ParentItemRemoveParent {
-                    child: menu_for.get_item().get_surreal_record_id().clone(),
-                    parent_to_remove: selected.get_item().get_surreal_record_id().clone(),
-                })
-                .await
-                .unwrap();
-            Ok(())
-        }
-        Ok(DoNowListSingleItemSelection::RemoveChild(_, selected)) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::ParentItemRemoveParent {
-                    child: selected.get_item().get_surreal_record_id().clone(),
-                    parent_to_remove: menu_for.get_item().get_surreal_record_id().clone(),
-                })
-                .await
-                .unwrap();
-            Ok(())
-        }
+        Ok(DoNowListSingleItemSelection::RemoveParent(_, selected)) => DataLayerCommands::change(
+            send_to_data_storage_layer,
+            DataChange::ParentItemRemoveParent {
+                child: menu_for.get_item().get_surreal_record_id().clone(),
+                parent_to_remove: selected.get_item().get_surreal_record_id().clone(),
+            },
+        )
+        .await
+        .or_else(report_not_saved),
+        Ok(DoNowListSingleItemSelection::RemoveChild(_, selected)) => DataLayerCommands::change(
+            send_to_data_storage_layer,
+            DataChange::ParentItemRemoveParent {
+                child: selected.get_item().get_surreal_record_id().clone(),
+                parent_to_remove: menu_for.get_item().get_surreal_record_id().clone(),
+            },
+        )
+        .await
+        .or_else(report_not_saved),
         Ok(DoNowListSingleItemSelection::UnableToDoThisRightNow) => {
             let base_data = do_now_list.get_base_data();
             present_set_ready_and_urgency_plan_menu(menu_for, base_data, send_to_data_storage_layer)
@@ -343,6 +392,52 @@ pub(crate) async fn present_do_now_list_item_selected(
             present_set_ready_and_urgency_plan_menu(menu_for, base_data, send_to_data_storage_layer)
                 .await
         }
+        Ok(DoNowListSingleItemSelection::ChangeModes) => {
+            let all_modes = do_now_list.get_base_data().get_modes();
+            if all_modes.is_empty() {
+                println!("There are no modes yet, add them from the mode configuration screen");
+                return Ok(());
+            }
+            let item = menu_for.get_item();
+            match select_modes(
+                "Which of your modes is this done in? It also shows in their smaller modes|",
+                all_modes,
+                item.get_modes(),
+            )? {
+                Some(modes) if modes != item.get_modes() => DataLayerCommands::change(
+                    send_to_data_storage_layer,
+                    DataChange::UpdateItemModes(item.get_surreal_record_id().clone(), modes),
+                )
+                .await
+                .or_else(report_not_saved),
+                Some(_) | None => Ok(()),
+            }
+        }
+        Ok(DoNowListSingleItemSelection::NotesAndLinks) => {
+            present_notes_and_links_menu(menu_for.get_item(), send_to_data_storage_layer).await
+        }
+        Ok(DoNowListSingleItemSelection::Tags) => {
+            present_tags_menu(
+                menu_for.get_item_node(),
+                do_now_list.get_base_data(),
+                send_to_data_storage_layer,
+            )
+            .await
+        }
+        Ok(DoNowListSingleItemSelection::Deadline) => {
+            present_deadline_menu(menu_for.get_item(), send_to_data_storage_layer).await
+        }
+        Ok(DoNowListSingleItemSelection::Estimate) => {
+            present_estimate_menu(
+                menu_for.get_item_node(),
+                do_now_list.get_time_spent_log(),
+                send_to_data_storage_layer,
+            )
+            .await
+        }
+        Ok(DoNowListSingleItemSelection::Repeat) => {
+            present_recurrence_menu(menu_for.get_item(), send_to_data_storage_layer).await
+        }
         Ok(DoNowListSingleItemSelection::UpdateSummary) => {
             update_item_summary(menu_for.get_item(), send_to_data_storage_layer).await?;
             //After updating the summary we want to stay on the same item with the same times
@@ -369,6 +464,46 @@ pub(crate) async fn present_do_now_list_item_selected(
         Ok(DoNowListSingleItemSelection::ParentToItem) => {
             parent_to_item(menu_for.get_item(), send_to_data_storage_layer).await
         }
+        Ok(DoNowListSingleItemSelection::History) => {
+            present_item_history(
+                menu_for.get_item(),
+                do_now_list.get_base_data(),
+                send_to_data_storage_layer,
+            )
+            .await?;
+            Box::pin(present_do_now_list_item_selected(
+                menu_for,
+                why_in_scope,
+                when_selected,
+                do_now_list,
+                send_to_data_storage_layer,
+            ))
+            .await
+        }
+        Ok(DoNowListSingleItemSelection::TimeLog) => {
+            //Merging items can bring in time spent from before this item was created so every entry is listed
+            present_time_log(
+                DateTime::<Utc>::MIN_UTC,
+                Utc::now(),
+                Some(menu_for.get_surreal_record_id()),
+                send_to_data_storage_layer,
+            )
+            .await?;
+            Box::pin(present_do_now_list_item_selected(
+                menu_for,
+                why_in_scope,
+                when_selected,
+                do_now_list,
+                send_to_data_storage_layer,
+            ))
+            .await
+        }
+        Ok(DoNowListSingleItemSelection::MergeWithADuplicate) => {
+            merge_this_item(menu_for.get_item(), send_to_data_storage_layer).await
+        }
+        Ok(DoNowListSingleItemSelection::DeleteThisItem) => {
+            delete_this_item(menu_for.get_item(), send_to_data_storage_layer).await
+        }
         Ok(DoNowListSingleItemSelection::DebugPrintItem) => {
             println!("{:?}", menu_for);
             Ok(())
@@ -569,15 +704,56 @@ async fn finish_do_now_item(
     now: DateTime<Utc>,
     send_to_data_storage_layer: &Sender<DataLayerCommands>,
 ) -> Result<(), ()> {
-    let when_this_function_was_called = now;
-    send_to_data_storage_layer
-        .send(DataLayerCommands::FinishItem {
-            item: finish_this.get_surreal_record_id().clone(),
-            when_finished: now.into(),
-        })
-        .await
-        .unwrap();
+    //The finish is not saved until something is picked below so it can be saved in one transaction with the next step
+    present_after_finish_menu(
+        finish_this,
+        why_in_scope,
+        do_now_list,
+        now,
+        false,
+        send_to_data_storage_layer,
+    )
+    .await
+}
+
+/// Saves the finish along with `changes` unless it was already saved
+async fn save_with_finish(
+    finish_this: &ItemStatus<'_>,
+    when_finished: DateTime<Utc>,
+    finish_saved: bool,
+    mut changes: Vec<DataChange>,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    if !finish_saved {
+        changes.insert(
+            0,
+            DataChange::FinishItem {
+                item: finish_this.get_surreal_record_id().clone(),
+                when_finished: when_finished.into(),
+            },
+        );
+    }
+    if let Err(err) = DataLayerCommands::transaction(send_to_data_storage_layer, changes).await {
+        return report_not_saved(err);
+    }
+    if !finish_saved && let Some(recurrence) = finish_this.get_item().get_recurrence() {
+        println!(
+            "🔁 This repeats {}, it will be back when it is due again",
+            DisplayRecurrence::new(recurrence)
+        );
+    }
+    Ok(())
+}
 
+/// `finish_saved` is false until the first thing picked from this menu saves the finish
+async fn present_after_finish_menu(
+    finish_this: &ItemStatus<'_>,
+    why_in_scope: &HashSet<WhyInScope>,
+    do_now_list: &DoNowList,
+    when_finished: DateTime<Utc>,
+    finish_saved: bool,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
     let list = FinishSelection::make_list(
         &finish_this
             .get_parents(Filter::Active)
@@ -587,7 +763,17 @@ async fn finish_do_now_item(
     let selection = Select::new("Select from the below list|", list).prompt();
 
     match selection {
-        Ok(FinishSelection::CaptureNewItem) => capture(send_to_data_storage_layer).await,
+        Ok(FinishSelection::CaptureNewItem) => {
+            save_with_finish(
+                finish_this,
+                when_finished,
+                finish_saved,
+                Vec::default(),
+                send_to_data_storage_layer,
+            )
+            .await?;
+            capture(send_to_data_storage_layer).await
+        }
         Ok(FinishSelection::CreateNextStepWithParent(parent)) => {
             let surreal_tables = SurrealTables::new(send_to_data_storage_layer)
                 .await
@@ -609,19 +795,37 @@ async fn finish_do_now_item(
                 time_spent_log,
             );
 
-            state_a_smaller_action(&updated_parent, send_to_data_storage_layer).await?;
+            let next_step =
+                pick_a_smaller_action(&updated_parent, send_to_data_storage_layer).await?;
+            save_with_finish(
+                finish_this,
+                when_finished,
+                finish_saved,
+                next_step.into_iter().collect(),
+                send_to_data_storage_layer,
+            )
+            .await?;
 
             //Recursively call as a way of creating a loop, we don't want to return to the main do now list
-            Box::pin(finish_do_now_item(
+            Box::pin(present_after_finish_menu(
                 finish_this,
                 why_in_scope,
                 do_now_list,
-                Utc::now(),
+                when_finished,
+                true,
                 send_to_data_storage_layer,
             ))
             .await
         }
         Ok(FinishSelection::GoToParent(parent)) => {
+            save_with_finish(
+                finish_this,
+                when_finished,
+                finish_saved,
+                Vec::default(),
+                send_to_data_storage_layer,
+            )
+            .await?;
             let surreal_tables = SurrealTables::new(send_to_data_storage_layer)
                 .await
                 .unwrap();
@@ -637,15 +841,31 @@ async fn finish_do_now_item(
             Box::pin(present_do_now_list_item_selected(
                 updated_parent,
                 why_in_scope,
-                when_this_function_was_called,
+                when_finished,
                 do_now_list,
                 send_to_data_storage_layer,
             ))
             .await
         }
-        Ok(FinishSelection::ReturnToDoNowList) => Ok(()),
+        Ok(FinishSelection::ReturnToDoNowList) => {
+            save_with_finish(
+                finish_this,
+                when_finished,
+                finish_saved,
+                Vec::default(),
+                send_to_data_storage_layer,
+            )
+            .await
+        }
         Err(InquireError::OperationCanceled) => {
-            todo!("This should undo the finish and put the item back to what it was before")
+            if finish_saved {
+                println!(
+                    "Other changes were made after finishing, use \"Undo last change\" to undo them one at a time"
+                );
+            } else {
+                println!("Not finished, the item is left as it was");
+            }
+            Ok(())
         }
         Err(InquireError::OperationInterrupted) => Err(()),
         Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
@@ -687,15 +907,16 @@ async fn parent_to_item(
             } else {
                 None
             };
-            send_to_data_storage_layer
-                .send(DataLayerCommands::ParentItemWithExistingItem {
+            DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::ParentItemWithExistingItem {
                     child: parent_this.get_surreal_record_id().clone(),
                     parent: item_node.get_surreal_record_id().clone(),
                     higher_importance_than_this,
-                })
-                .await
-                .unwrap();
-            Ok(())
+                },
+            )
+            .await
+            .or_else(report_not_saved)
         }
         Err(InquireError::InvalidConfiguration(_)) => {
             parent_to_new_item(parent_this, send_to_data_storage_layer).await
@@ -849,14 +1070,15 @@ pub(crate) async fn parent_to_new_item(
         }
         Ok(item_type_selection) => {
             let new_item = item_type_selection.create_new_item_prompt_user_for_summary();
-            send_to_data_storage_layer
-                .send(DataLayerCommands::ParentNewItemWithAnExistingChildItem {
+            DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::ParentNewItemWithAnExistingChildItem {
                     child: parent_this.get_surreal_record_id().clone(),
                     parent_new_item: new_item,
-                })
-                .await
-                .unwrap();
-            Ok(())
+                },
+            )
+            .await
+            .or_else(report_not_saved)
         }
         Err(InquireError::OperationCanceled) => todo!(),
         Err(InquireError::OperationInterrupted) => Err(()),
@@ -872,72 +1094,66 @@ pub(crate) async fn declare_item_type(
 
     let selection = Select::new("Select from the below list|", list).prompt();
     match selection {
-        Ok(ItemTypeSelection::Action) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::UpdateResponsibilityAndItemType(
-                    item.get_surreal_record_id().clone(),
-                    Responsibility::ProactiveActionToTake,
-                    SurrealItemType::Action,
-                ))
-                .await
-                .unwrap();
-            Ok(())
-        }
-        Ok(ItemTypeSelection::Goal) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::UpdateResponsibilityAndItemType(
-                    item.get_surreal_record_id().clone(),
-                    Responsibility::ProactiveActionToTake,
-                    SurrealItemType::Goal(SurrealHowMuchIsInMyControl::default()),
-                ))
-                .await
-                .unwrap();
-            Ok(())
-        }
-        Ok(ItemTypeSelection::MotivationCore) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::UpdateResponsibilityAndItemType(
-                    item.get_surreal_record_id().clone(),
-                    Responsibility::ReactiveBeAvailableToAct,
-                    SurrealItemType::Motivation(SurrealMotivationKind::CoreWork),
-                ))
-                .await
-                .unwrap();
-            Ok(())
-        }
-        Ok(ItemTypeSelection::MotivationNonCore) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::UpdateResponsibilityAndItemType(
-                    item.get_surreal_record_id().clone(),
-                    Responsibility::ReactiveBeAvailableToAct,
-                    SurrealItemType::Motivation(SurrealMotivationKind::NonCoreWork),
-                ))
-                .await
-                .unwrap();
-            Ok(())
-        }
-        Ok(ItemTypeSelection::MotivationNeither) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::UpdateResponsibilityAndItemType(
-                    item.get_surreal_record_id().clone(),
-                    Responsibility::ReactiveBeAvailableToAct,
-                    SurrealItemType::Motivation(SurrealMotivationKind::DoesNotFitInCoreOrNonCore),
-                ))
-                .await
-                .unwrap();
-            Ok(())
-        }
-        Ok(ItemTypeSelection::Idea) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::UpdateResponsibilityAndItemType(
-                    item.get_surreal_record_id().clone(),
-                    Responsibility::ProactiveActionToTake,
-                    SurrealItemType::IdeaOrThought,
-                ))
-                .await
-                .unwrap();
-            Ok(())
-        }
+        Ok(ItemTypeSelection::Action) => DataLayerCommands::change(
+            send_to_data_storage_layer,
+            DataChange::UpdateResponsibilityAndItemType(
+                item.get_surreal_record_id().clone(),
+                Responsibility::ProactiveActionToTake,
+                SurrealItemType::Action,
+            ),
+        )
+        .await
+        .or_else(report_not_saved),
+        Ok(ItemTypeSelection::Goal) => DataLayerCommands::change(
+            send_to_data_storage_layer,
+            DataChange::UpdateResponsibilityAndItemType(
+                item.get_surreal_record_id().clone(),
+                Responsibility::ProactiveActionToTake,
+                SurrealItemType::Goal(SurrealHowMuchIsInMyControl::default()),
+            ),
+        )
+        .await
+        .or_else(report_not_saved),
+        Ok(ItemTypeSelection::MotivationCore) => DataLayerCommands::change(
+            send_to_data_storage_layer,
+            DataChange::UpdateResponsibilityAndItemType(
+                item.get_surreal_record_id().clone(),
+                Responsibility::ReactiveBeAvailableToAct,
+                SurrealItemType::Motivation(SurrealMotivationKind::CoreWork),
+            ),
+        )
+        .await
+        .or_else(report_not_saved),
+        Ok(ItemTypeSelection::MotivationNonCore) => DataLayerCommands::change(
+            send_to_data_storage_layer,
+            DataChange::UpdateResponsibilityAndItemType(
+                item.get_surreal_record_id().clone(),
+                Responsibility::ReactiveBeAvailableToAct,
+                SurrealItemType::Motivation(SurrealMotivationKind::NonCoreWork),
+            ),
+        )
+        .await
+        .or_else(report_not_saved),
+        Ok(ItemTypeSelection::MotivationNeither) => DataLayerCommands::change(
+            send_to_data_storage_layer,
+            DataChange::UpdateResponsibilityAndItemType(
+                item.get_surreal_record_id().clone(),
+                Responsibility::ReactiveBeAvailableToAct,
+                SurrealItemType::Motivation(SurrealMotivationKind::DoesNotFitInCoreOrNonCore),
+            ),
+        )
+        .await
+        .or_else(report_not_saved),
+        Ok(ItemTypeSelection::Idea) => DataLayerCommands::change(
+            send_to_data_storage_layer,
+            DataChange::UpdateResponsibilityAndItemType(
+                item.get_surreal_record_id().clone(),
+                Responsibility::ProactiveActionToTake,
+                SurrealItemType::IdeaOrThought,
+            ),
+        )
+        .await
+        .or_else(report_not_saved),
         Ok(ItemTypeSelection::NormalHelp) => {
             ItemTypeSelection::print_normal_help();
             Box::pin(declare_item_type(item, send_to_data_storage_layer)).await
@@ -976,16 +1192,15 @@ pub(crate) async fn present_is_person_or_group_around_menu(
 
     let selection = Select::new("Select from the below list|", list).prompt();
     match selection {
-        Ok(IsAPersonOrGroupAroundSelection::Yes) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::FinishItem {
-                    item: person_or_group_node.get_surreal_record_id().clone(),
-                    when_finished: Utc::now().into(),
-                })
-                .await
-                .unwrap();
-            Ok(())
-        }
+        Ok(IsAPersonOrGroupAroundSelection::Yes) => DataLayerCommands::change(
+            send_to_data_storage_layer,
+            DataChange::FinishItem {
+                item: person_or_group_node.get_surreal_record_id().clone(),
+                when_finished: Utc::now().into(),
+            },
+        )
+        .await
+        .or_else(report_not_saved),
         Ok(IsAPersonOrGroupAroundSelection::No) => todo!(),
         Err(InquireError::OperationCanceled) => todo!(),
         Err(InquireError::OperationInterrupted) => Err(()),
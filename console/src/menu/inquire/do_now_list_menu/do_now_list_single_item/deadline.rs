@@ -0,0 +1,253 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use fundu::{CustomDurationParser, CustomTimeUnit, SaturatingInto, TimeUnit};
+use inquire::{InquireError, Select, Text};
+use lazy_static::lazy_static;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    base_data::item::Item,
+    data_storage::surrealdb_layer::{
+        data_layer_commands::{DataChange, DataLayerCommands},
+        surreal_item::{SurrealDeadline, SurrealLeadTimes},
+    },
+    display::display_deadline::DisplayDeadline,
+    menu::inquire::{
+        parse_exact_or_relative_datetime, parse_exact_or_relative_datetime_help_string,
+        report_not_saved,
+    },
+};
+
+enum DeadlineAction {
+    SetDeadline,
+    ChangeLeadTimes,
+    RemoveDeadline,
+}
+
+impl Display for DeadlineAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DeadlineAction::SetDeadline => write!(f, "⏰ Set when it is due"),
+            DeadlineAction::ChangeLeadTimes => {
+                write!(f, "Change how long before it is due it gets urgent")
+            }
+            DeadlineAction::RemoveDeadline => write!(f, "🚫 Remove the deadline"),
+        }
+    }
+}
+
+enum KeepUrgencyPlan {
+    Keep,
+    UseDeadline,
+}
+
+impl Display for KeepUrgencyPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            KeepUrgencyPlan::Keep => write!(f, "Keep the urgency plan, the deadline is only shown"),
+            KeepUrgencyPlan::UseDeadline => {
+                write!(
+                    f,
+                    "Remove the urgency plan so the deadline sets the urgency"
+                )
+            }
+        }
+    }
+}
+
+/// For the single item view, nothing is printed if the item has no deadline
+pub(crate) fn print_deadline(item: &Item<'_>) {
+    if let Some(deadline) = item.get_deadline() {
+        println!("{}", DisplayDeadline::new(deadline, item.get_now()));
+    }
+}
+
+/// As the deadline gets close the item becomes more urgent on its own, unless it has an urgency plan that was set by hand
+pub(crate) async fn present_deadline_menu(
+    item: &Item<'_>,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let mut list = vec![DeadlineAction::SetDeadline];
+    if item.get_deadline().is_some() {
+        list.push(DeadlineAction::ChangeLeadTimes);
+        list.push(DeadlineAction::RemoveDeadline);
+    }
+    let selection = Select::new("Select from the below list|", list).prompt();
+    let deadline = match selection {
+        Ok(DeadlineAction::SetDeadline) => {
+            let Some(due) = ask_for_due()? else {
+                return Ok(());
+            };
+            let lead_times = item
+                .get_deadline()
+                .map(|x| x.lead_times.clone())
+                .unwrap_or_default();
+            SurrealDeadline {
+                due: due.into(),
+                lead_times,
+            }
+        }
+        Ok(DeadlineAction::ChangeLeadTimes) => {
+            let deadline = item.get_deadline().expect("Only offered with a deadline");
+            let Some(lead_times) = ask_for_lead_times(&deadline.lead_times)? else {
+                return Ok(());
+            };
+            SurrealDeadline {
+                due: deadline.due.clone(),
+                lead_times,
+            }
+        }
+        Ok(DeadlineAction::RemoveDeadline) => {
+            return DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::UpdateItemDeadline(item.get_surreal_record_id().clone(), None),
+            )
+            .await
+            .or_else(report_not_saved);
+        }
+        Err(InquireError::OperationCanceled) => return Ok(()),
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    };
+
+    let record_id = item.get_surreal_record_id();
+    let mut changes = vec![DataChange::UpdateItemDeadline(
+        record_id.clone(),
+        Some(deadline),
+    )];
+    if item.get_surreal_urgency_plan().is_some() {
+        println!(
+            "This item has an urgency plan, which is used instead of the urgency from the deadline"
+        );
+        match Select::new(
+            "Select from the below list|",
+            vec![KeepUrgencyPlan::UseDeadline, KeepUrgencyPlan::Keep],
+        )
+        .prompt()
+        {
+            Ok(KeepUrgencyPlan::UseDeadline) => {
+                changes.push(DataChange::UpdateUrgencyPlan(record_id.clone(), None))
+            }
+            Ok(KeepUrgencyPlan::Keep) => {}
+            Err(InquireError::OperationCanceled) => return Ok(()),
+            Err(InquireError::OperationInterrupted) => return Err(()),
+            Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+        }
+    }
+    //Saved as one transaction so removing the urgency plan can be undone along with setting the deadline
+    DataLayerCommands::transaction(send_to_data_storage_layer, changes)
+        .await
+        .or_else(report_not_saved)
+}
+
+/// None if the user canceled
+fn ask_for_due() -> Result<Option<DateTime<Utc>>, ()> {
+    loop {
+        match Text::new("When is it due? (\"?\" for help)|").prompt() {
+            Ok(due) => match parse_exact_or_relative_datetime(&due) {
+                Some(due) => return Ok(Some(due.into())),
+                None => {
+                    println!("Invalid input. Please try again.");
+                    println!();
+                    println!("{}", parse_exact_or_relative_datetime_help_string());
+                }
+            },
+            Err(InquireError::OperationCanceled) => return Ok(None),
+            Err(InquireError::OperationInterrupted) => return Err(()),
+            Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+        }
+    }
+}
+
+/// None if the user canceled. Each step must come no earlier than the step before it.
+fn ask_for_lead_times(current: &SurrealLeadTimes) -> Result<Option<SurrealLeadTimes>, ()> {
+    let Some(maybe_urgent) = ask_for_lead_time(
+        "How long before it is due does it become 🟡 maybe urgent?",
+        current.maybe_urgent.into(),
+    )?
+    else {
+        return Ok(None);
+    };
+    let Some(definitely_urgent) = ask_for_lead_time(
+        "How long before it is due does it become 🔴 definitely urgent?",
+        current.definitely_urgent.into(),
+    )?
+    else {
+        return Ok(None);
+    };
+    let Some(more_urgent_than_mode) = ask_for_lead_time(
+        "How long before it is due does it become 🔥 more urgent than the mode?",
+        current.more_urgent_than_mode.into(),
+    )?
+    else {
+        return Ok(None);
+    };
+    if maybe_urgent < definitely_urgent || definitely_urgent < more_urgent_than_mode {
+        println!(
+            "Each step must be the same as or closer to when it is due than the step before it"
+        );
+        return Ok(None);
+    }
+    Ok(Some(SurrealLeadTimes {
+        maybe_urgent: maybe_urgent.into(),
+        definitely_urgent: definitely_urgent.into(),
+        more_urgent_than_mode: more_urgent_than_mode.into(),
+    }))
+}
+
+fn ask_for_lead_time(message: &str, current: Duration) -> Result<Option<Duration>, ()> {
+    lazy_static! {
+        static ref relative_parser: CustomDurationParser<'static> = CustomDurationParser::builder()
+            .allow_time_unit_delimiter()
+            .number_is_optional()
+            .time_units(&[
+                CustomTimeUnit::with_default(
+                    TimeUnit::Minute,
+                    &["m", "min", "mins", "minute", "minutes"]
+                ),
+                CustomTimeUnit::with_default(TimeUnit::Hour, &["h", "hour", "hours"]),
+                CustomTimeUnit::with_default(TimeUnit::Day, &["d", "day", "days"]),
+                CustomTimeUnit::with_default(TimeUnit::Week, &["w", "week", "weeks"]),
+            ])
+            .build();
+    }
+    let current = as_input(current);
+    loop {
+        match Text::new(&format!(
+            "{} (Examples: \"4h\", \"2d\", \"1w\")\n|",
+            message
+        ))
+        .with_default(&current)
+        .prompt()
+        {
+            Ok(lead_time) => match relative_parser.parse(&lead_time) {
+                Ok(lead_time) => return Ok(Some(lead_time.saturating_into())),
+                Err(_) => {
+                    println!("Invalid duration, please try again");
+                    println!();
+                }
+            },
+            Err(InquireError::OperationCanceled) => return Ok(None),
+            Err(InquireError::OperationInterrupted) => return Err(()),
+            Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+        }
+    }
+}
+
+/// In the largest whole unit that the parser above accepts
+fn as_input(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    if minutes % (60 * 24 * 7) == 0 && minutes != 0 {
+        format!("{}w", minutes / (60 * 24 * 7))
+    } else if minutes % (60 * 24) == 0 && minutes != 0 {
+        format!("{}d", minutes / (60 * 24))
+    } else if minutes % 60 == 0 {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
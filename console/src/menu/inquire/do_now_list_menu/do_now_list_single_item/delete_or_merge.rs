@@ -0,0 +1,141 @@
+use std::fmt::{self, Display, Formatter};
+
+use chrono::Utc;
+use inquire::{Confirm, InquireError, Select};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    base_data::{BaseData, item::Item},
+    data_storage::{
+        delete_and_merge::{PlannedChange, plan_delete, plan_merge},
+        surrealdb_layer::data_layer_commands::{DataChange, DataLayerCommands, DataLayerError},
+    },
+    display::display_item::DisplayItem,
+    menu::inquire::report_not_saved,
+};
+
+enum KeepSelection<'e> {
+    ThisItem(DisplayItem<'e>),
+    OtherItem(DisplayItem<'e>),
+}
+
+impl Display for KeepSelection<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            KeepSelection::ThisItem(item) | KeepSelection::OtherItem(item) => {
+                write!(f, "{}", item)
+            }
+        }
+    }
+}
+
+pub(crate) async fn delete_this_item(
+    delete: &Item<'_>,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let surreal_tables = match DataLayerCommands::get_raw_data(send_to_data_storage_layer).await {
+        Ok(surreal_tables) => surreal_tables,
+        Err(DataLayerError::Stopped) => return Err(()),
+        Err(err) => {
+            println!("Unable to read the database: {}", err);
+            return Ok(());
+        }
+    };
+    let Some(planned) = plan_delete(&surreal_tables, delete.get_surreal_record_id()) else {
+        println!("This item is no longer in the database");
+        return Ok(());
+    };
+    if !confirm_planned_changes(&planned, "Permanently delete this item?")? {
+        return Ok(());
+    }
+    DataLayerCommands::change(
+        send_to_data_storage_layer,
+        DataChange::DeleteItem(delete.get_surreal_record_id().clone()),
+    )
+    .await
+    .or_else(report_not_saved)
+}
+
+/// Asks for the duplicate and which of the two to keep, the one that is kept keeps its summary and everything else
+/// from the other one is folded into it
+pub(crate) async fn merge_this_item(
+    merge_this: &Item<'_>,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let surreal_tables = match DataLayerCommands::get_raw_data(send_to_data_storage_layer).await {
+        Ok(surreal_tables) => surreal_tables,
+        Err(DataLayerError::Stopped) => return Err(()),
+        Err(err) => {
+            println!("Unable to read the database: {}", err);
+            return Ok(());
+        }
+    };
+    let base_data = BaseData::new_from_surreal_tables(surreal_tables.clone(), Utc::now());
+    let list = base_data
+        .get_active_items()
+        .iter()
+        .filter(|x| x.get_surreal_record_id() != merge_this.get_surreal_record_id())
+        .copied()
+        .map(DisplayItem::new)
+        .collect::<Vec<_>>();
+    let selection = Select::new("Which item is a duplicate of this one?|", list).prompt();
+    let other = match selection {
+        Ok(other) => other,
+        Err(InquireError::OperationCanceled | InquireError::InvalidConfiguration(_)) => {
+            return Ok(());
+        }
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    };
+
+    let this_id = merge_this.get_surreal_record_id().clone();
+    let other_id = other.get_surreal_record_id().clone();
+    let other = base_data
+        .get_items()
+        .get(&other_id)
+        .expect("It was just picked from these items");
+    let list = vec![
+        KeepSelection::ThisItem(DisplayItem::new(merge_this)),
+        KeepSelection::OtherItem(DisplayItem::new(other)),
+    ];
+    let selection = Select::new("Which summary should the merged item keep?|", list).prompt();
+    let (keep, merge) = match selection {
+        Ok(KeepSelection::ThisItem(..)) => (this_id, other_id),
+        Ok(KeepSelection::OtherItem(..)) => (other_id, this_id),
+        Err(InquireError::OperationCanceled) => return Ok(()),
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    };
+    let Some(planned) = plan_merge(&surreal_tables, &keep, &merge) else {
+        println!("One of these items is no longer in the database");
+        return Ok(());
+    };
+    if !confirm_planned_changes(&planned, "Merge these items?")? {
+        return Ok(());
+    }
+    DataLayerCommands::change(
+        send_to_data_storage_layer,
+        DataChange::MergeItems { keep, merge },
+    )
+    .await
+    .or_else(report_not_saved)
+}
+
+/// Shows everything that will change and asks before any of it is changed
+pub(crate) fn confirm_planned_changes(
+    planned: &[PlannedChange],
+    question: &str,
+) -> Result<bool, ()> {
+    println!();
+    println!("This will:");
+    for change in planned.iter() {
+        println!("  {}", change);
+    }
+    println!();
+    match Confirm::new(question).with_default(false).prompt() {
+        Ok(confirmed) => Ok(confirmed),
+        Err(InquireError::OperationCanceled) => Ok(false),
+        Err(InquireError::OperationInterrupted) => Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    }
+}
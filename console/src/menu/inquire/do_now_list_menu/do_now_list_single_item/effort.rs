@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use fundu::{CustomDurationParser, CustomTimeUnit, SaturatingInto, TimeUnit};
+use inquire::{InquireError, Text};
+use lazy_static::lazy_static;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    base_data::time_spent::TimeSpent,
+    data_storage::surrealdb_layer::data_layer_commands::{DataChange, DataLayerCommands},
+    display::display_duration::DisplayDuration,
+    menu::inquire::report_not_saved,
+    node::{
+        Filter,
+        item_node::{ItemNode, get_logged_time},
+    },
+};
+
+/// For the single item view, nothing is printed if neither the item nor any of its smaller items have an estimate
+pub(crate) fn print_effort(item_node: &ItemNode<'_>, time_spent_log: &[TimeSpent<'_>]) {
+    let Some(estimate) = item_node.get_estimate_rolled_up(Filter::All) else {
+        return;
+    };
+    print!("⏱️ Estimated {}", DisplayDuration::new(&estimate));
+    if item_node.has_children(Filter::All) {
+        match item_node.get_item().get_estimate() {
+            Some(own) => print!(
+                ", {} for this and the rest for smaller items",
+                DisplayDuration::new(&own)
+            ),
+            None => print!(" for smaller items"),
+        }
+    }
+    match item_node.get_remaining_effort(time_spent_log) {
+        Some(remaining) if !remaining.is_zero() => {
+            println!(", {} remaining", DisplayDuration::new(&remaining))
+        }
+        _ if item_node.is_finished() => println!(),
+        _ => println!(", nothing remaining but it is not finished"),
+    }
+}
+
+/// An empty estimate removes it
+pub(crate) async fn present_estimate_menu(
+    item_node: &ItemNode<'_>,
+    time_spent_log: &[TimeSpent<'_>],
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    lazy_static! {
+        static ref relative_parser: CustomDurationParser<'static> = CustomDurationParser::builder()
+            .allow_time_unit_delimiter()
+            .number_is_optional()
+            .time_units(&[
+                CustomTimeUnit::with_default(
+                    TimeUnit::Minute,
+                    &["m", "min", "mins", "minute", "minutes"]
+                ),
+                CustomTimeUnit::with_default(TimeUnit::Hour, &["h", "hour", "hours"]),
+                CustomTimeUnit::with_default(TimeUnit::Day, &["d", "day", "days"]),
+            ])
+            .build();
+    }
+
+    let item = item_node.get_item();
+    let logged = get_logged_time(item, time_spent_log);
+    if !logged.is_zero() {
+        println!(
+            "{} has been logged on this item so far",
+            DisplayDuration::new(&logged)
+        );
+    }
+    let estimate: Option<Duration> = loop {
+        match Text::new("How long will this item itself take, not counting its smaller items?|")
+            .with_help_message("Examples: \"30m\", \"2h\", \"1d\", leave empty for no estimate")
+            .prompt()
+        {
+            Ok(estimate) if estimate.trim().is_empty() => break None,
+            Ok(estimate) => match relative_parser.parse(estimate.trim()) {
+                Ok(estimate) => break Some(estimate.saturating_into()),
+                Err(_) => {
+                    println!("Invalid duration, please try again");
+                    println!();
+                }
+            },
+            Err(InquireError::OperationCanceled) => return Ok(()),
+            Err(InquireError::OperationInterrupted) => return Err(()),
+            Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+        }
+    };
+    if estimate == item.get_estimate() {
+        return Ok(());
+    }
+    DataLayerCommands::change(
+        send_to_data_storage_layer,
+        DataChange::UpdateItemEstimate(
+            item.get_surreal_record_id().clone(),
+            estimate.map(|x| x.into()),
+        ),
+    )
+    .await
+    .or_else(report_not_saved)
+}
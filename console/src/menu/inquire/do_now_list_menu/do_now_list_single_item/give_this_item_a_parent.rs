@@ -8,9 +8,11 @@ use crate::{
     base_data::{BaseData, item::Item},
     calculated_data::parent_lookup::ParentLookup,
     data_storage::surrealdb_layer::{
-        data_layer_commands::DataLayerCommands, surreal_tables::SurrealTables,
+        data_layer_commands::{DataChange, DataLayerCommands},
+        surreal_tables::SurrealTables,
     },
     display::display_item_node::DisplayItemNode,
+    menu::inquire::report_not_saved,
     menu::inquire::{
         do_now_list_menu::do_now_list_single_item::ItemTypeSelection,
         select_higher_importance_than_this::select_higher_importance_than_this,
@@ -94,17 +96,15 @@ pub(crate) async fn give_this_item_a_parent(
     .with_page_size(8)
     .prompt();
     match selection {
-        Ok(ParentItem::FinishItem) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::FinishItem {
-                    item: parent_this.get_surreal_record_id().clone(),
-                    when_finished: (*parent_this.get_now()).into(),
-                })
-                .await
-                .unwrap();
-
-            Ok(())
-        }
+        Ok(ParentItem::FinishItem) => DataLayerCommands::change(
+            send_to_data_storage_layer,
+            DataChange::FinishItem {
+                item: parent_this.get_surreal_record_id().clone(),
+                when_finished: (*parent_this.get_now()).into(),
+            },
+        )
+        .await
+        .or_else(report_not_saved),
         Ok(ParentItem::ItemNode(parent)) => {
             let parent: &ItemNode<'_> = parent.get_item_node();
 
@@ -117,15 +117,16 @@ pub(crate) async fn give_this_item_a_parent(
             } else {
                 None
             };
-            send_to_data_storage_layer
-                .send(DataLayerCommands::ParentItemWithExistingItem {
+            DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::ParentItemWithExistingItem {
                     child: parent_this.get_surreal_record_id().clone(),
                     parent: parent.get_surreal_record_id().clone(),
                     higher_importance_than_this,
-                })
-                .await
-                .unwrap();
-            Ok(())
+                },
+            )
+            .await
+            .or_else(report_not_saved)
         }
         Ok(ParentItem::CreateNewItem) | Err(InquireError::InvalidConfiguration(_)) => {
             parent_to_a_goal_or_motivation_new_goal_or_motivation(
@@ -157,14 +158,15 @@ async fn parent_to_a_goal_or_motivation_new_goal_or_motivation(
         }
         Ok(item_type_selection) => {
             let new_item = item_type_selection.create_new_item_prompt_user_for_summary();
-            send_to_data_storage_layer
-                .send(DataLayerCommands::ParentNewItemWithAnExistingChildItem {
+            DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::ParentNewItemWithAnExistingChildItem {
                     child: parent_this.get_surreal_record_id().clone(),
                     parent_new_item: new_item,
-                })
-                .await
-                .unwrap();
-            Ok(())
+                },
+            )
+            .await
+            .or_else(report_not_saved)
         }
         Err(InquireError::OperationCanceled) => {
             todo!("I need to go back to what first called this");
@@ -0,0 +1,159 @@
+use chrono::{DateTime, Local, Utc};
+use surrealdb::opt::RecordId;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    base_data::{BaseData, item::Item},
+    data_storage::surrealdb_layer::{
+        data_layer_commands::{DataLayerCommands, DataLayerError},
+        surreal_history::SurrealHistoryEntry,
+        surreal_item::SurrealDependency,
+    },
+    display::display_duration::DisplayDuration,
+};
+
+struct TimelineEntry {
+    when: DateTime<Utc>,
+    description: String,
+}
+
+/// Prints everything that happened to the item, oldest first. That is the changes to the item, being added to or
+/// removed from a parent, the time spent on it and the events it waits on being triggered. Items from before history was
+/// kept only show when they were created and finished.
+pub(crate) async fn present_item_history(
+    item: &Item<'_>,
+    base_data: &BaseData,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let events = item
+        .get_surreal_dependencies()
+        .iter()
+        .filter_map(|x| match x {
+            SurrealDependency::AfterEvent(event) => Some(event.clone()),
+            SurrealDependency::AfterDateTime(..)
+            | SurrealDependency::AfterItem(..)
+            | SurrealDependency::DuringItem(..) => None,
+        })
+        .collect::<Vec<_>>();
+    let mut records = vec![item.get_surreal_record_id().clone()];
+    records.extend(events.iter().cloned());
+    let history = match DataLayerCommands::get_history(send_to_data_storage_layer, records).await {
+        Ok(history) => history,
+        Err(DataLayerError::Stopped) => return Err(()),
+        Err(err) => {
+            println!("Unable to read the history: {}", err);
+            return Ok(());
+        }
+    };
+
+    let mut timeline = history
+        .iter()
+        .map(|x| TimelineEntry {
+            when: x.when.clone().into(),
+            description: describe_history_entry(x, item.get_surreal_record_id(), base_data),
+        })
+        .collect::<Vec<_>>();
+    let has_field_history = |field: &str| {
+        history
+            .iter()
+            .any(|x| x.record == *item.get_surreal_record_id() && x.field == field)
+    };
+    if !has_field_history(SurrealHistoryEntry::EXISTS) {
+        timeline.push(TimelineEntry {
+            when: *item.get_created(),
+            description: format!("Created as \"{}\"", item.get_summary()),
+        });
+    }
+    if !has_field_history("finished")
+        && let Some(finished) = item.get_finished_at()
+    {
+        timeline.push(TimelineEntry {
+            when: finished.clone().into(),
+            description: "Finished".to_string(),
+        });
+    }
+    for time_spent in base_data
+        .get_time_spent_log()
+        .iter()
+        .filter(|x| x.worked_towards().contains(item.get_surreal_record_id()))
+    {
+        timeline.push(TimelineEntry {
+            when: *time_spent.get_started_at(),
+            description: format!(
+                "Worked on for {}",
+                DisplayDuration::new(time_spent.get_duration())
+            ),
+        });
+    }
+    for event in events.iter() {
+        if history.iter().any(|x| x.record == *event) {
+            continue;
+        }
+        if let Some(event) = base_data.get_events().get(event) {
+            let state = if event.is_active() {
+                "waiting to be triggered"
+            } else {
+                "triggered"
+            };
+            timeline.push(TimelineEntry {
+                when: *event.get_last_updated(),
+                description: format!(
+                    "Event \"{}\" last changed, it is {}",
+                    event.get_summary(),
+                    state
+                ),
+            });
+        }
+    }
+    timeline.sort_by_key(|x| x.when);
+
+    println!();
+    println!("History of \"{}\":", item.get_summary());
+    for entry in timeline.iter() {
+        let when: DateTime<Local> = entry.when.into();
+        println!(
+            "  {}  {}",
+            when.format("%a %d %b %Y %I:%M%p"),
+            entry.description
+        );
+    }
+    println!();
+    Ok(())
+}
+
+fn describe_history_entry(
+    entry: &SurrealHistoryEntry,
+    item: &RecordId,
+    base_data: &BaseData,
+) -> String {
+    let old_value = entry.old_value.as_deref().unwrap_or_default();
+    let new_value = entry.new_value.as_deref().unwrap_or_default();
+    if entry.record != *item {
+        let summary = base_data
+            .get_events()
+            .get(&entry.record)
+            .map(|x| x.get_summary().to_string())
+            .unwrap_or_else(|| entry.record.to_string());
+        return match (entry.field.as_str(), new_value) {
+            ("triggered", "true") => format!("Event \"{}\" was triggered", summary),
+            ("triggered", _) => format!("Event \"{}\" was untriggered", summary),
+            (SurrealHistoryEntry::EXISTS, "") => format!("Event \"{}\" was deleted", old_value),
+            (SurrealHistoryEntry::EXISTS, _) => format!("Event \"{}\" was created", new_value),
+            (field, _) => format!(
+                "Event \"{}\" {} changed from {} to {}",
+                summary, field, old_value, new_value
+            ),
+        };
+    }
+    match (entry.field.as_str(), &entry.old_value, &entry.new_value) {
+        (SurrealHistoryEntry::EXISTS, _, Some(summary)) => format!("Created as \"{}\"", summary),
+        (SurrealHistoryEntry::EXISTS, _, None) => "Deleted".to_string(),
+        (SurrealHistoryEntry::PARENT, _, Some(parent)) => {
+            format!("Became a smaller item of \"{}\"", parent)
+        }
+        (SurrealHistoryEntry::PARENT, Some(parent), None) => {
+            format!("No longer a smaller item of \"{}\"", parent)
+        }
+        (field, _, _) => format!("Changed {} from {} to {}", field, old_value, new_value),
+    }
+}
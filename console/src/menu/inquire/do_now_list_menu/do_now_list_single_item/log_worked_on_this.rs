@@ -8,10 +8,12 @@ use tokio::sync::{mpsc::Sender, oneshot};
 
 use crate::{
     data_storage::surrealdb_layer::{
-        data_layer_commands::DataLayerCommands, surreal_in_the_moment_priority::SurrealAction,
+        data_layer_commands::{DataChange, DataLayerCommands},
+        surreal_in_the_moment_priority::SurrealAction,
         surreal_time_spent::SurrealDedication,
     },
     display::display_duration::DisplayDuration,
+    menu::inquire::report_not_saved,
     menu::inquire::{
         parse_exact_or_relative_datetime, parse_exact_or_relative_datetime_help_string,
     },
@@ -54,10 +56,14 @@ pub(crate) async fn log_worked_on_this(
                 when_stopped,
                 dedication: Some(dedication),
             };
-            send_to_data_storage_layer
-                .send(DataLayerCommands::RecordTimeSpent(time_spent))
-                .await
-                .unwrap();
+            if let Err(err) = DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::RecordTimeSpent(time_spent),
+            )
+            .await
+            {
+                return report_not_saved(err);
+            }
             break;
         } else {
             continue; //If the user cancels they should be able to try again
@@ -66,7 +72,7 @@ pub(crate) async fn log_worked_on_this(
     Ok(())
 }
 
-fn create_working_on_list(selected: &ItemStatus<'_>) -> Vec<SurrealAction> {
+pub(crate) fn create_working_on_list(selected: &ItemStatus<'_>) -> Vec<SurrealAction> {
     selected
         .get_self_and_parents_flattened(Filter::Active)
         .iter()
@@ -299,7 +305,7 @@ impl Display for Dedication {
     }
 }
 
-fn ask_about_dedication() -> Result<Option<SurrealDedication>, ()> {
+pub(crate) fn ask_about_dedication() -> Result<Option<SurrealDedication>, ()> {
     let dedication = vec![Dedication::Primary, Dedication::Background];
     let dedication = Select::new("What is the dedication of this time spent?", dedication).prompt();
     match dedication {
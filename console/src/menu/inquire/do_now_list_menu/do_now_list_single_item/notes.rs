@@ -0,0 +1,191 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+    path::Path,
+    process::Command,
+};
+
+use inquire::{Editor, InquireError, Select, Text};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    base_data::item::Item,
+    data_storage::surrealdb_layer::{
+        data_layer_commands::{DataChange, DataLayerCommands},
+        surreal_item::{SurrealLink, SurrealLinkKind},
+    },
+    menu::inquire::report_not_saved,
+};
+
+struct DisplayLink<'e>(&'e SurrealLink);
+
+impl Display for DisplayLink<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let icon = match self.0.kind {
+            SurrealLinkKind::Web => "🌐",
+            SurrealLinkKind::FilePath => "📁",
+            SurrealLinkKind::OneNote => "📓",
+        };
+        write!(f, "{} {}", icon, self.0.target)
+    }
+}
+
+enum NotesAction<'e> {
+    EditNotes,
+    OpenLink(&'e SurrealLink),
+    AddLink,
+    RemoveLink(&'e SurrealLink),
+}
+
+impl Display for NotesAction<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            NotesAction::EditNotes => write!(f, "📝  Edit notes"),
+            NotesAction::OpenLink(link) => write!(f, "Open {}", DisplayLink(link)),
+            NotesAction::AddLink => write!(f, "🔗  Add a link"),
+            NotesAction::RemoveLink(link) => write!(f, "🚫 Remove link: {}", DisplayLink(link)),
+        }
+    }
+}
+
+struct LinkKindChoice(SurrealLinkKind);
+
+impl Display for LinkKindChoice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            SurrealLinkKind::Web => write!(f, "🌐 Web address"),
+            SurrealLinkKind::FilePath => write!(f, "📁 File or folder on this computer"),
+            SurrealLinkKind::OneNote => write!(f, "📓 OneNote page"),
+        }
+    }
+}
+
+/// For the single item view, nothing is printed if the item has no notes or links
+pub(crate) fn print_notes_and_links(item: &Item<'_>) {
+    let notes = item.get_notes().trim();
+    if !notes.is_empty() {
+        println!("Notes:");
+        for line in notes.lines() {
+            println!("  {}", line);
+        }
+    }
+    let links = item.get_links();
+    if !links.is_empty() {
+        println!("Links:");
+        for link in links.iter() {
+            println!("  {}", DisplayLink(link));
+        }
+    }
+}
+
+/// Notes are markdown edited in the user's editor, links are opened with whatever the platform opens them with
+pub(crate) async fn present_notes_and_links_menu(
+    item: &Item<'_>,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let links = item.get_links();
+    let mut list = vec![NotesAction::EditNotes];
+    list.extend(links.iter().map(NotesAction::OpenLink));
+    list.push(NotesAction::AddLink);
+    list.extend(links.iter().map(NotesAction::RemoveLink));
+
+    let selection = Select::new("Select from the below list|", list).prompt();
+    let change = match selection {
+        Ok(NotesAction::EditNotes) => {
+            let notes = Editor::new("Edit the notes for this item|")
+                .with_predefined_text(item.get_notes())
+                .with_file_extension(".md")
+                .prompt();
+            match notes {
+                Ok(notes) if notes == item.get_notes() => return Ok(()),
+                Ok(notes) => {
+                    DataChange::UpdateItemNotes(item.get_surreal_record_id().clone(), notes)
+                }
+                Err(InquireError::OperationCanceled) => return Ok(()),
+                Err(InquireError::OperationInterrupted) => return Err(()),
+                Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+            }
+        }
+        Ok(NotesAction::OpenLink(link)) => {
+            if let Err(err) = open_link(link) {
+                println!("Unable to open {}: {}", link.target, err);
+            }
+            //Stay here so more than one link can be opened
+            return Box::pin(present_notes_and_links_menu(
+                item,
+                send_to_data_storage_layer,
+            ))
+            .await;
+        }
+        Ok(NotesAction::AddLink) => {
+            let Some(link) = ask_for_link()? else {
+                return Ok(());
+            };
+            let mut links = links.clone();
+            links.push(link);
+            DataChange::UpdateItemLinks(item.get_surreal_record_id().clone(), links)
+        }
+        Ok(NotesAction::RemoveLink(link)) => {
+            let links = links.iter().filter(|x| *x != link).cloned().collect();
+            DataChange::UpdateItemLinks(item.get_surreal_record_id().clone(), links)
+        }
+        Err(InquireError::OperationCanceled) => return Ok(()),
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    };
+    DataLayerCommands::change(send_to_data_storage_layer, change)
+        .await
+        .or_else(report_not_saved)
+}
+
+/// None if the user canceled
+fn ask_for_link() -> Result<Option<SurrealLink>, ()> {
+    let list = vec![
+        LinkKindChoice(SurrealLinkKind::Web),
+        LinkKindChoice(SurrealLinkKind::FilePath),
+        LinkKindChoice(SurrealLinkKind::OneNote),
+    ];
+    let kind = match Select::new("What kind of link is it?|", list).prompt() {
+        Ok(LinkKindChoice(kind)) => kind,
+        Err(InquireError::OperationCanceled) => return Ok(None),
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    };
+    let message = match kind {
+        SurrealLinkKind::Web => "Enter the web address|",
+        SurrealLinkKind::FilePath => "Enter the path to the file or folder|",
+        SurrealLinkKind::OneNote => {
+            "Paste the link to the OneNote page (from \"Copy Link to Page\")|"
+        }
+    };
+    match Text::new(message).prompt() {
+        Ok(target) if target.trim().is_empty() => {
+            println!("The link cannot be empty");
+            Ok(None)
+        }
+        Ok(target) => {
+            let target = target.trim().to_string();
+            if kind == SurrealLinkKind::FilePath && !Path::new(&target).exists() {
+                println!("Note that {} does not exist on this computer", target);
+            }
+            Ok(Some(SurrealLink { kind, target }))
+        }
+        Err(InquireError::OperationCanceled) => Ok(None),
+        Err(InquireError::OperationInterrupted) => Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    }
+}
+
+/// Web addresses, files and OneNote links (onenote:...) are all opened the same way, the platform decides what to open
+/// them with. This does not wait for what was opened to close.
+fn open_link(link: &SurrealLink) -> io::Result<()> {
+    //explorer rather than "cmd /C start" so an & in a web address is not taken as the start of another command
+    let mut command = if cfg!(target_os = "windows") {
+        Command::new("explorer")
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(&link.target).spawn().map(|_| ())
+}
@@ -0,0 +1,175 @@
+use std::fmt::{self, Display, Formatter};
+
+use inquire::{CustomType, InquireError, MultiSelect, Select};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    base_data::item::Item,
+    data_storage::surrealdb_layer::{
+        data_layer_commands::{DataChange, DataLayerCommands},
+        surreal_item::{SurrealRecurrence, SurrealWeekday},
+    },
+    display::display_recurrence::{DisplayRecurrence, DisplayWeekday},
+    menu::inquire::report_not_saved,
+    systems::maintenance::streak,
+};
+
+enum RecurrenceChoice {
+    EveryFewDays,
+    EveryFewWeeks,
+    OnWeekdays,
+    DayOfMonth,
+    DaysAfterFinished,
+    StopRepeating,
+}
+
+impl Display for RecurrenceChoice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RecurrenceChoice::EveryFewDays => write!(f, "Every few days"),
+            RecurrenceChoice::EveryFewWeeks => write!(f, "Every few weeks"),
+            RecurrenceChoice::OnWeekdays => write!(f, "On certain days of the week"),
+            RecurrenceChoice::DayOfMonth => write!(f, "On a day of the month"),
+            RecurrenceChoice::DaysAfterFinished => {
+                write!(f, "Some days after it is finished, however late that is")
+            }
+            RecurrenceChoice::StopRepeating => write!(f, "🚫 Stop repeating, it can be finished"),
+        }
+    }
+}
+
+/// For the single item view, nothing is printed if the item does not repeat
+pub(crate) fn print_recurrence(item: &Item<'_>) {
+    if let Some(recurrence) = item.get_recurrence() {
+        print!("🔁 Repeats {}", DisplayRecurrence::new(recurrence));
+        let completions = item.get_completions();
+        if completions.is_empty() {
+            println!();
+        } else {
+            println!(
+                ", finished {} times, a streak of {}",
+                completions.len(),
+                streak(completions)
+            );
+        }
+    }
+}
+
+/// A recurring item is not finished when it is finished, it waits until it is due again
+pub(crate) async fn present_recurrence_menu(
+    item: &Item<'_>,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let mut list = vec![
+        RecurrenceChoice::EveryFewDays,
+        RecurrenceChoice::EveryFewWeeks,
+        RecurrenceChoice::OnWeekdays,
+        RecurrenceChoice::DayOfMonth,
+        RecurrenceChoice::DaysAfterFinished,
+    ];
+    if item.get_recurrence().is_some() {
+        list.push(RecurrenceChoice::StopRepeating);
+    }
+    let selection = Select::new("How often does this need to be done?|", list).prompt();
+    let recurrence = match selection {
+        Ok(RecurrenceChoice::EveryFewDays) => {
+            ask_for_number("How many days between each time?|")?.map(SurrealRecurrence::EveryDays)
+        }
+        Ok(RecurrenceChoice::EveryFewWeeks) => {
+            ask_for_number("How many weeks between each time?|")?.map(SurrealRecurrence::EveryWeeks)
+        }
+        Ok(RecurrenceChoice::OnWeekdays) => {
+            ask_for_weekdays(item.get_recurrence())?.map(SurrealRecurrence::OnWeekdays)
+        }
+        Ok(RecurrenceChoice::DayOfMonth) => ask_for_number(
+            "Which day of the month? (In shorter months a day past the end is the last day)|",
+        )?
+        .filter(|x| {
+            let valid = *x <= 31;
+            if !valid {
+                println!("No month has more than 31 days");
+            }
+            valid
+        })
+        .map(SurrealRecurrence::DayOfMonth),
+        Ok(RecurrenceChoice::DaysAfterFinished) => {
+            ask_for_number("How many days after it is finished?|")?
+                .map(SurrealRecurrence::DaysAfterFinished)
+        }
+        Ok(RecurrenceChoice::StopRepeating) => {
+            return DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::UpdateItemRecurrence(item.get_surreal_record_id().clone(), None),
+            )
+            .await
+            .or_else(report_not_saved);
+        }
+        Err(InquireError::OperationCanceled) => return Ok(()),
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    };
+    let Some(recurrence) = recurrence else {
+        return Ok(());
+    };
+    DataLayerCommands::change(
+        send_to_data_storage_layer,
+        DataChange::UpdateItemRecurrence(item.get_surreal_record_id().clone(), Some(recurrence)),
+    )
+    .await
+    .or_else(report_not_saved)
+}
+
+/// None if the user canceled, zero is not accepted
+fn ask_for_number(message: &str) -> Result<Option<u32>, ()> {
+    match CustomType::<u32>::new(message)
+        .with_error_message("Please enter a whole number")
+        .prompt()
+    {
+        Ok(0) => {
+            println!("It must be at least 1");
+            Ok(None)
+        }
+        Ok(number) => Ok(Some(number)),
+        Err(InquireError::OperationCanceled) => Ok(None),
+        Err(InquireError::OperationInterrupted) => Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    }
+}
+
+/// None if the user canceled or did not pick any days
+fn ask_for_weekdays(
+    current: Option<&SurrealRecurrence>,
+) -> Result<Option<Vec<SurrealWeekday>>, ()> {
+    let weekdays = [
+        SurrealWeekday::Monday,
+        SurrealWeekday::Tuesday,
+        SurrealWeekday::Wednesday,
+        SurrealWeekday::Thursday,
+        SurrealWeekday::Friday,
+        SurrealWeekday::Saturday,
+        SurrealWeekday::Sunday,
+    ];
+    let default = match current {
+        Some(SurrealRecurrence::OnWeekdays(current)) => weekdays
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| current.contains(x))
+            .map(|(i, _)| i)
+            .collect(),
+        _ => Vec::default(),
+    };
+    let list = weekdays.into_iter().map(DisplayWeekday).collect::<Vec<_>>();
+    match MultiSelect::new("Which days of the week?|", list)
+        .with_default(&default)
+        .prompt()
+    {
+        Ok(selected) if selected.is_empty() => {
+            println!("At least one day must be picked");
+            Ok(None)
+        }
+        Ok(selected) => Ok(Some(selected.into_iter().map(|x| x.0).collect())),
+        Err(InquireError::OperationCanceled) => Ok(None),
+        Err(InquireError::OperationInterrupted) => Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    }
+}
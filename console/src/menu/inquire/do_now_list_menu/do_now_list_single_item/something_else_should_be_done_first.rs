@@ -4,8 +4,11 @@ use tokio::sync::mpsc::Sender;
 
 use crate::{
     base_data::{BaseData, item::Item},
-    data_storage::surrealdb_layer::data_layer_commands::DataLayerCommands,
+    data_storage::surrealdb_layer::data_layer_commands::{
+        DataChange, DataLayerCommands, DataLayerError,
+    },
     display::display_item::DisplayItem,
+    menu::inquire::report_not_saved,
 };
 
 use super::ItemTypeSelection;
@@ -14,9 +17,14 @@ pub(crate) async fn something_else_should_be_done_first(
     unable_to_do: &Item<'_>,
     send_to_data_storage_layer: &Sender<DataLayerCommands>,
 ) -> Result<(), ()> {
-    let surreal_tables = DataLayerCommands::get_raw_data(send_to_data_storage_layer)
-        .await
-        .unwrap();
+    let surreal_tables = match DataLayerCommands::get_raw_data(send_to_data_storage_layer).await {
+        Ok(surreal_tables) => surreal_tables,
+        Err(DataLayerError::Stopped) => return Err(()),
+        Err(err) => {
+            println!("Unable to read the database: {}", err);
+            return Ok(());
+        }
+    };
     let now = Utc::now();
     let base_data = BaseData::new_from_surreal_tables(surreal_tables, now);
     let list = base_data
@@ -27,18 +35,17 @@ pub(crate) async fn something_else_should_be_done_first(
         .collect::<Vec<_>>();
     let selection = Select::new("Select from the below list|", list).prompt();
     match selection {
-        Ok(should_be_done_first) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::CoverItemWithAnExistingItem {
-                    item_to_be_covered: unable_to_do.get_surreal_record_id().clone(),
-                    item_that_should_do_the_covering: should_be_done_first
-                        .get_surreal_record_id()
-                        .clone(),
-                })
-                .await
-                .unwrap();
-            Ok(())
-        }
+        Ok(should_be_done_first) => DataLayerCommands::change(
+            send_to_data_storage_layer,
+            DataChange::CoverItemWithAnExistingItem {
+                item_to_be_covered: unable_to_do.get_surreal_record_id().clone(),
+                item_that_should_do_the_covering: should_be_done_first
+                    .get_surreal_record_id()
+                    .clone(),
+            },
+        )
+        .await
+        .or_else(report_not_saved),
         Err(InquireError::OperationCanceled | InquireError::InvalidConfiguration(_)) => {
             something_else_should_be_done_first_new_item(unable_to_do, send_to_data_storage_layer)
                 .await
@@ -65,14 +72,15 @@ pub(crate) async fn something_else_should_be_done_first_new_item(
         }
         Ok(selection) => {
             let new_item = selection.create_new_item_prompt_user_for_summary();
-            send_to_data_storage_layer
-                .send(DataLayerCommands::CoverItemWithANewItem {
+            DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::CoverItemWithANewItem {
                     cover_this: unable_to_do.get_surreal_record_id().clone(),
                     cover_with: new_item,
-                })
-                .await
-                .unwrap();
-            Ok(())
+                },
+            )
+            .await
+            .or_else(report_not_saved)
         }
         Err(_) => todo!(),
     }
@@ -9,9 +9,11 @@ use crate::{
     base_data::{BaseData, item::Item},
     calculated_data::CalculatedData,
     data_storage::surrealdb_layer::{
-        data_layer_commands::DataLayerCommands, surreal_tables::SurrealTables,
+        data_layer_commands::{DataChange, DataLayerCommands},
+        surreal_tables::SurrealTables,
     },
     display::display_item_node::DisplayItemNode,
+    menu::inquire::report_not_saved,
     menu::inquire::select_higher_importance_than_this::select_higher_importance_than_this,
     node::{Filter, item_node::ItemNode, item_status::ItemStatus},
 };
@@ -111,6 +113,20 @@ pub(crate) async fn state_a_smaller_action(
     selected_item: &ItemNode<'_>,
     send_to_data_storage_layer: &Sender<DataLayerCommands>,
 ) -> Result<(), ()> {
+    match pick_a_smaller_action(selected_item, send_to_data_storage_layer).await? {
+        Some(change) => DataLayerCommands::change(send_to_data_storage_layer, change)
+            .await
+            .or_else(report_not_saved),
+        None => Ok(()),
+    }
+}
+
+/// Prompts for the smaller action without saving it so it can be saved along with other changes, None if the user
+/// cancelled
+pub(crate) async fn pick_a_smaller_action(
+    selected_item: &ItemNode<'_>,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<Option<DataChange>, ()> {
     let surreal_tables = SurrealTables::new(send_to_data_storage_layer)
         .await
         .unwrap();
@@ -138,19 +154,14 @@ pub(crate) async fn state_a_smaller_action(
             } else {
                 None
             };
-            send_to_data_storage_layer
-                .send(DataLayerCommands::ParentItemWithExistingItem {
-                    child: child.get_surreal_record_id().clone(),
-                    parent: parent.get_surreal_record_id().clone(),
-                    higher_importance_than_this,
-                })
-                .await
-                .unwrap();
-
-            Ok(())
+            Ok(Some(DataChange::ParentItemWithExistingItem {
+                child: child.get_surreal_record_id().clone(),
+                parent: parent.get_surreal_record_id().clone(),
+                higher_importance_than_this,
+            }))
         }
         Ok(None) => {
-            state_a_child_action_new_item(
+            new_smaller_action(
                 selected_item,
                 calculated_data.get_base_data(),
                 send_to_data_storage_layer,
@@ -161,18 +172,18 @@ pub(crate) async fn state_a_smaller_action(
     }
 }
 
-pub(crate) async fn state_a_child_action_new_item(
+async fn new_smaller_action(
     selected_item: &ItemNode<'_>,
     base_data: &BaseData,
     send_to_data_storage_layer: &Sender<DataLayerCommands>,
-) -> Result<(), ()> {
+) -> Result<Option<DataChange>, ()> {
     let list = ItemTypeSelection::create_list();
 
     let selection = Select::new("Select from the below list|", list).prompt();
     match selection {
         Ok(ItemTypeSelection::NormalHelp) => {
             ItemTypeSelection::print_normal_help();
-            Box::pin(state_a_child_action_new_item(
+            Box::pin(new_smaller_action(
                 selected_item,
                 base_data,
                 send_to_data_storage_layer,
@@ -207,17 +218,13 @@ pub(crate) async fn state_a_child_action_new_item(
             new_item.dependencies = dependencies;
             new_item.urgency_plan = Some(urgency_plan);
 
-            send_to_data_storage_layer
-                .send(DataLayerCommands::ParentItemWithANewChildItem {
-                    child: new_item,
-                    parent: parent.get_surreal_record_id().clone(),
-                    higher_importance_than_this,
-                })
-                .await
-                .unwrap();
-            Ok(())
+            Ok(Some(DataChange::ParentItemWithANewChildItem {
+                child: new_item,
+                parent: parent.get_surreal_record_id().clone(),
+                higher_importance_than_this,
+            }))
         }
-        Err(InquireError::OperationCanceled) => todo!(),
+        Err(InquireError::OperationCanceled) => Ok(None),
         Err(InquireError::OperationInterrupted) => Err(()),
         Err(err) => todo!("Unexpected {}", err),
     }
@@ -0,0 +1,96 @@
+use std::fmt::{self, Display, Formatter};
+
+use inquire::{CustomUserError, InquireError, Select, Text};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    base_data::BaseData,
+    data_storage::surrealdb_layer::data_layer_commands::{DataChange, DataLayerCommands},
+    menu::inquire::report_not_saved,
+    node::{Filter, item_node::ItemNode},
+};
+
+enum TagsAction<'e> {
+    AddTag,
+    RemoveTag(&'e str),
+}
+
+impl Display for TagsAction<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TagsAction::AddTag => write!(f, "🏷️  Add a tag"),
+            TagsAction::RemoveTag(tag) => write!(f, "🚫 Remove tag: {}", tag),
+        }
+    }
+}
+
+/// For the single item view, tags that come from a larger item are marked so it is clear where to remove them
+pub(crate) fn print_tags(item_node: &ItemNode<'_>) {
+    let own = item_node.get_item().get_tags();
+    let tags = item_node
+        .get_tags(Filter::Active)
+        .into_iter()
+        .map(|tag| {
+            if own.iter().any(|x| x == tag) {
+                tag.to_string()
+            } else {
+                format!("{} (from a larger item)", tag)
+            }
+        })
+        .collect::<Vec<_>>();
+    if !tags.is_empty() {
+        println!("Tags: {}", tags.join(", "));
+    }
+}
+
+/// Only the tags given to this item can be removed here, an inherited tag is removed from the larger item it is on
+pub(crate) async fn present_tags_menu(
+    item_node: &ItemNode<'_>,
+    base_data: &BaseData,
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let item = item_node.get_item();
+    let mut list = vec![TagsAction::AddTag];
+    list.extend(item.get_tags().iter().map(|x| TagsAction::RemoveTag(x)));
+
+    let selection = Select::new("Select from the below list|", list).prompt();
+    let change = match selection {
+        Ok(TagsAction::AddTag) => {
+            let tags_in_use = base_data
+                .get_tags_in_use()
+                .into_iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>();
+            let suggester = move |input: &str| -> Result<Vec<String>, CustomUserError> {
+                let input = input.to_lowercase();
+                Ok(tags_in_use
+                    .iter()
+                    .filter(|x| x.to_lowercase().contains(&input))
+                    .cloned()
+                    .collect())
+            };
+            match Text::new("Enter the tag|")
+                .with_autocomplete(suggester)
+                .prompt()
+            {
+                Ok(tag) if tag.trim().is_empty() => {
+                    println!("The tag cannot be empty");
+                    return Ok(());
+                }
+                Ok(tag) => DataChange::AddItemTag(item.get_surreal_record_id().clone(), tag),
+                Err(InquireError::OperationCanceled) => return Ok(()),
+                Err(InquireError::OperationInterrupted) => return Err(()),
+                Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+            }
+        }
+        Ok(TagsAction::RemoveTag(tag)) => {
+            DataChange::RemoveItemTag(item.get_surreal_record_id().clone(), tag.to_string())
+        }
+        Err(InquireError::OperationCanceled) => return Ok(()),
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    };
+    DataLayerCommands::change(send_to_data_storage_layer, change)
+        .await
+        .or_else(report_not_saved)
+}
@@ -8,7 +8,7 @@ use crate::{
     calculated_data::CalculatedData,
     data_storage::surrealdb_layer::{
         SurrealItemsInScope, SurrealTrigger,
-        data_layer_commands::DataLayerCommands,
+        data_layer_commands::{DataChange, DataLayerCommands},
         surreal_item::{SurrealDependency, SurrealScheduled, SurrealUrgency, SurrealUrgencyPlan},
         surreal_tables::SurrealTables,
     },
@@ -16,6 +16,7 @@ use crate::{
         display_dependencies_with_item_node::DisplayDependenciesWithItemNode,
         display_item_node::DisplayFormat,
     },
+    menu::inquire::report_not_saved,
     menu::inquire::{
         do_now_list_menu::do_now_list_single_item::state_a_smaller_action::{
             SelectAnItemSortingOrder, select_an_item,
@@ -34,6 +35,7 @@ use std::{
     fmt::{Display, Formatter},
     iter::once,
 };
+use surrealdb::opt::RecordId;
 
 enum UrgencyPlanSelection {
     StaysTheSame,
@@ -93,6 +95,20 @@ pub(crate) enum AddOrRemove {
     RemoveExisting(SurrealDependency),
 }
 
+impl AddOrRemove {
+    pub(crate) fn into_data_change(self, item: RecordId) -> DataChange {
+        match self {
+            AddOrRemove::AddExisting(dependency) => DataChange::AddItemDependency(item, dependency),
+            AddOrRemove::AddNewEvent(new_event) => {
+                DataChange::AddItemDependencyNewEvent(item, new_event)
+            }
+            AddOrRemove::RemoveExisting(dependency) => {
+                DataChange::RemoveItemDependency(item, dependency)
+            }
+        }
+    }
+}
+
 enum RemoveOrKeep {
     Remove,
     Keep,
@@ -279,13 +295,18 @@ pub(crate) async fn prompt_for_dependencies(
                 Ok(EventSelection::ExistingEvent(event)) => {
                     //If the event is triggered so we need to untrigger the event so it can be triggered again
                     //But we also do this even if the event is not triggered because it also updates the last updated time
-                    send_to_data_storage_layer
-                        .send(DataLayerCommands::UntriggerEvent {
+                    if let Err(err) = DataLayerCommands::change(
+                        send_to_data_storage_layer,
+                        DataChange::UntriggerEvent {
                             event: event.get_surreal_record_id().clone(),
                             when: Utc::now().into(),
-                        })
-                        .await
-                        .unwrap();
+                        },
+                    )
+                    .await
+                    {
+                        //The event can still be waited on, it just keeps its old last updated time
+                        report_not_saved(err)?;
+                    }
                     let event =
                         SurrealDependency::AfterEvent(event.get_surreal_record_id().clone());
                     result.push(AddOrRemove::AddExisting(event));
@@ -811,45 +832,17 @@ pub(crate) async fn present_set_ready_and_urgency_plan_menu(
     )
     .await;
 
-    for command in dependencies.into_iter() {
-        match command {
-            AddOrRemove::AddExisting(dependency) => {
-                send_to_data_storage_layer
-                    .send(DataLayerCommands::AddItemDependency(
-                        selected.get_surreal_record_id().clone(),
-                        dependency,
-                    ))
-                    .await
-                    .unwrap();
-            }
-            AddOrRemove::RemoveExisting(dependency) => {
-                send_to_data_storage_layer
-                    .send(DataLayerCommands::RemoveItemDependency(
-                        selected.get_surreal_record_id().clone(),
-                        dependency,
-                    ))
-                    .await
-                    .unwrap();
-            }
-            AddOrRemove::AddNewEvent(new_event) => {
-                send_to_data_storage_layer
-                    .send(DataLayerCommands::AddItemDependencyNewEvent(
-                        selected.get_surreal_record_id().clone(),
-                        new_event,
-                    ))
-                    .await
-                    .unwrap();
-            }
-        }
-    }
-
-    send_to_data_storage_layer
-        .send(DataLayerCommands::UpdateUrgencyPlan(
-            selected.get_surreal_record_id().clone(),
+    //Saved as one transaction so the item is not left with only some of the changes
+    let item = selected.get_surreal_record_id();
+    let changes = dependencies
+        .into_iter()
+        .map(|dependency| dependency.into_data_change(item.clone()))
+        .chain(once(DataChange::UpdateUrgencyPlan(
+            item.clone(),
             Some(urgency_plan),
-        ))
+        )))
+        .collect();
+    DataLayerCommands::transaction(send_to_data_storage_layer, changes)
         .await
-        .unwrap();
-
-    Ok(())
+        .or_else(report_not_saved)
 }
@@ -5,9 +5,10 @@ use tokio::sync::mpsc::Sender;
 
 use crate::{
     data_storage::surrealdb_layer::{
-        data_layer_commands::DataLayerCommands,
+        data_layer_commands::{DataChange, DataLayerCommands},
         surreal_item::{SurrealFrequency, SurrealReviewGuidance},
     },
+    menu::inquire::report_not_saved,
     node::item_status::ItemStatus,
 };
 
@@ -132,14 +133,14 @@ pub(crate) async fn present_pick_item_review_frequency_menu(
     .prompt()
     .unwrap();
 
-    send_to_data_storage_layer
-        .send(DataLayerCommands::UpdateItemReviewFrequency(
+    DataLayerCommands::change(
+        send_to_data_storage_layer,
+        DataChange::UpdateItemReviewFrequency(
             item_status.get_surreal_record_id().clone(),
             surreal_review_frequency,
             review_guidance.into(),
-        ))
-        .await
-        .unwrap();
-
-    Ok(())
+        ),
+    )
+    .await
+    .or_else(report_not_saved)
 }
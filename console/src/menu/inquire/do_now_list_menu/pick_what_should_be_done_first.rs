@@ -7,7 +7,8 @@ use tokio::sync::mpsc::Sender;
 
 use crate::{
     data_storage::surrealdb_layer::{
-        data_layer_commands::DataLayerCommands, surreal_in_the_moment_priority::SurrealPriorityKind,
+        data_layer_commands::{DataChange, DataLayerCommands},
+        surreal_in_the_moment_priority::SurrealPriorityKind,
     },
     display::{
         display_item_node::DisplayFormat,
@@ -24,6 +25,7 @@ use crate::{
         present_do_now_list_menu,
         review_item::present_review_item_menu,
     },
+    menu::inquire::report_not_saved,
     node::{Filter, action_with_item_status::ActionWithItemStatus},
     systems::do_now_list::DoNowList,
 };
@@ -120,15 +122,15 @@ pub(crate) async fn present_pick_what_should_be_done_first_menu<'a>(
         HighestOrLowest::RecordLowestPriorityUntil => SurrealPriorityKind::LowestPriority,
         HighestOrLowest::FinishOrRetireItem => {
             let now = Utc::now();
-            send_to_data_storage_layer
-                .send(DataLayerCommands::FinishItem {
+            return DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::FinishItem {
                     item: choice.get_surreal_record_id().clone(),
                     when_finished: now.into(),
-                })
-                .await
-                .unwrap();
-
-            return Ok(());
+                },
+            )
+            .await
+            .or_else(report_not_saved);
         }
         HighestOrLowest::PickThisTime => {
             let why_in_scope = choice.get_why_in_scope();
@@ -241,15 +243,15 @@ pub(crate) async fn present_pick_what_should_be_done_first_menu<'a>(
     let now = Utc::now();
     let in_effect_until = prompt_for_triggers(&now, send_to_data_storage_layer).await;
 
-    send_to_data_storage_layer
-        .send(DataLayerCommands::DeclareInTheMomentPriority {
+    DataLayerCommands::change(
+        send_to_data_storage_layer,
+        DataChange::DeclareInTheMomentPriority {
             choice: choice.clone_to_surreal_action(),
             kind: highest_or_lowest,
             not_chosen,
             in_effect_until,
-        })
-        .await
-        .unwrap();
-
-    Ok(())
+        },
+    )
+    .await
+    .or_else(report_not_saved)
 }
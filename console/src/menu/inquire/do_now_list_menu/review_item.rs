@@ -11,18 +11,20 @@ use crate::{
     base_data::{BaseData, item::Item},
     calculated_data::CalculatedData,
     data_storage::surrealdb_layer::{
-        data_layer_commands::DataLayerCommands, surreal_tables::SurrealTables,
+        data_layer_commands::{DataChange, DataLayerCommands},
+        surreal_tables::SurrealTables,
     },
     display::{
         display_dependencies_with_item_node::DisplayDependenciesWithItemNode,
         display_item::DisplayItem, display_item_node::DisplayFormat,
         display_item_status::DisplayItemStatus, display_urgency_plan::DisplayUrgencyPlan,
     },
+    menu::inquire::report_not_saved,
     menu::inquire::{
         do_now_list_menu::do_now_list_single_item::{
             give_this_item_a_parent::give_this_item_a_parent,
             state_a_smaller_action::state_a_smaller_action,
-            urgency_plan::{AddOrRemove, prompt_for_dependencies, prompt_for_urgency_plan},
+            urgency_plan::{prompt_for_dependencies, prompt_for_urgency_plan},
         },
         select_higher_importance_than_this::select_higher_importance_than_this,
     },
@@ -238,15 +240,15 @@ async fn present_review_item_menu_internal<'a>(
     match selected {
         ReviewItemMenuChoices::DoneWithReview => {
             let now = Utc::now();
-            send_to_data_storage_layer
-                .send(DataLayerCommands::UpdateItemLastReviewedDate(
+            DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::UpdateItemLastReviewedDate(
                     item_under_review.get_surreal_record_id().clone(),
                     now.into(),
-                ))
-                .await
-                .unwrap();
-
-            Ok(())
+                ),
+            )
+            .await
+            .or_else(report_not_saved)
         }
         ReviewItemMenuChoices::UpdateRelativeImportanceDontShowSingleParent { parent }
         | ReviewItemMenuChoices::UpdateRelativeImportanceShowParent { parent } => {
@@ -279,36 +281,16 @@ async fn present_review_item_menu_internal<'a>(
                 prompt_for_dependencies(Some(selected_item), base_data, send_to_data_storage_layer)
                     .await
                     .unwrap();
-            for command in dependencies.into_iter() {
-                match command {
-                    AddOrRemove::AddExisting(dependency) => {
-                        send_to_data_storage_layer
-                            .send(DataLayerCommands::AddItemDependency(
-                                selected_item.get_surreal_record_id().clone(),
-                                dependency,
-                            ))
-                            .await
-                            .unwrap();
-                    }
-                    AddOrRemove::AddNewEvent(new_event) => {
-                        send_to_data_storage_layer
-                            .send(DataLayerCommands::AddItemDependencyNewEvent(
-                                selected_item.get_surreal_record_id().clone(),
-                                new_event,
-                            ))
-                            .await
-                            .unwrap();
-                    }
-                    AddOrRemove::RemoveExisting(dependency) => {
-                        send_to_data_storage_layer
-                            .send(DataLayerCommands::RemoveItemDependency(
-                                selected_item.get_surreal_record_id().clone(),
-                                dependency,
-                            ))
-                            .await
-                            .unwrap();
-                    }
-                }
+            let changes = dependencies
+                .into_iter()
+                .map(|dependency| {
+                    dependency.into_data_change(selected_item.get_surreal_record_id().clone())
+                })
+                .collect();
+            if let Err(err) =
+                DataLayerCommands::transaction(send_to_data_storage_layer, changes).await
+            {
+                return report_not_saved(err);
             }
 
             refresh_items_present_review_item_menu_internal(
@@ -321,13 +303,17 @@ async fn present_review_item_menu_internal<'a>(
         ReviewItemMenuChoices::UpdateUrgencyPlan { current_item } => {
             let now = Utc::now();
             let urgency_plan = prompt_for_urgency_plan(&now, send_to_data_storage_layer).await;
-            send_to_data_storage_layer
-                .send(DataLayerCommands::UpdateUrgencyPlan(
+            if let Err(err) = DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::UpdateUrgencyPlan(
                     current_item.get_surreal_record_id().clone(),
                     Some(urgency_plan),
-                ))
-                .await
-                .unwrap();
+                ),
+            )
+            .await
+            {
+                return report_not_saved(err);
+            }
 
             refresh_items_present_review_item_menu_internal(
                 item_under_review,
@@ -338,13 +324,17 @@ async fn present_review_item_menu_internal<'a>(
         }
         ReviewItemMenuChoices::FinishThisItem => {
             let when_finished: Datetime = (Utc::now()).into();
-            send_to_data_storage_layer
-                .send(DataLayerCommands::FinishItem {
+            if let Err(err) = DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::FinishItem {
                     item: selected_item.get_surreal_record_id().clone(),
                     when_finished,
-                })
-                .await
-                .unwrap();
+                },
+            )
+            .await
+            {
+                return report_not_saved(err);
+            }
 
             if selected_item.get_item() == item_under_review.get_item() {
                 Ok(())
@@ -402,13 +392,17 @@ async fn present_review_item_menu_internal<'a>(
             .await
         }
         ReviewItemMenuChoices::RemoveParent(item) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::ParentItemRemoveParent {
+            if let Err(err) = DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::ParentItemRemoveParent {
                     child: selected_item.get_surreal_record_id().clone(),
                     parent_to_remove: item.get_surreal_record_id().clone(),
-                })
-                .await
-                .unwrap();
+                },
+            )
+            .await
+            {
+                return report_not_saved(err);
+            }
 
             refresh_items_present_review_item_menu_internal(
                 item_under_review,
@@ -431,13 +425,17 @@ async fn present_review_item_menu_internal<'a>(
             .await
         }
         ReviewItemMenuChoices::RemoveChild(item) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::ParentItemRemoveParent {
+            if let Err(err) = DataLayerCommands::change(
+                send_to_data_storage_layer,
+                DataChange::ParentItemRemoveParent {
                     child: item.get_surreal_record_id().clone(),
                     parent_to_remove: selected_item.get_surreal_record_id().clone(),
-                })
-                .await
-                .unwrap();
+                },
+            )
+            .await
+            {
+                return report_not_saved(err);
+            }
 
             refresh_items_present_review_item_menu_internal(
                 item_under_review,
@@ -466,14 +464,14 @@ pub(crate) async fn update_relative_importance(
         .filter(|x| *x != item_to_move)
         .collect::<Vec<_>>();
     let higher_than = select_higher_importance_than_this(&priority_list, Some(current_position));
-    send_to_data_storage_layer
-        .send(DataLayerCommands::UpdateRelativeImportance {
+    DataLayerCommands::change(
+        send_to_data_storage_layer,
+        DataChange::UpdateRelativeImportance {
             parent: parent.get_surreal_record_id().clone(),
             update_this_child: item_to_move.get_surreal_record_id().clone(),
             higher_importance_than_this_child: higher_than,
-        })
-        .await
-        .unwrap();
-
-    Ok(())
+        },
+    )
+    .await
+    .or_else(report_not_saved)
 }
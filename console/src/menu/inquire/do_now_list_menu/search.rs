@@ -1,7 +1,10 @@
+pub(crate) mod search_archive;
+
 use std::fmt::{self, Display, Formatter};
 
 use chrono::Utc;
 use inquire::{InquireError, Select};
+use search_archive::present_search_archive_menu;
 use tokio::sync::mpsc::Sender;
 
 use crate::{
@@ -61,6 +64,8 @@ enum SearchMenuUrgencyItem<'e> {
     Item {
         item: &'e ItemStatus<'e>,
     },
+    /// Items that were finished long ago are archived and only searched when this is picked
+    Archive,
 }
 
 impl Display for SearchMenuUrgencyItem<'_> {
@@ -169,6 +174,9 @@ impl Display for SearchMenuUrgencyItem<'_> {
                     DisplayItemStatus::new(item, Filter::Active, DisplayFormat::SingleLine);
                 write!(f, "{}", display_item_status)
             }
+            SearchMenuUrgencyItem::Archive => {
+                write!(f, "📦  Search the archive, items finished long ago")
+            }
         }
     }
 }
@@ -186,7 +194,9 @@ impl<'e> SearchMenuUrgencyItem<'e> {
                 ready_highest_importance: ready,
                 ..
             } => ready.push(to_push),
-            SearchMenuUrgencyItem::Item { .. } | SearchMenuUrgencyItem::AllMotivations { .. } => {
+            SearchMenuUrgencyItem::Item { .. }
+            | SearchMenuUrgencyItem::AllMotivations { .. }
+            | SearchMenuUrgencyItem::Archive => {
                 panic!("Programming error. Can't push onto {:#?}", self)
             }
         }
@@ -206,7 +216,9 @@ impl<'e> SearchMenuUrgencyItem<'e> {
                 when_ready_will_be_highest_importance: not_ready,
                 ..
             } => not_ready.push(to_push),
-            SearchMenuUrgencyItem::Item { .. } | SearchMenuUrgencyItem::AllMotivations { .. } => {
+            SearchMenuUrgencyItem::Item { .. }
+            | SearchMenuUrgencyItem::AllMotivations { .. }
+            | SearchMenuUrgencyItem::Archive => {
                 panic!("Programming error. Can't push onto {:#?}", self)
             }
         }
@@ -228,7 +240,9 @@ impl<'e> SearchMenuUrgencyItem<'e> {
             SearchMenuUrgencyItem::HighestImportance {
                 nothing_is_ready, ..
             } => nothing_is_ready.push(to_push),
-            SearchMenuUrgencyItem::Item { .. } | SearchMenuUrgencyItem::AllMotivations { .. } => {
+            SearchMenuUrgencyItem::Item { .. }
+            | SearchMenuUrgencyItem::AllMotivations { .. }
+            | SearchMenuUrgencyItem::Archive => {
                 panic!("Programming error. Can't push onto {:#?}", self)
             }
         }
@@ -238,6 +252,7 @@ impl<'e> SearchMenuUrgencyItem<'e> {
         match self {
             SearchMenuUrgencyItem::AllMotivations { motivations } => motivations.push(to_push),
             SearchMenuUrgencyItem::Item { .. }
+            | SearchMenuUrgencyItem::Archive
             | SearchMenuUrgencyItem::MoreUrgentThanAnythingIncludingScheduled { .. }
             | SearchMenuUrgencyItem::ScheduledAnyMode { .. }
             | SearchMenuUrgencyItem::MoreUrgentThanMode { .. }
@@ -288,7 +303,7 @@ impl<'e> SearchMenuUrgencyItem<'e> {
                 nothing_is_ready: coming_later,
             } => ready.is_empty() && not_ready.is_empty() && coming_later.is_empty(),
             SearchMenuUrgencyItem::AllMotivations { motivations } => motivations.is_empty(),
-            SearchMenuUrgencyItem::Item { item: _item } => false,
+            SearchMenuUrgencyItem::Item { item: _item } | SearchMenuUrgencyItem::Archive => false,
         }
     }
 }
@@ -627,9 +642,44 @@ pub(crate) async fn present_search_menu(
     for (_, item) in items.iter().filter(|(_, x)| x.is_active()) {
         list.push(SearchMenuUrgencyItem::Item { item });
     }
-
+    list.push(SearchMenuUrgencyItem::Archive);
+
+    //Items can also be found by what is in their notes and links, those come after items found by their summary.
+    //Starting with # searches by tag instead, "#garden" finds the items tagged with a tag starting with garden.
+    let scorer = |input: &str, option: &SearchMenuUrgencyItem, string_value: &str, index: usize| {
+        if let Some(tag) = input.strip_prefix('#') {
+            let tag = tag.to_lowercase();
+            return match option {
+                SearchMenuUrgencyItem::Item { item }
+                    if item
+                        .get_item_node()
+                        .get_tags(Filter::Active)
+                        .iter()
+                        .any(|x| x.to_lowercase().starts_with(&tag)) =>
+                {
+                    Some(0)
+                }
+                _ => None,
+            };
+        }
+        Select::<SearchMenuUrgencyItem>::DEFAULT_SCORER(input, option, string_value, index).or_else(
+            || match option {
+                SearchMenuUrgencyItem::Item { item }
+                    if item.get_item().notes_or_links_contain(input) =>
+                {
+                    Some(0)
+                }
+                _ => None,
+            },
+        )
+    };
     println!();
-    let selection = Select::new("Select an item to view", list).prompt();
+    let selection = Select::new(
+        "Select an item to view (start with # to search by tag)",
+        list,
+    )
+    .with_scorer(&scorer)
+    .prompt();
 
     match selection {
         Ok(SearchMenuUrgencyItem::MoreUrgentThanAnythingIncludingScheduled {
@@ -778,6 +828,9 @@ pub(crate) async fn present_search_menu(
             )
             .await
         }
+        Ok(SearchMenuUrgencyItem::Archive) => {
+            present_search_archive_menu(send_to_data_storage_layer).await
+        }
         Ok(SearchMenuUrgencyItem::AllMotivations { motivations }) => {
             let list = motivations
                 .iter()
@@ -0,0 +1,105 @@
+use std::fmt::{self, Display, Formatter};
+
+use chrono::{DateTime, Local, Utc};
+use inquire::{InquireError, Select};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    data_storage::surrealdb_layer::{
+        data_layer_commands::DataLayerCommands,
+        surreal_archive::{SurrealArchive, SurrealArchivedItem},
+    },
+    menu::inquire::report_not_saved,
+};
+
+struct ArchivedItemChoice<'e>(&'e SurrealArchivedItem);
+
+impl Display for ArchivedItemChoice<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let item = &self.0.item;
+        match &item.finished {
+            Some(finished) => {
+                let finished: DateTime<Utc> = finished.clone().into();
+                write!(
+                    f,
+                    "{} (finished {})",
+                    item.summary,
+                    finished.with_timezone(&Local).format("%a %d %b %Y")
+                )
+            }
+            None => write!(f, "{}", item.summary),
+        }
+    }
+}
+
+enum ArchivedItemAction {
+    Unarchive,
+    UnarchiveAndReopen,
+    Back,
+}
+
+impl Display for ArchivedItemAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchivedItemAction::Unarchive => write!(f, "Bring it back, still finished"),
+            ArchivedItemAction::UnarchiveAndReopen => {
+                write!(
+                    f,
+                    "Bring it back and re-open it, it is not finished after all"
+                )
+            }
+            ArchivedItemAction::Back => write!(f, "Back"),
+        }
+    }
+}
+
+/// The archive is only read when it is searched because it only grows
+pub(crate) async fn present_search_archive_menu(
+    send_to_data_storage_layer: &Sender<DataLayerCommands>,
+) -> Result<(), ()> {
+    let surreal_archive = SurrealArchive::new(send_to_data_storage_layer)
+        .await
+        .unwrap();
+    let mut list = surreal_archive
+        .items
+        .iter()
+        .map(ArchivedItemChoice)
+        .collect::<Vec<_>>();
+    if list.is_empty() {
+        println!("Nothing has been archived");
+        return Ok(());
+    }
+    list.sort_by(|a, b| b.0.item.finished.cmp(&a.0.item.finished));
+
+    let selection = Select::new(
+        "Type to search the archive, the most recently finished are first|",
+        list,
+    )
+    .prompt();
+    let archived_item = match selection {
+        Ok(ArchivedItemChoice(archived_item)) => archived_item,
+        Err(InquireError::OperationCanceled) => return Ok(()),
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    };
+
+    let list = vec![
+        ArchivedItemAction::Unarchive,
+        ArchivedItemAction::UnarchiveAndReopen,
+        ArchivedItemAction::Back,
+    ];
+    let selection = Select::new("Select from the below list|", list).prompt();
+    let reopen = match selection {
+        Ok(ArchivedItemAction::Unarchive) => false,
+        Ok(ArchivedItemAction::UnarchiveAndReopen) => true,
+        Ok(ArchivedItemAction::Back) | Err(InquireError::OperationCanceled) => {
+            return Box::pin(present_search_archive_menu(send_to_data_storage_layer)).await;
+        }
+        Err(InquireError::OperationInterrupted) => return Err(()),
+        Err(err) => panic!("Unexpected error, try restarting the terminal: {}", err),
+    };
+    let item = archived_item.item.id.clone().expect("In DB");
+    DataLayerCommands::unarchive(send_to_data_storage_layer, item, reopen)
+        .await
+        .or_else(report_not_saved)
+}
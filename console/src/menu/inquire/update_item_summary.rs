@@ -2,7 +2,8 @@ use crate::base_data::item::Item;
 use inquire::{InquireError, Text};
 use tokio::sync::mpsc::Sender;
 
-use crate::data_storage::surrealdb_layer::data_layer_commands::DataLayerCommands;
+use crate::data_storage::surrealdb_layer::data_layer_commands::{DataChange, DataLayerCommands};
+use crate::menu::inquire::report_not_saved;
 
 pub(crate) async fn update_item_summary(
     item_to_update: &Item<'_>,
@@ -12,16 +13,12 @@ pub(crate) async fn update_item_summary(
         .with_initial_value(item_to_update.get_summary())
         .prompt();
     match new_summary {
-        Ok(new_summary) => {
-            send_to_data_storage_layer
-                .send(DataLayerCommands::UpdateSummary(
-                    item_to_update.get_surreal_record_id().clone(),
-                    new_summary,
-                ))
-                .await
-                .unwrap();
-            Ok(())
-        }
+        Ok(new_summary) => DataLayerCommands::change(
+            send_to_data_storage_layer,
+            DataChange::UpdateSummary(item_to_update.get_surreal_record_id().clone(), new_summary),
+        )
+        .await
+        .or_else(report_not_saved),
         Err(InquireError::OperationCanceled) => todo!("Handle return to caller"),
         Err(InquireError::OperationInterrupted) => Err(()),
         Err(err) => panic!("Unexpected error: {:?}", err),
@@ -1,7 +1,7 @@
 use std::{iter, time::Duration};
 
 use ahash::HashMap;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use surrealdb::{
     opt::RecordId,
     sql::{Datetime, Thing},
@@ -9,12 +9,12 @@ use surrealdb::{
 
 use crate::{
     base_data::{Visited, event::Event, item::Item, time_spent::TimeSpent},
-    calculated_data::parent_lookup::ParentLookup,
+    calculated_data::{node_shapes::ItemShape, parent_lookup::ParentLookup},
     data_storage::surrealdb_layer::{
         SurrealItemsInScope, SurrealTrigger,
         surreal_item::{
-            SurrealDependency, SurrealItem, SurrealItemType, SurrealReviewGuidance,
-            SurrealScheduled, SurrealUrgency, SurrealUrgencyPlan,
+            SurrealDeadline, SurrealDependency, SurrealItem, SurrealItemType,
+            SurrealReviewGuidance, SurrealScheduled, SurrealUrgency, SurrealUrgencyPlan,
         },
     },
 };
@@ -263,18 +263,22 @@ impl<'s> ItemNode<'s> {
         all_events: &'s HashMap<&'s RecordId, Event<'s>>,
         time_spent_log: &[TimeSpent],
     ) -> Self {
-        let visited = Visited::new(item.get_surreal_record_id(), None);
-        let parents = item.find_parents(parent_lookup, &visited);
-        let parents = create_growing_nodes(parents, parent_lookup, &visited);
-        let visited: Vec<&RecordId> = iter::once(item.get_surreal_record_id())
-            .chain(parents.iter().flat_map(|x| {
-                x.get_self_and_parents(Vec::default())
-                    .into_iter()
-                    .map(|x| x.get_surreal_record_id())
-            }))
-            .collect();
-        let children = item.find_children(all_items, &visited);
-        let children = create_shrinking_nodes(&children, all_items, visited);
+        let shape = ItemShape::new(item, all_items, parent_lookup);
+        ItemNode::new_from_shape(item, &shape, all_items, all_events, time_spent_log)
+    }
+
+    /// For when the larger and smaller items were already found, only what can change as time passes is worked out
+    pub(crate) fn new_from_shape(
+        item: &'s Item<'s>,
+        shape: &ItemShape,
+        all_items: &'s HashMap<&'s RecordId, Item<'s>>,
+        all_events: &'s HashMap<&'s RecordId, Event<'s>>,
+        time_spent_log: &[TimeSpent],
+    ) -> Self {
+        let parents: Vec<GrowingItemNode<'s>> =
+            shape.larger.iter().map(|x| x.to_node(all_items)).collect();
+        let children: Vec<ShrinkingItemNode<'s>> =
+            shape.smaller.iter().map(|x| x.to_node(all_items)).collect();
         let urgency_plan = calculate_urgency_plan(item, all_items, time_spent_log);
         let dependencies =
             calculate_dependencies(item, &urgency_plan, all_items, all_events, &children);
@@ -308,6 +312,20 @@ impl<'s> ItemNode<'s> {
         result
     }
 
+    /// The tags of this item and every larger item above it, each tag is only listed once
+    pub(crate) fn get_tags(&'s self, filter: Filter) -> Vec<&'s str> {
+        let mut tags: Vec<&'s str> = Vec::default();
+        let larger = self.create_parent_chain(filter).into_iter().map(|(_, x)| x);
+        for item in std::iter::once(self.item).chain(larger) {
+            for tag in item.get_tags() {
+                if !tags.contains(&tag.as_str()) {
+                    tags.push(tag);
+                }
+            }
+        }
+        tags
+    }
+
     pub(crate) fn get_children(
         &'s self,
         filter: Filter,
@@ -319,6 +337,30 @@ impl<'s> ItemNode<'s> {
         }))
     }
 
+    /// The estimates of this item and of its smaller items added together, None when none of them have an estimate
+    pub(crate) fn get_estimate_rolled_up(&'s self, filter: Filter) -> Option<Duration> {
+        add_estimates(
+            iter::once(self.item.get_estimate()).chain(
+                self.get_children(filter)
+                    .map(|x| x.get_estimate_rolled_up(filter)),
+            ),
+        )
+    }
+
+    /// What is left of the estimates of this item and of its smaller items that are not finished, after taking away
+    /// the time already logged on each of them. Going over the estimate on one item does not take from the others.
+    pub(crate) fn get_remaining_effort(
+        &'s self,
+        time_spent_log: &[TimeSpent<'_>],
+    ) -> Option<Duration> {
+        add_estimates(
+            iter::once(remaining_effort_of(self.item, time_spent_log)).chain(
+                self.get_children(Filter::Active)
+                    .map(|x| x.get_remaining_effort(time_spent_log)),
+            ),
+        )
+    }
+
     pub(crate) fn get_item(&self) -> &'s Item<'s> {
         self.item
     }
@@ -656,7 +698,13 @@ fn calculate_urgency_plan<'a>(
     all_items: &'a HashMap<&'a RecordId, Item>,
     time_spent_log: &[TimeSpent],
 ) -> Option<UrgencyPlanWithItem<'a>> {
-    item.get_surreal_urgency_plan().as_ref().map(|x| match x {
+    //An urgency plan set by hand is used over the one that comes from the deadline
+    let Some(urgency_plan) = item.get_surreal_urgency_plan() else {
+        return item
+            .get_deadline()
+            .map(|x| urgency_plan_from_deadline(x, item.get_now()));
+    };
+    Some(match urgency_plan {
         SurrealUrgencyPlan::WillEscalate {
             initial,
             triggers,
@@ -675,6 +723,54 @@ fn calculate_urgency_plan<'a>(
     })
 }
 
+/// The urgency for where `now` is on the way to the deadline, escalating at the start of the next step. Once the
+/// deadline is close enough to be more urgent than the mode it stays that way, including after the deadline has passed.
+pub(crate) fn urgency_plan_from_deadline<'a>(
+    deadline: &SurrealDeadline,
+    now: &DateTime<Utc>,
+) -> UrgencyPlanWithItem<'a> {
+    let due: DateTime<Utc> = deadline.due.clone().into();
+    let lead_times = &deadline.lead_times;
+    let steps = [
+        (
+            lead_times.maybe_urgent,
+            SurrealUrgency::InTheModeMaybeUrgent,
+        ),
+        (
+            lead_times.definitely_urgent,
+            SurrealUrgency::InTheModeDefinitelyUrgent,
+        ),
+        (
+            lead_times.more_urgent_than_mode,
+            SurrealUrgency::MoreUrgentThanMode,
+        ),
+    ]
+    .map(|(lead_time, urgency)| {
+        let lead_time = TimeDelta::from_std(lead_time.into()).unwrap_or(TimeDelta::MAX);
+        (
+            due.checked_sub_signed(lead_time)
+                .unwrap_or(DateTime::<Utc>::MIN_UTC),
+            urgency,
+        )
+    });
+
+    let mut urgency_now = SurrealUrgency::InTheModeByImportance;
+    for (starts, urgency) in steps.into_iter() {
+        if &starts > now {
+            return UrgencyPlanWithItem::WillEscalate {
+                initial: urgency_now,
+                triggers: vec![TriggerWithItem::WallClockDateTime {
+                    after: starts,
+                    is_triggered: false,
+                }],
+                later: urgency,
+            };
+        }
+        urgency_now = urgency;
+    }
+    UrgencyPlanWithItem::StaysTheSame(urgency_now)
+}
+
 fn get_time_spent_on_this<'a>(
     after: &'a DateTime<Utc>,
     items_in_scope: &'a ItemsInScopeWithItem<'a>,
@@ -724,6 +820,48 @@ impl<'s> ShrinkingItemNode<'s> {
             Filter::Finished => Box::new(self.smaller.iter().filter(|x| x.item.is_finished())),
         }
     }
+
+    pub(crate) fn get_estimate_rolled_up(&'s self, filter: Filter) -> Option<Duration> {
+        add_estimates(
+            iter::once(self.item.get_estimate()).chain(
+                self.get_children(filter)
+                    .map(|x| x.get_estimate_rolled_up(filter)),
+            ),
+        )
+    }
+
+    pub(crate) fn get_remaining_effort(
+        &'s self,
+        time_spent_log: &[TimeSpent<'_>],
+    ) -> Option<Duration> {
+        add_estimates(
+            iter::once(remaining_effort_of(self.item, time_spent_log)).chain(
+                self.get_children(Filter::Active)
+                    .map(|x| x.get_remaining_effort(time_spent_log)),
+            ),
+        )
+    }
+}
+
+fn add_estimates(estimates: impl Iterator<Item = Option<Duration>>) -> Option<Duration> {
+    estimates.flatten().reduce(|a, b| a + b)
+}
+
+/// Only for the item itself, a finished item has nothing remaining
+fn remaining_effort_of(item: &Item<'_>, time_spent_log: &[TimeSpent<'_>]) -> Option<Duration> {
+    if item.is_finished() {
+        return None;
+    }
+    let estimate = item.get_estimate()?;
+    Some(estimate.saturating_sub(get_logged_time(item, time_spent_log)))
+}
+
+/// All of the time logged working on the item itself
+pub(crate) fn get_logged_time(item: &Item<'_>, time_spent_log: &[TimeSpent<'_>]) -> Duration {
+    let items_in_scope = ItemsInScopeWithItem::Include(vec![item]);
+    get_time_spent_on_this(&DateTime::<Utc>::MIN_UTC, &items_in_scope, time_spent_log)
+        .map(|x| *x.get_duration())
+        .sum()
 }
 
 pub(crate) fn create_shrinking_nodes<'a>(
@@ -858,18 +996,26 @@ fn calculate_urgent_action_items<'a>(
 
 #[cfg(test)]
 mod tests {
-    use chrono::Utc;
+    use std::time::Duration;
+
+    use chrono::{TimeDelta, Utc};
 
     use crate::{
         base_data::item::ItemVecExtensions,
         calculated_data::parent_lookup::ParentLookup,
         data_storage::surrealdb_layer::{
+            surreal_in_the_moment_priority::SurrealAction,
             surreal_item::{
-                SurrealDependency, SurrealItemBuilder, SurrealItemType, SurrealOrderedSubItem,
+                SurrealDeadline, SurrealDependency, SurrealItemBuilder, SurrealItemType,
+                SurrealOrderedSubItem, SurrealUrgency, SurrealUrgencyPlan,
             },
             surreal_tables::SurrealTablesBuilder,
+            surreal_time_spent::SurrealTimeSpent,
+        },
+        node::{
+            Filter, GetUrgencyNow,
+            item_node::{ItemNode, urgency_plan_from_deadline},
         },
-        node::{Filter, item_node::ItemNode},
     };
 
     #[test]
@@ -913,7 +1059,7 @@ mod tests {
         let items = surreal_tables.make_items(&now);
         let parent_lookup = ParentLookup::new(&items);
         let active_items = items.filter_active_items();
-        let events = surreal_tables.make_events();
+        let events = surreal_tables.make_events(&now);
 
         let to_dos = active_items
             .iter()
@@ -976,7 +1122,7 @@ mod tests {
         let items = surreal_tables.make_items(&now);
         let parent_lookup = ParentLookup::new(&items);
         let active_items = items.filter_active_items();
-        let events = surreal_tables.make_events();
+        let events = surreal_tables.make_events(&now);
 
         let to_dos = active_items
             .iter()
@@ -994,4 +1140,202 @@ mod tests {
             0
         );
     }
+
+    #[test]
+    fn smaller_items_have_the_tags_of_their_larger_items_listed_once() {
+        let surreal_items = vec![
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "1").into()))
+                .summary("Garden")
+                .item_type(SurrealItemType::Goal(Default::default()))
+                .tags(vec!["outside".to_string(), "weekend".to_string()])
+                .smaller_items_in_priority_order(vec![SurrealOrderedSubItem::SubItem {
+                    surreal_item_id: ("surreal_item", "2").into(),
+                }])
+                .build()
+                .unwrap(),
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "2").into()))
+                .summary("Plant tomatoes")
+                .item_type(SurrealItemType::Action)
+                .tags(vec!["weekend".to_string(), "errand".to_string()])
+                .build()
+                .unwrap(),
+        ];
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(surreal_items)
+            .build()
+            .expect("no required fields");
+        let all_time_spent = surreal_tables.make_time_spent_log().collect::<Vec<_>>();
+        let now = Utc::now();
+        let items = surreal_tables.make_items(&now);
+        let parent_lookup = ParentLookup::new(&items);
+        let events = surreal_tables.make_events(&now);
+        let item = items
+            .values()
+            .find(|x| x.get_summary() == "Plant tomatoes")
+            .unwrap();
+
+        let item_node = ItemNode::new(item, &items, &parent_lookup, &events, &all_time_spent);
+
+        assert_eq!(
+            item_node.get_tags(Filter::Active),
+            vec!["weekend", "errand", "outside"]
+        );
+    }
+
+    #[test]
+    fn a_deadline_escalates_the_urgency_as_it_gets_close_unless_there_is_an_urgency_plan() {
+        let now = Utc::now();
+        let deadline_in = |time_delta: TimeDelta| SurrealDeadline {
+            due: (now + time_delta).into(),
+            lead_times: Default::default(),
+        };
+        let urgency_for = |time_delta: TimeDelta| {
+            urgency_plan_from_deadline(&deadline_in(time_delta), &now)
+                .get_urgency_now()
+                .cloned()
+        };
+
+        assert_eq!(
+            urgency_for(TimeDelta::days(30)),
+            Some(SurrealUrgency::InTheModeByImportance)
+        );
+        assert_eq!(
+            urgency_for(TimeDelta::days(5)),
+            Some(SurrealUrgency::InTheModeMaybeUrgent)
+        );
+        assert_eq!(
+            urgency_for(TimeDelta::days(1)),
+            Some(SurrealUrgency::InTheModeDefinitelyUrgent)
+        );
+        assert_eq!(
+            urgency_for(TimeDelta::hours(1)),
+            Some(SurrealUrgency::MoreUrgentThanMode)
+        );
+        assert_eq!(
+            urgency_for(TimeDelta::days(-3)),
+            Some(SurrealUrgency::MoreUrgentThanMode)
+        );
+
+        let surreal_items = vec![
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "1").into()))
+                .summary("File taxes")
+                .item_type(SurrealItemType::Action)
+                .deadline(Some(deadline_in(TimeDelta::days(1))))
+                .build()
+                .unwrap(),
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "2").into()))
+                .summary("Renew passport")
+                .item_type(SurrealItemType::Action)
+                .deadline(Some(deadline_in(TimeDelta::days(1))))
+                .urgency_plan(Some(SurrealUrgencyPlan::StaysTheSame(
+                    SurrealUrgency::InTheModeByImportance,
+                )))
+                .build()
+                .unwrap(),
+        ];
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(surreal_items)
+            .build()
+            .expect("no required fields");
+        let all_time_spent = surreal_tables.make_time_spent_log().collect::<Vec<_>>();
+        let items = surreal_tables.make_items(&now);
+        let parent_lookup = ParentLookup::new(&items);
+        let events = surreal_tables.make_events(&now);
+        let urgency_of = |summary: &str| {
+            let item = items.values().find(|x| x.get_summary() == summary).unwrap();
+            ItemNode::new(item, &items, &parent_lookup, &events, &all_time_spent)
+                .get_urgency_now()
+                .cloned()
+        };
+
+        assert_eq!(
+            urgency_of("File taxes"),
+            Some(SurrealUrgency::InTheModeDefinitelyUrgent)
+        );
+        assert_eq!(
+            urgency_of("Renew passport"),
+            Some(SurrealUrgency::InTheModeByImportance)
+        );
+    }
+
+    #[test]
+    fn estimates_roll_up_and_what_remains_is_after_the_time_logged_on_each_item() {
+        let hours = |hours: u64| Duration::from_secs(hours * 60 * 60);
+        let now = Utc::now();
+        let surreal_items = vec![
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "1").into()))
+                .summary("Move house")
+                .item_type(SurrealItemType::Goal(Default::default()))
+                .estimate(Some(hours(2).into()))
+                .smaller_items_in_priority_order(vec![
+                    SurrealOrderedSubItem::SubItem {
+                        surreal_item_id: ("surreal_item", "2").into(),
+                    },
+                    SurrealOrderedSubItem::SubItem {
+                        surreal_item_id: ("surreal_item", "3").into(),
+                    },
+                ])
+                .build()
+                .unwrap(),
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "2").into()))
+                .summary("Pack")
+                .item_type(SurrealItemType::Action)
+                .estimate(Some(hours(5).into()))
+                .build()
+                .unwrap(),
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "3").into()))
+                .summary("Book a van")
+                .item_type(SurrealItemType::Action)
+                .estimate(Some(hours(1).into()))
+                .finished(Some(now.into()))
+                .build()
+                .unwrap(),
+        ];
+        let time_spent = |key: &str, item: &str, logged: u64| SurrealTimeSpent {
+            id: Some(("time_spent_log", key).into()),
+            version: 1,
+            working_on: vec![SurrealAction::MakeProgress(("surreal_item", item).into())],
+            why_in_scope: vec![],
+            urgency: None,
+            when_started: (now - TimeDelta::hours(logged as i64)).into(),
+            when_stopped: now.into(),
+            dedication: None,
+        };
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(surreal_items)
+            .surreal_time_spent_log(vec![time_spent("1", "2", 3), time_spent("2", "3", 4)])
+            .build()
+            .expect("no required fields");
+        let all_time_spent = surreal_tables.make_time_spent_log().collect::<Vec<_>>();
+        let items = surreal_tables.make_items(&now);
+        let parent_lookup = ParentLookup::new(&items);
+        let events = surreal_tables.make_events(&now);
+        let item = items
+            .values()
+            .find(|x| x.get_summary() == "Move house")
+            .unwrap();
+
+        let item_node = ItemNode::new(item, &items, &parent_lookup, &events, &all_time_spent);
+
+        assert_eq!(
+            item_node.get_estimate_rolled_up(Filter::All),
+            Some(hours(8))
+        );
+        assert_eq!(
+            item_node.get_estimate_rolled_up(Filter::Active),
+            Some(hours(7))
+        );
+        //The finished item taking longer than estimated does not change what remains for the others
+        assert_eq!(
+            item_node.get_remaining_effort(&all_time_spent),
+            Some(hours(4))
+        );
+    }
 }
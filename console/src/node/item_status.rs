@@ -653,7 +653,9 @@ mod tests {
         base_data::BaseData,
         calculated_data::CalculatedData,
         data_storage::surrealdb_layer::{
-            data_layer_commands::{DataLayerCommands, data_storage_start_and_run},
+            data_layer_commands::{
+                DataChange, DataLayerCommands, DataStorageSettings, data_storage_start_and_run,
+            },
             surreal_item::SurrealDependency,
             surreal_tables::SurrealTables,
         },
@@ -665,18 +667,21 @@ mod tests {
     async fn item_with_a_child_has_that_child_as_a_dependency() {
         // Arrange
         let (sender, receiver) = mpsc::channel(1);
-        let data_storage_join_handle =
-            tokio::spawn(async move { data_storage_start_and_run(receiver, "mem://").await });
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
 
-        sender
-            .send(DataLayerCommands::NewItem(
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(
                 NewItemBuilder::default()
                     .summary("Parent Item with a child")
                     .build()
                     .expect("valid new item"),
-            ))
-            .await
-            .unwrap();
+            ),
+        )
+        .await
+        .unwrap();
 
         let surreal_tables = SurrealTables::new(&sender).await.unwrap();
         let parent_item = surreal_tables
@@ -685,17 +690,19 @@ mod tests {
             .find(|x| x.summary == "Parent Item with a child")
             .expect("Just added this very item");
 
-        sender
-            .send(DataLayerCommands::ParentItemWithANewChildItem {
+        DataLayerCommands::change(
+            &sender,
+            DataChange::ParentItemWithANewChildItem {
                 child: NewItemBuilder::default()
                     .summary("Child Item")
                     .build()
                     .expect("Valid item"),
                 parent: parent_item.id.as_ref().expect("Is in DB").clone(),
                 higher_importance_than_this: None,
-            })
-            .await
-            .expect("Unit test setup/arrange");
+            },
+        )
+        .await
+        .expect("Unit test setup/arrange");
 
         let now = Utc::now();
         let surreal_tables = SurrealTables::new(&sender).await.unwrap();
@@ -720,18 +727,21 @@ mod tests {
     async fn item_with_another_item_as_a_dependency_has_a_dependency() {
         // Arrange
         let (sender, receiver) = mpsc::channel(1);
-        let data_storage_join_handle =
-            tokio::spawn(async move { data_storage_start_and_run(receiver, "mem://").await });
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
 
-        sender
-            .send(DataLayerCommands::NewItem(
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(
                 NewItemBuilder::default()
                     .summary("Item to be covered")
                     .build()
                     .expect("valid new item"),
-            ))
-            .await
-            .unwrap();
+            ),
+        )
+        .await
+        .unwrap();
 
         let surreal_tables = SurrealTables::new(&sender).await.unwrap();
         let parent_item = surreal_tables
@@ -740,16 +750,18 @@ mod tests {
             .find(|x| x.summary == "Item to be covered")
             .expect("Just added this very item");
 
-        sender
-            .send(DataLayerCommands::CoverItemWithANewItem {
+        DataLayerCommands::change(
+            &sender,
+            DataChange::CoverItemWithANewItem {
                 cover_this: parent_item.id.as_ref().expect("Is in DB").clone(),
                 cover_with: NewItemBuilder::default()
                     .summary("Covering Item")
                     .build()
                     .expect("Valid item"),
-            })
-            .await
-            .expect("Unit test setup/arrange");
+            },
+        )
+        .await
+        .expect("Unit test setup/arrange");
 
         let now = Utc::now();
         let surreal_tables = SurrealTables::new(&sender).await.unwrap();
@@ -774,11 +786,13 @@ mod tests {
     async fn item_that_needs_to_wait_until_tomorrow_has_a_dependency() {
         // Arrange
         let (sender, receiver) = mpsc::channel(1);
-        let data_storage_join_handle =
-            tokio::spawn(async move { data_storage_start_and_run(receiver, "mem://").await });
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
 
-        sender
-            .send(DataLayerCommands::NewItem(
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(
                 NewItemBuilder::default()
                     .summary("Item that needs to wait until tomorrow")
                     .dependencies(vec![NewDependency::Existing(
@@ -791,9 +805,10 @@ mod tests {
                     )])
                     .build()
                     .expect("valid new item"),
-            ))
-            .await
-            .unwrap();
+            ),
+        )
+        .await
+        .unwrap();
 
         let now = Utc::now();
 
@@ -9,16 +9,17 @@ pub(crate) struct ModeNode<'s> {
 
 impl<'s> ModeNode<'s> {
     pub(crate) fn new(mode: &'s Mode<'s>, all_modes: &'s [Mode<'s>]) -> Self {
-        let parent = match mode.get_parent() {
-            Some(parent_id) => {
-                let parent = all_modes
+        //A parent that no longer exists, for example deleted on another device, is left for the integrity check to
+        //report so until then this is shown as a top level mode
+        let parent = mode
+            .get_parent()
+            .as_ref()
+            .and_then(|parent_id| {
+                all_modes
                     .iter()
                     .find(|mode| mode.get_surreal_id() == parent_id)
-                    .expect("Parent mode must exist");
-                Some(Box::new(ModeNode::new(parent, all_modes)))
-            }
-            None => None,
-        };
+            })
+            .map(|parent| Box::new(ModeNode::new(parent, all_modes)));
 
         Self { mode, parent }
     }
@@ -32,6 +33,10 @@ impl<'s> ModeNode<'s> {
         chain
     }
 
+    pub(crate) fn get_mode(&self) -> &'s Mode<'s> {
+        self.mode
+    }
+
     pub(crate) fn get_surreal_id(&self) -> &Thing {
         self.mode.get_surreal_id()
     }
@@ -40,3 +45,29 @@ impl<'s> ModeNode<'s> {
         self.mode.get_name()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        base_data::mode::Mode, data_storage::surrealdb_layer::surreal_mode::SurrealMode,
+        node::mode_node::ModeNode,
+    };
+
+    #[test]
+    fn a_mode_whose_parent_no_longer_exists_is_a_top_level_mode() {
+        let surreal_modes = vec![SurrealMode {
+            id: Some(("modes", "1").into()),
+            name: "Kitchen".to_string(),
+            version: 0,
+            parent: Some(("modes", "deleted on another device").into()),
+            display_order: 0,
+        }];
+        let modes = surreal_modes.iter().map(Mode::new).collect::<Vec<_>>();
+
+        let mode_node = ModeNode::new(&modes[0], &modes);
+
+        let chain = mode_node.create_parent_chain();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].get_name(), "Kitchen");
+    }
+}
@@ -1,10 +1,13 @@
 //! I believe the main list of systems would be something to the effect of:
 //! * Bullet List (Uses Upcoming List)
 //! * Upcoming List
+//! * Agenda (Uses Upcoming List)
 //! * Goal Planning
 //! * Maintenance Support
 //! * Reflection
 //! * Recall
 
+pub(crate) mod agenda;
 pub(crate) mod do_now_list;
+pub(crate) mod maintenance;
 pub(crate) mod upcoming;
@@ -0,0 +1,334 @@
+use std::time::Duration;
+
+use ahash::HashMap;
+use chrono::{DateTime, Local, NaiveDate, TimeDelta, TimeZone, Utc};
+use surrealdb::opt::RecordId;
+
+use crate::{
+    data_storage::surrealdb_layer::surreal_item::SurrealScheduled,
+    node::{
+        Filter,
+        item_status::{DependencyWithItemNode, ItemStatus},
+    },
+    systems::upcoming::Upcoming,
+};
+
+/// Today and the next 7 days
+const DAYS_SHOWN: u32 = 8;
+
+/// The scheduler leaves a minute between items so anything shorter than this is not worth showing as free
+const SHORTEST_FREE_BLOCK: TimeDelta = TimeDelta::minutes(5);
+
+pub(crate) enum AgendaEntry<'s> {
+    Scheduled {
+        item_status: &'s ItemStatus<'s>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        /// Scheduled items that don't fit are shown at the time that they asked for and marked as a conflict
+        is_conflict: bool,
+    },
+    BecomesReady {
+        item_status: &'s ItemStatus<'s>,
+        when: DateTime<Utc>,
+    },
+}
+
+impl<'s> AgendaEntry<'s> {
+    pub(crate) fn get_item_status(&self) -> &'s ItemStatus<'s> {
+        match self {
+            AgendaEntry::Scheduled { item_status, .. }
+            | AgendaEntry::BecomesReady { item_status, .. } => item_status,
+        }
+    }
+
+    pub(crate) fn get_start(&self) -> &DateTime<Utc> {
+        match self {
+            AgendaEntry::Scheduled { start, .. } => start,
+            AgendaEntry::BecomesReady { when, .. } => when,
+        }
+    }
+
+    /// The start and end of a range item's window, None for everything else
+    pub(crate) fn get_range_window(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        match self {
+            AgendaEntry::Scheduled { item_status, .. } => match item_status.get_scheduled_now() {
+                Some(SurrealScheduled::Range { start_range, .. }) => {
+                    Some((start_range.0.clone().into(), start_range.1.clone().into()))
+                }
+                Some(SurrealScheduled::Exact { .. }) | None => None,
+            },
+            AgendaEntry::BecomesReady { .. } => None,
+        }
+    }
+}
+
+pub(crate) struct AgendaDay<'s> {
+    date: NaiveDate,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    entries: Vec<AgendaEntry<'s>>,
+}
+
+impl<'s> AgendaDay<'s> {
+    pub(crate) fn get_date(&self) -> &NaiveDate {
+        &self.date
+    }
+
+    /// Sorted by when they start
+    pub(crate) fn get_entries(&self) -> &[AgendaEntry<'s>] {
+        &self.entries
+    }
+
+    /// Only the part of a scheduled item that falls on this day is counted, conflicts that overlap are counted once
+    pub(crate) fn get_committed_time(&self) -> Duration {
+        self.get_committed_blocks()
+            .iter()
+            .map(|(start, end)| (*end - *start).to_std().unwrap_or_default())
+            .sum()
+    }
+
+    /// The free time between scheduled items, not the time before the first one or after the last one
+    pub(crate) fn get_free_blocks(&self) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        self.get_committed_blocks()
+            .windows(2)
+            .map(|x| (x[0].1, x[1].0))
+            .filter(|(start, end)| *end - *start >= SHORTEST_FREE_BLOCK)
+            .collect()
+    }
+
+    /// Sorted, clipped to this day and with overlapping items joined together
+    fn get_committed_blocks(&self) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        let mut blocks: Vec<(DateTime<Utc>, DateTime<Utc>)> = Vec::default();
+        for entry in self.entries.iter() {
+            let AgendaEntry::Scheduled { start, end, .. } = entry else {
+                continue;
+            };
+            let start = *start.max(&self.start);
+            let end = *end.min(&self.end);
+            match blocks.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => blocks.push((start, end)),
+            }
+        }
+        blocks
+    }
+}
+
+/// What is scheduled for today and the next 7 days, along with when items that are waiting on a date and time become
+/// ready. Scheduled items are shown where `Upcoming` placed them.
+pub(crate) struct Agenda<'s> {
+    days: Vec<AgendaDay<'s>>,
+}
+
+impl<'s> Agenda<'s> {
+    pub(crate) fn new(
+        upcoming: &'s Upcoming<'s>,
+        items_status: &'s HashMap<&'s RecordId, ItemStatus<'s>>,
+        now: &DateTime<Utc>,
+    ) -> Self {
+        let today = now.with_timezone(&Local).date_naive();
+        let mut days = today
+            .iter_days()
+            .take(DAYS_SHOWN as usize)
+            .map(|date| AgendaDay {
+                date,
+                start: start_of_day(date),
+                end: start_of_day(date.succ_opt().expect("Not near the end of time")),
+                entries: Vec::default(),
+            })
+            .collect::<Vec<_>>();
+
+        let ordered = upcoming
+            .get_ordered_scheduled_items()
+            .as_deref()
+            .unwrap_or_default();
+        let mut entries = ordered
+            .iter()
+            .map(|x| AgendaEntry::Scheduled {
+                item_status: x.get_item_status(),
+                start: *x.get_scheduled_start(),
+                end: *x.get_scheduled_end(),
+                is_conflict: false,
+            })
+            .collect::<Vec<_>>();
+        //What could not be placed is shown at the time that it asked for
+        entries.extend(
+            items_status
+                .values()
+                .filter(|x| x.is_active())
+                .filter(|x| {
+                    !ordered.iter().any(|scheduled| {
+                        scheduled.get_item_status().get_surreal_record_id()
+                            == x.get_surreal_record_id()
+                    })
+                })
+                .filter_map(|item_status| {
+                    let (start, duration): (DateTime<Utc>, Duration) =
+                        match item_status.get_scheduled_now()? {
+                            SurrealScheduled::Exact { start, duration } => {
+                                (start.clone().into(), (*duration).into())
+                            }
+                            SurrealScheduled::Range {
+                                start_range,
+                                duration,
+                            } => (start_range.0.clone().into(), (*duration).into()),
+                        };
+                    Some(AgendaEntry::Scheduled {
+                        item_status,
+                        start,
+                        end: start + duration,
+                        is_conflict: upcoming
+                            .get_conflicts()
+                            .iter()
+                            .any(|x| x == &item_status.get_item()),
+                    })
+                }),
+        );
+        entries.extend(
+            items_status
+                .values()
+                .filter(|x| x.is_active())
+                .flat_map(|item_status| {
+                    item_status
+                        .get_dependencies(Filter::Active)
+                        .filter_map(move |x| match x {
+                            DependencyWithItemNode::AfterDateTime { after, .. } if after > now => {
+                                Some(AgendaEntry::BecomesReady {
+                                    item_status,
+                                    when: *after,
+                                })
+                            }
+                            _ => None,
+                        })
+                }),
+        );
+        entries.sort_by(|a, b| {
+            a.get_start().cmp(b.get_start()).then_with(|| {
+                a.get_item_status()
+                    .get_summary()
+                    .cmp(b.get_item_status().get_summary())
+            })
+        });
+
+        let last_end = days.last().expect("DAYS_SHOWN is not zero").end;
+        for entry in entries.into_iter().filter(|x| *x.get_start() < last_end) {
+            //Anything that should have already started goes on today
+            let day = days
+                .iter()
+                .rposition(|x| x.start <= *entry.get_start())
+                .unwrap_or(0);
+            days[day].entries.push(entry);
+        }
+        Self { days }
+    }
+
+    pub(crate) fn get_days(&self) -> &[AgendaDay<'s>] {
+        &self.days
+    }
+}
+
+/// On the days that midnight is skipped for daylight saving time, the day starts when the clocks go forward
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).expect("Midnight is valid");
+    (0..3)
+        .find_map(|hour| {
+            Local
+                .from_local_datetime(&(midnight + TimeDelta::hours(hour)))
+                .earliest()
+        })
+        .expect("Daylight saving time does not skip more than a couple of hours")
+        .with_timezone(&Utc)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeDelta, TimeZone, Utc};
+
+    use crate::{
+        base_data::BaseData,
+        calculated_data::CalculatedData,
+        data_storage::surrealdb_layer::{
+            surreal_item::{
+                SurrealDependency, SurrealItemBuilder, SurrealScheduled, SurrealUrgency,
+                SurrealUrgencyPlan,
+            },
+            surreal_tables::SurrealTablesBuilder,
+        },
+        systems::{
+            agenda::{Agenda, AgendaEntry},
+            upcoming::Upcoming,
+        },
+    };
+
+    #[test]
+    fn tomorrow_shows_the_scheduled_items_the_free_time_between_them_and_what_becomes_ready() {
+        let now = Utc::now();
+        let tomorrow = now.with_timezone(&Local).date_naive().succ_opt().unwrap();
+        let at = |hour: u32| {
+            Local
+                .from_local_datetime(&tomorrow.and_hms_opt(hour, 0, 0).unwrap())
+                .earliest()
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let scheduled = |id: &str, scheduled: SurrealScheduled| {
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", id).into()))
+                .summary(id)
+                .urgency_plan(Some(SurrealUrgencyPlan::StaysTheSame(
+                    SurrealUrgency::ScheduledAnyMode(scheduled),
+                )))
+                .build()
+                .unwrap()
+        };
+        let surreal_items = vec![
+            scheduled(
+                "Dentist",
+                SurrealScheduled::Exact {
+                    start: at(9).into(),
+                    duration: TimeDelta::hours(1).to_std().unwrap().into(),
+                },
+            ),
+            scheduled(
+                "Groceries",
+                SurrealScheduled::Range {
+                    start_range: (at(13).into(), at(15).into()),
+                    duration: TimeDelta::hours(2).to_std().unwrap().into(),
+                },
+            ),
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "Call back").into()))
+                .summary("Call back")
+                .dependencies(vec![SurrealDependency::AfterDateTime(at(11).into())])
+                .build()
+                .unwrap(),
+        ];
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(surreal_items)
+            .build()
+            .expect("no required fields");
+        let base_data = BaseData::new_from_surreal_tables(surreal_tables, now);
+        let calculated_data = CalculatedData::new_from_base_data(base_data);
+        let upcoming = Upcoming::new(&calculated_data, &now);
+
+        let agenda = Agenda::new(&upcoming, calculated_data.get_items_status(), &now);
+
+        assert_eq!(agenda.get_days().len(), 8);
+        let tomorrow = &agenda.get_days()[1];
+        let summaries = tomorrow
+            .get_entries()
+            .iter()
+            .map(|x| x.get_item_status().get_summary())
+            .collect::<Vec<_>>();
+        assert_eq!(summaries, vec!["Dentist", "Call back", "Groceries"]);
+        assert!(matches!(
+            tomorrow.get_entries()[1],
+            AgendaEntry::BecomesReady { .. }
+        ));
+        assert_eq!(
+            tomorrow.get_committed_time(),
+            TimeDelta::hours(3).to_std().unwrap()
+        );
+        assert_eq!(tomorrow.get_free_blocks(), vec![(at(10), at(13))]);
+    }
+}
@@ -1,4 +1,7 @@
+use surrealdb::opt::RecordId;
+
 use crate::{
+    base_data::mode::Mode,
     data_storage::surrealdb_layer::surreal_current_mode::{
         SurrealCurrentMode, SurrealSelectedSingleMode,
     },
@@ -8,6 +11,13 @@ use crate::{
 pub(crate) struct CurrentMode {
     urgency_in_scope: Vec<SelectedSingleMode>,
     importance_in_scope: Vec<SelectedSingleMode>,
+    /// The user defined modes that were picked, empty if the list is not filtered by user defined modes
+    selected_modes: Vec<RecordId>,
+    /// The selected modes and all of their larger modes. A smaller mode inherits the scope of its parent so an item in
+    /// "Low energy" is also in scope when "Low energy ➡ At the computer" is selected.
+    modes_in_scope: Vec<RecordId>,
+    /// Empty if the list is not filtered by tag
+    selected_tags: Vec<String>,
 }
 
 #[derive(PartialEq, Eq)]
@@ -28,6 +38,9 @@ impl Default for CurrentMode {
                 SelectedSingleMode::AllCoreMotivationalPurposes,
                 SelectedSingleMode::AllNonCoreMotivationalPurposes,
             ],
+            selected_modes: Vec::default(),
+            modes_in_scope: Vec::default(),
+            selected_tags: Vec::default(),
         }
     }
 }
@@ -46,7 +59,10 @@ impl SurrealSelectedSingleMode {
 }
 
 impl CurrentMode {
-    pub(crate) fn new(surreal_current_mode: &SurrealCurrentMode) -> CurrentMode {
+    pub(crate) fn new(
+        surreal_current_mode: &SurrealCurrentMode,
+        all_modes: &[Mode],
+    ) -> CurrentMode {
         let urgency_in_scope = surreal_current_mode
             .urgency_in_scope
             .iter()
@@ -58,18 +74,75 @@ impl CurrentMode {
             .map(|importance| importance.copy_to_items_in_scope_with_item_nodes())
             .collect::<Vec<_>>();
 
+        //A selected mode that was deleted is left out so it does not hide everything
+        let selected_modes = surreal_current_mode
+            .modes
+            .iter()
+            .filter(|x| all_modes.iter().any(|mode| mode.get_surreal_id() == *x))
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut modes_in_scope: Vec<RecordId> = Vec::default();
+        for selected in selected_modes.iter() {
+            let mut mode = Some(selected);
+            //Stops at a mode already seen so a cycle in the parents can't loop forever
+            while let Some(id) = mode.filter(|x| !modes_in_scope.contains(x)) {
+                modes_in_scope.push(id.clone());
+                mode = all_modes
+                    .iter()
+                    .find(|x| x.get_surreal_id() == id)
+                    .and_then(|x| x.get_parent().as_ref());
+            }
+        }
+
         CurrentMode {
             urgency_in_scope,
             importance_in_scope,
+            selected_modes,
+            modes_in_scope,
+            selected_tags: surreal_current_mode.tags.clone(),
         }
     }
 
     pub(crate) fn is_urgency_in_the_mode(&self, item_node: &ItemNode) -> bool {
         is_in_scope(self.get_urgency_in_scope(), item_node)
+            && self.is_in_user_modes(item_node)
+            && self.has_a_selected_tag(item_node)
     }
 
     pub(crate) fn is_importance_in_the_mode(&self, item_node: &ItemNode) -> bool {
         is_in_scope(self.get_importance_in_scope(), item_node)
+            && self.is_in_user_modes(item_node)
+            && self.has_a_selected_tag(item_node)
+    }
+
+    /// An item is in a mode if it or one of its larger items, like the motivation it is for, was put in the mode
+    fn is_in_user_modes(&self, item_node: &ItemNode) -> bool {
+        self.modes_in_scope.is_empty()
+            || item_node
+                .get_self_and_parents(Filter::Active)
+                .iter()
+                .any(|x| {
+                    x.get_modes()
+                        .iter()
+                        .any(|mode| self.modes_in_scope.contains(mode))
+                })
+    }
+
+    /// Tags are inherited from larger items like modes are
+    fn has_a_selected_tag(&self, item_node: &ItemNode) -> bool {
+        self.selected_tags.is_empty()
+            || item_node
+                .get_tags(Filter::Active)
+                .iter()
+                .any(|tag| self.selected_tags.iter().any(|x| x == tag))
+    }
+
+    pub(crate) fn get_selected_tags(&self) -> &[String] {
+        &self.selected_tags
+    }
+
+    pub(crate) fn get_selected_modes(&self) -> &[RecordId] {
+        &self.selected_modes
     }
 
     pub(crate) fn get_urgency_in_scope(&self) -> &Vec<SelectedSingleMode> {
@@ -91,3 +164,143 @@ fn is_in_scope(in_scope: &[SelectedSingleMode], item_node: &ItemNode) -> bool {
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use surrealdb::sql::Thing;
+
+    use crate::{
+        base_data::BaseData,
+        calculated_data::CalculatedData,
+        data_storage::surrealdb_layer::{
+            surreal_current_mode::{SurrealCurrentMode, SurrealSelectedSingleMode},
+            surreal_item::{
+                SurrealItemBuilder, SurrealItemType, SurrealMotivationKind, SurrealOrderedSubItem,
+            },
+            surreal_mode::SurrealMode,
+            surreal_tables::SurrealTablesBuilder,
+        },
+    };
+
+    fn mode(key: &str, parent: Option<&str>) -> SurrealMode {
+        SurrealMode {
+            id: Some(mode_id(key)),
+            name: key.into(),
+            version: 0,
+            parent: parent.map(mode_id),
+            display_order: 0,
+        }
+    }
+
+    fn mode_id(key: &str) -> Thing {
+        (SurrealMode::TABLE_NAME, key).into()
+    }
+
+    #[test]
+    fn items_are_in_the_selected_mode_through_larger_items_and_larger_modes() {
+        let surreal_items = vec![
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "home").into()))
+                .summary("Keep the house clean")
+                .item_type(SurrealItemType::Motivation(
+                    SurrealMotivationKind::DoesNotFitInCoreOrNonCore,
+                ))
+                .modes(vec![mode_id("home")])
+                .smaller_items_in_priority_order(vec![SurrealOrderedSubItem::SubItem {
+                    surreal_item_id: ("surreal_item", "dishes").into(),
+                }])
+                .build()
+                .unwrap(),
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "dishes").into()))
+                .summary("Do the dishes")
+                .item_type(SurrealItemType::Action)
+                .build()
+                .unwrap(),
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "life").into()))
+                .summary("Everything else")
+                .item_type(SurrealItemType::Motivation(
+                    SurrealMotivationKind::DoesNotFitInCoreOrNonCore,
+                ))
+                .smaller_items_in_priority_order(
+                    ["oven", "report", "untagged"]
+                        .into_iter()
+                        .map(|key| SurrealOrderedSubItem::SubItem {
+                            surreal_item_id: ("surreal_item", key).into(),
+                        })
+                        .collect(),
+                )
+                .build()
+                .unwrap(),
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "oven").into()))
+                .summary("Clean the oven")
+                .item_type(SurrealItemType::Action)
+                .modes(vec![mode_id("kitchen")])
+                .build()
+                .unwrap(),
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "report").into()))
+                .summary("Write the report")
+                .item_type(SurrealItemType::Action)
+                .modes(vec![mode_id("work")])
+                .build()
+                .unwrap(),
+            SurrealItemBuilder::default()
+                .id(Some(("surreal_item", "untagged").into()))
+                .summary("Not in any mode")
+                .item_type(SurrealItemType::Action)
+                .build()
+                .unwrap(),
+        ];
+        let surreal_tables = SurrealTablesBuilder::default()
+            .surreal_items(surreal_items)
+            .surreal_modes(vec![
+                mode("home", None),
+                mode("kitchen", Some("home")),
+                mode("work", None),
+            ])
+            .surreal_current_modes(vec![SurrealCurrentMode {
+                id: Some((SurrealCurrentMode::TABLE_NAME, "current_mode").into()),
+                version: 0,
+                urgency_in_scope: vec![
+                    SurrealSelectedSingleMode::AllCoreMotivationalPurposes,
+                    SurrealSelectedSingleMode::AllNonCoreMotivationalPurposes,
+                ],
+                importance_in_scope: vec![
+                    SurrealSelectedSingleMode::AllCoreMotivationalPurposes,
+                    SurrealSelectedSingleMode::AllNonCoreMotivationalPurposes,
+                ],
+                modes: vec![mode_id("kitchen")],
+                tags: Vec::default(),
+            }])
+            .build()
+            .expect("no required fields");
+        let base_data = BaseData::new_from_surreal_tables(surreal_tables, Utc::now());
+        let calculated_data = CalculatedData::new_from_base_data(base_data);
+        let current_mode = calculated_data.get_current_mode();
+        let in_the_mode = |summary: &str| {
+            let item_status = calculated_data
+                .get_items_status()
+                .values()
+                .find(|x| x.get_summary() == summary)
+                .expect("Item is in the test data");
+            let item_node = item_status.get_item_node();
+            assert_eq!(
+                current_mode.is_urgency_in_the_mode(item_node),
+                current_mode.is_importance_in_the_mode(item_node)
+            );
+            current_mode.is_urgency_in_the_mode(item_node)
+        };
+
+        assert_eq!(current_mode.get_selected_modes(), &[mode_id("kitchen")]);
+        assert!(in_the_mode("Clean the oven"));
+        assert!(in_the_mode("Keep the house clean"));
+        assert!(in_the_mode("Do the dishes"));
+        assert!(!in_the_mode("Write the report"));
+        assert!(!in_the_mode("Not in any mode"));
+        assert!(!in_the_mode("Everything else"));
+    }
+}
@@ -0,0 +1,214 @@
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+
+use crate::data_storage::surrealdb_layer::surreal_item::{
+    SurrealCompletion, SurrealRecurrence, SurrealWeekday,
+};
+
+/// When a recurring item that was `finished` is due next. It is due at the start of that day in `time_zone`.
+/// `last_due` is when it was due this time, if it was waiting to be due.
+pub(crate) fn next_due<Tz: TimeZone>(
+    recurrence: &SurrealRecurrence,
+    last_due: Option<&DateTime<Utc>>,
+    finished: &DateTime<Utc>,
+    time_zone: &Tz,
+) -> DateTime<Utc> {
+    let finished_on = finished.with_timezone(time_zone).date_naive();
+    let last_due_on = last_due
+        .map(|x| x.with_timezone(time_zone).date_naive())
+        .unwrap_or(finished_on);
+    let due_on = match recurrence {
+        SurrealRecurrence::EveryDays(days) => {
+            every(last_due_on, finished_on, Days::new((*days).max(1).into()))
+        }
+        SurrealRecurrence::EveryWeeks(weeks) => every(
+            last_due_on,
+            finished_on,
+            Days::new((*weeks).max(1) as u64 * 7),
+        ),
+        SurrealRecurrence::OnWeekdays(weekdays) => finished_on
+            .iter_days()
+            .skip(1)
+            .take(7)
+            .find(|x| {
+                weekdays
+                    .iter()
+                    .any(|weekday| x.weekday() == (*weekday).into())
+            })
+            //No days picked is taken as every day
+            .unwrap_or(finished_on + Days::new(1)),
+        SurrealRecurrence::DayOfMonth(day) => {
+            let this_month = day_of_month(finished_on, *day);
+            if this_month > finished_on {
+                this_month
+            } else {
+                day_of_month(first_of_month(finished_on) + Months::new(1), *day)
+            }
+        }
+        SurrealRecurrence::DaysAfterFinished(days) => finished_on + Days::new((*days).into()),
+    };
+    start_of_day(due_on, time_zone)
+}
+
+/// Whether finishing at `finished` was before the time after `due` came around
+pub(crate) fn is_on_time<Tz: TimeZone>(
+    recurrence: &SurrealRecurrence,
+    due: &DateTime<Utc>,
+    finished: &DateTime<Utc>,
+    time_zone: &Tz,
+) -> bool {
+    finished < &next_due(recurrence, Some(due), due, time_zone)
+}
+
+/// How many times in a row, counting back from the most recent, it was finished on time
+pub(crate) fn streak(completions: &[SurrealCompletion]) -> usize {
+    completions.iter().rev().take_while(|x| x.on_time).count()
+}
+
+fn every(last_due_on: NaiveDate, finished_on: NaiveDate, step: Days) -> NaiveDate {
+    let mut due_on = last_due_on + step;
+    while due_on <= finished_on {
+        due_on = due_on + step;
+    }
+    due_on
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).expect("Every month has a first day")
+}
+
+fn day_of_month(in_month: NaiveDate, day: u32) -> NaiveDate {
+    let first = first_of_month(in_month);
+    let last = first + Months::new(1) - Days::new(1);
+    first + Days::new((day.clamp(1, last.day()) - 1).into())
+}
+
+fn start_of_day<Tz: TimeZone>(date: NaiveDate, time_zone: &Tz) -> DateTime<Utc> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    time_zone
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|x| x.with_timezone(&Utc))
+        //When a clock change skips midnight the day starts at the same time it would in UTC
+        .unwrap_or_else(|| midnight.and_utc())
+}
+
+impl From<SurrealWeekday> for Weekday {
+    fn from(weekday: SurrealWeekday) -> Self {
+        match weekday {
+            SurrealWeekday::Monday => Weekday::Mon,
+            SurrealWeekday::Tuesday => Weekday::Tue,
+            SurrealWeekday::Wednesday => Weekday::Wed,
+            SurrealWeekday::Thursday => Weekday::Thu,
+            SurrealWeekday::Friday => Weekday::Fri,
+            SurrealWeekday::Saturday => Weekday::Sat,
+            SurrealWeekday::Sunday => Weekday::Sun,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use crate::data_storage::surrealdb_layer::surreal_item::{
+        SurrealCompletion, SurrealRecurrence, SurrealWeekday,
+    };
+
+    use super::{is_on_time, next_due, streak};
+
+    fn at(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn every_few_days_counts_from_when_it_was_due_and_skips_missed_times() {
+        let every_three_days = SurrealRecurrence::EveryDays(3);
+        let due = at(2025, 3, 10, 0);
+
+        assert_eq!(
+            next_due(&every_three_days, Some(&due), &at(2025, 3, 11, 15), &Utc),
+            at(2025, 3, 13, 0)
+        );
+        assert_eq!(
+            next_due(&every_three_days, Some(&due), &at(2025, 3, 17, 9), &Utc),
+            at(2025, 3, 19, 0)
+        );
+        assert_eq!(
+            next_due(
+                &SurrealRecurrence::EveryWeeks(2),
+                None,
+                &at(2025, 3, 11, 15),
+                &Utc
+            ),
+            at(2025, 3, 25, 0)
+        );
+    }
+
+    #[test]
+    fn weekdays_and_days_of_the_month_are_the_next_one_after_it_was_finished() {
+        //The 14th of March 2025 is a Friday
+        let finished = at(2025, 3, 14, 20);
+        let weekends =
+            SurrealRecurrence::OnWeekdays(vec![SurrealWeekday::Saturday, SurrealWeekday::Sunday]);
+        let mondays = SurrealRecurrence::OnWeekdays(vec![SurrealWeekday::Monday]);
+
+        assert_eq!(
+            next_due(&weekends, None, &finished, &Utc),
+            at(2025, 3, 15, 0)
+        );
+        assert_eq!(
+            next_due(&mondays, None, &finished, &Utc),
+            at(2025, 3, 17, 0)
+        );
+        assert_eq!(
+            next_due(&SurrealRecurrence::DayOfMonth(20), None, &finished, &Utc),
+            at(2025, 3, 20, 0)
+        );
+        assert_eq!(
+            next_due(&SurrealRecurrence::DayOfMonth(14), None, &finished, &Utc),
+            at(2025, 4, 14, 0)
+        );
+        assert_eq!(
+            next_due(
+                &SurrealRecurrence::DayOfMonth(31),
+                None,
+                &at(2025, 1, 31, 8),
+                &Utc
+            ),
+            at(2025, 2, 28, 0)
+        );
+        assert_eq!(
+            next_due(
+                &SurrealRecurrence::DaysAfterFinished(2),
+                None,
+                &finished,
+                &Utc
+            ),
+            at(2025, 3, 16, 0)
+        );
+    }
+
+    #[test]
+    fn a_streak_is_the_times_in_a_row_it_was_finished_on_time() {
+        let weekly = SurrealRecurrence::EveryWeeks(1);
+        let due = at(2025, 3, 10, 0);
+        assert!(is_on_time(&weekly, &due, &at(2025, 3, 16, 23), &Utc));
+        assert!(!is_on_time(&weekly, &due, &at(2025, 3, 17, 1), &Utc));
+
+        let completion = |on_time| SurrealCompletion {
+            finished: Utc::now().into(),
+            on_time,
+            due_again: None,
+        };
+        assert_eq!(streak(&[]), 0);
+        assert_eq!(
+            streak(&[
+                completion(true),
+                completion(false),
+                completion(true),
+                completion(true)
+            ]),
+            2
+        );
+    }
+}
@@ -25,7 +25,7 @@ impl<'s> Upcoming<'s> {
             .map(|(_, v)| v)
             .filter(|x| x.is_scheduled_now() && x.is_active())
             .collect::<Vec<_>>();
-        let order = find_a_valid_order(&items, earliest_starting_time, Vec::default());
+        let order = find_the_best_order(&items, earliest_starting_time);
         Self { order }
     }
 
@@ -67,22 +67,12 @@ impl<'s> Order<'s> {
         }
     }
 
-    fn is_none(&self) -> bool {
-        self.sorted_best_order.is_none()
-    }
-
     fn add_conflict_if_new(&mut self, item: &'s Item<'s>) {
         if !self.conflicts.contains(&item) {
             self.conflicts.push(item);
         }
     }
 
-    fn add_conflicts_if_new(&mut self, other: &Order<'s>) {
-        for conflict in other.conflicts.iter() {
-            self.add_conflict_if_new(conflict);
-        }
-    }
-
     fn keep_best_order(&mut self, proposal: Order<'s>) {
         if self.sorted_best_order.is_none() {
             self.take_proposal(proposal);
@@ -104,108 +94,261 @@ impl<'s> Order<'s> {
     }
 }
 
-fn find_a_valid_order<'s>(
+/// A scheduled item with the earliest and latest time that it can start
+struct ToSchedule<'s> {
+    item: &'s ItemStatus<'s>,
+    earliest_start: DateTime<Utc>,
+    latest_start: DateTime<Utc>,
+    duration: Duration,
+}
+
+impl<'s> ToSchedule<'s> {
+    fn new(item: &'s ItemStatus<'s>, earliest_starting_time: &DateTime<Utc>) -> Self {
+        let (earliest_start, latest_start, duration): (DateTime<Utc>, DateTime<Utc>, Duration) =
+            match item
+                .get_scheduled_now()
+                .expect("We should only be dealing with scheduled items")
+            {
+                SurrealScheduled::Exact { start, duration } => (
+                    start.clone().into(),
+                    start.clone().into(),
+                    (*duration).into(),
+                ),
+                SurrealScheduled::Range {
+                    start_range,
+                    duration,
+                } => (
+                    start_range.0.clone().into(),
+                    start_range.1.clone().into(),
+                    (*duration).into(),
+                ),
+            };
+        //Nothing can be scheduled before the earliest starting time, so an exact time before then does not fit
+        Self {
+            item,
+            earliest_start: earliest_start.max(*earliest_starting_time),
+            latest_start,
+            duration,
+        }
+    }
+
+    fn is_exact(&self) -> bool {
+        matches!(
+            self.item.get_scheduled_now(),
+            Some(SurrealScheduled::Exact { .. })
+        )
+    }
+
+    /// None if it no longer fits around what is already scheduled
+    fn find_start(&self, scheduled: &Vec<ScheduledItem<'s>>) -> Option<DateTime<Utc>> {
+        let start = scheduled.find_earliest_fit(self.earliest_start, self.duration);
+        if start <= self.latest_start {
+            Some(start)
+        } else {
+            None
+        }
+    }
+
+    fn overlaps(&self, other: &ScheduledItem<'_>) -> bool {
+        self.earliest_start <= *other.get_scheduled_end()
+            && self.latest_start + self.duration >= *other.get_scheduled_start()
+    }
+
+    fn is_interchangeable_with(&self, other: &ToSchedule<'_>) -> bool {
+        self.earliest_start == other.earliest_start
+            && self.latest_start == other.latest_start
+            && self.duration == other.duration
+    }
+}
+
+/// Each item goes at the earliest time it fits around the items already scheduled so what matters is the order that
+/// items are scheduled in, the best order has the lowest gap penalty and then the fewest big items before little ones.
+/// Exact items only have the one time they can go so they are scheduled first and any that overlap are conflicts.
+/// Range items are then tried with the one that must start soonest first, which finds an order that fits right away
+/// when there is one. The search carries on looking for a better order but gives up on an order as soon as one of the
+/// items left no longer fits and stops after `MAX_ITEMS_PLACED` so it stays fast however many items are scheduled.
+/// If it stops before finding an order that fits everything then the order that fit the most is used and the items
+/// left out of it are the conflicts.
+fn find_the_best_order<'s>(
     items: &[&'s ItemStatus<'s>],
     earliest_starting_time: &DateTime<Utc>,
-    scheduled: Vec<ScheduledItem<'s>>,
 ) -> Order<'s> {
-    //Go through each item as the next item and see if it fits, this is a brute force algorithm. Scheduled items are dealt
-    //with right away so I don't expect there to be too many of them, hence the brute force approach.
-
     let mut result = Order::default();
-    for item in items {
-        if scheduled.contains(item) {
-            continue;
-        }
-        let mut scheduled = scheduled.clone();
-        let to_schedule = item
-            .get_scheduled_now()
-            .expect("We should only be dealing with scheduled items");
-        let (mut start, duration): (DateTime<Utc>, Duration) = match to_schedule {
-            SurrealScheduled::Exact {
-                start, duration, ..
-            } => (start.clone().into(), (*duration).into()),
-            SurrealScheduled::Range {
-                start_range,
-                duration,
-                ..
-            } => (start_range.0.clone().into(), (*duration).into()),
-        };
-        //I'm looking for the earliest available time that fits to schedule this item
-        if earliest_starting_time > &start {
-            start = *earliest_starting_time;
-        }
-        let next_available = scheduled.find_next_available_time(start, start + duration);
-        if to_schedule.is_this_a_valid_starting_time(next_available) {
-            scheduled.push(ScheduledItem::new(
-                item,
-                next_available,
-                next_available + duration,
-            ));
-            if scheduled.len() == items.len() {
-                //We have scheduled all of the items
-                let new_ordering = Order::new(scheduled);
-                result.keep_best_order(new_ordering);
-            } else {
-                //We have scheduled this item, now we need to schedule the rest of the items
-                let valid_order = find_a_valid_order(items, earliest_starting_time, scheduled);
-                result.add_conflicts_if_new(&valid_order);
-                if valid_order.is_none() {
-                    //This ordering won't work
-                } else {
-                    result.keep_best_order(valid_order);
+    if items.is_empty() {
+        return result;
+    }
+    let (mut exact, mut range): (Vec<_>, Vec<_>) = items
+        .iter()
+        .map(|x| ToSchedule::new(x, earliest_starting_time))
+        .partition(|x| x.is_exact());
+
+    exact.sort_by(|a, b| a.latest_start.cmp(&b.latest_start));
+    let mut scheduled: Vec<ScheduledItem<'s>> = Vec::default();
+    for to_schedule in exact.iter() {
+        match to_schedule.find_start(&scheduled) {
+            Some(start) => scheduled.push(ScheduledItem::new(
+                to_schedule.item,
+                start,
+                start + to_schedule.duration,
+            )),
+            None => {
+                //Everything it overlaps is listed as well because any of them could be the one to move
+                result.add_conflict_if_new(to_schedule.item.get_item());
+                for overlapping in scheduled.iter().filter(|x| to_schedule.overlaps(x)) {
+                    result.add_conflict_if_new(overlapping.get_item_status().get_item());
                 }
             }
-        } else {
-            //This ordering won't work
-            result.add_conflict_if_new(item.get_item());
+        }
+    }
+    if !result.conflicts.is_empty() {
+        return result;
+    }
+
+    range.sort_by(|a, b| {
+        a.latest_start
+            .cmp(&b.latest_start)
+            .then_with(|| a.earliest_start.cmp(&b.earliest_start))
+            .then_with(|| a.duration.cmp(&b.duration))
+    });
+    let mut search = Search {
+        range: &range,
+        placed: vec![false; range.len()],
+        items_placed: 0,
+        ran_out: false,
+        result,
+        dead_ends: Vec::default(),
+        most_placed: (scheduled.clone(), vec![false; range.len()]),
+    };
+    search.place_the_rest(&mut scheduled, range.len());
+    let mut result = search.result;
+    if result.sorted_best_order.is_some() {
+        //Items that did not fit in orders that were given up on are not conflicts when another order fits everything
+    } else if search.ran_out {
+        //Stopped before finding an order that fits everything so rather than leave everything off the schedule use
+        //the order that fit the most and list what is left out as conflicts
+        let (most_placed, placed) = search.most_placed;
+        for (to_schedule, _) in range.iter().zip(placed).filter(|(_, placed)| !placed) {
+            result.add_conflict_if_new(to_schedule.item.get_item());
+        }
+        result.take_proposal(Order::new(most_placed));
+    } else {
+        for conflict in search.dead_ends {
+            result.add_conflict_if_new(conflict);
         }
     }
     result
 }
 
-impl SurrealScheduled {
-    fn is_this_a_valid_starting_time(&self, proposed: DateTime<Utc>) -> bool {
-        match self {
-            SurrealScheduled::Exact {
-                start: scheduled_start,
-                ..
-            } => proposed == scheduled_start.clone().into(),
-            SurrealScheduled::Range {
-                start_range: (start, end),
-                ..
-            } => proposed >= start.clone().into() && proposed <= end.clone().into(),
+/// More than enough to find an order for dozens of range items while keeping the search to a few milliseconds
+const MAX_ITEMS_PLACED: u32 = 2_000;
+
+struct Search<'a, 's> {
+    range: &'a [ToSchedule<'s>],
+    placed: Vec<bool>,
+    items_placed: u32,
+    ran_out: bool,
+    result: Order<'s>,
+    /// Items that no longer fit when an order was given up on
+    dead_ends: Vec<&'s Item<'s>>,
+    /// The order that has fit the most items so far and which range items are in it
+    most_placed: (Vec<ScheduledItem<'s>>, Vec<bool>),
+}
+
+impl<'s> Search<'_, 's> {
+    fn place_the_rest(&mut self, scheduled: &mut Vec<ScheduledItem<'s>>, left: usize) {
+        if left == 0 {
+            self.result.keep_best_order(Order::new(scheduled.clone()));
+            return;
+        }
+        if scheduled.len() > self.most_placed.0.len() {
+            self.most_placed = (scheduled.clone(), self.placed.clone());
+        }
+        //Scheduling more only ever makes the times left later so if something no longer fits this order is a dead end
+        let range = self.range;
+        let mut starts = Vec::with_capacity(left);
+        for (i, to_schedule) in range.iter().enumerate() {
+            if self.placed[i] {
+                continue;
+            }
+            match to_schedule.find_start(scheduled) {
+                Some(start) => starts.push((i, start)),
+                None => {
+                    let item = to_schedule.item.get_item();
+                    if self.result.sorted_best_order.is_none() && !self.dead_ends.contains(&item) {
+                        self.dead_ends.push(item);
+                    }
+                    return;
+                }
+            }
+        }
+
+        for (n, (i, start)) in starts.iter().enumerate() {
+            //Trying an item that is the same as one already tried here would only find the same orders again
+            let to_schedule = &range[*i];
+            if starts[..n]
+                .iter()
+                .any(|(tried, _)| range[*tried].is_interchangeable_with(to_schedule))
+            {
+                continue;
+            }
+            if self.items_placed >= MAX_ITEMS_PLACED {
+                self.ran_out = true;
+                return;
+            }
+            self.items_placed += 1;
+
+            let position = scheduled.partition_point(|x| x.get_scheduled_start() < start);
+            scheduled.insert(
+                position,
+                ScheduledItem::new(to_schedule.item, *start, *start + to_schedule.duration),
+            );
+            self.placed[*i] = true;
+            self.place_the_rest(scheduled, left - 1);
+            self.placed[*i] = false;
+            scheduled.remove(position);
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::{TimeDelta, Utc};
+    use chrono::{DateTime, TimeDelta, Utc};
     use tokio::sync::mpsc;
 
+    use std::{
+        hint,
+        time::{Duration, Instant},
+    };
+
     use crate::base_data::BaseData;
     use crate::calculated_data::CalculatedData;
     use crate::data_storage::surrealdb_layer::data_layer_commands::{
-        DataLayerCommands, data_storage_start_and_run,
+        DataChange, DataLayerCommands, DataStorageSettings, data_storage_start_and_run,
     };
     use crate::data_storage::surrealdb_layer::surreal_item::{
-        SurrealScheduled, SurrealUrgency, SurrealUrgencyPlan,
+        SurrealItem, SurrealItemBuilder, SurrealScheduled, SurrealUrgency, SurrealUrgencyPlan,
+    };
+    use crate::data_storage::surrealdb_layer::surreal_tables::{
+        SurrealTables, SurrealTablesBuilder,
     };
-    use crate::data_storage::surrealdb_layer::surreal_tables::SurrealTables;
     use crate::new_item::NewItemBuilder;
-    use crate::systems::upcoming::Upcoming;
+    use crate::systems::upcoming::{
+        MAX_ITEMS_PLACED, Order, Search, ToSchedule, Upcoming, find_the_best_order,
+        scheduled_item::ScheduledItem,
+    };
 
     #[tokio::test]
     async fn when_one_item_is_scheduled_inside_of_another_item_it_is_marked_as_a_conflict() {
         //Arrange
         let (sender, receiver) = mpsc::channel(1);
-        let data_storage_join_handle =
-            tokio::spawn(async move { data_storage_start_and_run(receiver, "mem://").await });
+        let data_storage_join_handle = tokio::spawn(async move {
+            data_storage_start_and_run(receiver, "mem://", DataStorageSettings::default()).await
+        });
 
         let now = Utc::now();
-        sender
-            .send(DataLayerCommands::NewItem(
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(
                 NewItemBuilder::default()
                     .summary("3 hour item")
                     .urgency_plan(Some(SurrealUrgencyPlan::StaysTheSame(
@@ -222,12 +365,14 @@ mod tests {
                     )))
                     .build()
                     .expect("Valid new item"),
-            ))
-            .await
-            .expect("Should pass");
+            ),
+        )
+        .await
+        .expect("Should pass");
 
-        sender
-            .send(DataLayerCommands::NewItem(
+        DataLayerCommands::change(
+            &sender,
+            DataChange::NewItem(
                 NewItemBuilder::default()
                     .summary("1 hour item")
                     .urgency_plan(Some(SurrealUrgencyPlan::StaysTheSame(
@@ -244,9 +389,10 @@ mod tests {
                     )))
                     .build()
                     .expect("Valid new item"),
-            ))
-            .await
-            .expect("Should pass");
+            ),
+        )
+        .await
+        .expect("Should pass");
 
         let surreal_tables = SurrealTables::new(&sender).await.expect("Should pass");
         let base_data = BaseData::new_from_surreal_tables(surreal_tables, now);
@@ -261,4 +407,221 @@ mod tests {
         drop(sender);
         data_storage_join_handle.await.expect("Should pass");
     }
+
+    #[test]
+    fn sixty_scheduled_items_that_all_fit_are_ordered_without_conflicts_in_well_under_a_second() {
+        let now = Utc::now();
+        let base_data = BaseData::new_from_surreal_tables(sixty_items_that_all_fit(now), now);
+        let calculated_data = CalculatedData::new_from_base_data(base_data);
+
+        let started = Instant::now();
+        let result = Upcoming::new(&calculated_data, &now);
+        let elapsed = started.elapsed();
+
+        assert!(!result.has_conflicts());
+        let order = result
+            .get_ordered_scheduled_items()
+            .as_ref()
+            .expect("Everything fits");
+        assert_eq!(order.len(), 60);
+        assert_valid_order(order);
+        //This is meant to finish in milliseconds, a second leaves plenty of room for a debug build on a slow machine
+        assert!(elapsed.as_secs_f64() < 1.0, "Took {:?}", elapsed);
+    }
+
+    #[test]
+    fn when_the_search_stops_before_everything_fits_the_order_that_fit_the_most_is_used() {
+        let now = Utc::now();
+        let base_data =
+            BaseData::new_from_surreal_tables(twenty_items_that_cannot_all_fit(now), now);
+        let calculated_data = CalculatedData::new_from_base_data(base_data);
+
+        let result = Upcoming::new(&calculated_data, &now);
+
+        assert!(result.has_conflicts());
+        let order = result
+            .get_ordered_scheduled_items()
+            .as_ref()
+            .expect("The order that fit the most");
+        assert!(order.len() >= 10);
+        assert_valid_order(order);
+        //Everything is either on the schedule or listed as a conflict
+        assert_eq!(order.len() + result.get_conflicts().len(), 20);
+        for conflict in result.get_conflicts() {
+            assert!(
+                !order
+                    .iter()
+                    .any(|x| x.get_item_status().get_item() == *conflict)
+            );
+        }
+    }
+
+    #[test]
+    fn when_the_search_reaches_max_items_placed_the_order_it_falls_back_on_has_no_overlaps() {
+        let now = Utc::now();
+        let base_data =
+            BaseData::new_from_surreal_tables(twenty_items_that_cannot_all_fit(now), now);
+        let calculated_data = CalculatedData::new_from_base_data(base_data);
+        let items = calculated_data
+            .get_items_status()
+            .iter()
+            .map(|(_, v)| v)
+            .filter(|x| x.is_scheduled_now() && x.is_active())
+            .collect::<Vec<_>>();
+        let range = items
+            .iter()
+            .map(|x| ToSchedule::new(x, &now))
+            .collect::<Vec<_>>();
+        let mut search = Search {
+            range: &range,
+            placed: vec![false; range.len()],
+            items_placed: 0,
+            ran_out: false,
+            result: Order::default(),
+            dead_ends: Vec::default(),
+            most_placed: (Vec::default(), vec![false; range.len()]),
+        };
+        search.place_the_rest(&mut Vec::default(), range.len());
+        assert!(search.ran_out);
+        assert_eq!(search.items_placed, MAX_ITEMS_PLACED);
+        assert!(search.result.sorted_best_order.is_none());
+
+        let result = find_the_best_order(&items, &now);
+
+        let order = result
+            .sorted_best_order
+            .as_ref()
+            .expect("The order that fit the most");
+        assert_valid_order(order);
+        assert_eq!(order.len() + result.conflicts.len(), 20);
+    }
+
+    /// A benchmark rather than a check, run it with `cargo test --release -- --ignored --nocapture` to see the timings
+    #[test]
+    #[ignore]
+    fn benchmark_ordering_scheduled_items() {
+        assert!(
+            !cfg!(debug_assertions),
+            "Timings from a debug build do not mean much, use --release"
+        );
+        let runs = 100;
+        let now = Utc::now();
+        for (name, surreal_tables) in [
+            ("sixty items that all fit", sixty_items_that_all_fit(now)),
+            (
+                "twenty items that stop at MAX_ITEMS_PLACED",
+                twenty_items_that_cannot_all_fit(now),
+            ),
+        ] {
+            let base_data = BaseData::new_from_surreal_tables(surreal_tables, now);
+            let calculated_data = CalculatedData::new_from_base_data(base_data);
+
+            let started = Instant::now();
+            for _ in 0..runs {
+                hint::black_box(Upcoming::new(&calculated_data, &now));
+            }
+            let each = started.elapsed() / runs;
+
+            println!("Ordering {} took {:?} each", name, each);
+            assert!(each < Duration::from_millis(50), "{} took {:?}", name, each);
+        }
+    }
+
+    /// An hour long exact item every other hour leaves an hour gap for each range item to go into
+    fn sixty_items_that_all_fit(now: DateTime<Utc>) -> SurrealTables {
+        let mut surreal_items = (0..30)
+            .map(|i| {
+                scheduled_item(
+                    i,
+                    SurrealScheduled::Exact {
+                        start: (now + TimeDelta::hours(1 + 2 * i as i64)).into(),
+                        duration: TimeDelta::hours(1).to_std().unwrap().into(),
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        surreal_items.extend((0..30).map(|i| {
+            let range_start = now + TimeDelta::hours(2 * i as i64);
+            scheduled_item(
+                30 + i,
+                SurrealScheduled::Range {
+                    start_range: (
+                        range_start.into(),
+                        (range_start + TimeDelta::hours(4)).into(),
+                    ),
+                    duration: TimeDelta::minutes(20 + (i % 3) as i64 * 10)
+                        .to_std()
+                        .unwrap()
+                        .into(),
+                },
+            )
+        }));
+        SurrealTablesBuilder::default()
+            .surreal_items(surreal_items)
+            .build()
+            .expect("no required fields")
+    }
+
+    /// Twenty hours of items that all have to start in the next ten hours or so can never all fit, and there are far
+    /// too many orders to try them all
+    fn twenty_items_that_cannot_all_fit(now: DateTime<Utc>) -> SurrealTables {
+        let surreal_items = (0..20)
+            .map(|i| {
+                let range_start = now + TimeDelta::minutes(i as i64);
+                scheduled_item(
+                    i,
+                    SurrealScheduled::Range {
+                        start_range: (
+                            range_start.into(),
+                            (range_start + TimeDelta::hours(10)).into(),
+                        ),
+                        duration: TimeDelta::hours(1).to_std().unwrap().into(),
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        SurrealTablesBuilder::default()
+            .surreal_items(surreal_items)
+            .build()
+            .expect("no required fields")
+    }
+
+    fn scheduled_item(id: usize, scheduled: SurrealScheduled) -> SurrealItem {
+        SurrealItemBuilder::default()
+            .id(Some(("surreal_item", id.to_string().as_str()).into()))
+            .summary(format!("Scheduled item {}", id))
+            .urgency_plan(Some(SurrealUrgencyPlan::StaysTheSame(
+                SurrealUrgency::ScheduledAnyMode(scheduled),
+            )))
+            .build()
+            .unwrap()
+    }
+
+    /// Sorted, nothing overlaps and everything is at a time that it asked for and for as long as it asked for
+    fn assert_valid_order(order: &[ScheduledItem<'_>]) {
+        for pair in order.windows(2) {
+            assert!(pair[0].get_scheduled_end() < pair[1].get_scheduled_start());
+        }
+        for scheduled_item in order {
+            let start = *scheduled_item.get_scheduled_start();
+            let (earliest, latest, duration): (DateTime<Utc>, DateTime<Utc>, Duration) =
+                match scheduled_item.get_scheduled_now().unwrap() {
+                    SurrealScheduled::Exact { start, duration } => (
+                        start.clone().into(),
+                        start.clone().into(),
+                        (*duration).into(),
+                    ),
+                    SurrealScheduled::Range {
+                        start_range,
+                        duration,
+                    } => (
+                        start_range.0.clone().into(),
+                        start_range.1.clone().into(),
+                        (*duration).into(),
+                    ),
+                };
+            assert!(earliest <= start && start <= latest);
+            assert_eq!(*scheduled_item.get_scheduled_end(), start + duration);
+        }
+    }
 }
@@ -14,33 +14,32 @@ pub struct ScheduledItem<'s> {
 }
 
 pub(crate) trait Scheduled {
-    fn contains(&self, item: &ItemStatus) -> bool;
-    fn find_next_available_time(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> DateTime<Utc>;
+    fn find_earliest_fit(&self, earliest_start: DateTime<Utc>, duration: Duration)
+    -> DateTime<Utc>;
     fn calculate_gap_penalty(&self) -> f64;
     fn calculate_big_to_little_count(&self) -> u32;
 }
 
 impl Scheduled for Vec<ScheduledItem<'_>> {
-    fn contains(&self, item: &ItemStatus) -> bool {
-        self.iter().any(|x| x.item == item)
-    }
-
-    fn find_next_available_time(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> DateTime<Utc> {
-        //Does anything conflict with the given time? If so then advance the time to the end of that conflict and check again (call this function again). Otherwise just return that time as the starting time.
-        let conflict = self.iter().find(|x| {
-            //Is there any overlap between the range of x and the range of the passed in start and end?
-            (start >= x.start || end >= x.start) && (start <= x.end || end <= x.end)
-        });
-        match conflict {
-            Some(conflict) => {
-                let one_minute = Duration::new(60, 0);
-                let next_proposed_start = conflict.end + one_minute;
-                let amount_added = next_proposed_start - start;
-                let next_proposed_end = end + amount_added;
-                self.find_next_available_time(next_proposed_start, next_proposed_end)
+    fn find_earliest_fit(
+        &self,
+        earliest_start: DateTime<Utc>,
+        duration: Duration,
+    ) -> DateTime<Utc> {
+        //This assumes that self is sorted and never overlaps so one pass is enough. Anything that overlaps the proposed
+        //time pushes the proposed start to a minute after it ends, once something starts after the proposed end then
+        //nothing after it can overlap.
+        let one_minute = Duration::new(60, 0);
+        let mut start = earliest_start;
+        for x in self.iter() {
+            if x.start > start + duration {
+                break;
+            }
+            if x.end >= start {
+                start = x.end + one_minute;
             }
-            None => start,
         }
+        start
     }
 
     fn calculate_gap_penalty(&self) -> f64 {
@@ -51,17 +50,17 @@ impl Scheduled for Vec<ScheduledItem<'_>> {
             //Also note that elsewhere in the code something is scheduled a minute later than the last
             //thing scheduled and log10(1) is 0 so there is no penalty for a one minute gap.
             let gap_penalty_raw = (self[i + 1].start - self[i].end).num_minutes() as f64;
-            if gap_penalty_raw < 1.0 {
-                //This is because log10 of something less than 1 is a negative number. It is possible that we would want a larger penalty or maybe this would be better to just be an assert as this scenario shouldn't really happen.
-                //gap_penalty_raw = 1.0;
+            if gap_penalty_raw < 0.0 {
                 panic!(
-                    "This should never happen, it means that we have things that overlap, or we are not sorted properly, or we have a gap of less than a minute. gap_penalty_raw={}, self[i + 1].start={}, self[i].end={}",
+                    "This should never happen, it means that we have things that overlap, or we are not sorted properly. gap_penalty_raw={}, self[i + 1].start={}, self[i].end={}",
                     gap_penalty_raw,
                     self[i + 1].start,
                     self[i].end
                 );
             }
-            gap_penalty_sum += gap_penalty_raw.log10();
+            //An exact time or the start of a range can leave a gap of less than a minute, log10 of something less than 1
+            //is a negative number so this is treated the same as a one minute gap
+            gap_penalty_sum += gap_penalty_raw.max(1.0).log10();
         }
         gap_penalty_sum
     }
@@ -87,6 +86,14 @@ impl<'s> ScheduledItem<'s> {
         &self.start
     }
 
+    pub(crate) fn get_scheduled_end(&self) -> &DateTime<Utc> {
+        &self.end
+    }
+
+    pub(crate) fn get_item_status(&self) -> &'s ItemStatus<'s> {
+        self.item
+    }
+
     pub(crate) fn get_summary(&self) -> &str {
         self.item.get_summary()
     }